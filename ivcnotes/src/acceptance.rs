@@ -0,0 +1,113 @@
+use crate::{circuit::IVC, note::NoteHistory, Address, AssetHash};
+use ark_ff::PrimeField;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    HistoryTooLong { len: usize, max: usize },
+    AssetNotAllowed,
+    IssuerNotAllowed,
+    // first step is not issued by the issuer of the asset
+    IssuerMismatch,
+}
+
+impl Rejection {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Rejection::HistoryTooLong { .. } => "history too long",
+            Rejection::AssetNotAllowed => "asset not allowed",
+            Rejection::IssuerNotAllowed => "issuer not allowed",
+            Rejection::IssuerMismatch => "note is not issued by the asset issuer",
+        }
+    }
+}
+
+impl From<Rejection> for crate::Error {
+    fn from(rejection: Rejection) -> Self {
+        crate::Error::With(rejection.message())
+    }
+}
+
+// `AcceptancePolicy` holds the rules a receiver evaluates before accepting a
+// note. Rules that are `None` are not enforced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AcceptancePolicy<F: PrimeField> {
+    // maximum number of steps of the incoming history
+    pub max_history: Option<usize>,
+    pub allowed_assets: Option<Vec<AssetHash<F>>>,
+    pub allowed_issuers: Option<Vec<Address<F>>>,
+    // published epochs a transfer needs before the note is spendable, see
+    // `confirmation`
+    pub min_confirmations: Option<u64>,
+}
+
+impl<F: PrimeField> Default for AcceptancePolicy<F> {
+    fn default() -> Self {
+        Self {
+            max_history: None,
+            allowed_assets: None,
+            allowed_issuers: None,
+            min_confirmations: None,
+        }
+    }
+}
+
+impl<F: PrimeField> AcceptancePolicy<F> {
+    pub fn with_max_history(mut self, max: usize) -> Self {
+        self.max_history = Some(max);
+        self
+    }
+
+    pub fn with_allowed_assets(mut self, assets: Vec<AssetHash<F>>) -> Self {
+        self.allowed_assets = Some(assets);
+        self
+    }
+
+    pub fn with_allowed_issuers(mut self, issuers: Vec<Address<F>>) -> Self {
+        self.allowed_issuers = Some(issuers);
+        self
+    }
+
+    pub fn with_min_confirmations(mut self, confirmations: u64) -> Self {
+        self.min_confirmations = Some(confirmations);
+        self
+    }
+
+    // all the rules the history violates
+    pub fn check<E: IVC<Field = F>>(&self, history: &NoteHistory<E>) -> Vec<Rejection> {
+        let mut rejections = vec![];
+
+        let len = history.steps.len();
+        if let Some(max) = self.max_history {
+            if len > max {
+                rejections.push(Rejection::HistoryTooLong { len, max });
+            }
+        }
+
+        if let Some(assets) = &self.allowed_assets {
+            if !assets.contains(&history.current_note.asset_hash) {
+                rejections.push(Rejection::AssetNotAllowed);
+            }
+        }
+
+        let issuer = &history.asset.issuer;
+        if !history.asset.is_key_bound()
+            && history.steps.first().map(|step| &step.sender) != Some(issuer)
+        {
+            rejections.push(Rejection::IssuerMismatch);
+        }
+        if let Some(issuers) = &self.allowed_issuers {
+            if !issuers.contains(issuer) {
+                rejections.push(Rejection::IssuerNotAllowed);
+            }
+        }
+
+        rejections
+    }
+
+    pub fn evaluate<E: IVC<Field = F>>(&self, history: &NoteHistory<E>) -> Result<(), Rejection> {
+        match self.check(history).first() {
+            Some(rejection) => Err(*rejection),
+            None => Ok(()),
+        }
+    }
+}
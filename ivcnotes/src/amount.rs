@@ -0,0 +1,362 @@
+use crate::Error;
+
+// `Locale` defines the separators used to read and write amounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal: char,
+    pub group: char,
+}
+
+impl Locale {
+    pub const EN: Locale = Locale {
+        decimal: '.',
+        group: ',',
+    };
+    pub const DE: Locale = Locale {
+        decimal: ',',
+        group: '.',
+    };
+    pub const FR: Locale = Locale {
+        decimal: ',',
+        group: ' ',
+    };
+    pub const CH: Locale = Locale {
+        decimal: '.',
+        group: '\'',
+    };
+}
+
+fn scale(decimals: u8) -> Result<u64, Error> {
+    10u64
+        .checked_pow(decimals as u32)
+        .ok_or(Error::With("too many decimals for the asset"))
+}
+
+// Parses an amount into base units of an asset with `decimals` decimals.
+// Inputs that could be read in more than one way are rejected rather than
+// guessed, eg. misplaced group separators or more fraction digits than the
+// asset supports.
+pub fn parse_amount(input: &str, decimals: u8, locale: &Locale) -> Result<u64, Error> {
+    let input = input.trim();
+    let scale = scale(decimals)?;
+
+    if input
+        .chars()
+        .any(|c| !c.is_ascii_digit() && c != locale.decimal && c != locale.group)
+    {
+        return Err(Error::With("unexpected character in amount"));
+    }
+
+    let (integer, fraction) = match input.split_once(locale.decimal) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (input, None),
+    };
+
+    // integer part, groups must be of three digits after the leading one
+    let groups: Vec<&str> = integer.split(locale.group).collect();
+    let (head, tail) = groups.split_first().unwrap();
+    if head.is_empty() {
+        return Err(Error::With("missing integer part in amount"));
+    }
+    if head.len() > 3 && !tail.is_empty() {
+        return Err(Error::With("misplaced group separator in amount"));
+    }
+    if tail.iter().any(|group| group.len() != 3) {
+        return Err(Error::With("misplaced group separator in amount"));
+    }
+    let integer = groups.concat();
+
+    // fraction part, must fit into the decimals of the asset
+    let fraction = match fraction {
+        None => String::new(),
+        Some(fraction) => {
+            if fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+                return Err(Error::With("malformed fraction in amount"));
+            }
+            if fraction.len() > decimals as usize {
+                return Err(Error::With("more fraction digits than the asset supports"));
+            }
+            format!("{:0<width$}", fraction, width = decimals as usize)
+        }
+    };
+
+    let integer: u64 = integer
+        .parse()
+        .map_err(|_| Error::With("amount overflow"))?;
+    let fraction: u64 = match fraction.is_empty() {
+        true => 0,
+        false => fraction
+            .parse()
+            .map_err(|_| Error::With("amount overflow"))?,
+    };
+
+    integer
+        .checked_mul(scale)
+        .and_then(|integer| integer.checked_add(fraction))
+        .ok_or(Error::With("amount overflow"))
+}
+
+// Formats base units of an asset with `decimals` decimals
+pub fn format_amount(value: u64, decimals: u8, locale: &Locale) -> Result<String, Error> {
+    let scale = scale(decimals)?;
+    let integer = (value / scale).to_string();
+    let fraction = value % scale;
+
+    let mut out = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            out.push(locale.group);
+        }
+        out.push(c);
+    }
+    if decimals > 0 {
+        out.push(locale.decimal);
+        out.push_str(&format!("{:0>width$}", fraction, width = decimals as usize));
+    }
+    Ok(out)
+}
+
+// Checked arithmetic on base units. Bindings go through these rather than
+// reimplementing them so that every wallet rounds and fails the same way.
+
+pub fn add_amounts(a: u64, b: u64) -> Result<u64, Error> {
+    a.checked_add(b).ok_or(Error::With("amount overflow"))
+}
+
+pub fn sub_amounts(a: u64, b: u64) -> Result<u64, Error> {
+    a.checked_sub(b).ok_or(Error::With("amount underflow"))
+}
+
+// Splits a value into `parts` amounts that sum to it exactly. Parts are in
+// multiples of the smallest unit shown with `precision` of the `decimals` of
+// the asset, the remainder goes one unit at a time to the first parts and what
+// is left below the unit goes to the first part.
+pub fn split_amount(
+    value: u64,
+    parts: usize,
+    decimals: u8,
+    precision: u8,
+) -> Result<Vec<u64>, Error> {
+    (parts > 0)
+        .then_some(())
+        .ok_or(Error::With("cannot split into zero parts"))?;
+    (precision <= decimals)
+        .then_some(())
+        .ok_or(Error::With("too many decimals for the asset"))?;
+    let unit = scale(decimals - precision)?;
+    let units = value / unit;
+    let (each, extra) = (units / parts as u64, units % parts as u64);
+    let mut out = (0..parts as u64)
+        .map(|i| (each + (i < extra) as u64) * unit)
+        .collect::<Vec<_>>();
+    out[0] += value % unit;
+    Ok(out)
+}
+
+// Converts base units between assets of different decimals, fails rather
+// than rounds if the value is not exact in the target decimals
+pub fn rescale_amount(value: u64, from: u8, to: u8) -> Result<u64, Error> {
+    match from <= to {
+        true => value
+            .checked_mul(scale(to - from)?)
+            .ok_or(Error::With("amount overflow")),
+        false => {
+            let scale = scale(from - to)?;
+            (value % scale == 0)
+                .then_some(value / scale)
+                .ok_or(Error::With("amount is not exact in the decimals"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCALES: [Locale; 4] = [Locale::EN, Locale::DE, Locale::FR, Locale::CH];
+
+    #[test]
+    fn parse_table() {
+        let cases: &[(&str, u8, Locale, Result<u64, Error>)] = &[
+            // thousand separators per locale
+            ("1,234,567.89", 2, Locale::EN, Ok(123456789)),
+            ("1.234.567,89", 2, Locale::DE, Ok(123456789)),
+            ("1 234 567,89", 2, Locale::FR, Ok(123456789)),
+            ("1'234'567.89", 2, Locale::CH, Ok(123456789)),
+            ("1234567.89", 2, Locale::EN, Ok(123456789)),
+            (" 12 ", 2, Locale::EN, Ok(1200)),
+            (
+                "12,34.00",
+                2,
+                Locale::EN,
+                Err(Error::With("misplaced group separator in amount")),
+            ),
+            (
+                "1234,567",
+                2,
+                Locale::EN,
+                Err(Error::With("misplaced group separator in amount")),
+            ),
+            (
+                "1,2345",
+                2,
+                Locale::EN,
+                Err(Error::With("misplaced group separator in amount")),
+            ),
+            (
+                "1'234,5",
+                2,
+                Locale::EN,
+                Err(Error::With("unexpected character in amount")),
+            ),
+            (
+                ",123",
+                2,
+                Locale::EN,
+                Err(Error::With("missing integer part in amount")),
+            ),
+            (
+                "",
+                2,
+                Locale::EN,
+                Err(Error::With("missing integer part in amount")),
+            ),
+            (
+                "-1",
+                2,
+                Locale::EN,
+                Err(Error::With("unexpected character in amount")),
+            ),
+            (
+                "1e3",
+                2,
+                Locale::EN,
+                Err(Error::With("unexpected character in amount")),
+            ),
+            // too many decimals, for the input or for the asset
+            (
+                "1.234",
+                2,
+                Locale::EN,
+                Err(Error::With("more fraction digits than the asset supports")),
+            ),
+            (
+                "1.5",
+                0,
+                Locale::EN,
+                Err(Error::With("more fraction digits than the asset supports")),
+            ),
+            ("1.50", 2, Locale::EN, Ok(150)),
+            ("1.5", 2, Locale::EN, Ok(150)),
+            (
+                "1.",
+                2,
+                Locale::EN,
+                Err(Error::With("malformed fraction in amount")),
+            ),
+            (
+                "1.2.3",
+                2,
+                Locale::EN,
+                Err(Error::With("malformed fraction in amount")),
+            ),
+            ("1", 19, Locale::EN, Ok(10u64.pow(19))),
+            (
+                "1",
+                20,
+                Locale::EN,
+                Err(Error::With("too many decimals for the asset")),
+            ),
+            // 1,000 and 1.000 are read by the locale, never guessed
+            ("1,000", 2, Locale::EN, Ok(100000)),
+            (
+                "1,000",
+                2,
+                Locale::DE,
+                Err(Error::With("more fraction digits than the asset supports")),
+            ),
+            ("1.000", 2, Locale::DE, Ok(100000)),
+            (
+                "1.000",
+                2,
+                Locale::EN,
+                Err(Error::With("more fraction digits than the asset supports")),
+            ),
+            ("1,000", 3, Locale::EN, Ok(1000000)),
+            ("1,000", 3, Locale::DE, Ok(1000)),
+            // overflow
+            ("18446744073709551615", 0, Locale::EN, Ok(u64::MAX)),
+            (
+                "18446744073709551616",
+                0,
+                Locale::EN,
+                Err(Error::With("amount overflow")),
+            ),
+            ("18446744073709551.615", 3, Locale::EN, Ok(u64::MAX)),
+            (
+                "18446744073709551.616",
+                3,
+                Locale::EN,
+                Err(Error::With("amount overflow")),
+            ),
+            (
+                "18446744073709552",
+                3,
+                Locale::EN,
+                Err(Error::With("amount overflow")),
+            ),
+            (
+                "99999999999999999999999",
+                0,
+                Locale::EN,
+                Err(Error::With("amount overflow")),
+            ),
+        ];
+        for (input, decimals, locale, expected) in cases {
+            assert_eq!(
+                parse_amount(input, *decimals, locale),
+                *expected,
+                "{input:?} with {decimals} decimals in {locale:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn format_table() {
+        let cases: &[(u64, u8, Locale, &str)] = &[
+            (123456789, 2, Locale::EN, "1,234,567.89"),
+            (123456789, 2, Locale::DE, "1.234.567,89"),
+            (123456789, 2, Locale::FR, "1 234 567,89"),
+            (123456789, 2, Locale::CH, "1'234'567.89"),
+            (5, 3, Locale::DE, "0,005"),
+            (1000, 0, Locale::CH, "1'000"),
+            (100, 0, Locale::EN, "100"),
+            (0, 2, Locale::EN, "0.00"),
+            (u64::MAX, 0, Locale::EN, "18,446,744,073,709,551,615"),
+        ];
+        for (value, decimals, locale, expected) in cases {
+            assert_eq!(format_amount(*value, *decimals, locale).unwrap(), *expected);
+        }
+        assert_eq!(
+            format_amount(1, 20, &Locale::EN),
+            Err(Error::With("too many decimals for the asset"))
+        );
+    }
+
+    #[test]
+    fn format_parse_round_trip() {
+        let values = [0, 1, 99, 100, 1000, 123456789, 10u64.pow(18), u64::MAX];
+        for locale in LOCALES.iter() {
+            for decimals in [0, 1, 2, 6, 18, 19] {
+                for value in values {
+                    let formatted = format_amount(value, decimals, locale).unwrap();
+                    assert_eq!(
+                        parse_amount(&formatted, decimals, locale),
+                        Ok(value),
+                        "{formatted:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Address, AssetHash, FWrap};
+#[cfg(feature = "prover")]
+use crate::circuit::policy::AssetPolicyCircuit;
+use crate::{poseidon::PoseidonConfigs, Address, AssetHash, FWrap, IssuerKey, PolicyHash};
+use ark_crypto_primitives::sponge::Absorb;
 use ark_ff::PrimeField;
 use digest::Digest;
 
@@ -6,6 +9,13 @@ use digest::Digest;
 pub struct Asset<F: PrimeField> {
     pub(crate) issuer: Address<F>,
     pub(crate) terms: Terms,
+    // commitment to the policy circuit of the asset, zero if there is none
+    pub(crate) policy: PolicyHash<F>,
+    // signing key the asset is bound to, zero if it is bound to the address
+    // of the issuer
+    pub(crate) issuer_key: IssuerKey<F>,
+    // redemptions need the co-signature of the issuer key
+    pub(crate) dual_control: bool,
 }
 
 impl<F: PrimeField> Asset<F> {
@@ -13,15 +23,67 @@ impl<F: PrimeField> Asset<F> {
         Asset {
             issuer: *issuer,
             terms: *terms,
+            policy: PolicyHash::default(),
+            issuer_key: IssuerKey::default(),
+            dual_control: false,
         }
     }
 
-    pub(crate) fn hash(&self) -> AssetHash<F> {
-        let bytes = sha2::Sha512::new()
-            .chain_update(self.terms.to_bytes())
-            .chain_update(self.issuer.to_bytes())
-            .finalize();
-        AssetHash::reduce_bytes(bytes.as_ref())
+    // binds the asset to a signing key of the issuer instead of its address.
+    // Any identity with the key can issue, eg. identities of the issuer that
+    // don't share their nullifier keys.
+    pub fn with_issuer_key(mut self, issuer_key: &IssuerKey<F>) -> Self {
+        self.issuer_key = *issuer_key;
+        self
+    }
+
+    pub fn is_key_bound(&self) -> bool {
+        !self.issuer_key.inner().is_zero()
+    }
+
+    // requires the issuer key to co-sign every redemption of the asset, eg.
+    // when its off-ramp legally needs the issuer. Only a key-bound asset can
+    // be redeemed under dual control, plain burns don't need the issuer.
+    pub fn with_dual_control(mut self) -> Self {
+        self.dual_control = true;
+        self
+    }
+
+    pub fn is_dual_control(&self) -> bool {
+        self.dual_control
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn with_policy(mut self, policy: &impl AssetPolicyCircuit<F>) -> Self {
+        self.policy = policy.commitment();
+        self
+    }
+
+    // issuer the asset hash commits to, the key if the asset is bound to one
+    pub(crate) fn issuer_commitment(&self) -> F {
+        match self.is_key_bound() {
+            true => self.issuer_key.inner(),
+            false => self.issuer.inner(),
+        }
+    }
+
+    // digest of the terms, the circuit witnesses it and recomputes the asset
+    // hash with the issuer and the commitment of its own policy
+    pub(crate) fn terms_digest(&self) -> F {
+        let hasher = sha2::Sha512::new().chain_update(self.terms.to_bytes());
+        F::from_le_bytes_mod_order(hasher.finalize().as_ref())
+    }
+
+    pub(crate) fn hash(&self, h: &PoseidonConfigs<F>) -> AssetHash<F>
+    where
+        F: Absorb,
+    {
+        h.asset(
+            &self.issuer_commitment(),
+            &self.terms_digest(),
+            &self.policy,
+            self.dual_control,
+        )
     }
 }
 
@@ -0,0 +1,43 @@
+use ivcnotes::{circuit::IVC, scenario::Report};
+
+// Runs the benchmark scenarios on every instantiation and backend compiled in
+// and prints the report as json, see `ivcnotes::scenario`.
+//
+// bench-scenarios [seed]
+//
+// Build with `parallel` to compare the rayon prover with the sequential one.
+
+#[derive(Clone)]
+struct Bn254;
+
+impl IVC for Bn254 {
+    type Snark = ark_groth16::Groth16<ark_bn254::Bn254>;
+    type Field = ark_bn254::Fr;
+    type TE = ark_ed_on_bn254::EdwardsConfig;
+}
+
+// splits to three outputs, the state is wider
+#[derive(Clone)]
+struct Bn254Outputs3;
+
+impl IVC for Bn254Outputs3 {
+    type Snark = ark_groth16::Groth16<ark_bn254::Bn254>;
+    type Field = ark_bn254::Fr;
+    type TE = ark_ed_on_bn254::EdwardsConfig;
+    const OUTPUTS: usize = 3;
+}
+
+fn main() -> Result<(), ivcnotes::Error> {
+    let seed = match std::env::args().nth(1) {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| ivcnotes::Error::With("bad seed"))?,
+        None => 0,
+    };
+    let mut report = Report::new(seed);
+    report.add::<Bn254>("bn254-groth16")?;
+    report.add_batch::<ark_bn254::Bn254, Bn254>("bn254-groth16")?;
+    report.add::<Bn254Outputs3>("bn254-groth16-outputs3")?;
+    println!("{}", report.to_json());
+    Ok(())
+}
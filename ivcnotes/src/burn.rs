@@ -0,0 +1,245 @@
+use crate::{
+    asset::Asset,
+    circuit::{inputs::PublicInput, Verifier, IVC},
+    limits::Limits,
+    note::{statements, IVCStep, NoteHistory, Statement},
+    poseidon::PoseidonConfigs,
+    AssetHash, FWrap, NoteHash, Nullifier, SettlementRef, SigHash, StateHash,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::PrimeField;
+use arkeddsa::{signature::Signature, PublicKey};
+use std::collections::{HashMap, HashSet};
+
+// `BurnRecord` is the public record of a destroyed note. It carries the steps
+// from issuance up to the burnt note and the burn step itself. The burn step
+// binds the asset, the burnt value and the payout reference of a redemption
+// to the nullifier of the consumed note.
+#[derive(Clone, Debug)]
+pub struct BurnRecord<E: IVC> {
+    pub(crate) asset: Asset<E::Field>,
+    // steps of the history the burnt note belongs to
+    pub(crate) steps: Vec<IVCStep<E>>,
+    // the burn step, leaves no output state
+    pub(crate) burn: IVCStep<E>,
+    // burnt value
+    pub(crate) value: u64,
+    // payout reference if the note is redeemed
+    pub(crate) settlement: SettlementRef<E::Field>,
+}
+
+impl<E: IVC> BurnRecord<E> {
+    pub(crate) fn new(
+        history: &NoteHistory<E>,
+        burn: IVCStep<E>,
+        value: u64,
+        settlement: &SettlementRef<E::Field>,
+    ) -> Self {
+        Self {
+            asset: history.asset,
+            steps: history.steps.clone(),
+            burn,
+            value,
+            settlement: *settlement,
+        }
+    }
+
+    pub fn asset(&self) -> &Asset<E::Field> {
+        &self.asset
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn nullifier(&self) -> &Nullifier<E::Field> {
+        &self.burn.nullifier
+    }
+
+    // payout reference of a redemption, none for a plain burn
+    pub fn settlement(&self) -> Option<&SettlementRef<E::Field>> {
+        (self.settlement != SettlementRef::default()).then_some(&self.settlement)
+    }
+
+    // proofs of the steps and of the burn with their public inputs
+    pub(crate) fn statements(&self, h: &PoseidonConfigs<E::Field>) -> Vec<Statement<'_, E>> {
+        let asset_hash = &self.asset.hash(h);
+        let state_in = self.steps.last().map(|step| step.state).unwrap_or_default();
+        let burn = PublicInput::new(
+            asset_hash,
+            &self.burn.sender,
+            &state_in,
+            &self.burn.state,
+            self.steps.len() as u32,
+            &self.burn.nullifier,
+            self.value,
+        )
+        .with_settlement(&self.settlement)
+        .with_disclosure(&h.disclosure(&self.burn.disclosure));
+        let mut statements = statements(h, asset_hash, &self.steps);
+        statements.push((&self.burn.proof, burn));
+        statements
+    }
+
+    pub fn verify(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        verifier: &Verifier<E>,
+    ) -> Result<(), crate::Error> {
+        (self.burn.state == StateHash::default())
+            .then_some(())
+            .ok_or(crate::Error::With("burn leaves a state"))?;
+
+        for (proof, public_input) in self.statements(h) {
+            verifier
+                .verify_proof(proof, &public_input)?
+                .then_some(())
+                .ok_or(crate::Error::With("verification failed"))?;
+        }
+        Ok(())
+    }
+}
+
+// `RedemptionRequest` is what the holder of a note of a dual-control asset
+// hands to the issuer to co-sign. The note stays hidden behind its hash, the
+// issuer sees the value it pays out and the payout reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RedemptionRequest<F: PrimeField> {
+    pub(crate) asset_hash: AssetHash<F>,
+    pub(crate) note: NoteHash<F>,
+    pub(crate) value: u64,
+    pub(crate) settlement: SettlementRef<F>,
+}
+
+impl<F: PrimeField + Absorb> RedemptionRequest<F> {
+    pub fn asset_hash(&self) -> &AssetHash<F> {
+        &self.asset_hash
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn settlement(&self) -> &SettlementRef<F> {
+        &self.settlement
+    }
+
+    // the redemption sighash, signed by both the holder and the issuer
+    pub(crate) fn sighash(&self, h: &PoseidonConfigs<F>) -> SigHash<F> {
+        h.sighash_redeem(&self.note, self.value, &self.settlement)
+    }
+}
+
+// co-signature of the issuer key over a redemption, verified in the circuit
+// against the key the asset is bound to
+#[derive(Clone, Debug)]
+pub struct Cosignature<E: IVC> {
+    pub(crate) public_key: PublicKey<E::TE>,
+    pub(crate) signature: Signature<E::TE>,
+}
+
+impl<E: IVC> Cosignature<E> {
+    pub(crate) fn verify(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        asset: &Asset<E::Field>,
+        request: &RedemptionRequest<E::Field>,
+    ) -> Result<(), crate::Error> {
+        let err = crate::Error::With("bad co-signature");
+        (h.issuer_key(&self.public_key) == asset.issuer_key)
+            .then_some(())
+            .ok_or(err)?;
+        self.public_key
+            .verify(&h.eddsa, &[request.sighash(h).inner()], &self.signature)
+            .map_err(|_| err)
+    }
+}
+
+// `BurnLedger` accumulates verified burns per asset. A note is counted once
+// since records are deduplicated by the nullifier of the burnt note.
+pub struct BurnLedger<E: IVC> {
+    h: PoseidonConfigs<E::Field>,
+    verifier: Verifier<E>,
+    totals: HashMap<AssetHash<E::Field>, u64>,
+    nullifiers: HashSet<Nullifier<E::Field>>,
+    // bounds of the incoming records
+    limits: Limits,
+}
+
+impl<E: IVC> BurnLedger<E> {
+    pub fn new(h: &PoseidonConfigs<E::Field>, verifier: Verifier<E>) -> Self {
+        Self {
+            h: h.clone(),
+            verifier,
+            totals: HashMap::new(),
+            nullifiers: HashSet::new(),
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // verifies the record and adds the burnt value to the total of the asset
+    pub fn record(&mut self, record: &BurnRecord<E>) -> Result<(), crate::Error> {
+        (!self.nullifiers.contains(record.nullifier()))
+            .then_some(())
+            .ok_or(crate::Error::With("already burnt"))?;
+        self.limits.check_steps(&record.steps)?;
+        record.verify(&self.h, &self.verifier)?;
+
+        let total = self.totals.entry(record.asset.hash(&self.h)).or_default();
+        *total = total
+            .checked_add(record.value)
+            .ok_or(crate::Error::With("total burned overflows"))?;
+        self.nullifiers.insert(*record.nullifier());
+        Ok(())
+    }
+
+    pub fn total_burned(&self, asset_hash: &AssetHash<E::Field>) -> u64 {
+        self.totals.get(asset_hash).copied().unwrap_or_default()
+    }
+
+    pub fn totals(&self) -> &HashMap<AssetHash<E::Field>, u64> {
+        &self.totals
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::Terms,
+        circuit::mock::{self, Mock},
+        wallet::CommReceiver,
+    };
+    use ark_bn254::Fr;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn redemption_is_bound_to_its_settlement() {
+        let h = PoseidonConfigs::generate();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut alice = mock::wallet(&h, &mut rng);
+        let asset = Asset::new(issuer.address(), &Terms::iou(1, 1));
+        issuer.issue(&mut rng, &mut alice, &asset, 100).unwrap();
+
+        let settlement = SettlementRef::from(Fr::from(7));
+        let record = alice.redeem(&mut rng, 0, &settlement).unwrap();
+        let verifier = Verifier::<Mock>::new(());
+        record.verify(&h, &verifier).unwrap();
+
+        // another payout, or none, is not what the redeemer signed
+        for settlement in [Fr::from(8), Fr::from(0)] {
+            let mut record = record.clone();
+            record.settlement = SettlementRef::from(settlement);
+            assert_eq!(
+                record.verify(&h, &verifier).unwrap_err(),
+                crate::Error::With("verification failed")
+            );
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Stable numeric codes of the error messages so that bindings and UIs can
+// localize them. Codes are grouped by subsystem, they are never renumbered or
+// reused. Messages are the english templates and can be reworded freely.
+
+// code of the messages that are not in the catalog
+pub const UNKNOWN: u16 = 0;
+
+pub const CATALOG: &[(u16, &str)] = &[
+    // proofs and verification
+    (100, "verification failed"),
+    (101, "proof generation failed"),
+    (102, "broken evidence chain"),
+    (103, "bad current state"),
+    (104, "bad transfer index"),
+    (105, "history exceeds limits"),
+    (106, "poseidon config below the security level"),
+    (107, "bad history structure"),
+    (108, "repeated nullifier"),
+    (109, "cannot build thread pool"),
+    (110, "memo does not match its commitment"),
+    (111, "note is not bound to its receiver"),
+    (112, "setup failed"),
+    (113, "poseidon domains are not separated"),
+    (114, "cannot verify proof"),
+    (115, "no verifier for the asset policy"),
+    (116, "prover does not match the asset policy"),
+    (117, "cannot write witness dump"),
+    (118, "cannot open witness dump"),
+    // wallet
+    (200, "not me"),
+    (201, "bad spendable index"),
+    (202, "insufficient funds"),
+    (203, "note is not received"),
+    (204, "bad auth"),
+    (205, "screening hit"),
+    (206, "nothing to burn"),
+    (207, "bad rng"),
+    (208, "too many receivers"),
+    (209, "cannot merge different assets"),
+    (210, "idempotency key reused"),
+    (211, "cannot decrypt note"),
+    (212, "bad seed"),
+    (213, "bad mnemonic"),
+    (214, "mnemonic checksum mismatch"),
+    (215, "bad keystore"),
+    (216, "wrong password"),
+    (217, "calibration failed"),
+    (218, "memo too long"),
+    (219, "note is expired"),
+    (220, "cannot merge notes of different expiry"),
+    (221, "not the issuer of the asset"),
+    (222, "payment does not answer a challenge"),
+    (223, "bad challenge"),
+    (224, "bad cold storage file"),
+    (225, "cannot rotate with pending or cold notes"),
+    (226, "key rotation in progress"),
+    (227, "no key rotation in progress"),
+    (228, "cold identity is the wallet identity"),
+    // acceptance policy
+    (300, "history too long"),
+    (301, "asset not allowed"),
+    (302, "issuer not allowed"),
+    (303, "note is not issued by the asset issuer"),
+    // amounts
+    (400, "amount overflow"),
+    (401, "unexpected character in amount"),
+    (402, "misplaced group separator in amount"),
+    (403, "missing integer part in amount"),
+    (404, "malformed fraction in amount"),
+    (405, "more fraction digits than the asset supports"),
+    (406, "too many decimals for the asset"),
+    (407, "amount underflow"),
+    (408, "cannot split into zero parts"),
+    (409, "amount is not exact in the decimals"),
+    // burns and receipts
+    (500, "burn leaves a state"),
+    (501, "already burnt"),
+    (502, "total burned overflows"),
+    (503, "receipt of an issuance"),
+    (504, "receipt asset mismatch"),
+    (505, "note is not in the transfer"),
+    (506, "empty settlement reference"),
+    (507, "redemption needs the co-signature of the issuer"),
+    (508, "bad co-signature"),
+    (509, "asset is not under dual control"),
+    (510, "dual control needs a key-bound asset"),
+    (511, "redemption request of another asset"),
+    // recurring payments
+    (600, "bad authorization"),
+    (601, "bad authorization signature"),
+    (602, "unknown authorization"),
+    (603, "not the payee"),
+    (604, "installment exceeds the cap"),
+    (605, "installment out of period"),
+    (606, "installment is not due"),
+    // rest api
+    (700, "rest api is local only"),
+    (701, "empty api token"),
+    (702, "cannot bind rest api"),
+    (703, "rest api failed"),
+    (704, "wallet lock poisoned"),
+    // handshake
+    (800, "bad handshake"),
+    (801, "no common circuit version"),
+    (802, "unknown codec"),
+    (803, "bad compressed artifact"),
+    // issuer log
+    (900, "bad log index"),
+    (901, "bad tree head signature"),
+    (902, "log is not consistent"),
+    (903, "action is not in the log"),
+    (904, "no verified tree head"),
+    // nullifier gossip
+    (1000, "bad nullifier digest"),
+    (1001, "nullifier digest mismatch"),
+    // transfer payloads
+    (1100, "bad payload"),
+    (1101, "cannot open payload"),
+    (1102, "bad wire encoding"),
+    (1103, "unsupported wire version"),
+    (1104, "unsupported wire record"),
+    (1105, "bad note store"),
+    (1106, "bad outcome store"),
+    // relay
+    (1200, "relay lock poisoned"),
+    (1201, "payload too large"),
+    (1202, "inbox is full"),
+    (1203, "cannot bind relay"),
+    (1204, "relay failed"),
+    (1205, "bad relay response"),
+    (1206, "relay rejected the request"),
+    (1207, "bad ack key"),
+    // nullifier registry
+    (1300, "registry lock poisoned"),
+    (1301, "double spend"),
+    (1302, "note is already spent"),
+    (1303, "spend reveals no nullifier"),
+    (1304, "bad spend"),
+    // bindings
+    (1400, "null pointer"),
+    (1401, "buffer too small"),
+    (1402, "string is not utf-8"),
+    (1403, "bad address"),
+    (1404, "bad asset"),
+    (1405, "bad proving key"),
+    (1406, "bad verifying key"),
+    (1407, "bad payload index"),
+    // asset policies
+    (1500, "bad receiver tree depth"),
+    (1501, "too many registered receivers"),
+];
+
+pub fn code(message: &str) -> u16 {
+    CATALOG
+        .iter()
+        .find(|(_, m)| *m == message)
+        .map(|(code, _)| *code)
+        .unwrap_or(UNKNOWN)
+}
+
+pub fn message(code: u16) -> Option<&'static str> {
+    CATALOG
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, message)| *message)
+}
@@ -0,0 +1,45 @@
+use rand_core::CryptoRngCore;
+
+// Payment challenges of merchants. The merchant hands out a fresh challenge
+// for every payment it expects and the payer answers it with a split bound to
+// the merchant, whose note commits to the challenge as its memo. The memo is
+// in the note hash so the answer is signed and proven along with the note and
+// can't be moved to another one. The merchant consumes the challenge when it
+// receives the payment, so a history it has already received or that answers
+// another challenge doesn't count as a second payment.
+
+const PREFIX: &[u8; 4] = b"IVCC";
+// outstanding challenges a wallet keeps, the oldest are dropped first
+pub const MAX_CHALLENGES: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Challenge([u8; 32]);
+
+impl Challenge {
+    pub fn random(rng: &mut impl CryptoRngCore) -> Self {
+        let mut bytes = [0u8; 32];
+        rng.fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, crate::Error> {
+        bytes
+            .try_into()
+            .map(Self)
+            .map_err(|_| crate::Error::With("bad challenge"))
+    }
+
+    // memo of the note that answers the challenge
+    pub(crate) fn to_memo(self) -> Vec<u8> {
+        PREFIX.iter().chain(self.0.iter()).copied().collect()
+    }
+
+    pub(crate) fn from_memo(memo: &[u8]) -> Option<Self> {
+        memo.strip_prefix(PREFIX.as_slice())
+            .and_then(|bytes| Self::from_bytes(bytes).ok())
+    }
+}
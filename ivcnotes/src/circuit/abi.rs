@@ -0,0 +1,120 @@
+use ark_ff::PrimeField;
+use std::fmt::Write;
+
+// how a public input value is mapped into a field element
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    // field element as is
+    Field,
+    // unsigned integers embedded into the field
+    U32,
+    U64,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Field => "field",
+            Encoding::U32 => "u32",
+            Encoding::U64 => "u64",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublicInputField {
+    AssetHash,
+    Sender,
+    StateIn,
+    StateOut,
+    Step,
+    Nullifier,
+    BurnValue,
+    Settlement,
+    MergeStateIn,
+    MergeNullifier,
+    ReceiverTag,
+    Disclosure,
+}
+
+impl PublicInputField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PublicInputField::AssetHash => "asset_hash",
+            PublicInputField::Sender => "sender",
+            PublicInputField::StateIn => "state_in",
+            PublicInputField::StateOut => "state_out",
+            PublicInputField::Step => "step",
+            PublicInputField::Nullifier => "nullifier",
+            PublicInputField::BurnValue => "burn_value",
+            PublicInputField::Settlement => "settlement",
+            PublicInputField::MergeStateIn => "merge_state_in",
+            PublicInputField::MergeNullifier => "merge_nullifier",
+            PublicInputField::ReceiverTag => "receiver_tag",
+            PublicInputField::Disclosure => "disclosure",
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        match self {
+            PublicInputField::Step => Encoding::U32,
+            PublicInputField::BurnValue => Encoding::U64,
+            _ => Encoding::Field,
+        }
+    }
+
+    // position in the verifier input vector
+    pub fn index(&self) -> usize {
+        LAYOUT.iter().position(|field| field == self).unwrap()
+    }
+}
+
+// version of the circuit, bumped on any change of the constraints or the
+// public inputs that makes proofs incompatible
+pub const VERSION: u32 = 15;
+
+// Public inputs in allocation order. This is the only place that defines the
+// order, both the circuit and the native verifier inputs follow it.
+pub const LAYOUT: [PublicInputField; 12] = [
+    PublicInputField::AssetHash,
+    PublicInputField::Sender,
+    PublicInputField::StateIn,
+    PublicInputField::StateOut,
+    PublicInputField::Step,
+    PublicInputField::Nullifier,
+    PublicInputField::BurnValue,
+    PublicInputField::Settlement,
+    PublicInputField::MergeStateIn,
+    PublicInputField::MergeNullifier,
+    PublicInputField::ReceiverTag,
+    PublicInputField::Disclosure,
+];
+
+// descriptor of the public inputs for external verifiers, field elements are
+// given as integers less than the modulus
+pub fn to_json<F: PrimeField>() -> String {
+    let mut out = String::new();
+    write!(
+        out,
+        "{{\"version\":{},\"modulus\":\"{}\",\"modulus_bits\":{},\"public_inputs\":[",
+        VERSION,
+        F::MODULUS,
+        F::MODULUS_BIT_SIZE
+    )
+    .unwrap();
+    for (i, field) in LAYOUT.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(
+            out,
+            "{{\"index\":{},\"name\":\"{}\",\"encoding\":\"{}\"}}",
+            i,
+            field.name(),
+            field.encoding().name()
+        )
+        .unwrap();
+    }
+    out.push_str("]}");
+    out
+}
@@ -0,0 +1,202 @@
+use super::{abi, IVC};
+use crate::{
+    id::Auth,
+    payload::{self, EncryptionKey},
+};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+// Audit mode of the prover, opt-in with `Prover::with_audit`. The witness of
+// a step is checked against the circuit before proving, a step that doesn't
+// satisfy it fails and its complete assignment is written to a file in the
+// audit directory. The assignment holds the secrets of the user so it is
+// encrypted to the key of a developer identity, see `payload`, and only that
+// identity opens it with `Auth::open_witness_dump`.
+//
+// magic | version | ephemeral key | ciphertext | tag
+//
+// The plaintext is the circuit version as u32 | instance | witness, both
+// assignments are vectors of field elements in the compressed canonical
+// serialization. The instance starts with the constant one.
+
+const MAGIC: &[u8; 4] = b"IVCA";
+const VERSION: u8 = 1;
+const DOMAIN: &[u8] = b"ivcnotes witness dump";
+
+fn header() -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    header
+}
+
+pub struct Audit<E: IVC> {
+    key: EncryptionKey<E>,
+    dir: PathBuf,
+}
+
+impl<E: IVC> Audit<E> {
+    // dumps are encrypted to `key` and written to `dir`
+    pub fn new(key: &EncryptionKey<E>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            key: key.clone(),
+            dir: dir.into(),
+        }
+    }
+
+    // writes the encrypted assignment to a file named by its digest
+    pub(crate) fn record(
+        &self,
+        instance: &[E::Field],
+        witness: &[E::Field],
+        rng: &mut impl CryptoRngCore,
+    ) -> Result<PathBuf, crate::Error> {
+        let mut plaintext = abi::VERSION.to_le_bytes().to_vec();
+        instance.serialize_compressed(&mut plaintext).unwrap();
+        witness.serialize_compressed(&mut plaintext).unwrap();
+        let bytes = payload::encrypt(DOMAIN, &header(), &plaintext, &self.key, rng);
+
+        let name = u64::from_be_bytes(Sha256::digest(&bytes)[..8].try_into().unwrap());
+        let path = self.dir.join(format!("witness-{name:016x}.ivca"));
+        std::fs::write(&path, &bytes)
+            .map_err(|_| crate::Error::With("cannot write witness dump"))?;
+        Ok(path)
+    }
+}
+
+// assignment of a step that failed to prove
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WitnessDump<F: PrimeField> {
+    pub(crate) version: u32,
+    pub(crate) instance: Vec<F>,
+    pub(crate) witness: Vec<F>,
+}
+
+impl<F: PrimeField> WitnessDump<F> {
+    // circuit version of the prover that failed
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn instance(&self) -> &[F] {
+        &self.instance
+    }
+
+    pub fn witness(&self) -> &[F] {
+        &self.witness
+    }
+}
+
+impl<E: IVC> Auth<E> {
+    // opens a witness dump encrypted to the key of this identity
+    pub fn open_witness_dump(&self, bytes: &[u8]) -> Result<WitnessDump<E::Field>, crate::Error> {
+        let err = crate::Error::With("cannot open witness dump");
+        let plaintext = self.decrypt(DOMAIN, &header(), bytes).ok_or(err)?;
+        let (version, mut reader) = (plaintext.len() >= 4)
+            .then(|| plaintext.split_at(4))
+            .ok_or(err)?;
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        let instance = Vec::deserialize_compressed(&mut reader).map_err(|_| err)?;
+        let witness = Vec::deserialize_compressed(&mut reader).map_err(|_| err)?;
+        reader.is_empty().then_some(()).ok_or(err)?;
+        Ok(WitnessDump {
+            version,
+            instance,
+            witness,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::{Asset, Terms},
+        circuit::{
+            mock::{self, Mock},
+            policy::{AssetPolicyCircuit, SplitVars},
+            Prover, Verifier,
+        },
+        poseidon::PoseidonConfigs,
+        wallet::{CommReceiver, Wallet},
+        PolicyHash,
+    };
+    use ark_bn254::Fr;
+    use ark_r1cs_std::{boolean::Boolean, eq::EqGadget};
+    use ark_relations::r1cs::{ConstraintSystemRef, Result as CSResult};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // no split satisfies it
+    struct Frozen;
+
+    impl AssetPolicyCircuit<Fr> for Frozen {
+        fn commitment(&self) -> PolicyHash<Fr> {
+            Fr::from(1).into()
+        }
+
+        fn enforce(
+            &self,
+            _cs: ConstraintSystemRef<Fr>,
+            _split: &SplitVars<Fr>,
+            enabled: &Boolean<Fr>,
+        ) -> CSResult<()> {
+            enabled.enforce_equal(&Boolean::FALSE)
+        }
+    }
+
+    #[test]
+    fn failed_step_is_dumped_to_the_developer() {
+        let h = PoseidonConfigs::<Fr>::generate();
+        let mut rng = StdRng::seed_from_u64(1);
+        let developer = Auth::generate(&h, &mut rng).unwrap();
+        let dir = std::env::temp_dir().join(format!("ivcnotes-audit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let wallet = |rng: &mut StdRng| {
+            let auth = Auth::generate(&h, rng).unwrap();
+            let verifier = Verifier::new(()).with_policy(&Frozen.commitment());
+            let prover = Prover::new(())
+                .with_policy(Box::new(Frozen))
+                .with_audit(Audit::new(&developer.encryption_key(), &dir));
+            Wallet::new(auth, &h, prover, verifier)
+        };
+        let mut issuer = wallet(&mut rng);
+        let mut alice = wallet(&mut rng);
+        let mut bob = mock::wallet(&h, &mut rng);
+        let asset = Asset::new(issuer.address(), &Terms::iou(1, 1)).with_policy(&Frozen);
+
+        // the issuance satisfies the circuit and leaves nothing behind
+        issuer.issue(&mut rng, &mut alice, &asset, 100).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        assert_eq!(
+            alice.split(&mut rng, &mut bob, 0, 10).unwrap_err(),
+            crate::Error::With("proof generation failed")
+        );
+        let paths = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(paths.len(), 1);
+        let bytes = std::fs::read(&paths[0]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dump = developer.open_witness_dump(&bytes).unwrap();
+        assert_eq!(dump.version(), abi::VERSION);
+        assert_eq!(dump.instance()[0], Fr::from(1));
+        assert_eq!(dump.instance().len(), abi::LAYOUT.len() + 1);
+        assert!(!dump.witness().is_empty());
+
+        // the secrets are only for the developer
+        let other = Auth::<Mock>::generate(&h, &mut rng).unwrap();
+        assert_eq!(
+            other.open_witness_dump(&bytes).unwrap_err(),
+            crate::Error::With("cannot open witness dump")
+        );
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(developer.open_witness_dump(&tampered).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use super::{cache::cache_key, Verifier, IVC};
+use crate::{
+    note::{statements, NoteHistory},
+    poseidon::PoseidonConfigs,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::{pairing::Pairing, CurveGroup, VariableBaseMSM};
+use ark_ff::{Field, PrimeField, UniformRand, Zero};
+use ark_groth16::Groth16;
+use rand_core::CryptoRngCore;
+use std::collections::HashSet;
+
+// Batch verification of note histories, for receivers that take many notes at
+// once. Groth16 checks `e(a, b) = e(alpha, beta) e(x, gamma) e(c, delta)` for a
+// proof `(a, b, c)` and the commitment `x` to its public input. A random
+// linear combination of the checks of all proofs is checked instead, so the
+// commitments to the inputs take a single msm, the pairings with the fixed
+// elements of the key are computed once for the batch and the final
+// exponentiation is shared. What is left is a miller loop per proof. A batch
+// with an invalid proof passes only if the coefficients are guessed.
+//
+// Steps shared by the histories, eg. the common prefix of notes split from the
+// same note, are checked once.
+
+impl<P: Pairing, E: IVC<Snark = Groth16<P>, Field = P::ScalarField>> Verifier<E>
+where
+    P::ScalarField: Absorb,
+{
+    // fails if any history doesn't verify without telling which one, the
+    // histories can be verified one by one then
+    pub fn verify_batch(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        histories: &[NoteHistory<E>],
+        rng: &mut impl CryptoRngCore,
+    ) -> Result<(), crate::Error> {
+        let err = crate::Error::With("verification failed");
+        let mut seen = HashSet::new();
+        let mut batch = vec![];
+        for history in histories.iter() {
+            let state_out = history.steps.last().map(|step| step.state);
+            (Some(history.state(h)) == state_out)
+                .then_some(())
+                .ok_or(crate::Error::With("bad current state"))?;
+            for (proof, public_input) in statements(h, &history.asset.hash(h), &history.steps) {
+                if seen.insert(cache_key::<E>(proof, &public_input)) {
+                    batch.push((proof, public_input.to_verifier()));
+                }
+            }
+        }
+
+        let pvk = &self.prepared()?.pvk;
+        let gamma_abc = &pvk.vk.gamma_abc_g1;
+        // coefficients of the bases of the input commitment summed over the batch
+        let mut scalars = vec![P::ScalarField::zero(); gamma_abc.len()];
+        let mut c = P::G1::zero();
+        let mut g1 = vec![];
+        let mut g2 = vec![];
+        for (proof, inputs) in batch.into_iter() {
+            (inputs.len() + 1 == gamma_abc.len())
+                .then_some(())
+                .ok_or(err)?;
+            let r = P::ScalarField::rand(rng);
+            scalars[0] += r;
+            for (scalar, input) in scalars[1..].iter_mut().zip(inputs.iter()) {
+                *scalar += r * input;
+            }
+            c += proof.c * r;
+            g1.push(proof.a * r);
+            g2.push(P::G2Prepared::from(proof.b));
+        }
+        let x = P::G1::msm(gamma_abc, &scalars).map_err(|_| err)?;
+        g1.extend([x, c]);
+        g2.extend([pvk.gamma_g2_neg_pc.clone(), pvk.delta_g2_neg_pc.clone()]);
+
+        let g1 = P::G1::normalize_batch(&g1);
+        let lhs = P::final_exponentiation(P::multi_miller_loop(g1, g2)).ok_or(err)?;
+        // the coefficient of the first base is the sum of the coefficients
+        let rhs = pvk.alpha_g1_beta_g2.pow(scalars[0].into_bigint());
+        (lhs.0 == rhs).then_some(()).ok_or(err)
+    }
+}
@@ -0,0 +1,120 @@
+use super::{inputs::PublicInput, IVC};
+use ark_crypto_primitives::snark::SNARK;
+use ark_serialize::CanonicalSerialize;
+use digest::Digest;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+pub type CacheKey = [u8; 32];
+
+// key of a verification is the hash of the proof and the public inputs
+pub fn cache_key<E: IVC>(
+    proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+    pi: &PublicInput<E::Field>,
+) -> CacheKey {
+    let mut bytes = vec![];
+    proof.serialize_compressed(&mut bytes).unwrap();
+    pi.to_verifier()
+        .iter()
+        .for_each(|e| e.serialize_compressed(&mut bytes).unwrap());
+    sha2::Sha256::digest(bytes).into()
+}
+
+// bounded map that evicts the least recently used entry
+#[derive(Clone, Debug)]
+pub(crate) struct Lru<K, V> {
+    capacity: usize,
+    // value and the last time the entry is used
+    entries: HashMap<K, (V, u64)>,
+    // entries ordered by the last time they are used
+    order: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Lru<K, V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn get(&mut self, key: &K) -> Option<V> {
+        let tick = self.next_tick();
+        let (value, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        self.order.insert(tick, key.clone());
+        *used = tick;
+        Some(value.clone())
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let tick = self.next_tick();
+        if let Some((_, used)) = self.entries.insert(key.clone(), (value, tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(tick, key);
+
+        // evict the least recently used
+        while self.entries.len() > self.capacity {
+            let (_, key) = self.order.pop_first().unwrap();
+            self.entries.remove(&key);
+        }
+    }
+
+    // entries from least to most recently used
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.order
+            .values()
+            .map(|key| (key, &self.entries.get(key).unwrap().0))
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+// `VerificationCache` is a bounded LRU of verification results
+#[derive(Clone, Debug)]
+pub struct VerificationCache(Lru<CacheKey, bool>);
+
+impl VerificationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self(Lru::new(capacity))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<bool> {
+        self.0.get(key)
+    }
+
+    // only the verifier inserts, a result that is not computed here could
+    // skip the verification of a proof
+    pub(crate) fn insert(&mut self, key: CacheKey, result: bool) {
+        self.0.insert(key, result)
+    }
+
+    // entries from least to most recently used
+    pub fn entries(&self) -> impl Iterator<Item = (&CacheKey, bool)> {
+        self.0.entries().map(|(key, result)| (key, *result))
+    }
+}
@@ -1,5 +1,6 @@
 use crate::note::NoteOutIndex;
-use ark_ff::Field;
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::{Field, PrimeField};
 use ark_r1cs_std::alloc::AllocVar;
 use ark_r1cs_std::boolean::Boolean;
 use ark_r1cs_std::eq::EqGadget;
@@ -8,193 +9,633 @@ use ark_r1cs_std::fields::nonnative::NonNativeFieldVar;
 use ark_r1cs_std::select::CondSelectGadget;
 use ark_relations::r1cs::{ConstraintSystemRef, Result as CSResult, SynthesisError};
 
-use super::inputs::{witness_in, witness_point_in, NoteVar, PublicInputVar};
+use super::inputs::{witness_in, witness_point_in, witness_u64_in, NoteVar, PublicInputVar};
+use super::policy::{OutputVars, SplitVars};
+use super::trace::Tracer;
 use super::{verify_signature, Circuit, IVC};
+use crate::poseidon::{PoseidonConfigs, ToCRH};
+use crate::FWrap;
+
+struct InputState<F: PrimeField> {
+    note_hash: FpVar<F>,
+    blind_note_hash: FpVar<F>,
+    state: FpVar<F>,
+    // index of the note is one of the outputs
+    is_out: Boolean<F>,
+}
+
+// recovers the hashes and the state of an input note given the other leaves of
+// the state in order, an issued note takes the leaf of its position
+#[allow(clippy::too_many_arguments)]
+fn input_state<F: PrimeField + Absorb>(
+    cs: ConstraintSystemRef<F>,
+    tracer: &Tracer<F>,
+    h: &PoseidonConfigs<F>,
+    note: &NoteVar<F>,
+    blind: &FpVar<F>,
+    siblings: &[FpVar<F>],
+    index_issue: &FpVar<F>,
+    index_out: &[FpVar<F>],
+) -> CSResult<InputState<F>> {
+    let outputs = index_out.len();
+    let mut is_at = index_out
+        .iter()
+        .map(|index_i| tracer.section("comparison", || note.out_index.is_eq(index_i)))
+        .collect::<CSResult<Vec<_>>>()?;
+    let is_issued = tracer.section("comparison", || note.out_index.is_eq(index_issue))?;
+    let position = NoteOutIndex::Issue.position();
+    is_at[position] = is_at[position].or(&is_issued)?;
+
+    // recover note hash
+    let note_hash = tracer.section("poseidon", || h.var_note(cs.clone(), note))?;
+
+    // recover blinded note hash
+    let blind_note_hash = tracer.section("poseidon", || {
+        h.var_blind_note(cs.clone(), &note_hash, blind)
+    })?;
+
+    // recover the state, siblings fill the leaves before and after the note
+    let mut leaves = vec![];
+    for i in 0..outputs {
+        let sibling = match i {
+            0 => siblings[0].clone(),
+            i if i == outputs - 1 => siblings[i - 1].clone(),
+            i => {
+                let is_after = Boolean::kary_or(&is_at[i + 1..])?;
+                CondSelectGadget::conditionally_select(&is_after, &siblings[i], &siblings[i - 1])?
+            }
+        };
+        leaves.push(CondSelectGadget::conditionally_select(
+            &is_at[i],
+            &blind_note_hash,
+            &sibling,
+        )?);
+    }
+    let state = tracer.section("poseidon", || h.var_state(cs.clone(), &leaves))?;
+
+    Ok(InputState {
+        note_hash,
+        blind_note_hash,
+        state,
+        is_out: Boolean::kary_or(&is_at)?,
+    })
+}
 
 pub(crate) fn synth<E: IVC>(cs: ConstraintSystemRef<E::Field>, cir: Circuit<E>) -> CSResult<()> {
     let pi = cir.public.as_ref();
     let aux = cir.aux.as_ref();
+    let tracer = Tracer::new(cs.clone(), cir.trace);
 
     let zero = E::Field::ZERO;
     let const_zero = FpVar::new_constant(cs.clone(), zero)?;
     let const_true = Boolean::new_constant(cs.clone(), true)?;
 
+    // the state has a leaf for each output
+    let outputs = E::OUTPUTS;
+    (2..=u8::MAX as usize)
+        .contains(&outputs)
+        .then_some(())
+        .ok_or(SynthesisError::Unsatisfiable)?;
+    aux.map_or(true, |e| {
+        e.outputs.len() == outputs && e.siblings.len() == outputs - 1
+    })
+    .then_some(())
+    .ok_or(SynthesisError::AssignmentMissing)?;
+
     let index_issue =
         FpVar::new_constant(cs.clone(), (NoteOutIndex::Issue {}).inner::<E::Field>())?;
-    let index_0 = FpVar::new_constant(cs.clone(), (NoteOutIndex::Out0 {}).inner::<E::Field>())?;
-    let index_1 = FpVar::new_constant(cs.clone(), (NoteOutIndex::Out1 {}).inner::<E::Field>())?;
+    let index_out = (0..outputs as u8)
+        .map(|i| FpVar::new_constant(cs.clone(), NoteOutIndex::Out(i).inner::<E::Field>()))
+        .collect::<CSResult<Vec<_>>>()?;
 
+    tracer.enter("public_input");
     let pi = PublicInputVar::new(cs.clone(), pi)?;
+    tracer.exit();
 
     // identity commitment integrity
+    tracer.enter("identity");
     let pubkey = witness_point_in(cs.clone(), aux, |e| *e.public_key.as_ref())?;
     let nullifier_key = witness_in(cs.clone(), aux, |e| e.nullifier_key)?;
-    let sender = cir
-        .h
-        .var_id_commitment(cs.clone(), &nullifier_key, &pubkey)?;
+    let sender = tracer.section("poseidon", || {
+        cir.h.var_id_commitment(cs.clone(), &nullifier_key, &pubkey)
+    })?;
     pi.sender.enforce_equal(&sender)?;
+    tracer.exit();
+
+    // asset integrity, the asset hash commits to the policy of the circuit so
+    // a proof of another circuit can't carry the notes of the asset.
+    // A step moves a single asset, every input and output note is built with
+    // this asset hash so value is conserved per asset by construction. Notes
+    // of several assets in one step would need an asset per output and value
+    // conserved per asset, which every step would pay for while no
+    // transaction moves more than one asset. A swap would also need the
+    // signatures of both parties, so it is left until there is one.
+    tracer.enter("asset");
+    let asset_issuer = witness_in(cs.clone(), aux, |e| e.asset_issuer)?;
+    let terms = witness_in(cs.clone(), aux, |e| e.asset_terms)?;
+    let policy = cir
+        .policy
+        .map(|policy| policy.commitment().inner())
+        .unwrap_or_default();
+    let policy = FpVar::new_constant(cs.clone(), policy)?;
+    let dual_control = Boolean::new_witness(cs.clone(), || {
+        aux.map(|e| e.dual_control)
+            .ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    let asset_hash = tracer.section("poseidon", || {
+        cir.h.var_asset(
+            cs.clone(),
+            &asset_issuer,
+            &terms,
+            &policy,
+            &dual_control.clone().into(),
+        )
+    })?;
+    pi.asset_hash.enforce_equal(&asset_hash)?;
+    tracer.exit();
 
     // Branch 1: IssueTx
-    let is_issue_tx = pi.step.is_eq(&const_zero)?;
-    let (sighash_issue, _note_hash, is_issue_tx) = {
-        let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_out))?;
-        let blind = witness_in(cs.clone(), aux, |e| e.blind_out_0)?;
+    tracer.enter("issue");
+    let is_issue_tx = tracer.section("comparison", || pi.step.is_eq(&const_zero))?;
+    let (sighash_issue, issued, is_issue_tx) = {
+        // the asset commits to its issuer, only the issuer can sign an issuance.
+        // The issuer is the sender or the key that signs.
+        let key_bound = Boolean::new_witness(cs.clone(), || {
+            aux.map(|e| e.key_bound)
+                .ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let issuer_key =
+            tracer.section("poseidon", || cir.h.var_issuer_key(cs.clone(), &pubkey))?;
+        let issuer = Boolean::select(&key_bound, &issuer_key, &pi.sender)?;
+        asset_issuer.conditional_enforce_equal(&issuer, &is_issue_tx)?;
+
+        let owner = witness_in(cs.clone(), aux, |e| e.outputs[0].owner)?;
+        let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.outputs[0].value))?;
+        let blind = witness_in(cs.clone(), aux, |e| e.outputs[0].blind)?;
+        let memo = witness_in(cs.clone(), aux, |e| e.outputs[0].memo)?;
+        let expiry_step = witness_in(cs.clone(), aux, |e| {
+            E::Field::from(e.outputs[0].expiry_step)
+        })?;
         let note = NoteVar::new(
             &pi.asset_hash,
-            &pi.sender,
+            &owner,
             &value,
             &pi.step,
             &const_zero,
             &index_issue,
+            &memo,
+            &expiry_step,
         );
 
         // recover note hash
-        let note_hash = cir.h.var_note(cs.clone(), &note)?;
+        let note_hash = tracer.section("poseidon", || cir.h.var_note(cs.clone(), &note))?;
         // recover blind note hash
-        let blind_note_hash = cir.h.var_blind_note(cs.clone(), &note_hash, &blind)?;
+        let blind_note_hash = tracer.section("poseidon", || {
+            cir.h.var_blind_note(cs.clone(), &note_hash, &blind)
+        })?;
 
         // initial state is asset hash. match it
         pi.state_in
             .conditional_enforce_equal(&pi.asset_hash, &is_issue_tx)?;
 
-        // recover the output state
-        let state_out = cir.h.var_state(cs.clone(), &const_zero, &blind_note_hash)?;
+        // recover the output state, the issued note is the only non empty leaf
+        let mut leaves = vec![const_zero.clone(); outputs];
+        leaves[NoteOutIndex::Issue.position()] = blind_note_hash;
+        let state_out = tracer.section("poseidon", || cir.h.var_state(cs.clone(), &leaves))?;
 
         pi.state_out
             .conditional_enforce_equal(&state_out, &is_issue_tx)?;
 
         // recover sighash
-        let sighash = cir
-            .h
-            .var_sighash(cs.clone(), &const_zero, &const_zero, &note_hash)?;
-
-        (sighash, note_hash, is_issue_tx)
+        let sighash = tracer.section("poseidon", || {
+            cir.h.var_sighash(
+                cs.clone(),
+                &const_zero,
+                &[const_zero.clone(), note_hash.clone()],
+            )
+        })?;
+
+        // the issued note with its blind is disclosed
+        let mut issued = note.to_crh();
+        issued.push(blind);
+
+        (sighash, issued, is_issue_tx)
     };
+    tracer.exit();
+
+    // SplitTx and BurnTx spend an input note, a burn is marked with a non zero burn value
+    let is_burn_tx = tracer
+        .section("comparison", || pi.burn_value.is_eq(&const_zero))?
+        .not();
+    is_issue_tx
+        .and(&is_burn_tx)?
+        .enforce_equal(&Boolean::FALSE)?;
+    let is_spend_tx = is_issue_tx.not();
+    let is_split_tx = is_spend_tx.and(&is_burn_tx.not())?;
+
+    // MergeTx is a split that spends a second note, marked with its nullifier
+    let is_merge_tx = tracer
+        .section("comparison", || pi.merge_nullifier.is_eq(&const_zero))?
+        .not();
+    is_merge_tx
+        .and(&is_split_tx.not())?
+        .enforce_equal(&Boolean::FALSE)?;
+
+    // enforce input state integrity
+    tracer.enter("input");
+    let expiry_in = witness_in(cs.clone(), aux, |e| E::Field::from(e.expiry_in))?;
+    let (blind_note_in_hash, note_in_hash, value_in, spent) = {
+        let siblings = (0..outputs - 1)
+            .map(|i| witness_in(cs.clone(), aux, |e| e.siblings[i]))
+            .collect::<CSResult<Vec<_>>>()?;
+        let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_in))?;
+        let blind = witness_in(cs.clone(), aux, |e| e.blind_in)?;
+        let parent_note = witness_in(cs.clone(), aux, |e| e.parent)?;
+        let memo = witness_in(cs.clone(), aux, |e| e.memo_in)?;
+
+        let index = witness_in(cs.clone(), aux, |e| e.input_index.inner::<E::Field>())?;
+
+        // the input is the output of the previous step
+        let step_in = &pi.step - E::Field::ONE;
+        let note_in = NoteVar::new(
+            &pi.asset_hash,
+            &pi.sender,
+            &value,
+            &step_in,
+            &parent_note,
+            &index,
+            &memo,
+            &expiry_in,
+        );
 
-    // Branch 2: SplitTx
-    let sighash_split = {
-        let is_split_tx = is_issue_tx.not();
-
-        // enforce input state integrity
-        let (blind_note_in_hash, note_in_hash, value_in) = {
-            let sibling = witness_in(cs.clone(), aux, |e| e.sibling)?;
-            let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_in))?;
-            let blind = witness_in(cs.clone(), aux, |e| e.blind_in)?;
-            let parent_note = witness_in(cs.clone(), aux, |e| e.parent)?;
-
-            let index = witness_in(cs.clone(), aux, |e| e.input_index.inner::<E::Field>())?;
-            // enforce index to be either ::Out0 or ::Out1
-            let is_i0 = index.is_eq(&index_0)?;
-            let is_i1 = index.is_eq(&index_1)?;
-            is_i0.or(&is_i1)?.enforce_equal(&const_true)?;
-
-            let note_in = NoteVar::new(
-                &pi.asset_hash,
-                &pi.sender,
-                &value,
-                &pi.step,
-                &parent_note,
-                &index,
-            );
-
-            // recover note hash
-            let note_hash = cir.h.var_note(cs.clone(), &note_in)?;
-
-            // recover blinded note hash
-            let blind_note_hash = cir.h.var_blind_note(cs.clone(), &note_hash, &blind)?;
-
-            // recover input state
-            let lhs = CondSelectGadget::conditionally_select(&is_i0, &blind_note_hash, &sibling)?;
-            let rhs = CondSelectGadget::conditionally_select(&is_i1, &sibling, &blind_note_hash)?;
-            let state_in = cir.h.var_state(cs.clone(), &lhs, &rhs)?;
+        // recover input state
+        let InputState {
+            note_hash,
+            blind_note_hash,
+            state: state_in,
+            is_out,
+        } = input_state(
+            cs.clone(),
+            &tracer,
+            cir.h,
+            &note_in,
+            &blind,
+            &siblings,
+            &index_issue,
+            &index_out,
+        )?;
 
-            // match with public input
-            pi.state_in
-                .conditional_enforce_equal(&state_in, &is_split_tx)?;
+        // enforce index to be one of the outputs
+        is_out.conditional_enforce_equal(&const_true, &is_spend_tx)?;
 
-            // enforce nullifier integrity
-            let nullifier = cir
-                .h
-                .var_nullifier(cs.clone(), &note_hash, &nullifier_key)?;
+        // match with public input
+        pi.state_in
+            .conditional_enforce_equal(&state_in, &is_spend_tx)?;
 
-            // match with public input
-            pi.nullifier
-                .conditional_enforce_equal(&nullifier, &is_split_tx)?;
+        // enforce nullifier integrity
+        let nullifier = tracer.section("poseidon", || {
+            cir.h.var_nullifier(cs.clone(), &note_hash, &nullifier_key)
+        })?;
 
-            (blind_note_hash, note_hash, value)
-        };
+        // match with public input
+        pi.nullifier
+            .conditional_enforce_equal(&nullifier, &is_spend_tx)?;
 
-        // enforce output state integrity
-        let (note_out_hash_0, note_out_hash_1) = {
-            let value_out_1 = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_out))?;
-            let blind_1 = witness_in(cs.clone(), aux, |e| e.blind_out_1)?;
-            let note_out_1 = NoteVar {
-                asset_hash: pi.asset_hash.clone(),
-                owner: pi.sender.clone(),
-                value: value_out_1.clone(),
-                step: pi.step.clone(),
-                parent_note: blind_note_in_hash.clone(),
-                out_index: index_1,
-            };
-            // recover note hash
-            let note_hash_1 = cir.h.var_note(cs.clone(), &note_out_1)?;
+        // a split happens at the latest at the expiry step, burns are not
+        // bound. The difference is range checked so that it doesn't wrap.
+        let validity = witness_u64_in(
+            cs.clone(),
+            cir.public.as_ref().zip(aux).as_ref(),
+            |(p, e)| (e.expiry_in as u64).wrapping_sub(p.step as u64),
+        )?;
+        (&expiry_in - &pi.step).conditional_enforce_equal(&validity, &is_split_tx)?;
 
-            // recover blinded note hash
-            let blind_note_hash_1 = cir.h.var_blind_note(cs.clone(), &note_hash_1, &blind_1)?;
-            let value_out_0 = value_in - &value_out_1;
+        // the input note with its blind is disclosed
+        let mut spent = note_in.to_crh();
+        spent.push(blind);
 
-            let max = FpVar::new_constant(cs.clone(), E::Field::from(u64::MAX))?;
-            value_out_0.enforce_cmp(&value_out_1, std::cmp::Ordering::Less, true)?;
-            value_out_1.enforce_cmp(&max, std::cmp::Ordering::Less, true)?; // maybe not required
+        (blind_note_hash, note_hash, value, spent)
+    };
+    tracer.exit();
+
+    // enforce integrity of the second input of a merge
+    tracer.enter("merge");
+    let (note_merged_hash, value_merged) = {
+        let siblings = (0..outputs - 1)
+            .map(|i| witness_in(cs.clone(), aux, |e| e.merge.siblings[i]))
+            .collect::<CSResult<Vec<_>>>()?;
+        let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.merge.value))?;
+        let blind = witness_in(cs.clone(), aux, |e| e.merge.blind)?;
+        let parent_note = witness_in(cs.clone(), aux, |e| e.merge.parent)?;
+        let index = witness_in(cs.clone(), aux, |e| e.merge.index.inner::<E::Field>())?;
+        let memo = witness_in(cs.clone(), aux, |e| e.merge.memo)?;
+        // the note comes from another history so its step is free
+        let step = witness_in(cs.clone(), aux, |e| E::Field::from(e.merge.step))?;
+
+        // the note is of the same asset and owned by the sender
+        let note_merged = NoteVar::new(
+            &pi.asset_hash,
+            &pi.sender,
+            &value,
+            &step,
+            &parent_note,
+            &index,
+            &memo,
+            &expiry_in,
+        );
 
-            let blind_0 = witness_in(cs.clone(), aux, |e| e.blind_out_0)?;
-            let receiver = witness_in(cs.clone(), aux, |e| e.receiver)?;
-            let note_out_0 = NoteVar {
-                asset_hash: pi.asset_hash.clone(),
-                owner: receiver,
-                value: value_out_0.clone(),
-                step: pi.step.clone(),
-                parent_note: blind_note_in_hash,
-                out_index: index_0,
-            };
-            // recover note hash
-            let note_hash_0 = cir.h.var_note(cs.clone(), &note_out_0)?;
+        // the merged note is spent at the step after its own, at the latest
+        // at the expiry step as the main input
+        let validity = witness_u64_in(cs.clone(), aux, |e| {
+            (e.expiry_in as u64).wrapping_sub(e.merge.step as u64 + 1)
+        })?;
+        (&expiry_in - &step - E::Field::ONE).conditional_enforce_equal(&validity, &is_merge_tx)?;
+
+        // recover the state and match with public input
+        let InputState {
+            note_hash,
+            state,
+            is_out,
+            ..
+        } = input_state(
+            cs.clone(),
+            &tracer,
+            cir.h,
+            &note_merged,
+            &blind,
+            &siblings,
+            &index_issue,
+            &index_out,
+        )?;
+        is_out.conditional_enforce_equal(&const_true, &is_merge_tx)?;
+        pi.merge_state_in
+            .conditional_enforce_equal(&state, &is_merge_tx)?;
+
+        // enforce nullifier integrity
+        let nullifier = tracer.section("poseidon", || {
+            cir.h.var_nullifier(cs.clone(), &note_hash, &nullifier_key)
+        })?;
+        pi.merge_nullifier
+            .conditional_enforce_equal(&nullifier, &is_merge_tx)?;
+
+        // a note can't be merged with itself
+        tracer
+            .section("comparison", || pi.merge_nullifier.is_eq(&pi.nullifier))?
+            .and(&is_merge_tx)?
+            .enforce_equal(&Boolean::FALSE)?;
+
+        // value counts only if the tx is a merge
+        let value = CondSelectGadget::conditionally_select(&is_merge_tx, &value, &const_zero)?;
+        (note_hash, value)
+    };
+    tracer.exit();
+    let value_in = value_in + &value_merged;
+
+    // the disclosed note is encrypted to the viewing key of the sender so
+    // that an auditor holding the key can decrypt every step the sender
+    // signed. A merge discloses only its first input.
+    tracer.enter("disclosure");
+    {
+        let viewing_key = tracer.section("poseidon", || {
+            cir.h.var_viewing_key(cs.clone(), &nullifier_key)
+        })?;
+        let nonce = witness_in(cs.clone(), aux, |e| e.disclosure_nonce)?;
+        let plaintext = issued
+            .iter()
+            .zip(spent.iter())
+            .map(|(issued, spent)| Boolean::select(&is_issue_tx, issued, spent))
+            .collect::<CSResult<Vec<_>>>()?;
+        let (ciphertext, tag) = tracer.section("poseidon", || {
+            cir.h
+                .var_encrypt(cs.clone(), &viewing_key, &nonce, &plaintext)
+        })?;
+        let disclosure = tracer.section("poseidon", || {
+            cir.h.var_disclosure(cs.clone(), &nonce, &ciphertext, &tag)
+        })?;
+        pi.disclosure.enforce_equal(&disclosure)?;
+    }
+    tracer.exit();
 
-            // recover blinded note hash
-            let blind_note_hash_0 = cir.h.var_blind_note(cs.clone(), &note_hash_1, &blind_0)?;
+    // Branch 2: SplitTx
+    tracer.enter("split");
+    let sighash_split = {
+        // enforce output state integrity
+        tracer.enter("output");
+        let (note_out_hashes, output_vars) = {
+            // values are 64 bits so their sum can't wrap around
+            tracer.enter("range_check");
+            let values = (0..outputs)
+                .map(|i| witness_u64_in(cs.clone(), aux, |e| e.outputs[i].value))
+                .collect::<CSResult<Vec<_>>>()?;
+            tracer.exit();
+
+            let mut note_hashes = vec![];
+            let mut blind_note_hashes = vec![];
+            let mut blinds = vec![];
+            let mut output_vars = vec![];
+            for (i, value) in values.into_iter().enumerate() {
+                let owner = witness_in(cs.clone(), aux, |e| e.outputs[i].owner)?;
+                let blind = witness_in(cs.clone(), aux, |e| e.outputs[i].blind)?;
+                let memo = witness_in(cs.clone(), aux, |e| e.outputs[i].memo)?;
+                let note_out = NoteVar::new(
+                    &pi.asset_hash,
+                    &owner,
+                    &value,
+                    &pi.step,
+                    &blind_note_in_hash,
+                    &index_out[i],
+                    &memo,
+                    &expiry_in,
+                );
+                // recover note hash
+                let note_hash =
+                    tracer.section("poseidon", || cir.h.var_note(cs.clone(), &note_out))?;
+
+                // recover blinded note hash
+                let blind_note_hash = tracer.section("poseidon", || {
+                    cir.h.var_blind_note(cs.clone(), &note_hash, &blind)
+                })?;
+
+                note_hashes.push(note_hash);
+                blind_note_hashes.push(blind_note_hash);
+                blinds.push(blind);
+                output_vars.push(OutputVars { owner, value });
+            }
+
+            // value is conserved, it applies only to splits since a burn has no outputs
+            let value_out = output_vars
+                .iter()
+                .fold(const_zero.clone(), |sum, output| sum + &output.value);
+            value_out.conditional_enforce_equal(&value_in, &is_split_tx)?;
+
+            // the change is the second output and goes back to the sender, a
+            // merge leaves only the change
+            let change = NoteOutIndex::CHANGE.position();
+            output_vars[change]
+                .owner
+                .conditional_enforce_equal(&pi.sender, &is_split_tx)?;
+            for (_, output) in output_vars.iter().enumerate().filter(|(i, _)| *i != change) {
+                output
+                    .value
+                    .conditional_enforce_equal(&const_zero, &is_merge_tx)?;
+            }
+
+            // a split bound to its receiver exposes the tag of the owner of
+            // the first sent note, other txs are never bound
+            let is_bound = tracer
+                .section("comparison", || pi.receiver_tag.is_eq(&const_zero))?
+                .not();
+            is_bound
+                .and(&is_split_tx.not())?
+                .enforce_equal(&Boolean::FALSE)?;
+            let receiver = NoteOutIndex::RECEIVER.position();
+            let receiver_tag = tracer.section("poseidon", || {
+                cir.h
+                    .var_receiver_tag(cs.clone(), &output_vars[receiver].owner, &blinds[receiver])
+            })?;
+            pi.receiver_tag
+                .conditional_enforce_equal(&receiver_tag, &is_bound)?;
 
             // recover the output state
-            let state_out = cir
-                .h
-                .var_state(cs.clone(), &blind_note_hash_0, &blind_note_hash_1)?;
+            let state_out = tracer.section("poseidon", || {
+                cir.h.var_state(cs.clone(), &blind_note_hashes)
+            })?;
 
             // match with public input
             pi.state_out
                 .conditional_enforce_equal(&state_out, &is_split_tx)?;
 
-            (note_hash_0, note_hash_1)
+            (note_hashes, output_vars)
         };
+        tracer.exit();
+
+        // compose the asset policy if there is any
+        if let Some(policy) = cir.policy {
+            tracer.enter("policy");
+            let split = SplitVars {
+                asset_hash: pi.asset_hash.clone(),
+                sender: pi.sender.clone(),
+                step: pi.step.clone(),
+                value_in: value_in.clone(),
+                outputs: output_vars,
+            };
+            policy.enforce(cs.clone(), &split, &is_split_tx)?;
+            tracer.exit();
+        }
+
+        // recover sighash
+        let sighash_split = tracer.section("poseidon", || {
+            cir.h
+                .var_sighash(cs.clone(), &note_in_hash, &note_out_hashes)
+        })?;
+        let sighash_merge = tracer.section("poseidon", || {
+            cir.h.var_sighash_merge(
+                cs.clone(),
+                &note_in_hash,
+                &note_merged_hash,
+                &note_out_hashes,
+            )
+        })?;
+        CondSelectGadget::conditionally_select(&is_merge_tx, &sighash_merge, &sighash_split)?
+    };
+    tracer.exit();
+
+    // Branch 3: BurnTx
+    tracer.enter("burn");
+    let sighash_burn = {
+        // whole value of the input note is burnt
+        pi.burn_value
+            .conditional_enforce_equal(&value_in, &is_burn_tx)?;
+
+        // and nothing is left behind
+        pi.state_out
+            .conditional_enforce_equal(&const_zero, &is_burn_tx)?;
+
+        // only a burn can redeem the note to a payout
+        let is_redeem_tx = tracer
+            .section("comparison", || pi.settlement.is_eq(&const_zero))?
+            .not();
+        is_redeem_tx
+            .and(&is_burn_tx.not())?
+            .enforce_equal(&Boolean::FALSE)?;
 
         // recover sighash
-        cir.h.var_sighash(
+        let sighash_burn = tracer.section("poseidon", || {
+            cir.h
+                .var_sighash_burn(cs.clone(), &note_in_hash, &pi.burn_value)
+        })?;
+        let sighash_redeem = tracer.section("poseidon", || {
+            cir.h
+                .var_sighash_redeem(cs.clone(), &note_in_hash, &pi.burn_value, &pi.settlement)
+        })?;
+
+        // a redemption of a dual-control asset is co-signed by the key the
+        // asset is bound to. An asset bound to the address of its issuer
+        // can't be redeemed under dual control, the digest of a key never
+        // opens to an address.
+        tracer.enter("cosignature");
+        let is_cosigned = is_redeem_tx.and(&dual_control)?;
+        let cosigner = witness_point_in(cs.clone(), aux, |e| {
+            e.cosignature
+                .as_ref()
+                .map(|c| *c.public_key.as_ref())
+                .unwrap_or_default()
+        })?;
+        let cosig_r = witness_point_in(cs.clone(), aux, |e| {
+            e.cosignature
+                .as_ref()
+                .map(|c| *c.signature.r())
+                .unwrap_or_default()
+        })?;
+        let cosig_s = NonNativeFieldVar::new_witness(cs.clone(), || {
+            aux.map(|e| {
+                e.cosignature
+                    .as_ref()
+                    .map(|c| *c.signature.s())
+                    .unwrap_or_default()
+            })
+            .ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let cosigner_key =
+            tracer.section("poseidon", || cir.h.var_issuer_key(cs.clone(), &cosigner))?;
+        asset_issuer.conditional_enforce_equal(&cosigner_key, &is_cosigned)?;
+        verify_signature(
             cs.clone(),
-            &note_in_hash,
-            &note_out_hash_0,
-            &note_out_hash_1,
-        )?
+            &cir.h.eddsa,
+            &cosigner,
+            &cosig_r,
+            &cosig_s,
+            &sighash_redeem,
+            &is_cosigned,
+        )?;
+        tracer.exit();
+        CondSelectGadget::conditionally_select(&is_redeem_tx, &sighash_redeem, &sighash_burn)?
     };
+    tracer.exit();
 
     // select sighash based on the tx type
+    let sighash_spend =
+        CondSelectGadget::conditionally_select(&is_burn_tx, &sighash_burn, &sighash_split)?;
     let sighash =
-        CondSelectGadget::conditionally_select(&is_issue_tx, &sighash_issue, &sighash_split)?;
+        CondSelectGadget::conditionally_select(&is_issue_tx, &sighash_issue, &sighash_spend)?;
 
     // recover signature & verify
+    tracer.enter("signature");
     let sig_r = witness_point_in(cs.clone(), aux, |e| *e.signature.r())?;
     let sig_s = NonNativeFieldVar::new_witness(cs.clone(), || {
         aux.map(|e| e.signature.s())
             .ok_or(SynthesisError::AssignmentMissing)
     })?;
-    verify_signature(cs.clone(), &cir.h.eddsa, &pubkey, &sig_r, &sig_s, &sighash)?;
+    verify_signature(
+        cs.clone(),
+        &cir.h.eddsa,
+        &pubkey,
+        &sig_r,
+        &sig_s,
+        &sighash,
+        &Boolean::TRUE,
+    )?;
+    tracer.exit();
 
     Ok(())
 }
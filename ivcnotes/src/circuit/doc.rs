@@ -0,0 +1,108 @@
+use super::abi::LAYOUT;
+use super::trace::{Section, Trace};
+use super::{Circuit, IVC};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, Result as CSResult, SynthesisMode,
+};
+use std::cell::RefCell;
+use std::fmt::Write;
+
+// `CircuitDoc` is a machine readable description of the step circuit
+// generated by synthesizing it in setup mode
+#[derive(Clone, Debug)]
+pub struct CircuitDoc {
+    // public inputs in allocation order
+    pub public_inputs: Vec<&'static str>,
+    pub num_constraints: usize,
+    pub num_witnesses: usize,
+    // gadget tree, also gives the witness layout through witness offsets
+    pub sections: Vec<Section>,
+}
+
+impl CircuitDoc {
+    pub fn generate<E: IVC>(circuit: Circuit<E>) -> CSResult<Self> {
+        let cs = ConstraintSystem::<E::Field>::new_ref();
+        cs.set_mode(SynthesisMode::Setup);
+        let trace = RefCell::new(Trace::default());
+        circuit
+            .with_trace(&trace)
+            .generate_constraints(cs.clone())?;
+
+        Ok(Self {
+            public_inputs: LAYOUT.iter().map(|field| field.name()).collect(),
+            num_constraints: cs.num_constraints(),
+            num_witnesses: cs.num_witness_variables(),
+            sections: trace.into_inner().sections,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        fn section(out: &mut String, s: &Section) {
+            write!(
+                out,
+                "{{\"name\":\"{}\",\"constraints\":{},\"witnesses\":{},\"witness_offset\":{},\"children\":[",
+                s.name, s.constraints, s.witnesses, s.witness_offset
+            )
+            .unwrap();
+            for (i, child) in s.children.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                section(out, child);
+            }
+            out.push_str("]}");
+        }
+
+        let mut out = String::new();
+        let public_inputs = self
+            .public_inputs
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            out,
+            "{{\"public_inputs\":[{}],\"num_constraints\":{},\"num_witnesses\":{},\"sections\":[",
+            public_inputs, self.num_constraints, self.num_witnesses
+        )
+        .unwrap();
+        for (i, s) in self.sections.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            section(&mut out, s);
+        }
+        out.push_str("]}");
+        out
+    }
+
+    pub fn to_markdown(&self) -> String {
+        fn section(out: &mut String, s: &Section, depth: usize) {
+            writeln!(
+                out,
+                "{}- `{}`: {} constraints, witnesses {}..{}",
+                "  ".repeat(depth),
+                s.name,
+                s.constraints,
+                s.witness_offset,
+                s.witness_offset + s.witnesses
+            )
+            .unwrap();
+            s.children
+                .iter()
+                .for_each(|child| section(out, child, depth + 1));
+        }
+
+        let mut out = String::new();
+        writeln!(out, "# Step circuit\n").unwrap();
+        writeln!(out, "- constraints: {}", self.num_constraints).unwrap();
+        writeln!(out, "- witnesses: {}\n", self.num_witnesses).unwrap();
+        writeln!(out, "## Public inputs\n").unwrap();
+        for (i, name) in self.public_inputs.iter().enumerate() {
+            writeln!(out, "{}. `{}`", i, name).unwrap();
+        }
+        writeln!(out, "\n## Gadgets\n").unwrap();
+        self.sections.iter().for_each(|s| section(&mut out, s, 0));
+        out
+    }
+}
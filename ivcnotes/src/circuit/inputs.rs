@@ -1,19 +1,32 @@
+use super::abi::{PublicInputField, LAYOUT};
 use super::IVC;
-use crate::note::NoteOutIndex;
-use crate::poseidon::ToCRH;
-use crate::{Address, AssetHash, Blind, BlindNoteHash, FWrap, Nullifier, NullifierKey, StateHash};
-use ark_ec::twisted_edwards::Affine;
-use ark_ec::twisted_edwards::TECurveConfig;
+use crate::asset::Asset;
+use crate::burn::Cosignature;
+use crate::note::{Note, NoteOutIndex};
+use crate::{
+    Address, AssetHash, Blind, BlindNoteHash, DisclosureHash, FWrap, MemoHash, Nullifier,
+    NullifierKey, ReceiverTag, SettlementRef, StateHash,
+};
 use ark_ff::PrimeField;
-use ark_r1cs_std::alloc::{AllocVar, AllocationMode};
-use ark_r1cs_std::fields::fp::FpVar;
-use ark_r1cs_std::groups::curves::twisted_edwards::AffineVar;
-use ark_relations::r1cs::{Namespace, Result as CSResult, SynthesisError};
 use arkeddsa::signature::Signature;
 use arkeddsa::PublicKey;
-use std::borrow::Borrow;
 use std::fmt::Debug;
 
+#[cfg(feature = "prover")]
+use {
+    crate::poseidon::ToCRH,
+    ark_ec::twisted_edwards::Affine,
+    ark_ec::twisted_edwards::TECurveConfig,
+    ark_r1cs_std::alloc::{AllocVar, AllocationMode},
+    ark_r1cs_std::boolean::Boolean,
+    ark_r1cs_std::fields::fp::FpVar,
+    ark_r1cs_std::groups::curves::twisted_edwards::AffineVar,
+    ark_r1cs_std::uint64::UInt64,
+    ark_relations::r1cs::{Namespace, Result as CSResult, SynthesisError},
+    std::borrow::Borrow,
+};
+
+#[cfg(feature = "prover")]
 pub(super) fn var_in<Z, F: PrimeField, V, Var: AllocVar<V, F>, T: Borrow<V> + Clone>(
     cs: impl Into<Namespace<F>>,
     st: Option<&Z>,
@@ -30,6 +43,7 @@ pub(super) fn var_in<Z, F: PrimeField, V, Var: AllocVar<V, F>, T: Borrow<V> + Cl
     )
 }
 
+#[cfg(feature = "prover")]
 pub(super) fn witness_in<Z, F: PrimeField, T: Borrow<F> + Clone>(
     cs: impl Into<Namespace<F>>,
     st: Option<&Z>,
@@ -38,6 +52,20 @@ pub(super) fn witness_in<Z, F: PrimeField, T: Borrow<F> + Clone>(
     var_in::<_, _, _, _, _>(cs.into().cs(), st, access, AllocationMode::Witness)
 }
 
+// allocates a 64 bit value, the bit decomposition constrains the range
+#[cfg(feature = "prover")]
+pub(super) fn witness_u64_in<Z, F: PrimeField>(
+    cs: impl Into<Namespace<F>>,
+    st: Option<&Z>,
+    access: impl FnOnce(&Z) -> u64,
+) -> CSResult<FpVar<F>> {
+    let value = UInt64::new_witness(cs.into().cs(), || {
+        st.map(access).ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    Boolean::le_bits_to_fp_var(&value.to_bits_le())
+}
+
+#[cfg(feature = "prover")]
 pub(super) fn witness_point_in<Z, F: PrimeField, TE: TECurveConfig<BaseField = F> + Clone>(
     cs: impl Into<Namespace<F>>,
     st: Option<&Z>,
@@ -59,6 +87,7 @@ impl<F: PrimeField> PublicInput<F> {
         state_out: &StateHash<F>,
         step: u32,
         nullifier: &Nullifier<F>,
+        burn_value: u64,
     ) -> Self {
         Self {
             asset_hash: *asset_hash,
@@ -67,18 +96,88 @@ impl<F: PrimeField> PublicInput<F> {
             state_out: *state_out,
             step,
             nullifier: *nullifier,
+            burn_value,
+            settlement: SettlementRef::default(),
+            merge_state_in: StateHash::default(),
+            merge_nullifier: Nullifier::default(),
+            receiver_tag: ReceiverTag::default(),
+            disclosure: DisclosureHash::default(),
+        }
+    }
+
+    // a burn that redeems the note to the referenced payout
+    pub(crate) fn with_settlement(mut self, settlement: &SettlementRef<F>) -> Self {
+        self.settlement = *settlement;
+        self
+    }
+
+    // a split that also consumes the note of `merge_state_in`
+    pub(crate) fn with_merge(
+        mut self,
+        merge_state_in: &StateHash<F>,
+        merge_nullifier: &Nullifier<F>,
+    ) -> Self {
+        self.merge_state_in = *merge_state_in;
+        self.merge_nullifier = *merge_nullifier;
+        self
+    }
+
+    // a split that is bound to the receiver of its first sent note
+    pub(crate) fn with_receiver_tag(mut self, receiver_tag: &ReceiverTag<F>) -> Self {
+        self.receiver_tag = *receiver_tag;
+        self
+    }
+
+    // commitment to the note the step discloses to the viewing key of the sender
+    pub(crate) fn with_disclosure(mut self, disclosure: &DisclosureHash<F>) -> Self {
+        self.disclosure = *disclosure;
+        self
+    }
+
+    pub(crate) fn get(&self, field: PublicInputField) -> F {
+        match field {
+            PublicInputField::AssetHash => self.asset_hash.inner(),
+            PublicInputField::Sender => self.sender.inner(),
+            PublicInputField::StateIn => self.state_in.inner(),
+            PublicInputField::StateOut => self.state_out.inner(),
+            PublicInputField::Step => F::from(self.step as u64),
+            PublicInputField::Nullifier => self.nullifier.inner(),
+            PublicInputField::BurnValue => F::from(self.burn_value),
+            PublicInputField::Settlement => self.settlement.inner(),
+            PublicInputField::MergeStateIn => self.merge_state_in.inner(),
+            PublicInputField::MergeNullifier => self.merge_nullifier.inner(),
+            PublicInputField::ReceiverTag => self.receiver_tag.inner(),
+            PublicInputField::Disclosure => self.disclosure.inner(),
         }
     }
 
     pub(crate) fn to_verifier(&self) -> Vec<F> {
-        vec![
-            self.asset_hash.inner(),
-            self.sender.inner(),
-            self.state_in.inner(),
-            self.state_out.inner(),
-            F::from(self.step as u64),
-            self.nullifier.inner(),
-        ]
+        LAYOUT.iter().map(|field| self.get(*field)).collect()
+    }
+
+    // inverse of `to_verifier`, none if an integer doesn't fit its type
+    pub(crate) fn from_verifier(elements: &[F]) -> Option<Self> {
+        (elements.len() == LAYOUT.len()).then_some(())?;
+        let at = |field: PublicInputField| elements[field.index()];
+        let int = |field| {
+            let bigint = at(field).into_bigint();
+            let limbs = bigint.as_ref();
+            limbs[1..].iter().all(|limb| *limb == 0).then_some(limbs[0])
+        };
+        Some(Self {
+            asset_hash: at(PublicInputField::AssetHash).into(),
+            sender: at(PublicInputField::Sender).into(),
+            state_in: at(PublicInputField::StateIn).into(),
+            state_out: at(PublicInputField::StateOut).into(),
+            step: u32::try_from(int(PublicInputField::Step)?).ok()?,
+            nullifier: at(PublicInputField::Nullifier).into(),
+            burn_value: int(PublicInputField::BurnValue)?,
+            settlement: at(PublicInputField::Settlement).into(),
+            merge_state_in: at(PublicInputField::MergeStateIn).into(),
+            merge_nullifier: at(PublicInputField::MergeNullifier).into(),
+            receiver_tag: at(PublicInputField::ReceiverTag).into(),
+            disclosure: at(PublicInputField::Disclosure).into(),
+        })
     }
 }
 
@@ -88,16 +187,31 @@ pub struct PublicInput<F: PrimeField> {
     pub(crate) asset_hash: AssetHash<F>,
     // sender of the note
     pub(crate) sender: Address<F>,
-    // input state `state_in = hash(sibling, input_note)` or `state_in = hash(input_note, sibling)`
+    // input state, the hash of the input note and its siblings in the order of output index
     pub(crate) state_in: StateHash<F>,
-    // output state `state_out = hash(note_out_0, note_out_1)`
+    // output state `state_out = hash(note_out_0, note_out_1, ...)`
     pub(crate) state_out: StateHash<F>,
     // number of steps so far in the ivc propagation
     pub(crate) step: u32,
     // nullifier of the spent note
     pub(crate) nullifier: Nullifier<F>,
+    // value of the burnt input note, zero if the note is not burnt
+    pub(crate) burn_value: u64,
+    // payout reference of a redeemed note, zero if the burn is not a redemption
+    pub(crate) settlement: SettlementRef<F>,
+    // state of the second input note of a merge, zero if the tx is not a merge
+    pub(crate) merge_state_in: StateHash<F>,
+    // nullifier of the second input note of a merge, zero if the tx is not a merge
+    pub(crate) merge_nullifier: Nullifier<F>,
+    // tag of the receiver of the first sent note, zero if the tx is not bound
+    // to its receiver
+    pub(crate) receiver_tag: ReceiverTag<F>,
+    // commitment to the input note, or the issued note, encrypted to the
+    // viewing key of the sender
+    pub(crate) disclosure: DisclosureHash<F>,
 }
 
+#[cfg(feature = "prover")]
 #[derive(Debug, Clone)]
 pub struct PublicInputVar<F: PrimeField> {
     pub(crate) asset_hash: FpVar<F>,
@@ -106,8 +220,15 @@ pub struct PublicInputVar<F: PrimeField> {
     pub(crate) state_out: FpVar<F>,
     pub(crate) step: FpVar<F>,
     pub(crate) nullifier: FpVar<F>,
+    pub(crate) burn_value: FpVar<F>,
+    pub(crate) settlement: FpVar<F>,
+    pub(crate) merge_state_in: FpVar<F>,
+    pub(crate) merge_nullifier: FpVar<F>,
+    pub(crate) receiver_tag: FpVar<F>,
+    pub(crate) disclosure: FpVar<F>,
 }
 
+#[cfg(feature = "prover")]
 impl<F: PrimeField> PublicInputVar<F> {
     fn input_in<Z, T: Borrow<F> + Clone>(
         cs: impl Into<Namespace<F>>,
@@ -122,27 +243,81 @@ impl<F: PrimeField> PublicInputVar<F> {
         pi: Option<&PublicInput<F>>,
     ) -> CSResult<PublicInputVar<F>> {
         let cs = cs.into().cs();
-        let asset_hash = Self::input_in(cs.clone(), pi, |e| e.asset_hash)?;
-        let sender = Self::input_in(cs.clone(), pi, |e| e.sender)?;
-        let state_in = Self::input_in(cs.clone(), pi, |e| e.state_in)?;
-        let state_out = Self::input_in(cs.clone(), pi, |e| e.state_out)?;
-        let nullifier = Self::input_in(cs.clone(), pi, |e| e.nullifier)?;
-        let step = Self::input_in(cs.clone(), pi, |e| F::from(e.step as u64))?;
+        // allocate in the layout order
+        let vars = LAYOUT
+            .iter()
+            .map(|field| Self::input_in(cs.clone(), pi, |e| e.get(*field)))
+            .collect::<CSResult<Vec<_>>>()?;
+        let var = |field: PublicInputField| vars[field.index()].clone();
         Ok(PublicInputVar {
-            asset_hash,
-            sender,
-            state_in,
-            state_out,
-            step,
-            nullifier,
+            asset_hash: var(PublicInputField::AssetHash),
+            sender: var(PublicInputField::Sender),
+            state_in: var(PublicInputField::StateIn),
+            state_out: var(PublicInputField::StateOut),
+            step: var(PublicInputField::Step),
+            nullifier: var(PublicInputField::Nullifier),
+            burn_value: var(PublicInputField::BurnValue),
+            settlement: var(PublicInputField::Settlement),
+            merge_state_in: var(PublicInputField::MergeStateIn),
+            merge_nullifier: var(PublicInputField::MergeNullifier),
+            receiver_tag: var(PublicInputField::ReceiverTag),
+            disclosure: var(PublicInputField::Disclosure),
         })
     }
 }
 
+// owner, value, blind, memo and expiry of an output note, the outputs of a
+// split inherit the expiry of the input
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Output<F: PrimeField> {
+    pub(crate) owner: Address<F>,
+    pub(crate) value: u64,
+    pub(crate) blind: Blind<F>,
+    pub(crate) memo: MemoHash<F>,
+    pub(crate) expiry_step: u32,
+}
+
+impl<F: PrimeField> From<&Note<F>> for Output<F> {
+    fn from(note: &Note<F>) -> Self {
+        Self {
+            owner: note.owner,
+            value: note.value,
+            blind: note.blind,
+            memo: note.memo,
+            expiry_step: note.expiry_step,
+        }
+    }
+}
+
+// second input note of a merge, owned by the sender of the first one
+#[derive(Debug, Clone)]
+pub struct MergeInput<F: PrimeField> {
+    pub(crate) parent: BlindNoteHash<F>,
+    pub(crate) index: NoteOutIndex,
+    // step of the note in its own history
+    pub(crate) step: u32,
+    pub(crate) value: u64,
+    pub(crate) siblings: Vec<BlindNoteHash<F>>,
+    pub(crate) blind: Blind<F>,
+    pub(crate) memo: MemoHash<F>,
+}
+
+impl<F: PrimeField> MergeInput<F> {
+    pub(crate) fn new(note: &Note<F>, siblings: &[BlindNoteHash<F>]) -> Self {
+        Self {
+            parent: note.parent_note,
+            index: note.out_index,
+            step: note.step,
+            value: note.value,
+            siblings: siblings.to_vec(),
+            blind: note.blind,
+            memo: note.memo,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AuxInputs<E: IVC> {
-    // receiver address
-    pub(crate) receiver: Address<E::Field>,
     // public key of the signer (sender or issuer)
     pub(crate) public_key: PublicKey<E::TE>,
     // signature of sender or issuer
@@ -155,51 +330,111 @@ pub struct AuxInputs<E: IVC> {
     pub(crate) input_index: NoteOutIndex,
     // input value
     pub(crate) value_in: u64,
-    // output value
-    pub(crate) value_out: u64,
-    // sibling note to recover the state
-    pub(crate) sibling: BlindNoteHash<E::Field>,
+    // other leaves of the input state
+    pub(crate) siblings: Vec<BlindNoteHash<E::Field>>,
     // input blind
     pub(crate) blind_in: Blind<E::Field>,
-    // output blind of note 0
-    pub(crate) blind_out_0: Blind<E::Field>,
-    // output blind of note 1
-    pub(crate) blind_out_1: Blind<E::Field>,
+    // input memo
+    pub(crate) memo_in: MemoHash<E::Field>,
+    // expiry of the input, the merged note expires at the same step
+    pub(crate) expiry_in: u32,
+    // split outputs, an issued note is the first one
+    pub(crate) outputs: Vec<Output<E::Field>>,
+    // second input of a merge, empty otherwise
+    pub(crate) merge: MergeInput<E::Field>,
+    // issuer the asset commits to, its address or its issuer key
+    pub(crate) asset_issuer: E::Field,
+    // digest of the terms of the asset, the asset hash is recomputed from it
+    // at every step
+    pub(crate) asset_terms: E::Field,
+    // the asset is bound to the signing key of the issuer
+    pub(crate) key_bound: bool,
+    // redemptions of the asset need the co-signature of the issuer key
+    pub(crate) dual_control: bool,
+    // co-signature of the issuer key if the step redeems under dual control
+    pub(crate) cosignature: Option<Cosignature<E>>,
+    // nonce of the encryption of the disclosed note
+    pub(crate) disclosure_nonce: E::Field,
 }
 
 impl<E: IVC> AuxInputs<E> {
+    // missing siblings and outputs are padded with empty ones
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        receiver: &Address<E::Field>,
         public_key: &PublicKey<E::TE>,
         signature: &Signature<E::TE>,
         nullifier_key: &NullifierKey<E::Field>,
         parent: &BlindNoteHash<E::Field>,
         input_index: &NoteOutIndex,
         value_in: u64,
-        value_out: u64,
-        sibling: &BlindNoteHash<E::Field>,
+        siblings: &[BlindNoteHash<E::Field>],
         blind_in: &Blind<E::Field>,
-        blind_out_0: &Blind<E::Field>,
-        blind_out_1: &Blind<E::Field>,
+        memo_in: &MemoHash<E::Field>,
+        expiry_in: u32,
+        outputs: &[Output<E::Field>],
     ) -> Self {
+        let mut siblings = siblings.to_vec();
+        siblings.resize(E::OUTPUTS - 1, Default::default());
+        let mut outputs = outputs.to_vec();
+        outputs.resize(E::OUTPUTS, Default::default());
         Self {
-            receiver: *receiver,
             public_key: public_key.clone(),
             signature: signature.clone(),
             nullifier_key: *nullifier_key,
             parent: *parent,
             input_index: *input_index,
             value_in,
-            value_out,
-            sibling: *sibling,
+            siblings,
             blind_in: *blind_in,
-            blind_out_0: *blind_out_0,
-            blind_out_1: *blind_out_1,
+            memo_in: *memo_in,
+            expiry_in,
+            outputs,
+            merge: MergeInput {
+                parent: Default::default(),
+                index: NoteOutIndex::Issue,
+                step: 0,
+                value: 0,
+                siblings: vec![Default::default(); E::OUTPUTS - 1],
+                blind: Default::default(),
+                memo: Default::default(),
+            },
+            asset_issuer: Default::default(),
+            asset_terms: Default::default(),
+            key_bound: false,
+            dual_control: false,
+            cosignature: None,
+            disclosure_nonce: Default::default(),
         }
     }
+
+    pub(crate) fn with_merge(mut self, merge: &MergeInput<E::Field>) -> Self {
+        self.merge = merge.clone();
+        self.merge
+            .siblings
+            .resize(E::OUTPUTS - 1, Default::default());
+        self
+    }
+
+    pub(crate) fn with_asset(mut self, asset: &Asset<E::Field>) -> Self {
+        self.asset_issuer = asset.issuer_commitment();
+        self.asset_terms = asset.terms_digest();
+        self.key_bound = asset.is_key_bound();
+        self.dual_control = asset.is_dual_control();
+        self
+    }
+
+    pub(crate) fn with_cosignature(mut self, cosignature: &Cosignature<E>) -> Self {
+        self.cosignature = Some(cosignature.clone());
+        self
+    }
+
+    pub(crate) fn with_disclosure_nonce(mut self, nonce: &E::Field) -> Self {
+        self.disclosure_nonce = *nonce;
+        self
+    }
 }
 
+#[cfg(feature = "prover")]
 #[derive(Clone, Debug)]
 pub struct NoteVar<F: PrimeField> {
     pub(crate) asset_hash: FpVar<F>,
@@ -208,8 +443,11 @@ pub struct NoteVar<F: PrimeField> {
     pub(crate) step: FpVar<F>,
     pub(crate) parent_note: FpVar<F>,
     pub(crate) out_index: FpVar<F>,
+    pub(crate) memo: FpVar<F>,
+    pub(crate) expiry_step: FpVar<F>,
 }
 
+#[cfg(feature = "prover")]
 impl<F: PrimeField> ToCRH<F> for NoteVar<F> {
     // serialize into field elements
     type Output = FpVar<F>;
@@ -221,11 +459,15 @@ impl<F: PrimeField> ToCRH<F> for NoteVar<F> {
             self.step.clone(),
             self.parent_note.clone(),
             self.out_index.clone(),
+            self.memo.clone(),
+            self.expiry_step.clone(),
         ]
     }
 }
 
+#[cfg(feature = "prover")]
 impl<F: PrimeField> NoteVar<F> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         asset_hash: &FpVar<F>,
         owner: &FpVar<F>,
@@ -233,6 +475,8 @@ impl<F: PrimeField> NoteVar<F> {
         step: &FpVar<F>,
         parent_note: &FpVar<F>,
         out_index: &FpVar<F>,
+        memo: &FpVar<F>,
+        expiry_step: &FpVar<F>,
     ) -> Self {
         Self {
             asset_hash: asset_hash.clone(),
@@ -241,6 +485,8 @@ impl<F: PrimeField> NoteVar<F> {
             step: step.clone(),
             parent_note: parent_note.clone(),
             out_index: out_index.clone(),
+            memo: memo.clone(),
+            expiry_step: expiry_step.clone(),
         }
     }
 }
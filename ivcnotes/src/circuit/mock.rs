@@ -0,0 +1,70 @@
+use super::{Prover, Verifier, IVC};
+use crate::{id::Auth, poseidon::PoseidonConfigs, wallet::Wallet};
+use ark_crypto_primitives::snark::SNARK;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use rand::{CryptoRng, RngCore};
+use std::marker::PhantomData;
+
+// Stand-in of the proof system for the tests. A proof is whether the witness
+// satisfies the circuit along with the public inputs it is generated for, so
+// the steps of a wallet are checked by synthesis alone without a setup.
+
+pub(crate) struct MockSnark<F>(PhantomData<F>);
+
+impl<F: PrimeField> SNARK<F> for MockSnark<F> {
+    type ProvingKey = ();
+    type VerifyingKey = ();
+    type Proof = (bool, Vec<F>);
+    type ProcessedVerifyingKey = ();
+    type Error = SynthesisError;
+
+    fn circuit_specific_setup<C: ConstraintSynthesizer<F>, R: RngCore + CryptoRng>(
+        _circuit: C,
+        _rng: &mut R,
+    ) -> Result<((), ()), SynthesisError> {
+        Ok(((), ()))
+    }
+
+    fn prove<C: ConstraintSynthesizer<F>, R: RngCore + CryptoRng>(
+        _pk: &(),
+        circuit: C,
+        _rng: &mut R,
+    ) -> Result<Self::Proof, SynthesisError> {
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone())?;
+        let satisfied = cs.is_satisfied()?;
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        // the first instance variable is the constant one
+        Ok((satisfied, cs.instance_assignment[1..].to_vec()))
+    }
+
+    fn process_vk(_vk: &()) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn verify_with_processed_vk(
+        _pvk: &(),
+        public_input: &[F],
+        proof: &Self::Proof,
+    ) -> Result<bool, SynthesisError> {
+        Ok(proof.0 && proof.1 == public_input)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Mock;
+
+impl IVC for Mock {
+    type Snark = MockSnark<ark_bn254::Fr>;
+    type Field = ark_bn254::Fr;
+    type TE = ark_ed_on_bn254::EdwardsConfig;
+}
+
+pub(crate) fn wallet<R: RngCore + CryptoRng>(
+    h: &PoseidonConfigs<ark_bn254::Fr>,
+    rng: &mut R,
+) -> Wallet<Mock> {
+    let auth = Auth::generate(h, rng).unwrap();
+    Wallet::new(auth, h, Prover::new(()), Verifier::new(()))
+}
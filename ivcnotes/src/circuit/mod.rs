@@ -1,29 +1,67 @@
-use crate::poseidon::PoseidonConfigs;
+use crate::{
+    note::{statements, NoteHistory},
+    poseidon::PoseidonConfigs,
+    security::{Checked, SecurityLevel},
+    PolicyHash,
+};
 use ark_crypto_primitives::snark::SNARK;
-use ark_crypto_primitives::sponge::constraints::CryptographicSpongeVar;
-use ark_crypto_primitives::sponge::poseidon::constraints::PoseidonSpongeVar;
-use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
 use ark_crypto_primitives::sponge::Absorb;
-use ark_ec::twisted_edwards::{Affine, TECurveConfig};
-use ark_ec::{AffineRepr, CurveConfig};
+use ark_ec::twisted_edwards::TECurveConfig;
 use ark_ff::PrimeField;
-use ark_r1cs_std::alloc::AllocVar;
-use ark_r1cs_std::eq::EqGadget;
-use ark_r1cs_std::fields::fp::FpVar;
-use ark_r1cs_std::fields::nonnative::NonNativeFieldVar;
-use ark_r1cs_std::groups::curves::twisted_edwards::AffineVar;
-use ark_r1cs_std::groups::CurveVar;
-use ark_r1cs_std::ToBitsGadget;
-use ark_relations::r1cs::{
-    ConstraintSynthesizer, ConstraintSystemRef, Namespace, Result as CSResult,
+use cache::{cache_key, VerificationCache};
+use inputs::PublicInput;
+use std::sync::OnceLock;
+
+#[cfg(feature = "prover")]
+use {
+    ark_crypto_primitives::sponge::constraints::CryptographicSpongeVar,
+    ark_crypto_primitives::sponge::poseidon::constraints::PoseidonSpongeVar,
+    ark_crypto_primitives::sponge::poseidon::PoseidonConfig,
+    ark_ec::twisted_edwards::Affine,
+    ark_ec::{AffineRepr, CurveConfig},
+    ark_r1cs_std::alloc::AllocVar,
+    ark_r1cs_std::boolean::Boolean,
+    ark_r1cs_std::eq::EqGadget,
+    ark_r1cs_std::fields::fp::FpVar,
+    ark_r1cs_std::fields::nonnative::NonNativeFieldVar,
+    ark_r1cs_std::groups::curves::twisted_edwards::AffineVar,
+    ark_r1cs_std::groups::CurveVar,
+    ark_r1cs_std::ToBitsGadget,
+    ark_relations::r1cs::{
+        ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, Namespace, Result as CSResult,
+    },
+    audit::Audit,
+    cs::synth,
+    inputs::AuxInputs,
+    policy::AssetPolicyCircuit,
+    rand::{CryptoRng, RngCore},
+    std::cell::RefCell,
+    trace::Trace,
 };
-use cs::synth;
-use inputs::{AuxInputs, PublicInput};
-use rand::{CryptoRng, RngCore};
 
+pub mod abi;
+#[cfg(feature = "prover")]
+pub mod audit;
+#[cfg(feature = "batch")]
+pub mod batch;
+pub mod cache;
+#[cfg(feature = "prover")]
 pub mod cs;
+#[cfg(feature = "prover")]
+pub mod doc;
 pub mod inputs;
+#[cfg(all(test, feature = "prover"))]
+pub(crate) mod mock;
+#[cfg(feature = "prover")]
+pub mod policy;
+#[cfg(feature = "prover")]
+pub mod profile;
+#[cfg(feature = "prover")]
+pub mod receivers;
+#[cfg(feature = "prover")]
+pub mod trace;
 
+#[cfg(feature = "prover")]
 fn verify_signature<F: PrimeField, TE: TECurveConfig<BaseField = F>>(
     cs: impl Into<Namespace<F>>,
     poseidon: &PoseidonConfig<F>,
@@ -31,6 +69,7 @@ fn verify_signature<F: PrimeField, TE: TECurveConfig<BaseField = F>>(
     sig_r: &AffineVar<TE, FpVar<F>>,
     sig_s: &NonNativeFieldVar<<TE as CurveConfig>::ScalarField, F>,
     msg: &FpVar<F>,
+    enabled: &Boolean<F>,
 ) -> CSResult<()> {
     let cs = cs.into().cs();
 
@@ -49,7 +88,7 @@ fn verify_signature<F: PrimeField, TE: TECurveConfig<BaseField = F>>(
     let sig_s_bits = sig_s.to_bits_le()?;
     let s_b = b.scalar_mul_le(sig_s_bits.iter())?;
 
-    sig_r.enforce_equal(&(s_b - kx_b0))
+    sig_r.conditional_enforce_equal(&(s_b - kx_b0), enabled)
 }
 
 pub trait IVC: Clone {
@@ -59,14 +98,22 @@ pub trait IVC: Clone {
     type Field: PrimeField + Absorb;
     // inner curve - (baby)jubjub config
     type TE: TECurveConfig<BaseField = Self::Field> + Clone;
+    // number of outputs of a split which is also the width of the state, at least 2
+    const OUTPUTS: usize = 2;
+    // level the config claims, see `security`
+    const SECURITY: SecurityLevel = SecurityLevel::Bits100;
 }
 
+#[cfg(feature = "prover")]
 pub struct Circuit<'a, E: IVC> {
     pub(crate) h: &'a PoseidonConfigs<E::Field>,
     pub(crate) public: Option<PublicInput<E::Field>>,
     pub(crate) aux: Option<AuxInputs<E>>,
+    pub(crate) policy: Option<&'a dyn AssetPolicyCircuit<E::Field>>,
+    pub(crate) trace: Option<&'a RefCell<Trace>>,
 }
 
+#[cfg(feature = "prover")]
 impl<'a, E: IVC> Circuit<'a, E> {
     pub fn new(
         h: &'a PoseidonConfigs<E::Field>,
@@ -77,6 +124,8 @@ impl<'a, E: IVC> Circuit<'a, E> {
             h,
             public: Some(public),
             aux: Some(aux),
+            policy: None,
+            trace: None,
         }
     }
 
@@ -85,25 +134,106 @@ impl<'a, E: IVC> Circuit<'a, E> {
             h,
             public: None,
             aux: None,
+            policy: None,
+            trace: None,
         }
     }
+
+    pub fn with_policy(mut self, policy: &'a dyn AssetPolicyCircuit<E::Field>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn with_trace(mut self, trace: &'a RefCell<Trace>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
 }
 
+#[cfg(feature = "prover")]
 impl<'a, E: IVC> ConstraintSynthesizer<E::Field> for Circuit<'a, E> {
     fn generate_constraints(self, cs: ConstraintSystemRef<E::Field>) -> CSResult<()> {
         synth(cs, self)
     }
 }
 
+#[cfg(feature = "prover")]
 pub struct Prover<E: IVC> {
     pub(crate) pk: <<E as IVC>::Snark as SNARK<E::Field>>::ProvingKey,
+    // asset policy the proving key is generated with
+    pub(crate) policy: Option<Box<dyn AssetPolicyCircuit<E::Field> + Send + Sync>>,
+    // where the witness of a failing step is dumped, see `audit`
+    pub(crate) audit: Option<Audit<E>>,
 }
 
 pub struct Verifier<E: IVC> {
     pub(crate) vk: <<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey,
+    // commitment of the asset policy the key is generated with, zero if none
+    policy: PolicyHash<E::Field>,
+    // prepared at the first verification and reused after
+    prepared: OnceLock<PreparedVerifier<E>>,
+}
+
+// `PreparedVerifier` holds the verifying key with the fixed part of the
+// verification, eg. the pairing of the constant elements, precomputed
+pub struct PreparedVerifier<E: IVC> {
+    pub(crate) pvk: <<E as IVC>::Snark as SNARK<E::Field>>::ProcessedVerifyingKey,
 }
 
+impl<E: IVC> PreparedVerifier<E> {
+    pub fn new(
+        vk: &<<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey,
+    ) -> Result<Self, crate::Error> {
+        let pvk =
+            E::Snark::process_vk(vk).map_err(|_err| crate::Error::With("verification failed"))?;
+        Ok(Self { pvk })
+    }
+
+    pub fn verify_proof(
+        &self,
+        proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+        pi: &PublicInput<E::Field>,
+    ) -> Result<bool, crate::Error> {
+        let pi = pi.to_verifier();
+        E::Snark::verify_with_processed_vk(&self.pvk, &pi, proof)
+            .map_err(|_err| crate::Error::With("verification failed"))
+    }
+}
+
+#[cfg(feature = "prover")]
 impl<E: IVC> Prover<E> {
+    pub fn new(pk: <<E as IVC>::Snark as SNARK<E::Field>>::ProvingKey) -> Self {
+        assert!(Checked::<E>::OK);
+        Self {
+            pk,
+            policy: None,
+            audit: None,
+        }
+    }
+
+    pub fn with_policy(
+        mut self,
+        policy: Box<dyn AssetPolicyCircuit<E::Field> + Send + Sync>,
+    ) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    // checks the witness before proving and dumps it if it fails, opt-in
+    // since it synthesizes the circuit once more for every proof
+    pub fn with_audit(mut self, audit: Audit<E>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    // commitment of the policy of the proving key, zero if it has none
+    pub fn policy(&self) -> PolicyHash<E::Field> {
+        self.policy
+            .as_ref()
+            .map(|policy| policy.commitment())
+            .unwrap_or_default()
+    }
+
     pub fn create_proof<R: RngCore + CryptoRng>(
         &self,
         h: &PoseidonConfigs<E::Field>,
@@ -111,20 +241,139 @@ impl<E: IVC> Prover<E> {
         aux: AuxInputs<E>,
         rng: &mut R,
     ) -> Result<<<E as IVC>::Snark as SNARK<E::Field>>::Proof, crate::Error> {
-        let circuit = Circuit::new(h, public, aux);
-        <E as IVC>::Snark::prove(&self.pk, circuit, rng)
+        if let Some(audit) = &self.audit {
+            let cs = ConstraintSystem::new_ref();
+            let satisfied = self
+                .circuit(h, public.clone(), aux.clone())
+                .generate_constraints(cs.clone())
+                .and_then(|_| cs.is_satisfied())
+                .unwrap_or(false);
+            if !satisfied {
+                let cs = cs.borrow().unwrap();
+                audit.record(&cs.instance_assignment, &cs.witness_assignment, rng)?;
+                return Err(crate::Error::With("proof generation failed"));
+            }
+        }
+        <E as IVC>::Snark::prove(&self.pk, self.circuit(h, public, aux), rng)
             .map_err(|_err| crate::Error::With("proof generation failed"))
     }
+
+    fn circuit<'a>(
+        &'a self,
+        h: &'a PoseidonConfigs<E::Field>,
+        public: PublicInput<E::Field>,
+        aux: AuxInputs<E>,
+    ) -> Circuit<'a, E> {
+        let circuit = Circuit::new(h, public, aux);
+        match &self.policy {
+            Some(policy) => circuit.with_policy(policy.as_ref()),
+            None => circuit,
+        }
+    }
 }
 
 impl<E: IVC> Verifier<E> {
+    pub fn new(vk: <<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey) -> Self {
+        assert!(Checked::<E>::OK);
+        Self {
+            vk,
+            policy: PolicyHash::default(),
+            prepared: OnceLock::new(),
+        }
+    }
+
+    // the key is generated with the policy of the commitment, it verifies only
+    // the steps of the assets of the policy
+    pub fn with_policy(mut self, policy: &PolicyHash<E::Field>) -> Self {
+        self.policy = *policy;
+        self
+    }
+
+    pub fn policy(&self) -> &PolicyHash<E::Field> {
+        &self.policy
+    }
+
+    pub fn prepared(&self) -> Result<&PreparedVerifier<E>, crate::Error> {
+        if let Some(prepared) = self.prepared.get() {
+            return Ok(prepared);
+        }
+        let prepared = PreparedVerifier::new(&self.vk)?;
+        Ok(self.prepared.get_or_init(|| prepared))
+    }
+
     pub fn verify_proof(
         &self,
         proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
         pi: &PublicInput<E::Field>,
     ) -> Result<bool, crate::Error> {
-        let pi = pi.to_verifier();
-        E::Snark::verify(&self.vk, &pi, proof)
-            .map_err(|_err| crate::Error::With("verification failed"))
+        self.prepared()?.verify_proof(proof, pi)
+    }
+
+    // Verifies a history with its merged histories on `threads` threads. Steps
+    // are chained by their states which is checked up front, after that the
+    // proofs are independent of each other.
+    pub fn verify_history_parallel(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        history: &NoteHistory<E>,
+        threads: usize,
+    ) -> Result<(), crate::Error>
+    where
+        <<E as IVC>::Snark as SNARK<E::Field>>::ProcessedVerifyingKey: Send,
+    {
+        let state_out = history.steps.last().map(|step| step.state);
+        (Some(history.state(h)) == state_out)
+            .then_some(())
+            .ok_or(crate::Error::With("bad current state"))?;
+
+        let statements = statements(h, &history.asset.hash(h), &history.steps);
+        let prepared = self.prepared()?;
+        let chunk = statements.len().div_ceil(threads.max(1)).max(1);
+        let verified = std::thread::scope(|scope| {
+            let handles: Vec<_> = statements
+                .chunks(chunk)
+                .map(|statements| {
+                    scope.spawn(move || {
+                        statements
+                            .iter()
+                            .try_fold(true, |ok, (proof, public_input)| {
+                                Ok::<_, crate::Error>(
+                                    ok && prepared.verify_proof(proof, public_input)?,
+                                )
+                            })
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or(Err(crate::Error::With("verification failed")))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+        verified
+            .into_iter()
+            .all(|ok| ok)
+            .then_some(())
+            .ok_or(crate::Error::With("verification failed"))
+    }
+
+    pub fn verify_proof_cached(
+        &self,
+        cache: &mut VerificationCache,
+        proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+        pi: &PublicInput<E::Field>,
+    ) -> Result<bool, crate::Error> {
+        let key = cache_key::<E>(proof, pi);
+        match cache.get(&key) {
+            Some(result) => Ok(result),
+            None => {
+                let result = self.verify_proof(proof, pi)?;
+                cache.insert(key, result);
+                Ok(result)
+            }
+        }
     }
 }
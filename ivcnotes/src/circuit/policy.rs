@@ -0,0 +1,39 @@
+use crate::PolicyHash;
+use ark_ff::PrimeField;
+use ark_r1cs_std::boolean::Boolean;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_relations::r1cs::{ConstraintSystemRef, Result as CSResult};
+
+#[derive(Clone, Debug)]
+pub struct OutputVars<F: PrimeField> {
+    pub owner: FpVar<F>,
+    pub value: FpVar<F>,
+}
+
+// variables of the split branch that are exposed to asset policies
+#[derive(Clone, Debug)]
+pub struct SplitVars<F: PrimeField> {
+    pub asset_hash: FpVar<F>,
+    pub sender: FpVar<F>,
+    pub step: FpVar<F>,
+    // value of the spent notes
+    pub value_in: FpVar<F>,
+    // outputs in the order of their index, 1 is the change
+    pub outputs: Vec<OutputVars<F>>,
+}
+
+// `AssetPolicyCircuit` lets an asset compose extra constraints into the split
+// branch. The policy is identified by its commitment which is bound into the
+// asset hash, so receivers can tell which policy a note tree is subject to.
+pub trait AssetPolicyCircuit<F: PrimeField> {
+    // commitment to the policy, must be non zero
+    fn commitment(&self) -> PolicyHash<F>;
+
+    // extra constraints, must only be enforced when `enabled` is true
+    fn enforce(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        split: &SplitVars<F>,
+        enabled: &Boolean<F>,
+    ) -> CSResult<()>;
+}
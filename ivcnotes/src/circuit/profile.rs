@@ -0,0 +1,214 @@
+use super::trace::{Section, Trace};
+use super::{Circuit, IVC};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, Result as CSResult, SynthesisMode,
+};
+use std::cell::RefCell;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    Constraints,
+    Witnesses,
+}
+
+// cost of a gadget excluding its children
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    // names of the enclosing gadgets from the root, joined with `;`
+    pub path: String,
+    pub constraints: usize,
+    pub witnesses: usize,
+}
+
+// `Profile` attributes the cost of the step circuit to the gadgets that
+// allocate it. Costs outside of any gadget are attributed to `unattributed`.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub num_constraints: usize,
+    pub num_witnesses: usize,
+    pub frames: Vec<Frame>,
+}
+
+// cost of every call of a gadget, including the gadgets it calls
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GadgetStats {
+    pub name: &'static str,
+    pub calls: usize,
+    pub constraints: usize,
+    pub witnesses: usize,
+}
+
+// `Stats` sums up the size of the step circuit so that regressions can be
+// caught in tests and benchmarks. Gadgets are in the order they are first
+// called, costs of the gadgets that call each other overlap, eg. `split`
+// includes the `poseidon` calls of the outputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub num_constraints: usize,
+    pub num_witnesses: usize,
+    pub gadgets: Vec<GadgetStats>,
+}
+
+// synthesizes without a witness, returns the counts and the sections
+fn synthesize<E: IVC>(circuit: Circuit<E>) -> CSResult<(usize, usize, Vec<Section>)> {
+    let cs = ConstraintSystem::<E::Field>::new_ref();
+    cs.set_mode(SynthesisMode::Setup);
+    let trace = RefCell::new(Trace::default());
+    circuit
+        .with_trace(&trace)
+        .generate_constraints(cs.clone())?;
+    let sections = trace.into_inner().sections;
+    Ok((cs.num_constraints(), cs.num_witness_variables(), sections))
+}
+
+impl<'a, E: IVC> Circuit<'a, E> {
+    pub fn stats(self) -> CSResult<Stats> {
+        fn walk(gadgets: &mut Vec<GadgetStats>, s: &Section) {
+            match gadgets.iter_mut().find(|gadget| gadget.name == s.name) {
+                Some(gadget) => {
+                    gadget.calls += 1;
+                    gadget.constraints += s.constraints;
+                    gadget.witnesses += s.witnesses;
+                }
+                None => gadgets.push(GadgetStats {
+                    name: s.name,
+                    calls: 1,
+                    constraints: s.constraints,
+                    witnesses: s.witnesses,
+                }),
+            }
+            s.children.iter().for_each(|child| walk(gadgets, child));
+        }
+
+        let (num_constraints, num_witnesses, sections) = synthesize(self)?;
+        let mut gadgets = vec![];
+        sections.iter().for_each(|s| walk(&mut gadgets, s));
+        Ok(Stats {
+            num_constraints,
+            num_witnesses,
+            gadgets,
+        })
+    }
+}
+
+impl Stats {
+    pub fn gadget(&self, name: &str) -> Option<&GadgetStats> {
+        self.gadgets.iter().find(|gadget| gadget.name == name)
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{:>8} {:>8} {:>6} total",
+            self.num_constraints, self.num_witnesses, ""
+        )
+        .unwrap();
+        for gadget in self.gadgets.iter() {
+            writeln!(
+                out,
+                "{:>8} {:>8} {:>5}x {}",
+                gadget.constraints, gadget.witnesses, gadget.calls, gadget.name
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl Profile {
+    pub fn generate<E: IVC>(circuit: Circuit<E>) -> CSResult<Self> {
+        let (num_constraints, num_witnesses, sections) = synthesize(circuit)?;
+        Ok(Self::new(num_constraints, num_witnesses, &sections))
+    }
+
+    pub(crate) fn new(num_constraints: usize, num_witnesses: usize, sections: &[Section]) -> Self {
+        fn walk(frames: &mut Vec<Frame>, prefix: &str, s: &Section) {
+            let path = match prefix.is_empty() {
+                true => s.name.to_string(),
+                false => format!("{};{}", prefix, s.name),
+            };
+            let children =
+                |metric: fn(&Section) -> usize| s.children.iter().map(metric).sum::<usize>();
+            let constraints = s.constraints - children(|c| c.constraints);
+            let witnesses = s.witnesses - children(|c| c.witnesses);
+            // repeated calls of a gadget at the same place make a single frame
+            match frames.iter_mut().find(|frame| frame.path == path) {
+                Some(frame) => {
+                    frame.constraints += constraints;
+                    frame.witnesses += witnesses;
+                }
+                None => frames.push(Frame {
+                    path: path.clone(),
+                    constraints,
+                    witnesses,
+                }),
+            }
+            s.children
+                .iter()
+                .for_each(|child| walk(frames, &path, child));
+        }
+
+        let mut frames = vec![];
+        sections.iter().for_each(|s| walk(&mut frames, "", s));
+        frames.push(Frame {
+            path: "unattributed".to_string(),
+            constraints: num_constraints - sections.iter().map(|s| s.constraints).sum::<usize>(),
+            witnesses: num_witnesses - sections.iter().map(|s| s.witnesses).sum::<usize>(),
+        });
+
+        Self {
+            num_constraints,
+            num_witnesses,
+            frames,
+        }
+    }
+
+    // frames sorted by the metric, most expensive first
+    pub fn hottest(&self, metric: Metric) -> Vec<&Frame> {
+        let mut frames: Vec<&Frame> = self.frames.iter().collect();
+        frames.sort_by_key(|frame| std::cmp::Reverse(frame.cost(metric)));
+        frames
+    }
+
+    // folded stacks, the input format of flame graph tools
+    pub fn to_folded(&self, metric: Metric) -> String {
+        let mut out = String::new();
+        for frame in self.frames.iter().filter(|frame| frame.cost(metric) > 0) {
+            writeln!(out, "{} {}", frame.path, frame.cost(metric)).unwrap();
+        }
+        out
+    }
+
+    pub fn to_table(&self, metric: Metric) -> String {
+        let total = match metric {
+            Metric::Constraints => self.num_constraints,
+            Metric::Witnesses => self.num_witnesses,
+        };
+        let mut out = String::new();
+        for frame in self.hottest(metric) {
+            let cost = frame.cost(metric);
+            let share = (cost * 1000).checked_div(total).unwrap_or_default();
+            writeln!(
+                out,
+                "{:>8} {:>3}.{}% {}",
+                cost,
+                share / 10,
+                share % 10,
+                frame.path
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl Frame {
+    pub fn cost(&self, metric: Metric) -> usize {
+        match metric {
+            Metric::Constraints => self.constraints,
+            Metric::Witnesses => self.witnesses,
+        }
+    }
+}
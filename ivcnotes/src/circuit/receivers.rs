@@ -0,0 +1,150 @@
+use super::policy::{AssetPolicyCircuit, SplitVars};
+use crate::{poseidon::PoseidonConfigs, Address, PolicyHash};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::PrimeField;
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::boolean::Boolean;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::fields::FieldVar;
+use ark_r1cs_std::R1CSVar;
+use ark_relations::r1cs::{ConstraintSystemRef, Result as CSResult, SynthesisError};
+
+// Asset policy that only lets value go to addresses the issuer registered, so
+// a buggy sender can't burn value to an address no one holds the keys of. The
+// registered addresses are the leaves of a poseidon tree of fixed depth, its
+// root is the commitment of the policy. Every output of a split must be
+// registered, go back to the sender or be empty. The sender is a well formed
+// identity already since the circuit recovers it from its keys.
+//
+// The tree is fixed with the asset, registering more receivers takes a new
+// policy and so a new asset.
+
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Clone, Debug)]
+pub struct RegisteredReceivers<F: PrimeField + Absorb> {
+    h: PoseidonConfigs<F>,
+    depth: usize,
+    addresses: Vec<Address<F>>,
+    // nodes of the tree by level from the leaves up, empty nodes on the right
+    // are left out
+    levels: Vec<Vec<F>>,
+    // root of an empty subtree by level
+    empty: Vec<F>,
+}
+
+impl<F: PrimeField + Absorb> RegisteredReceivers<F> {
+    pub fn new(
+        h: &PoseidonConfigs<F>,
+        depth: usize,
+        addresses: &[Address<F>],
+    ) -> Result<Self, crate::Error> {
+        (1..=MAX_DEPTH)
+            .contains(&depth)
+            .then_some(())
+            .ok_or(crate::Error::With("bad receiver tree depth"))?;
+        (addresses.len() as u64 <= 1 << depth)
+            .then_some(())
+            .ok_or(crate::Error::With("too many registered receivers"))?;
+
+        let mut empty = vec![F::zero()];
+        let mut levels = vec![addresses
+            .iter()
+            .map(|address| h.receiver_leaf(address))
+            .collect::<Vec<_>>()];
+        for level in 0..depth {
+            let nodes = levels[level]
+                .chunks(2)
+                .map(|pair| h.receiver_node(&pair[0], pair.get(1).unwrap_or(&empty[level])))
+                .collect();
+            levels.push(nodes);
+            empty.push(h.receiver_node(&empty[level], &empty[level]));
+        }
+
+        Ok(Self {
+            h: h.clone(),
+            depth,
+            addresses: addresses.to_vec(),
+            levels,
+            empty,
+        })
+    }
+
+    pub fn root(&self) -> F {
+        self.levels[self.depth]
+            .first()
+            .copied()
+            .unwrap_or(self.empty[self.depth])
+    }
+
+    pub fn contains(&self, address: &Address<F>) -> bool {
+        self.addresses.contains(address)
+    }
+
+    // index and siblings of the address, an address that is not registered
+    // gets the path of the first leaf
+    fn path(&self, address: &Address<F>) -> (usize, Vec<F>) {
+        let index = self
+            .addresses
+            .iter()
+            .position(|registered| registered == address)
+            .unwrap_or(0);
+        let siblings = (0..self.depth)
+            .map(|level| {
+                let sibling = (index >> level) ^ 1;
+                self.levels[level]
+                    .get(sibling)
+                    .copied()
+                    .unwrap_or(self.empty[level])
+            })
+            .collect();
+        (index, siblings)
+    }
+}
+
+impl<F: PrimeField + Absorb> AssetPolicyCircuit<F> for RegisteredReceivers<F> {
+    fn commitment(&self) -> PolicyHash<F> {
+        self.root().into()
+    }
+
+    fn enforce(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        split: &SplitVars<F>,
+        enabled: &Boolean<F>,
+    ) -> CSResult<()> {
+        let root = FpVar::new_constant(cs.clone(), self.root())?;
+        for output in split.outputs.iter() {
+            // paths are found from the assignment of the owner when proving
+            let path = output
+                .owner
+                .value()
+                .ok()
+                .map(|owner| self.path(&owner.into()));
+            let mut node = self.h.var_receiver_leaf(cs.clone(), &output.owner)?;
+            for level in 0..self.depth {
+                let is_right = Boolean::new_witness(cs.clone(), || {
+                    path.as_ref()
+                        .map(|(index, _)| (index >> level) & 1 == 1)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                let sibling = FpVar::new_witness(cs.clone(), || {
+                    path.as_ref()
+                        .map(|(_, siblings)| siblings[level])
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                let left = is_right.select(&sibling, &node)?;
+                let right = is_right.select(&node, &sibling)?;
+                node = self.h.var_receiver_node(cs.clone(), &left, &right)?;
+            }
+
+            let is_registered = node.is_eq(&root)?;
+            let is_sender = output.owner.is_eq(&split.sender)?;
+            let is_empty = output.value.is_eq(&FpVar::zero())?;
+            Boolean::kary_or(&[is_registered, is_sender, is_empty])?
+                .conditional_enforce_equal(&Boolean::TRUE, enabled)?;
+        }
+        Ok(())
+    }
+}
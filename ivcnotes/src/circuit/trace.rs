@@ -0,0 +1,78 @@
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, Result as CSResult};
+use std::cell::RefCell;
+
+// `Section` is a named gadget in the circuit with the constraints and the
+// witnesses that it allocates, including the ones allocated by its children
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Section {
+    pub name: &'static str,
+    pub constraints: usize,
+    pub witnesses: usize,
+    // index of the first witness allocated in the section
+    pub witness_offset: usize,
+    pub children: Vec<Section>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    pub(crate) sections: Vec<Section>,
+    // open sections and the constraint count when they are entered
+    stack: Vec<(Section, usize)>,
+}
+
+impl Trace {
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+}
+
+// records sections into the trace if there is any
+pub(crate) struct Tracer<'a, F: PrimeField> {
+    cs: ConstraintSystemRef<F>,
+    trace: Option<&'a RefCell<Trace>>,
+}
+
+impl<'a, F: PrimeField> Tracer<'a, F> {
+    pub(crate) fn new(cs: ConstraintSystemRef<F>, trace: Option<&'a RefCell<Trace>>) -> Self {
+        Self { cs, trace }
+    }
+
+    pub(crate) fn enter(&self, name: &'static str) {
+        if let Some(trace) = self.trace {
+            let section = Section {
+                name,
+                witness_offset: self.cs.num_witness_variables(),
+                ..Default::default()
+            };
+            let constraints = self.cs.num_constraints();
+            trace.borrow_mut().stack.push((section, constraints));
+        }
+    }
+
+    pub(crate) fn exit(&self) {
+        if let Some(trace) = self.trace {
+            let mut trace = trace.borrow_mut();
+            let (mut section, constraints) = trace.stack.pop().expect("no open section");
+            section.constraints = self.cs.num_constraints() - constraints;
+            section.witnesses = self.cs.num_witness_variables() - section.witness_offset;
+            match trace.stack.last_mut() {
+                Some((parent, _)) => parent.children.push(section),
+                None => trace.sections.push(section),
+            }
+        }
+    }
+
+    // records a gadget call as a section, a failed call fails the synthesis
+    // so the section is left open
+    pub(crate) fn section<T>(
+        &self,
+        name: &'static str,
+        gadget: impl FnOnce() -> CSResult<T>,
+    ) -> CSResult<T> {
+        self.enter(name);
+        let out = gadget()?;
+        self.exit();
+        Ok(out)
+    }
+}
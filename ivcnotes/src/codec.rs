@@ -0,0 +1,141 @@
+use crate::{
+    handshake::{Agreement, Feature},
+    limits::Limits,
+};
+
+// Compression of wire artifacts. Histories are mostly proofs and dominate the
+// bandwidth of mobile wallets on constrained networks. Wallets announce the
+// codecs they have as features of their `Hello` and the best common one is
+// used, none if they have nothing in common. This crate depends on no
+// compressor, applications bring zstd or brotli by implementing `Codec`.
+//
+// magic | codec | uncompressed len | body
+//
+// The length is checked against the limits before anything is decompressed.
+
+const MAGIC: &[u8; 4] = b"IVCZ";
+pub(crate) const HEADER_LEN: usize = 4 + 1 + 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodecId {
+    None,
+    Zstd,
+    Brotli,
+}
+
+impl CodecId {
+    // preferred first, brotli compresses best and bandwidth is what matters
+    pub const PREFERENCE: [CodecId; 3] = [CodecId::Brotli, CodecId::Zstd, CodecId::None];
+
+    // byte in the frame, never reused
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            CodecId::None => 0,
+            CodecId::Zstd => 1,
+            CodecId::Brotli => 2,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|codec| codec.to_byte() == byte)
+    }
+
+    // feature that announces the codec, every wallet can send uncompressed
+    pub fn feature(&self) -> Option<Feature> {
+        match self {
+            CodecId::None => None,
+            CodecId::Zstd => Some(Feature::Zstd),
+            CodecId::Brotli => Some(Feature::Brotli),
+        }
+    }
+}
+
+pub trait Codec {
+    fn id(&self) -> CodecId;
+    fn compress(&self, bytes: &[u8]) -> Vec<u8>;
+    // fails if the body is malformed or doesn't decompress to `len` bytes
+    fn decompress(&self, body: &[u8], len: usize) -> Option<Vec<u8>>;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl Codec for Identity {
+    fn id(&self) -> CodecId {
+        CodecId::None
+    }
+
+    fn compress(&self, bytes: &[u8]) -> Vec<u8> {
+        bytes.to_vec()
+    }
+
+    fn decompress(&self, body: &[u8], len: usize) -> Option<Vec<u8>> {
+        (body.len() == len).then(|| body.to_vec())
+    }
+}
+
+// codecs of a wallet, the identity is always there
+pub struct Codecs {
+    codecs: Vec<Box<dyn Codec + Send + Sync>>,
+}
+
+impl Default for Codecs {
+    fn default() -> Self {
+        Self {
+            codecs: vec![Box::new(Identity)],
+        }
+    }
+}
+
+impl Codecs {
+    // replaces the codec with the same id
+    pub fn with_codec(mut self, codec: Box<dyn Codec + Send + Sync>) -> Self {
+        self.codecs.retain(|other| other.id() != codec.id());
+        self.codecs.push(codec);
+        self
+    }
+
+    fn get(&self, id: CodecId) -> Option<&(dyn Codec + Send + Sync)> {
+        self.codecs
+            .iter()
+            .find(|codec| codec.id() == id)
+            .map(|codec| codec.as_ref())
+    }
+
+    // to announce in the `Hello` along with the other features
+    pub fn features(&self) -> Vec<Feature> {
+        CodecId::PREFERENCE
+            .into_iter()
+            .filter(|id| self.get(*id).is_some())
+            .filter_map(|id| id.feature())
+            .collect()
+    }
+
+    // compresses with the codec of the agreement
+    pub fn compress(&self, agreement: &Agreement, bytes: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        let id = agreement.codec();
+        let codec = self.get(id).ok_or(crate::Error::With("unknown codec"))?;
+        let mut out = MAGIC.to_vec();
+        out.push(id.to_byte());
+        out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(&codec.compress(bytes));
+        Ok(out)
+    }
+
+    pub fn decompress(&self, bytes: &[u8], limits: &Limits) -> Result<Vec<u8>, crate::Error> {
+        let err = crate::Error::With("bad compressed artifact");
+        (bytes.len() >= HEADER_LEN && bytes[..4] == *MAGIC)
+            .then_some(())
+            .ok_or(err)?;
+        let codec = CodecId::from_byte(bytes[4])
+            .and_then(|id| self.get(id))
+            .ok_or(crate::Error::With("unknown codec"))?;
+        let len = u32::from_le_bytes(bytes[5..9].try_into().unwrap()) as usize;
+        (len <= limits.max_decompressed_len)
+            .then_some(())
+            .ok_or(err)?;
+        codec.decompress(&bytes[HEADER_LEN..], len).ok_or(err)
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    circuit::IVC,
+    codec::Codecs,
+    handshake::{negotiate, Hello, Reader},
+    note::{NoteHistory, NoteOutIndex},
+    payload::{self, EncryptionKey},
+    AssetHash, StateHash,
+};
+use ark_ff::PrimeField;
+use rand_core::CryptoRngCore;
+
+// Cold storage of notes. Exported notes are transferred in full to a separate
+// identity whose `Auth` never leaves an offline device, and the histories are
+// sealed to its key in a file. The online wallet can neither open the file nor
+// spend the notes, a wallet of the offline identity imports them. The online
+// wallet remembers the exported notes as cold, they count in `cold_balance`
+// but not in the balances until they are forgotten.
+//
+// magic | version | count | (len | sealed history)*
+//
+// Histories are sealed uncompressed, the offline wallet may have no codecs.
+
+const MAGIC: &[u8; 4] = b"IVCO";
+const VERSION: u8 = 1;
+
+// notes that failed to move are left in the wallet and reported
+#[derive(Clone, Debug)]
+pub struct ColdExport {
+    pub file: Vec<u8>,
+    pub failed: Vec<crate::Error>,
+}
+
+// note in cold storage as the wallet tracks it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColdNote<F: PrimeField> {
+    pub asset_hash: AssetHash<F>,
+    pub value: u64,
+    // the note is the output at `out_index` of the step ending in `state`
+    pub(crate) state: StateHash<F>,
+    pub(crate) out_index: NoteOutIndex,
+}
+
+impl<F: PrimeField> ColdNote<F> {
+    pub(crate) fn new<E: IVC<Field = F>>(
+        asset_hash: &AssetHash<F>,
+        history: &NoteHistory<E>,
+    ) -> Self {
+        Self {
+            asset_hash: *asset_hash,
+            value: history.current_note.value,
+            state: history
+                .steps
+                .last()
+                .map(|step| step.state)
+                .unwrap_or_default(),
+            out_index: history.current_note.out_index,
+        }
+    }
+
+    pub(crate) fn is<E: IVC<Field = F>>(&self, history: &NoteHistory<E>) -> bool {
+        history.steps.last().map(|step| step.state) == Some(self.state)
+            && history.current_note.out_index == self.out_index
+    }
+}
+
+pub(crate) fn seal<E: IVC>(
+    histories: &[NoteHistory<E>],
+    to: &EncryptionKey<E>,
+    rng: &mut impl CryptoRngCore,
+) -> Vec<u8> {
+    let agreement = negotiate(&Hello::default(), &Hello::default()).unwrap();
+    let codecs = Codecs::default();
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&(histories.len() as u32).to_le_bytes());
+    for history in histories.iter() {
+        // the identity codec of the default agreement doesn't fail
+        let sealed = payload::seal(history, to, &codecs, &agreement, rng).unwrap();
+        bytes.extend_from_slice(&(sealed.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&sealed);
+    }
+    bytes
+}
+
+// sealed histories of a file, they are opened by the wallet
+pub(crate) fn sealed(bytes: &[u8]) -> Result<Vec<&[u8]>, crate::Error> {
+    let err = crate::Error::With("bad cold storage file");
+    (bytes.len() >= 9 && bytes[..4] == *MAGIC && bytes[4] == VERSION)
+        .then_some(())
+        .ok_or(err)?;
+    let mut reader = Reader(&bytes[5..]);
+    let count = reader.u32().ok_or(err)?;
+    let sealed = (0..count)
+        .map(|_| {
+            let len = reader.u32().ok_or(err)? as usize;
+            reader.take(len).ok_or(err)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    reader.0.is_empty().then_some(sealed).ok_or(err)
+}
@@ -0,0 +1,73 @@
+use crate::{circuit::IVC, note::NoteHistory, Nullifier};
+use ark_ff::PrimeField;
+
+// Finality of received notes. A sender can spend the input of a transfer
+// twice and hand a valid history to each receiver, only the spend whose
+// nullifier gets published survives. A receiver that requires confirmations
+// keeps an incoming note pending until the nullifier of its transfer appears
+// in a published epoch and enough epochs follow, the note is not spendable
+// before that.
+//
+// Epochs are numbered batches of published nullifiers, eg. what a registry
+// took in an hour. They can be observed out of order and more than once, a
+// note received after its nullifier was published is confirmed when that
+// epoch is observed again. Issuances reveal no nullifier and are final.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Epoch<F: PrimeField> {
+    pub number: u64,
+    pub nullifiers: Vec<Nullifier<F>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PendingNote<E: IVC> {
+    pub(crate) history: NoteHistory<E>,
+    // nullifier of the transfer the note is an output of
+    pub(crate) nullifier: Nullifier<E::Field>,
+    // first epoch the nullifier is seen in
+    pub(crate) included: Option<u64>,
+}
+
+impl<E: IVC> PendingNote<E> {
+    pub(crate) fn new(history: NoteHistory<E>) -> Self {
+        let nullifier = history
+            .steps
+            .last()
+            .map(|step| step.nullifier)
+            .unwrap_or_default();
+        Self {
+            history,
+            nullifier,
+            included: None,
+        }
+    }
+
+    pub fn history(&self) -> &NoteHistory<E> {
+        &self.history
+    }
+
+    pub fn nullifier(&self) -> &Nullifier<E::Field> {
+        &self.nullifier
+    }
+
+    pub fn included(&self) -> Option<u64> {
+        self.included
+    }
+
+    // epochs from the one the nullifier is seen in up to the latest
+    pub fn confirmations(&self, latest: Option<u64>) -> u64 {
+        match (self.included, latest) {
+            (Some(included), Some(latest)) => latest.saturating_sub(included) + 1,
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn observe(&mut self, epoch: &Epoch<E::Field>) {
+        let earlier = self
+            .included
+            .map_or(true, |included| epoch.number < included);
+        if earlier && epoch.nullifiers.contains(&self.nullifier) {
+            self.included = Some(epoch.number);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    circuit::inputs::PublicInput,
+    note::{Note, NoteOutIndex},
+    poseidon::{PoseidonConfigs, ToCRH},
+    Address, AssetHash, Blind, BlindNoteHash, DisclosureHash, FWrap, MemoHash, NoteHash, Nullifier,
+    NullifierKey, ReceiverTag, SettlementRef, StateHash,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::PrimeField;
+
+// `Adapter` is implemented by alternative implementations to certify that
+// they compute the protocol values the same way as this crate does
+pub trait Adapter<F: PrimeField> {
+    // note fields are given in hashing order:
+    // asset hash, owner, value, step, parent, output index, memo, expiry step
+    fn note_hash(&self, note: &[F]) -> F;
+    fn blind_note_hash(&self, note_hash: &F, blind: &F) -> F;
+    // leaves in the order of output index
+    fn state(&self, leaves: &[F]) -> F;
+    fn nullifier(&self, note_hash: &F, nullifier_key: &F) -> F;
+    fn sighash(&self, input: &F, outputs: &[F]) -> F;
+    fn sighash_burn(&self, input: &F, value: u64) -> F;
+    fn sighash_redeem(&self, input: &F, value: u64, settlement: &F) -> F;
+    fn sighash_merge(&self, input: &F, merged: &F, outputs: &[F]) -> F;
+    // public inputs in the order expected by the verifier
+    #[allow(clippy::too_many_arguments)]
+    fn public_inputs(
+        &self,
+        asset_hash: &F,
+        sender: &F,
+        state_in: &F,
+        state_out: &F,
+        step: u32,
+        nullifier: &F,
+        burn_value: u64,
+        settlement: &F,
+        merge_state_in: &F,
+        merge_nullifier: &F,
+        receiver_tag: &F,
+        disclosure: &F,
+    ) -> Vec<F>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub vector: &'static str,
+    pub case: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub(crate) fn check(&mut self, vector: &'static str, case: usize, ok: bool) {
+        match ok {
+            true => self.passed += 1,
+            false => self.failures.push(Failure { vector, case }),
+        }
+    }
+}
+
+// deterministic inputs including the edge cases
+pub(crate) fn samples<F: PrimeField>() -> Vec<F> {
+    let mut samples = vec![F::zero(), F::one(), -F::one(), F::from(u64::MAX)];
+    let mut x = F::from(0x9e37_79b9_7f4a_7c15u64);
+    for _ in 0..4 {
+        samples.push(x);
+        x = x.square() + F::one();
+    }
+    samples
+}
+
+const VALUES: [u64; 4] = [0, 1, 1000, u64::MAX];
+const INDEXES: [NoteOutIndex; 4] = [
+    NoteOutIndex::Issue,
+    NoteOutIndex::Out(0),
+    NoteOutIndex::Out(1),
+    NoteOutIndex::Out(2),
+];
+
+pub fn run_conformance<F: PrimeField + Absorb>(
+    h: &PoseidonConfigs<F>,
+    adapter: &impl Adapter<F>,
+) -> Report {
+    let mut report = Report::default();
+    let samples = samples::<F>();
+    let n = samples.len();
+
+    for (case, x) in samples.iter().enumerate() {
+        let y = &samples[(case + 1) % n];
+        let z = &samples[(case + 2) % n];
+
+        let note = Note::new(
+            &AssetHash::from(x),
+            &Address::from(y),
+            VALUES[case % VALUES.len()],
+            case as u32,
+            &INDEXES[case % INDEXES.len()],
+            &BlindNoteHash::from(z),
+            Blind::from(y),
+        )
+        .with_memo(&MemoHash::from(x));
+        let (note_hash, blind_note_hash) = h.note(&note);
+        let fields = note.to_crh();
+        report.check(
+            "note_hash",
+            case,
+            adapter.note_hash(&fields) == note_hash.inner(),
+        );
+        report.check(
+            "blind_note_hash",
+            case,
+            adapter.blind_note_hash(&note_hash.inner(), y) == blind_note_hash.inner(),
+        );
+
+        // two leaves and a wider state
+        for leaves in [vec![*x, *y], vec![*x, *y, *z]] {
+            let blinds: Vec<_> = leaves
+                .iter()
+                .map(|leaf| BlindNoteHash::from(*leaf))
+                .collect();
+            let state = h.state(&blinds);
+            report.check("state", case, adapter.state(&leaves) == state.inner());
+        }
+
+        let nullifier = h.nullifier(&NoteHash::from(x), &NullifierKey::from(z));
+        report.check(
+            "nullifier",
+            case,
+            adapter.nullifier(x, z) == nullifier.inner(),
+        );
+
+        let sighash = h.sighash(&NoteHash::from(x), &[NoteHash::from(y), NoteHash::from(z)]);
+        report.check(
+            "sighash",
+            case,
+            adapter.sighash(x, &[*y, *z]) == sighash.inner(),
+        );
+
+        let sighash_burn = h.sighash_burn(&NoteHash::from(x), case as u64);
+        report.check(
+            "sighash_burn",
+            case,
+            adapter.sighash_burn(x, case as u64) == sighash_burn.inner(),
+        );
+
+        let sighash_redeem =
+            h.sighash_redeem(&NoteHash::from(x), case as u64, &SettlementRef::from(y));
+        report.check(
+            "sighash_redeem",
+            case,
+            adapter.sighash_redeem(x, case as u64, y) == sighash_redeem.inner(),
+        );
+
+        let sighash_merge = h.sighash_merge(
+            &NoteHash::from(x),
+            &NoteHash::from(y),
+            &[NoteHash::from(z), NoteHash::from(x)],
+        );
+        report.check(
+            "sighash_merge",
+            case,
+            adapter.sighash_merge(x, y, &[*z, *x]) == sighash_merge.inner(),
+        );
+
+        let public_input = PublicInput::new(
+            &AssetHash::from(x),
+            &Address::from(y),
+            &StateHash::from(z),
+            &StateHash::from(y),
+            case as u32,
+            &Nullifier::from(x),
+            case as u64,
+        )
+        .with_settlement(&SettlementRef::from(z))
+        .with_merge(&StateHash::from(x), &Nullifier::from(z))
+        .with_receiver_tag(&ReceiverTag::from(y))
+        .with_disclosure(&DisclosureHash::from(z));
+        report.check(
+            "public_inputs",
+            case,
+            adapter.public_inputs(x, y, z, y, case as u32, x, case as u64, z, x, z, y, z)
+                == public_input.to_verifier(),
+        );
+    }
+    report
+}
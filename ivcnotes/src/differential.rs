@@ -0,0 +1,128 @@
+use crate::{
+    circuit::{inputs::NoteVar, IVC},
+    conformance::{samples, Report},
+    note::{Note, NoteOutIndex},
+    poseidon::PoseidonConfigs,
+    Address, AssetHash, Blind, BlindNoteHash, FWrap, MemoHash, NoteHash, NullifierKey,
+    SettlementRef,
+};
+use ark_ec::{twisted_edwards::Affine, AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::{
+    alloc::AllocVar, fields::fp::FpVar, groups::curves::twisted_edwards::AffineVar, R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSystem, ConstraintSystemRef, Result as CSResult};
+use arkeddsa::PublicKey;
+
+// Differential checks of the native hashes against their gadgets. Every
+// sample is fed through both and the gadget output is extracted from the
+// witness. Synthesis of all the gadgets is slow, so this is only built with
+// the `heavy-tests` feature.
+
+const VALUES: [u64; 4] = [0, 1, u64::MAX - 1, u64::MAX];
+const STEPS: [u32; 3] = [0, 1, u32::MAX];
+const INDEXES: [NoteOutIndex; 4] = [
+    NoteOutIndex::Issue,
+    NoteOutIndex::Out(0),
+    NoteOutIndex::Out(1),
+    NoteOutIndex::Out(2),
+];
+
+fn witness<F: PrimeField>(cs: &ConstraintSystemRef<F>, value: F) -> CSResult<FpVar<F>> {
+    FpVar::new_witness(cs.clone(), || Ok(value))
+}
+
+pub fn run_differential<E: IVC>(h: &PoseidonConfigs<E::Field>) -> CSResult<Report> {
+    let mut report = Report::default();
+    let samples = samples::<E::Field>();
+    let n = samples.len();
+
+    // distinct inputs, and the same input repeated in every position
+    let cases = (0..n)
+        .map(|i| (samples[i], samples[(i + 1) % n], samples[(i + 2) % n]))
+        .chain(samples.iter().map(|x| (*x, *x, *x)));
+
+    for (case, (x, y, z)) in cases.enumerate() {
+        let cs = ConstraintSystem::<E::Field>::new_ref();
+        let (xv, yv, zv) = (witness(&cs, x)?, witness(&cs, y)?, witness(&cs, z)?);
+
+        // identity commitment with a key derived from the sample
+        let scalar = <E::TE as ark_ec::CurveConfig>::ScalarField::from_le_bytes_mod_order(
+            &x.into_bigint().to_bytes_le(),
+        );
+        let point = (Affine::<E::TE>::generator() * scalar).into_affine();
+        let public_key = PublicKey::from(point);
+        let native = h.id_commitment(&NullifierKey::from(y), &public_key);
+        let pv = AffineVar::new_witness(cs.clone(), || Ok(point))?;
+        let gadget = h.var_id_commitment(cs.clone(), &yv, &pv)?;
+        report.check("id_commitment", case, gadget.value()? == native.inner());
+
+        let note = Note::new(
+            &AssetHash::from(x),
+            &Address::from(y),
+            VALUES[case % VALUES.len()],
+            STEPS[case % STEPS.len()],
+            &INDEXES[case % INDEXES.len()],
+            &BlindNoteHash::from(z),
+            Blind::from(x),
+        )
+        .with_memo(&MemoHash::from(y))
+        .with_expiry_step(STEPS[(case + 1) % STEPS.len()]);
+        let (note_hash, blind_note_hash) = h.note(&note);
+        let note_var = NoteVar::new(
+            &xv,
+            &yv,
+            &witness(&cs, E::Field::from(note.value))?,
+            &witness(&cs, E::Field::from(note.step))?,
+            &zv,
+            &witness(&cs, note.out_index.inner::<E::Field>())?,
+            &yv,
+            &witness(&cs, E::Field::from(note.expiry_step))?,
+        );
+        let gadget = h.var_note(cs.clone(), &note_var)?;
+        report.check("note_hash", case, gadget.value()? == note_hash.inner());
+        let gadget = h.var_blind_note(cs.clone(), &gadget, &xv)?;
+        report.check(
+            "blind_note_hash",
+            case,
+            gadget.value()? == blind_note_hash.inner(),
+        );
+
+        let native = h.state(&[BlindNoteHash::from(x), BlindNoteHash::from(y)]);
+        let gadget = h.var_state(cs.clone(), &[xv.clone(), yv.clone()])?;
+        report.check("state", case, gadget.value()? == native.inner());
+        let native = h.state(&[x, y, z].map(BlindNoteHash::from));
+        let gadget = h.var_state(cs.clone(), &[xv.clone(), yv.clone(), zv.clone()])?;
+        report.check("state", case, gadget.value()? == native.inner());
+
+        let native = h.nullifier(&NoteHash::from(x), &NullifierKey::from(z));
+        let gadget = h.var_nullifier(cs.clone(), &xv, &zv)?;
+        report.check("nullifier", case, gadget.value()? == native.inner());
+
+        let native = h.sighash(&NoteHash::from(x), &[NoteHash::from(y), NoteHash::from(z)]);
+        let gadget = h.var_sighash(cs.clone(), &xv, &[yv.clone(), zv.clone()])?;
+        report.check("sighash", case, gadget.value()? == native.inner());
+
+        let value = VALUES[case % VALUES.len()];
+        let native = h.sighash_burn(&NoteHash::from(x), value);
+        let gadget = h.var_sighash_burn(cs.clone(), &xv, &witness(&cs, E::Field::from(value))?)?;
+        report.check("sighash_burn", case, gadget.value()? == native.inner());
+
+        let native = h.sighash_redeem(&NoteHash::from(x), value, &SettlementRef::from(y));
+        let gadget =
+            h.var_sighash_redeem(cs.clone(), &xv, &witness(&cs, E::Field::from(value))?, &yv)?;
+        report.check("sighash_redeem", case, gadget.value()? == native.inner());
+
+        let native = h.sighash_merge(
+            &NoteHash::from(x),
+            &NoteHash::from(y),
+            &[NoteHash::from(z), NoteHash::from(x)],
+        );
+        let gadget = h.var_sighash_merge(cs.clone(), &xv, &yv, &[zv.clone(), xv.clone()])?;
+        report.check("sighash_merge", case, gadget.value()? == native.inner());
+
+        report.check("satisfied", case, cs.is_satisfied()?);
+    }
+
+    Ok(report)
+}
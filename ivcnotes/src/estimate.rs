@@ -0,0 +1,156 @@
+use crate::{
+    circuit::{profile::Profile, Circuit, IVC},
+    payload,
+    poseidon::PoseidonConfigs,
+    select::CoinSelector,
+    wallet::Wallet,
+    wire,
+};
+use ark_crypto_primitives::snark::SNARK;
+use ark_ff::{PrimeField, Zero};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable},
+};
+use ark_serialize::CanonicalSerialize;
+use rand::{CryptoRng, RngCore};
+use std::time::{Duration, Instant};
+
+// What a payment will cost before it is made, so that applications can show
+// it to the user. Proving dominates the time and scales with the constraints
+// of the step circuit, the rate of the device is taken from its calibration.
+
+// proving speed of the device
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub constraints_per_second: f64,
+}
+
+impl Default for Calibration {
+    // conservative figure of a phone, used until the device is calibrated
+    // with `calibrate`
+    fn default() -> Self {
+        Self {
+            constraints_per_second: 50_000.0,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn proving_time(&self, constraints: usize) -> Duration {
+        Duration::from_secs_f64(constraints as f64 / self.constraints_per_second)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    // one proof per spent note
+    pub proofs: usize,
+    pub proving_time: Duration,
+    // bytes of the sealed payloads sent to the receiver
+    pub payload_size: usize,
+    // proofs the receiver verifies for the longest history it gets
+    pub history_len: usize,
+    // notes carry no fees, relays that charge add theirs
+    pub fee: u64,
+}
+
+// constraints of the benchmark, small enough to run once on a phone
+const BENCH_CONSTRAINTS: usize = 1 << 13;
+
+// chain of squarings, dense like the poseidon rounds that dominate a step
+struct Bench<F: PrimeField> {
+    x: F,
+}
+
+impl<F: PrimeField> ConstraintSynthesizer<F> for Bench<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        let mut x = self.x;
+        let mut var = cs.new_input_variable(|| Ok(x))?;
+        for _ in 0..BENCH_CONSTRAINTS {
+            let next = x.square() + F::one();
+            let next_var = cs.new_witness_variable(|| Ok(next))?;
+            cs.enforce_constraint(
+                lc!() + var,
+                lc!() + var,
+                lc!() + next_var - (F::one(), Variable::One),
+            )?;
+            (x, var) = (next, next_var);
+        }
+        Ok(())
+    }
+}
+
+// proves the benchmark with the snark of `E` and measures the rate. It runs
+// a setup too so it takes about twice as long as a proof of the benchmark.
+pub fn calibrate<E: IVC, R: RngCore + CryptoRng>(rng: &mut R) -> Result<Calibration, crate::Error> {
+    let err = |_| crate::Error::With("calibration failed");
+    let bench = || Bench {
+        x: E::Field::from(3u64),
+    };
+    let (pk, _) = E::Snark::circuit_specific_setup(bench(), rng).map_err(err)?;
+    let start = Instant::now();
+    E::Snark::prove(&pk, bench(), rng).map_err(err)?;
+    let elapsed = start.elapsed().as_secs_f64().max(1e-6);
+    Ok(Calibration {
+        constraints_per_second: BENCH_CONSTRAINTS as f64 / elapsed,
+    })
+}
+
+// constraints of a step without an asset policy
+pub(crate) fn step_constraints<E: IVC>(h: &PoseidonConfigs<E::Field>) -> usize {
+    Profile::generate(Circuit::<E>::empty(h))
+        .map(|profile| profile.num_constraints)
+        .unwrap_or_default()
+}
+
+impl<E: IVC> Wallet<E> {
+    // measures the device once, the result can be stored and set back with
+    // `set_calibration` on the next start
+    pub fn calibrate<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Calibration, crate::Error> {
+        let calibration = calibrate::<E, _>(rng)?;
+        self.set_calibration(calibration);
+        Ok(calibration)
+    }
+
+    // cost of paying `value` with the notes `selector` picks, as `pay` does
+    pub fn estimate(
+        &self,
+        selector: &impl CoinSelector<E>,
+        value: u64,
+    ) -> Result<Estimate, crate::Error> {
+        let plan = self.plan(selector, value)?;
+        let field = E::Field::zero().compressed_size();
+        let mut estimate = Estimate {
+            proofs: plan.len(),
+            proving_time: self
+                .calibration()
+                .proving_time(self.step_constraints() * plan.len()),
+            payload_size: 0,
+            history_len: 0,
+            fee: 0,
+        };
+        for split in plan.iter() {
+            let history = &self.spendables()[split.index];
+            // a split appends a step without merged steps to the history and
+            // the receiver gets none of the unknown records
+            let step = history.steps[0].proof.compressed_size() + 4 * field + 4;
+            let unknown: usize = history
+                .unknown
+                .iter()
+                .map(|(_, record)| 6 + record.len())
+                .sum();
+            estimate.payload_size +=
+                payload::overhead::<E>() + wire::encode(history).len() + step - unknown;
+            estimate.history_len = estimate.history_len.max(proofs(&history.steps) + 1);
+        }
+        Ok(estimate)
+    }
+}
+
+fn proofs<E: IVC>(steps: &[crate::note::IVCStep<E>]) -> usize {
+    steps.iter().map(|step| 1 + proofs(&step.merged)).sum()
+}
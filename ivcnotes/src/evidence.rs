@@ -0,0 +1,163 @@
+use crate::{
+    asset::Asset,
+    circuit::{inputs::PublicInput, Verifier, IVC},
+    note::{leaves, Note, NoteHistory, NoteOutIndex},
+    poseidon::PoseidonConfigs,
+    Address, BlindNoteHash,
+};
+use ark_crypto_primitives::snark::SNARK;
+
+#[derive(Clone)]
+pub struct EvidenceStep<E: IVC> {
+    pub(crate) public_input: PublicInput<E::Field>,
+    pub(crate) proof: <<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+}
+
+impl<E: IVC> std::fmt::Debug for EvidenceStep<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvidenceStep")
+            .field("public_input", &self.public_input)
+            .finish()
+    }
+}
+
+// `Evidence` is a redacted package to show a transfer happened, eg. to an
+// arbitrator. It carries only the public inputs and the proofs of the steps
+// from issuance up to the transfer. Note plaintexts, blinding factors and
+// siblings of the history are stripped.
+#[derive(Clone, Debug)]
+pub struct Evidence<E: IVC> {
+    pub(crate) asset: Asset<E::Field>,
+    pub(crate) steps: Vec<EvidenceStep<E>>,
+}
+
+impl<E: IVC> NoteHistory<E> {
+    // redacted evidence of the transfer at step `transfer` of this history
+    pub fn evidence(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        transfer: usize,
+    ) -> Result<Evidence<E>, crate::Error> {
+        (transfer < self.steps.len())
+            .then_some(())
+            .ok_or(crate::Error::With("bad transfer index"))?;
+
+        let asset_hash = &self.asset.hash(h);
+        let mut state_in = &asset_hash.as_ref().into();
+        let mut steps = vec![];
+        for (i, step) in self.steps.iter().take(transfer + 1).enumerate() {
+            let public_input = step.public_input(h, asset_hash, state_in, i as u32);
+            steps.push(EvidenceStep {
+                public_input,
+                proof: step.proof.clone(),
+            });
+            state_in = &step.state;
+        }
+
+        Ok(Evidence {
+            asset: self.asset,
+            steps,
+        })
+    }
+}
+
+impl<E: IVC> Evidence<E> {
+    pub fn asset(&self) -> &Asset<E::Field> {
+        &self.asset
+    }
+
+    // public inputs of the disputed transfer
+    pub fn transfer(&self) -> &PublicInput<E::Field> {
+        &self.steps.last().unwrap().public_input
+    }
+
+    pub fn steps(&self) -> &[EvidenceStep<E>] {
+        &self.steps
+    }
+
+    pub fn verify(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        verifier: &Verifier<E>,
+    ) -> Result<(), crate::Error> {
+        let asset_hash = self.asset.hash(h);
+        let mut state_in = asset_hash.as_ref().into();
+        for (i, step) in self.steps.iter().enumerate() {
+            let pi = &step.public_input;
+            (pi.asset_hash == asset_hash && pi.state_in == state_in && pi.step == i as u32)
+                .then_some(())
+                .ok_or(crate::Error::With("broken evidence chain"))?;
+            verifier
+                .verify_proof(&step.proof, pi)?
+                .then_some(())
+                .ok_or(crate::Error::With("verification failed"))?;
+            state_in = pi.state_out;
+        }
+        Ok(())
+    }
+}
+
+// `Receipt` is the proof of payment the sender keeps. It opens the sent note
+// of the last step of the evidence, which is committed in the proven output
+// state next to its sibling notes.
+#[derive(Clone, Debug)]
+pub struct Receipt<E: IVC> {
+    pub(crate) evidence: Evidence<E>,
+    // sent note
+    pub(crate) note: Note<E::Field>,
+    // blinded hashes of the other outputs of the step
+    pub(crate) siblings: Vec<BlindNoteHash<E::Field>>,
+}
+
+impl<E: IVC> Receipt<E> {
+    // receipt of the current note of a history that is just sent
+    pub(crate) fn new(
+        h: &PoseidonConfigs<E::Field>,
+        history: &NoteHistory<E>,
+    ) -> Result<Self, crate::Error> {
+        let evidence = history.evidence(h, history.steps.len() - 1)?;
+        Ok(Self {
+            evidence,
+            note: history.current_note,
+            siblings: history.siblings.clone(),
+        })
+    }
+
+    pub fn evidence(&self) -> &Evidence<E> {
+        &self.evidence
+    }
+
+    pub fn receiver(&self) -> &Address<E::Field> {
+        &self.note.owner
+    }
+
+    pub fn value(&self) -> u64 {
+        self.note.value
+    }
+
+    pub fn verify(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        verifier: &Verifier<E>,
+    ) -> Result<(), crate::Error> {
+        self.evidence.verify(h, verifier)?;
+
+        let transfer = self.evidence.transfer();
+        (self.note.asset_hash == transfer.asset_hash)
+            .then_some(())
+            .ok_or(crate::Error::With("receipt asset mismatch"))?;
+
+        let (_, blind_note_hash) = h.note(&self.note);
+        let state = match self.note.out_index {
+            NoteOutIndex::Out(_) => h.state(&leaves(
+                &blind_note_hash,
+                &self.note.out_index,
+                &self.siblings,
+            )),
+            NoteOutIndex::Issue => return Err(crate::Error::With("receipt of an issuance")),
+        };
+        (state == transfer.state_out)
+            .then_some(())
+            .ok_or(crate::Error::With("note is not in the transfer"))
+    }
+}
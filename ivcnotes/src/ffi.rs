@@ -0,0 +1,1158 @@
+// Stable C ABI for embedding the crate in apps and in other languages, eg.
+// through ctypes or cffi from Python or as exports of a wasm32 build.
+//
+// - every call returns a status, `OK` or the catalog code of the error, and
+//   writes its results through out pointers that the caller owns
+// - strings are utf-8 bytes with a length, never NUL terminated
+// - outputs of variable length go into a buffer of the caller, the written
+//   length is set even when the buffer is too small so it can retry
+//
+// Pointers must be valid for their lengths for the duration of the call, null
+// pointers are rejected. A panic doesn't cross the boundary, the call returns
+// `ERROR_UNKNOWN` and the handles it was given should be released.
+//
+// Auth, wallet, note and outbox are opaque handles. A call that creates one
+// writes it through an out pointer and the caller releases it with the `free`
+// call of its kind, a call that takes one by value, eg. `wallet_new` taking the
+// auth, releases it whether it succeeds or not. The handles are not thread
+// safe, a wallet must not be used from two threads at once.
+//
+// The config of the wallet is a type parameter that C cannot name, so the
+// calls that take handles are exported by the app crate that picks the config
+// with `ffi_exports!`.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{
+    amount::{self, Locale},
+    catalog,
+};
+#[cfg(feature = "prover")]
+use crate::{
+    circuit::{Prover, Verifier, IVC},
+    codec::Codecs,
+    handshake::{negotiate, Hello},
+    id::Auth,
+    limits::Limits,
+    note::NoteHistory,
+    payload::{seal, EncryptionKey},
+    poseidon::PoseidonConfigs,
+    select::MinDepth,
+    wallet::{CommReceiver, Wallet},
+    wire, Address, FWrap,
+};
+#[cfg(feature = "prover")]
+use ark_crypto_primitives::snark::SNARK;
+#[cfg(feature = "prover")]
+use ark_serialize::CanonicalDeserialize;
+use std::panic::AssertUnwindSafe;
+
+pub const OK: i32 = 0;
+// errors whose message is not in the catalog
+pub const ERROR_UNKNOWN: i32 = -1;
+
+// runs the body of a call, a panic must not unwind into the caller so it is
+// caught and reported as an unknown error
+fn status(call: impl FnOnce() -> Result<(), crate::Error>) -> i32 {
+    match std::panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => OK,
+        Ok(Err(e)) => match e.code() {
+            catalog::UNKNOWN => ERROR_UNKNOWN,
+            code => code as i32,
+        },
+        Err(_) => ERROR_UNKNOWN,
+    }
+}
+
+fn null() -> crate::Error {
+    crate::Error::With("null pointer")
+}
+
+unsafe fn write<T>(out: *mut T, value: T) -> Result<(), crate::Error> {
+    out.as_mut().map(|out| *out = value).ok_or_else(null)
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], crate::Error> {
+    match len {
+        0 => Ok(&[]),
+        _ => (!ptr.is_null())
+            .then(|| std::slice::from_raw_parts(ptr, len))
+            .ok_or_else(null),
+    }
+}
+
+// copies into the buffer of the caller
+unsafe fn fill(
+    data: &[u8],
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> Result<(), crate::Error> {
+    write(written, data.len())?;
+    (data.len() <= capacity)
+        .then_some(())
+        .ok_or(crate::Error::With("buffer too small"))?;
+    match data.len() {
+        0 => Ok(()),
+        _ => {
+            (!out.is_null()).then_some(()).ok_or_else(null)?;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), out, data.len());
+            Ok(())
+        }
+    }
+}
+
+fn locale(decimal: u32, group: u32) -> Result<Locale, crate::Error> {
+    let char = |c| char::from_u32(c).ok_or(crate::Error::With("unexpected character in amount"));
+    Ok(Locale {
+        decimal: char(decimal)?,
+        group: char(group)?,
+    })
+}
+
+// english template of a status, see `catalog`
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_error_message(
+    code: i32,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    let message = u16::try_from(code)
+        .ok()
+        .and_then(catalog::message)
+        .unwrap_or("unknown error");
+    status(|| fill(message.as_bytes(), out, capacity, written))
+}
+
+// amounts
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_amount_add(a: u64, b: u64, out: *mut u64) -> i32 {
+    status(|| amount::add_amounts(a, b).and_then(|value| write(out, value)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_amount_sub(a: u64, b: u64, out: *mut u64) -> i32 {
+    status(|| amount::sub_amounts(a, b).and_then(|value| write(out, value)))
+}
+
+// `out` takes `parts` values
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_amount_split(
+    value: u64,
+    parts: usize,
+    decimals: u8,
+    precision: u8,
+    out: *mut u64,
+) -> i32 {
+    status(|| {
+        let split = amount::split_amount(value, parts, decimals, precision)?;
+        (!out.is_null()).then_some(()).ok_or_else(null)?;
+        std::ptr::copy_nonoverlapping(split.as_ptr(), out, split.len());
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_amount_rescale(
+    value: u64,
+    from: u8,
+    to: u8,
+    out: *mut u64,
+) -> i32 {
+    status(|| amount::rescale_amount(value, from, to).and_then(|value| write(out, value)))
+}
+
+// separators are unicode scalar values, eg. '.' and ','
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_amount_parse(
+    input: *const u8,
+    len: usize,
+    decimals: u8,
+    decimal: u32,
+    group: u32,
+    out: *mut u64,
+) -> i32 {
+    status(|| {
+        let input = std::str::from_utf8(bytes(input, len)?)
+            .map_err(|_| crate::Error::With("unexpected character in amount"))?;
+        let value = amount::parse_amount(input, decimals, &locale(decimal, group)?)?;
+        write(out, value)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_amount_format(
+    value: u64,
+    decimals: u8,
+    decimal: u32,
+    group: u32,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| {
+        let formatted = amount::format_amount(value, decimals, &locale(decimal, group)?)?;
+        fill(formatted.as_bytes(), out, capacity, written)
+    })
+}
+
+#[cfg(feature = "prover")]
+unsafe fn handle<'a, T>(ptr: *const T) -> Result<&'a T, crate::Error> {
+    ptr.as_ref().ok_or_else(null)
+}
+
+#[cfg(feature = "prover")]
+unsafe fn handle_mut<'a, T>(ptr: *mut T) -> Result<&'a mut T, crate::Error> {
+    ptr.as_mut().ok_or_else(null)
+}
+
+// moves the value behind a new handle
+#[cfg(feature = "prover")]
+unsafe fn give<T>(out: *mut *mut T, value: T) -> Result<(), crate::Error> {
+    (!out.is_null()).then_some(()).ok_or_else(null)?;
+    *out = Box::into_raw(Box::new(value));
+    Ok(())
+}
+
+#[cfg(feature = "prover")]
+unsafe fn take<T>(ptr: *mut T) -> Result<T, crate::Error> {
+    (!ptr.is_null())
+        .then(|| *Box::from_raw(ptr))
+        .ok_or_else(null)
+}
+
+// freeing a null handle does nothing, a panic while dropping is swallowed
+pub unsafe fn free<T>(ptr: *mut T) {
+    if !ptr.is_null() {
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(ptr))));
+    }
+}
+
+#[cfg(feature = "prover")]
+unsafe fn string<'a>(ptr: *const u8, len: usize) -> Result<&'a str, crate::Error> {
+    std::str::from_utf8(bytes(ptr, len)?).map_err(|_| crate::Error::With("string is not utf-8"))
+}
+
+// auth
+
+#[cfg(feature = "prover")]
+pub unsafe fn auth_generate<E: IVC>(h: &PoseidonConfigs<E::Field>, out: *mut *mut Auth<E>) -> i32 {
+    status(|| {
+        Auth::generate(h, &mut rand::thread_rng())
+            .map_err(|_| crate::Error::With("bad seed"))
+            .and_then(|auth| give(out, auth))
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn auth_from_seed<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    seed: *const u8,
+    len: usize,
+    out: *mut *mut Auth<E>,
+) -> i32 {
+    status(|| {
+        let auth =
+            Auth::from_seed(h, bytes(seed, len)?).map_err(|_| crate::Error::With("bad seed"))?;
+        give(out, auth)
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn auth_from_keystore<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    password: *const u8,
+    password_len: usize,
+    keystore: *const u8,
+    len: usize,
+    out: *mut *mut Auth<E>,
+) -> i32 {
+    status(|| {
+        let password = string(password, password_len)?;
+        give(
+            out,
+            Auth::from_keystore(h, password, bytes(keystore, len)?)?,
+        )
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn auth_to_keystore<E: IVC>(
+    auth: *const Auth<E>,
+    password: *const u8,
+    password_len: usize,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| {
+        let password = string(password, password_len)?;
+        let keystore = handle(auth)?.to_keystore(password, &mut rand::thread_rng());
+        fill(&keystore, out, capacity, written)
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn auth_address<E: IVC>(
+    auth: *const Auth<E>,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| fill(&handle(auth)?.address().to_bytes(), out, capacity, written))
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn auth_encryption_key<E: IVC>(
+    auth: *const Auth<E>,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| {
+        fill(
+            &handle(auth)?.encryption_key().to_bytes(),
+            out,
+            capacity,
+            written,
+        )
+    })
+}
+
+// wallet
+
+// takes the auth, keys are in the compressed canonical serialization
+#[cfg(feature = "prover")]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn wallet_new<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    auth: *mut Auth<E>,
+    proving_key: *const u8,
+    proving_key_len: usize,
+    verifying_key: *const u8,
+    verifying_key_len: usize,
+    out: *mut *mut Wallet<E>,
+) -> i32 {
+    status(|| {
+        let auth = take(auth)?;
+        let pk = <E::Snark as SNARK<E::Field>>::ProvingKey::deserialize_compressed(bytes(
+            proving_key,
+            proving_key_len,
+        )?)
+        .map_err(|_| crate::Error::With("bad proving key"))?;
+        let vk = <E::Snark as SNARK<E::Field>>::VerifyingKey::deserialize_compressed(bytes(
+            verifying_key,
+            verifying_key_len,
+        )?)
+        .map_err(|_| crate::Error::With("bad verifying key"))?;
+        give(
+            out,
+            Wallet::new(auth, h, Prover::new(pk), Verifier::new(vk)),
+        )
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn wallet_receive<E: IVC>(
+    wallet: *mut Wallet<E>,
+    payload: *const u8,
+    len: usize,
+) -> i32 {
+    status(|| handle_mut(wallet)?.receive_sealed(bytes(payload, len)?))
+}
+
+// balance of an asset as a decimal string of base units
+#[cfg(feature = "prover")]
+pub unsafe fn wallet_balance<E: IVC>(
+    wallet: *const Wallet<E>,
+    asset: *const u8,
+    len: usize,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| {
+        let asset = crate::AssetHash::from_bytes(bytes(asset, len)?)
+            .map_err(|_| crate::Error::With("bad asset"))?;
+        let balance = handle(wallet)?.balance(&asset).to_string();
+        fill(balance.as_bytes(), out, capacity, written)
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn wallet_note_count<E: IVC>(wallet: *const Wallet<E>, out: *mut usize) -> i32 {
+    status(|| write(out, handle(wallet)?.spendables().len()))
+}
+
+// copy of the unspent note at `index`
+#[cfg(feature = "prover")]
+pub unsafe fn wallet_note<E: IVC>(
+    wallet: *const Wallet<E>,
+    index: usize,
+    out: *mut *mut NoteHistory<E>,
+) -> i32 {
+    status(|| {
+        let history = handle(wallet)?
+            .spendables()
+            .get(index)
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        give(out, history.clone())
+    })
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn wallet_export_notes<E: IVC>(
+    wallet: *const Wallet<E>,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| fill(&handle(wallet)?.export_notes(), out, capacity, written))
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn wallet_import_notes<E: IVC>(
+    wallet: *mut Wallet<E>,
+    store: *const u8,
+    len: usize,
+    imported: *mut usize,
+) -> i32 {
+    status(|| {
+        let count = handle_mut(wallet)?.import_notes(bytes(store, len)?)?;
+        write(imported, count)
+    })
+}
+
+// `Outbox` holds the payloads of a payment sealed to the receiver, one for
+// each note sent, until they are handed to the transport
+pub struct Outbox {
+    payloads: Vec<Vec<u8>>,
+}
+
+#[cfg(feature = "prover")]
+struct Sealer<E: IVC> {
+    address: Address<E::Field>,
+    key: EncryptionKey<E>,
+    payloads: Vec<Vec<u8>>,
+}
+
+#[cfg(feature = "prover")]
+impl<E: IVC> CommReceiver<E> for Sealer<E> {
+    // the handshake is not exposed to C so the payloads are not compressed
+    fn receive(&mut self, history: &NoteHistory<E>) -> Result<(), crate::Error> {
+        let agreement = negotiate(&Hello::default(), &Hello::default())?;
+        let payload = seal(
+            history,
+            &self.key,
+            &Codecs::default(),
+            &agreement,
+            &mut rand::thread_rng(),
+        )?;
+        self.payloads.push(payload);
+        Ok(())
+    }
+
+    fn address(&self) -> &Address<E::Field> {
+        &self.address
+    }
+}
+
+// pays the receiver out of the notes with the least history
+#[cfg(feature = "prover")]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn wallet_pay<E: IVC>(
+    wallet: *mut Wallet<E>,
+    address: *const u8,
+    address_len: usize,
+    encryption_key: *const u8,
+    encryption_key_len: usize,
+    value: u64,
+    out: *mut *mut Outbox,
+) -> i32 {
+    status(|| {
+        let wallet = handle_mut(wallet)?;
+        let address = Address::from_bytes(bytes(address, address_len)?)
+            .map_err(|_| crate::Error::With("bad address"))?;
+        let key = EncryptionKey::from_bytes(bytes(encryption_key, encryption_key_len)?)?;
+        let mut sealer = Sealer {
+            address,
+            key,
+            payloads: vec![],
+        };
+        // a failed payment can leave some of its splits sent
+        let result = wallet
+            .pay(&mut rand::thread_rng(), &mut sealer, &MinDepth, value)
+            .and_then(|payment| payment.failed.map_or(Ok(()), Err));
+        give(
+            out,
+            Outbox {
+                payloads: sealer.payloads,
+            },
+        )?;
+        result
+    })
+}
+
+// notes
+
+#[cfg(feature = "prover")]
+pub unsafe fn note_decode<E: IVC>(
+    bytes_: *const u8,
+    len: usize,
+    out: *mut *mut NoteHistory<E>,
+) -> i32 {
+    status(|| give(out, wire::decode(bytes(bytes_, len)?, &Limits::default())?))
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn note_encode<E: IVC>(
+    note: *const NoteHistory<E>,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| fill(&wire::encode(handle(note)?), out, capacity, written))
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn note_value<E: IVC>(note: *const NoteHistory<E>, out: *mut u64) -> i32 {
+    status(|| write(out, handle(note)?.current_note.value))
+}
+
+#[cfg(feature = "prover")]
+pub unsafe fn note_asset<E: IVC>(
+    note: *const NoteHistory<E>,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| {
+        fill(
+            &handle(note)?.current_note.asset_hash.to_bytes(),
+            out,
+            capacity,
+            written,
+        )
+    })
+}
+
+// outbox
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_outbox_len(outbox: *const Outbox, out: *mut usize) -> i32 {
+    status(|| {
+        outbox
+            .as_ref()
+            .ok_or_else(null)
+            .and_then(|outbox| write(out, outbox.payloads.len()))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_outbox_payload(
+    outbox: *const Outbox,
+    index: usize,
+    out: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    status(|| {
+        let outbox = outbox.as_ref().ok_or_else(null)?;
+        let payload = outbox
+            .payloads
+            .get(index)
+            .ok_or(crate::Error::With("bad payload index"))?;
+        fill(payload, out, capacity, written)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivcnotes_outbox_free(outbox: *mut Outbox) {
+    free(outbox)
+}
+
+// Exports the calls that take handles for a config, eg. in the crate that
+// builds the static library of an app
+//
+// ivcnotes::ffi_exports!(Config, &POSEIDON);
+//
+// where the second argument gives the poseidon configs of the deployment.
+#[cfg(feature = "prover")]
+#[macro_export]
+macro_rules! ffi_exports {
+    ($ivc:ty, $h:expr) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_generate(
+            out: *mut *mut $crate::id::Auth<$ivc>,
+        ) -> i32 {
+            $crate::ffi::auth_generate::<$ivc>($h, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_from_seed(
+            seed: *const u8,
+            len: usize,
+            out: *mut *mut $crate::id::Auth<$ivc>,
+        ) -> i32 {
+            $crate::ffi::auth_from_seed::<$ivc>($h, seed, len, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_from_keystore(
+            password: *const u8,
+            password_len: usize,
+            keystore: *const u8,
+            len: usize,
+            out: *mut *mut $crate::id::Auth<$ivc>,
+        ) -> i32 {
+            $crate::ffi::auth_from_keystore::<$ivc>($h, password, password_len, keystore, len, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_to_keystore(
+            auth: *const $crate::id::Auth<$ivc>,
+            password: *const u8,
+            password_len: usize,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::auth_to_keystore(auth, password, password_len, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_address(
+            auth: *const $crate::id::Auth<$ivc>,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::auth_address(auth, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_encryption_key(
+            auth: *const $crate::id::Auth<$ivc>,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::auth_encryption_key(auth, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_auth_free(auth: *mut $crate::id::Auth<$ivc>) {
+            $crate::ffi::free(auth)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_new(
+            auth: *mut $crate::id::Auth<$ivc>,
+            proving_key: *const u8,
+            proving_key_len: usize,
+            verifying_key: *const u8,
+            verifying_key_len: usize,
+            out: *mut *mut $crate::wallet::Wallet<$ivc>,
+        ) -> i32 {
+            $crate::ffi::wallet_new::<$ivc>(
+                $h,
+                auth,
+                proving_key,
+                proving_key_len,
+                verifying_key,
+                verifying_key_len,
+                out,
+            )
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_receive(
+            wallet: *mut $crate::wallet::Wallet<$ivc>,
+            payload: *const u8,
+            len: usize,
+        ) -> i32 {
+            $crate::ffi::wallet_receive(wallet, payload, len)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_balance(
+            wallet: *const $crate::wallet::Wallet<$ivc>,
+            asset: *const u8,
+            len: usize,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::wallet_balance(wallet, asset, len, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_note_count(
+            wallet: *const $crate::wallet::Wallet<$ivc>,
+            out: *mut usize,
+        ) -> i32 {
+            $crate::ffi::wallet_note_count(wallet, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_note(
+            wallet: *const $crate::wallet::Wallet<$ivc>,
+            index: usize,
+            out: *mut *mut $crate::note::NoteHistory<$ivc>,
+        ) -> i32 {
+            $crate::ffi::wallet_note(wallet, index, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_export_notes(
+            wallet: *const $crate::wallet::Wallet<$ivc>,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::wallet_export_notes(wallet, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_import_notes(
+            wallet: *mut $crate::wallet::Wallet<$ivc>,
+            store: *const u8,
+            len: usize,
+            imported: *mut usize,
+        ) -> i32 {
+            $crate::ffi::wallet_import_notes(wallet, store, len, imported)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_pay(
+            wallet: *mut $crate::wallet::Wallet<$ivc>,
+            address: *const u8,
+            address_len: usize,
+            encryption_key: *const u8,
+            encryption_key_len: usize,
+            value: u64,
+            out: *mut *mut $crate::ffi::Outbox,
+        ) -> i32 {
+            $crate::ffi::wallet_pay(
+                wallet,
+                address,
+                address_len,
+                encryption_key,
+                encryption_key_len,
+                value,
+                out,
+            )
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_wallet_free(wallet: *mut $crate::wallet::Wallet<$ivc>) {
+            $crate::ffi::free(wallet)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_note_decode(
+            bytes: *const u8,
+            len: usize,
+            out: *mut *mut $crate::note::NoteHistory<$ivc>,
+        ) -> i32 {
+            $crate::ffi::note_decode::<$ivc>(bytes, len, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_note_encode(
+            note: *const $crate::note::NoteHistory<$ivc>,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::note_encode(note, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_note_value(
+            note: *const $crate::note::NoteHistory<$ivc>,
+            out: *mut u64,
+        ) -> i32 {
+            $crate::ffi::note_value(note, out)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_note_asset(
+            note: *const $crate::note::NoteHistory<$ivc>,
+            out: *mut u8,
+            capacity: usize,
+            written: *mut usize,
+        ) -> i32 {
+            $crate::ffi::note_asset(note, out, capacity, written)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn ivcnotes_note_free(note: *mut $crate::note::NoteHistory<$ivc>) {
+            $crate::ffi::free(note)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::{null, null_mut};
+
+    fn code(message: &str) -> i32 {
+        catalog::code(message) as i32
+    }
+
+    // output of a call into the buffer of the caller, sized by a first call
+    // with no buffer as a c caller would
+    unsafe fn output(call: impl Fn(*mut u8, usize, *mut usize) -> i32) -> Result<Vec<u8>, i32> {
+        let mut len = 0;
+        match call(null_mut(), 0, &mut len) {
+            OK => return Ok(vec![]),
+            status if status != code("buffer too small") => return Err(status),
+            _ => {}
+        }
+        let mut buffer = vec![0; len];
+        match call(buffer.as_mut_ptr(), len, &mut len) {
+            OK => Ok(buffer[..len].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    unsafe fn message(status: i32) -> String {
+        let message =
+            output(|out, capacity, written| ivcnotes_error_message(status, out, capacity, written));
+        String::from_utf8(message.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn status_is_the_catalog_code() {
+        unsafe {
+            let mut out = 0;
+            assert_eq!(ivcnotes_amount_add(1, 2, &mut out), OK);
+            assert_eq!(out, 3);
+            let status = ivcnotes_amount_add(u64::MAX, 1, &mut out);
+            assert_eq!(status, code("amount overflow"));
+            assert_eq!(message(status), "amount overflow");
+            let status = ivcnotes_amount_sub(1, 2, &mut out);
+            assert_eq!(status, code("amount underflow"));
+            assert_eq!(message(status), "amount underflow");
+            // the output is left alone on failure
+            assert_eq!(out, 3);
+
+            let mut parts = [0; 3];
+            assert_eq!(ivcnotes_amount_split(10, 3, 0, 0, parts.as_mut_ptr()), OK);
+            assert_eq!(parts.iter().sum::<u64>(), 10);
+            assert_eq!(
+                ivcnotes_amount_split(10, 0, 0, 0, parts.as_mut_ptr()),
+                code("cannot split into zero parts")
+            );
+
+            let input = "1,234.5";
+            let (decimal, group) = ('.' as u32, ',' as u32);
+            let parse = |input: &[u8], decimal, out: &mut u64| {
+                ivcnotes_amount_parse(input.as_ptr(), input.len(), 2, decimal, group, out)
+            };
+            assert_eq!(parse(input.as_bytes(), decimal, &mut out), OK);
+            assert_eq!(out, 123450);
+            let formatted = output(|buffer, capacity, written| {
+                ivcnotes_amount_format(out, 2, decimal, group, buffer, capacity, written)
+            });
+            assert_eq!(formatted.unwrap(), b"1,234.50");
+            assert_eq!(
+                parse(b"1.2.3", decimal, &mut out),
+                code("malformed fraction in amount")
+            );
+            // not utf-8, nor a unicode scalar value
+            assert_eq!(
+                parse(&[0xff], decimal, &mut out),
+                code("unexpected character in amount")
+            );
+            assert_eq!(
+                parse(b"1", 0xd800, &mut out),
+                code("unexpected character in amount")
+            );
+
+            // the written length is set when the buffer is too small
+            let mut buffer = [0; 4];
+            let mut written = 0;
+            let status = ivcnotes_amount_format(
+                123450,
+                2,
+                decimal,
+                group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut written,
+            );
+            assert_eq!(status, code("buffer too small"));
+            assert_eq!(written, 8);
+
+            assert_eq!(message(-2), "unknown error");
+            assert_eq!(message(ERROR_UNKNOWN), "unknown error");
+        }
+    }
+
+    #[test]
+    fn errors_out_of_the_catalog() {
+        assert_eq!(
+            status(|| Err(crate::Error::With("not in the catalog"))),
+            ERROR_UNKNOWN
+        );
+        // a panic doesn't unwind into the caller
+        assert_eq!(status(|| panic!("unwinds")), ERROR_UNKNOWN);
+    }
+
+    #[test]
+    fn null_pointers() {
+        let null_pointer = code("null pointer");
+        unsafe {
+            assert_eq!(ivcnotes_amount_add(1, 2, null_mut()), null_pointer);
+            assert_eq!(ivcnotes_amount_split(10, 3, 0, 0, null_mut()), null_pointer);
+            let mut out = 0;
+            let (decimal, group) = ('.' as u32, ',' as u32);
+            assert_eq!(
+                ivcnotes_amount_parse(null(), 1, 0, decimal, group, &mut out),
+                null_pointer
+            );
+            // an empty input may be null
+            assert_eq!(
+                ivcnotes_amount_parse(null(), 0, 0, decimal, group, &mut out),
+                code("missing integer part in amount")
+            );
+            assert_eq!(
+                ivcnotes_amount_format(1, 0, decimal, group, null_mut(), 8, null_mut()),
+                null_pointer
+            );
+            let mut written = 0;
+            assert_eq!(
+                ivcnotes_amount_format(1, 0, decimal, group, null_mut(), 8, &mut written),
+                null_pointer
+            );
+            assert_eq!(ivcnotes_outbox_len(null(), &mut written), null_pointer);
+            assert_eq!(
+                ivcnotes_outbox_payload(null(), 0, null_mut(), 0, &mut written),
+                null_pointer
+            );
+            ivcnotes_outbox_free(null_mut());
+        }
+    }
+
+    #[cfg(feature = "prover")]
+    mod handles {
+        use super::*;
+        use crate::{
+            asset::{Asset, Terms},
+            circuit::mock::{self, Mock},
+        };
+        use rand::{rngs::StdRng, SeedableRng};
+        use std::sync::OnceLock;
+
+        fn h() -> &'static PoseidonConfigs<ark_bn254::Fr> {
+            static H: OnceLock<PoseidonConfigs<ark_bn254::Fr>> = OnceLock::new();
+            H.get_or_init(PoseidonConfigs::generate)
+        }
+
+        crate::ffi_exports!(Mock, h());
+
+        unsafe fn auth(seed: u8) -> *mut Auth<Mock> {
+            let seed = [seed; 32];
+            let mut auth = null_mut();
+            assert_eq!(ivcnotes_auth_from_seed(seed.as_ptr(), 32, &mut auth), OK);
+            auth
+        }
+
+        // the mock proof system has no keys
+        unsafe fn wallet(seed: u8) -> *mut Wallet<Mock> {
+            let mut wallet = null_mut();
+            let status = ivcnotes_wallet_new(auth(seed), null(), 0, null(), 0, &mut wallet);
+            assert_eq!(status, OK);
+            wallet
+        }
+
+        unsafe fn address(auth: *const Auth<Mock>) -> Vec<u8> {
+            output(|out, capacity, written| ivcnotes_auth_address(auth, out, capacity, written))
+                .unwrap()
+        }
+
+        unsafe fn encryption_key(auth: *const Auth<Mock>) -> Vec<u8> {
+            output(|out, capacity, written| {
+                ivcnotes_auth_encryption_key(auth, out, capacity, written)
+            })
+            .unwrap()
+        }
+
+        #[test]
+        fn invalid_handles_and_inputs() {
+            let null_pointer = code("null pointer");
+            unsafe {
+                let mut auth = null_mut();
+                assert_eq!(ivcnotes_auth_generate(null_mut()), null_pointer);
+                assert_eq!(
+                    ivcnotes_auth_from_seed([0; 8].as_ptr(), 8, &mut auth),
+                    code("bad seed")
+                );
+                assert!(auth.is_null());
+                assert_eq!(
+                    ivcnotes_auth_address(null(), null_mut(), 0, &mut 0),
+                    null_pointer
+                );
+                assert_eq!(
+                    ivcnotes_auth_from_keystore(b"pw".as_ptr(), 2, [0; 4].as_ptr(), 4, &mut auth),
+                    code("bad keystore")
+                );
+                assert_eq!(
+                    ivcnotes_auth_from_keystore([0xff].as_ptr(), 1, null(), 0, &mut auth),
+                    code("string is not utf-8")
+                );
+
+                let mut wallet = null_mut();
+                assert_eq!(
+                    ivcnotes_wallet_new(null_mut(), null(), 0, null(), 0, &mut wallet),
+                    null_pointer
+                );
+                // the auth is taken even if the call fails
+                assert_eq!(
+                    ivcnotes_wallet_new(self::auth(1), null(), 0, null(), 0, null_mut()),
+                    null_pointer
+                );
+                assert_eq!(ivcnotes_wallet_note_count(null(), &mut 0), null_pointer);
+                assert_eq!(ivcnotes_wallet_receive(null_mut(), null(), 0), null_pointer);
+
+                let wallet = self::wallet(1);
+                assert_eq!(
+                    ivcnotes_wallet_receive(wallet, b"IVCE".as_ptr(), 4),
+                    code("bad payload")
+                );
+                assert_eq!(
+                    ivcnotes_wallet_balance(wallet, [0xff; 32].as_ptr(), 32, null_mut(), 0, &mut 0),
+                    code("bad asset")
+                );
+                let mut note = null_mut();
+                assert_eq!(
+                    ivcnotes_wallet_note(wallet, 0, &mut note),
+                    code("bad spendable index")
+                );
+                assert_eq!(
+                    ivcnotes_note_decode(b"IVCW".as_ptr(), 4, &mut note),
+                    code("bad wire encoding")
+                );
+                assert_eq!(ivcnotes_note_value(null(), &mut 0), null_pointer);
+
+                let other = self::auth(2);
+                let (address, key) = (self::address(other), encryption_key(other));
+                ivcnotes_auth_free(other);
+                let mut outbox = null_mut();
+                let pay = |address: &[u8], key: &[u8], outbox: &mut *mut Outbox| {
+                    let (a, k) = (address.as_ptr(), key.as_ptr());
+                    ivcnotes_wallet_pay(wallet, a, address.len(), k, key.len(), 1, outbox)
+                };
+                assert_eq!(pay(&[0xff; 32], &key, &mut outbox), code("bad address"));
+                assert_eq!(pay(&address, &[0; 32], &mut outbox), code("bad payload"));
+                assert!(outbox.is_null());
+                ivcnotes_wallet_free(wallet);
+                ivcnotes_wallet_free(null_mut());
+            }
+        }
+
+        #[test]
+        fn payment_through_the_handles() {
+            let mut rng = StdRng::seed_from_u64(1);
+            unsafe {
+                let auth = self::auth(1);
+                let (address, key) = (self::address(auth), encryption_key(auth));
+                let mut alice = null_mut();
+                assert_eq!(
+                    ivcnotes_wallet_new(auth, null(), 0, null(), 0, &mut alice),
+                    OK
+                );
+
+                // a payload sealed by another wallet is received through the handle
+                let mut issuer = mock::wallet(h(), &mut rng);
+                let asset = Asset::new(issuer.address(), &Terms::iou(1, 1));
+                let mut sealer = Sealer {
+                    address: Address::from_bytes(&address).unwrap(),
+                    key: EncryptionKey::from_bytes(&key).unwrap(),
+                    payloads: vec![],
+                };
+                issuer.issue(&mut rng, &mut sealer, &asset, 100).unwrap();
+                let payload = &sealer.payloads[0];
+                assert_eq!(
+                    ivcnotes_wallet_receive(alice, payload.as_ptr(), payload.len()),
+                    OK
+                );
+                let mut count = 0;
+                assert_eq!(ivcnotes_wallet_note_count(alice, &mut count), OK);
+                assert_eq!(count, 1);
+
+                let mut note = null_mut();
+                assert_eq!(ivcnotes_wallet_note(alice, 0, &mut note), OK);
+                let mut value = 0;
+                assert_eq!(ivcnotes_note_value(note, &mut value), OK);
+                assert_eq!(value, 100);
+                let asset_hash = output(|out, capacity, written| {
+                    ivcnotes_note_asset(note, out, capacity, written)
+                })
+                .unwrap();
+                let balance = output(|out, capacity, written| {
+                    let len = asset_hash.len();
+                    ivcnotes_wallet_balance(alice, asset_hash.as_ptr(), len, out, capacity, written)
+                });
+                assert_eq!(balance.unwrap(), b"100");
+
+                // the note round trips through the wire encoding
+                let encoded = output(|out, capacity, written| {
+                    ivcnotes_note_encode(note, out, capacity, written)
+                })
+                .unwrap();
+                let mut decoded = null_mut();
+                assert_eq!(
+                    ivcnotes_note_decode(encoded.as_ptr(), encoded.len(), &mut decoded),
+                    OK
+                );
+                assert_eq!(ivcnotes_note_value(decoded, &mut value), OK);
+                assert_eq!(value, 100);
+                ivcnotes_note_free(note);
+                ivcnotes_note_free(decoded);
+
+                let bob = self::auth(2);
+                let (address, key) = (self::address(bob), encryption_key(bob));
+                let pay = |value, outbox: &mut *mut Outbox| {
+                    let (a, k) = (address.as_ptr(), key.as_ptr());
+                    ivcnotes_wallet_pay(alice, a, address.len(), k, key.len(), value, outbox)
+                };
+                let mut outbox = null_mut();
+                assert_eq!(pay(30, &mut outbox), OK);
+                let mut len = 0;
+                assert_eq!(ivcnotes_outbox_len(outbox, &mut len), OK);
+                assert_eq!(len, 1);
+                let sent = output(|out, capacity, written| {
+                    ivcnotes_outbox_payload(outbox, 0, out, capacity, written)
+                })
+                .unwrap();
+                assert!(bob
+                    .as_ref()
+                    .unwrap()
+                    .open(&sent, &Codecs::default(), &Limits::default())
+                    .is_ok());
+                assert_eq!(
+                    ivcnotes_outbox_payload(outbox, 1, null_mut(), 0, &mut len),
+                    code("bad payload index")
+                );
+                ivcnotes_outbox_free(outbox);
+
+                // the outbox of a failed payment is still handed out
+                let mut outbox = null_mut();
+                let status = pay(1000, &mut outbox);
+                assert_eq!(status, code("insufficient funds"));
+                assert_eq!(message(status), "insufficient funds");
+                assert_eq!(ivcnotes_outbox_len(outbox, &mut len), OK);
+                assert_eq!(len, 0);
+                ivcnotes_outbox_free(outbox);
+
+                ivcnotes_auth_free(bob);
+                ivcnotes_wallet_free(alice);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{FWrap, Nullifier};
+use ark_ff::PrimeField;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, marker::PhantomData};
+
+// Optional gossip between wallets to detect a relay that withholds spends from
+// some of its users. For each epoch a wallet keeps a bloom filter of the
+// nullifiers it observed and shares only the filter. A peer whose filter holds
+// nullifiers that are missing locally saw spends this wallet was not shown.
+//
+// Epochs are whatever the wallets agree on, eg. relay batches or hours.
+
+const MAGIC: &[u8; 4] = b"IVCG";
+// bounds the filter an untrusted peer can send, 1 MiB of bits
+const MAX_WORDS: usize = 1 << 17;
+const MAX_HASHES: u32 = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NullifierDigest<F: PrimeField> {
+    epoch: u64,
+    hashes: u32,
+    bits: Vec<u64>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> NullifierDigest<F> {
+    // `bits` is rounded up to a multiple of 64
+    pub fn new(epoch: u64, bits: usize, hashes: u32) -> Self {
+        Self {
+            epoch,
+            hashes: hashes.clamp(1, MAX_HASHES),
+            bits: vec![0; bits.div_ceil(64).clamp(1, MAX_WORDS)],
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    fn len(&self) -> u64 {
+        self.bits.len() as u64 * 64
+    }
+
+    // positions of the nullifier by double hashing
+    fn positions(&self, nullifier: &Nullifier<F>) -> impl Iterator<Item = u64> {
+        let digest = Sha256::digest(nullifier.to_bytes());
+        let h1 = u64::from_le_bytes(digest[..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+        let len = self.len();
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % len)
+    }
+
+    pub fn insert(&mut self, nullifier: &Nullifier<F>) {
+        for position in self.positions(nullifier).collect::<Vec<_>>() {
+            self.bits[(position / 64) as usize] |= 1 << (position % 64);
+        }
+    }
+
+    // can be a false positive, never a false negative
+    pub fn contains(&self, nullifier: &Nullifier<F>) -> bool {
+        self.positions(nullifier)
+            .all(|position| self.bits[(position / 64) as usize] & (1 << (position % 64)) != 0)
+    }
+
+    // estimated number of nullifiers in a filter with `ones` bits set
+    fn estimate(&self, ones: u64) -> f64 {
+        let (m, k) = (self.len() as f64, self.hashes as f64);
+        match ones as f64 >= m {
+            true => f64::INFINITY,
+            false => -m / k * (1.0 - ones as f64 / m).ln(),
+        }
+    }
+
+    pub fn estimated_count(&self) -> f64 {
+        self.estimate(self.bits.iter().map(|w| w.count_ones() as u64).sum())
+    }
+
+    // estimated number of nullifiers in `other` that are not in this filter
+    pub fn missing_from(&self, other: &Self) -> Result<f64, crate::Error> {
+        (self.epoch == other.epoch
+            && self.hashes == other.hashes
+            && self.bits.len() == other.bits.len())
+        .then_some(())
+        .ok_or(crate::Error::With("nullifier digest mismatch"))?;
+        let union = self
+            .bits
+            .iter()
+            .zip(other.bits.iter())
+            .map(|(a, b)| (a | b).count_ones() as u64)
+            .sum();
+        Ok((self.estimate(union) - self.estimated_count()).max(0.0))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&self.hashes.to_le_bytes());
+        bytes.extend_from_slice(&(self.bits.len() as u32).to_le_bytes());
+        self.bits
+            .iter()
+            .for_each(|word| bytes.extend_from_slice(&word.to_le_bytes()));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, crate::Error> {
+        let err = crate::Error::With("bad nullifier digest");
+        (bytes.len() >= 20 && bytes[..4] == *MAGIC)
+            .then_some(())
+            .ok_or(err)?;
+        let epoch = u64::from_le_bytes(bytes[4..12].try_into().unwrap());
+        let hashes = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+        let words = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        ((1..=MAX_HASHES).contains(&hashes)
+            && (1..=MAX_WORDS).contains(&words)
+            && bytes.len() == 20 + words * 8)
+            .then_some(())
+            .ok_or(err)?;
+        let bits = bytes[20..]
+            .chunks(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            epoch,
+            hashes,
+            bits,
+            _marker: PhantomData,
+        })
+    }
+}
+
+// `NullifierGossip` keeps the digests of the recent epochs
+#[derive(Clone, Debug)]
+pub struct NullifierGossip<F: PrimeField> {
+    bits: usize,
+    hashes: u32,
+    // number of the most recent epochs kept
+    retention: usize,
+    digests: BTreeMap<u64, NullifierDigest<F>>,
+}
+
+impl<F: PrimeField> NullifierGossip<F> {
+    pub fn new(bits: usize, hashes: u32, retention: usize) -> Self {
+        Self {
+            bits,
+            hashes,
+            retention,
+            digests: BTreeMap::new(),
+        }
+    }
+
+    pub fn observe<'a>(
+        &mut self,
+        epoch: u64,
+        nullifiers: impl IntoIterator<Item = &'a Nullifier<F>>,
+    ) {
+        let (bits, hashes) = (self.bits, self.hashes);
+        let digest = self
+            .digests
+            .entry(epoch)
+            .or_insert_with(|| NullifierDigest::new(epoch, bits, hashes));
+        nullifiers
+            .into_iter()
+            .for_each(|nullifier| digest.insert(nullifier));
+
+        while self.digests.len() > self.retention {
+            self.digests.pop_first();
+        }
+    }
+
+    // digest to share with the peers
+    pub fn digest(&self, epoch: u64) -> Option<&NullifierDigest<F>> {
+        self.digests.get(&epoch)
+    }
+
+    // estimated number of spends the peer saw in its epoch that were not
+    // shown to this wallet, an empty digest is used for an unseen epoch
+    pub fn compare(&self, peer: &NullifierDigest<F>) -> Result<f64, crate::Error> {
+        match self.digests.get(&peer.epoch) {
+            Some(ours) => ours.missing_from(peer),
+            None => NullifierDigest::new(peer.epoch, self.bits, self.hashes).missing_from(peer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+
+    fn nullifiers(range: std::ops::Range<u64>) -> Vec<Nullifier<Fr>> {
+        range.map(|i| Fr::from(i).into()).collect()
+    }
+
+    #[test]
+    fn gap_is_estimated_from_the_union() {
+        let mut ours = NullifierGossip::new(8192, 5, 4);
+        let mut peer = NullifierGossip::new(8192, 5, 4);
+        ours.observe(7, &nullifiers(0..400));
+        peer.observe(7, &nullifiers(0..500));
+        let peer = NullifierDigest::from_bytes(&peer.digest(7).unwrap().to_bytes()).unwrap();
+
+        // 100 missing out of 500 on a filter of 8192 bits with 5 hashes
+        let gap = ours.compare(&peer).unwrap();
+        assert_eq!((gap * 10.0).round(), 997.0);
+        assert!((peer.estimated_count() - 500.0).abs() < 10.0);
+        // an epoch that was not observed misses all of the peer
+        let fresh = NullifierGossip::new(8192, 5, 4);
+        assert!((fresh.compare(&peer).unwrap() - 500.0).abs() < 10.0);
+        // the peer holds nothing this wallet misses
+        assert_eq!(peer.missing_from(ours.digest(7).unwrap()), Ok(0.0));
+        assert!(ours.compare(&NullifierDigest::new(7, 8192, 4)).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{circuit::abi, codec::CodecId, limits::Limits};
+
+// Handshake exchanged by two wallets before a transfer. Each side sends a
+// `Hello` and runs `negotiate` on both, the outcome is the same on both sides
+// regardless of who initiated.
+
+const MAGIC: &[u8; 4] = b"IVCH";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    Burn,
+    Subscriptions,
+    Join,
+    StealthAddresses,
+    Denominations,
+    // compression codecs, see `codec`
+    Zstd,
+    Brotli,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 7] = [
+        Feature::Burn,
+        Feature::Subscriptions,
+        Feature::Join,
+        Feature::StealthAddresses,
+        Feature::Denominations,
+        Feature::Zstd,
+        Feature::Brotli,
+    ];
+
+    // features this build of the wallet supports
+    pub const SUPPORTED: [Feature; 3] = [Feature::Burn, Feature::Subscriptions, Feature::Join];
+
+    // position in the feature bitmask, never reused
+    fn bit(&self) -> u32 {
+        match self {
+            Feature::Burn => 0,
+            Feature::Subscriptions => 1,
+            Feature::Join => 2,
+            Feature::StealthAddresses => 3,
+            Feature::Denominations => 4,
+            Feature::Zstd => 5,
+            Feature::Brotli => 6,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hello {
+    // circuit versions the wallet can prove and verify
+    pub(crate) versions: Vec<u32>,
+    // bitmask of the features, unknown bits are ignored
+    pub(crate) features: u32,
+    // relays in the order of preference
+    pub(crate) relays: Vec<String>,
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self::new(&[abi::VERSION], &Feature::SUPPORTED)
+    }
+}
+
+impl Hello {
+    pub fn new(versions: &[u32], features: &[Feature]) -> Self {
+        Self {
+            versions: versions.to_vec(),
+            features: features.iter().fold(0, |acc, f| acc | 1 << f.bit()),
+            relays: vec![],
+        }
+    }
+
+    pub fn with_relays(mut self, relays: &[&str]) -> Self {
+        self.relays = relays.iter().map(|relay| relay.to_string()).collect();
+        self
+    }
+
+    pub fn versions(&self) -> &[u32] {
+        &self.versions
+    }
+
+    pub fn relays(&self) -> &[String] {
+        &self.relays
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features & (1 << feature.bit()) != 0
+    }
+
+    pub fn features(&self) -> Vec<Feature> {
+        Feature::ALL
+            .into_iter()
+            .filter(|feature| self.supports(*feature))
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.versions.len() as u32).to_le_bytes());
+        self.versions
+            .iter()
+            .for_each(|version| bytes.extend_from_slice(&version.to_le_bytes()));
+        bytes.extend_from_slice(&self.features.to_le_bytes());
+        bytes.extend_from_slice(&(self.relays.len() as u32).to_le_bytes());
+        for relay in self.relays.iter() {
+            bytes.extend_from_slice(&(relay.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(relay.as_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, crate::Error> {
+        Self::from_bytes_with(bytes, &Limits::default())
+    }
+
+    pub fn from_bytes_with(bytes: &[u8], limits: &Limits) -> Result<Self, crate::Error> {
+        let err = crate::Error::With("bad handshake");
+        let mut reader = Reader(bytes);
+        (reader.take(4).ok_or(err)? == MAGIC)
+            .then_some(())
+            .ok_or(err)?;
+
+        let n = reader.u32().ok_or(err)? as usize;
+        (n <= limits.max_versions && n <= reader.0.len() / 4)
+            .then_some(())
+            .ok_or(err)?;
+        let versions = (0..n)
+            .map(|_| reader.u32().ok_or(err))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let features = reader.u32().ok_or(err)?;
+
+        let n = reader.u32().ok_or(err)? as usize;
+        (n <= limits.max_relays).then_some(()).ok_or(err)?;
+        let mut relays = vec![];
+        for _ in 0..n {
+            let len = reader.u32().ok_or(err)? as usize;
+            (len <= limits.max_relay_len).then_some(()).ok_or(err)?;
+            let relay = reader.take(len).ok_or(err)?;
+            relays.push(String::from_utf8(relay.to_vec()).map_err(|_| err)?);
+        }
+        reader.0.is_empty().then_some(()).ok_or(err)?;
+
+        Ok(Self {
+            versions,
+            features,
+            relays,
+        })
+    }
+}
+
+// reads little endian fields of an untrusted message
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        (n <= self.0.len()).then(|| {
+            let (head, tail) = self.0.split_at(n);
+            self.0 = tail;
+            head
+        })
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+// transfer mode both wallets agree on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Agreement {
+    pub(crate) version: u32,
+    pub(crate) features: u32,
+    pub(crate) relay: Option<String>,
+}
+
+impl Agreement {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn relay(&self) -> Option<&str> {
+        self.relay.as_deref()
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features & (1 << feature.bit()) != 0
+    }
+
+    // best codec both wallets have
+    pub fn codec(&self) -> CodecId {
+        CodecId::PREFERENCE
+            .into_iter()
+            .find(|codec| codec.feature().map_or(true, |f| self.supports(f)))
+            .unwrap_or(CodecId::None)
+    }
+}
+
+// picks the highest common circuit version, the common features and the
+// common relay with the best combined preference, ties go to the smaller name
+pub fn negotiate(ours: &Hello, theirs: &Hello) -> Result<Agreement, crate::Error> {
+    let version = ours
+        .versions
+        .iter()
+        .filter(|version| theirs.versions.contains(version))
+        .max()
+        .copied()
+        .ok_or(crate::Error::With("no common circuit version"))?;
+
+    let relay = ours
+        .relays
+        .iter()
+        .enumerate()
+        .filter_map(|(i, relay)| {
+            let j = theirs.relays.iter().position(|other| other == relay)?;
+            Some((i + j, relay))
+        })
+        .min()
+        .map(|(_, relay)| relay.clone());
+
+    Ok(Agreement {
+        version,
+        features: ours.features & theirs.features,
+        relay,
+    })
+}
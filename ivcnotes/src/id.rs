@@ -1,9 +1,27 @@
-use crate::{circuit::IVC, poseidon::PoseidonConfigs, Address, FWrap, NullifierKey, SigHash};
-use ark_crypto_primitives::{sponge::poseidon::PoseidonConfig, Error};
+use crate::{
+    circuit::IVC,
+    note::{Note, NoteOutIndex},
+    payload::EncryptionKey,
+    poseidon::{PoseidonConfigs, ToCRH},
+    Address, FWrap, NullifierKey, SigHash, ViewingKey,
+};
+use ark_crypto_primitives::{
+    sponge::{poseidon::PoseidonConfig, Absorb},
+    Error,
+};
+use ark_ec::CurveConfig;
+use ark_ff::PrimeField;
 use arkeddsa::{signature::Signature, PublicKey, SigningKey};
 use rand_core::CryptoRngCore;
+use sha2::Digest;
+use zeroize::Zeroize;
 type PreHash = sha2::Sha512;
 
+const SEED_DOMAIN: &[u8] = b"ivcnotes seed";
+const SIGNER_DOMAIN: &[u8] = b"ivcnotes signer";
+const NULLIFIER_KEY_DOMAIN: &[u8] = b"ivcnotes nullifier key";
+const ENCRYPTION_KEY_DOMAIN: &[u8] = b"ivcnotes encryption key";
+
 #[derive(Debug)]
 // Signer has the signer key and eddsa poseidon config
 pub struct Signer<E: IVC> {
@@ -12,19 +30,17 @@ pub struct Signer<E: IVC> {
 }
 
 impl<E: IVC> Signer<E> {
-    pub(crate) fn generate(
-        poseidon: &PoseidonConfig<E::Field>,
-        rng: &mut impl CryptoRngCore,
-    ) -> Self {
-        let signing_key = SigningKey::generate::<PreHash>(rng).unwrap();
+    pub(crate) fn from_bytes(poseidon: &PoseidonConfig<E::Field>, bytes: &[u8]) -> Self {
+        let signing_key = SigningKey::from_bytes::<PreHash>(bytes).unwrap();
         Self {
             signing_key,
             poseidon: poseidon.clone(),
         }
     }
 
-    pub(crate) fn sign(&self, msg: &E::Field) -> Signature<E::TE> {
-        self.signing_key.sign::<PreHash, _>(&self.poseidon, &[*msg])
+    pub(crate) fn sign(&self, msg: &SigHash<E::Field>) -> Signature<E::TE> {
+        self.signing_key
+            .sign::<PreHash, _>(&self.poseidon, &[msg.inner()])
     }
 
     pub(crate) fn public_key(&self) -> &PublicKey<E::TE> {
@@ -32,11 +48,90 @@ impl<E: IVC> Signer<E> {
     }
 }
 
+// Secret an identity is derived from and the chain code to derive its
+// children. Derivation is hardened only, an address is a hash of the keys so a
+// public parent key cannot derive child addresses anyway.
+#[derive(Clone)]
+struct ExtendedKey {
+    key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    fn from_digest(digest: &[u8]) -> Self {
+        let (key, chain_code) = digest.split_at(32);
+        Self {
+            key: key.try_into().unwrap(),
+            chain_code: chain_code.try_into().unwrap(),
+        }
+    }
+
+    fn master(seed: &[u8]) -> Self {
+        let digest = PreHash::new()
+            .chain_update(SEED_DOMAIN)
+            .chain_update(seed)
+            .finalize();
+        Self::from_digest(&digest)
+    }
+
+    fn child(&self, index: u32) -> Self {
+        let digest = PreHash::new()
+            .chain_update(self.chain_code)
+            .chain_update([0])
+            .chain_update(self.key)
+            .chain_update(index.to_be_bytes())
+            .finalize();
+        Self::from_digest(&digest)
+    }
+
+    fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.key);
+        bytes[32..].copy_from_slice(&self.chain_code);
+        bytes
+    }
+
+    fn signer_seed(&self) -> Vec<u8> {
+        [SIGNER_DOMAIN, &self.key].concat()
+    }
+
+    fn encryption_secret<S: PrimeField>(&self) -> S {
+        let digest = PreHash::new()
+            .chain_update(ENCRYPTION_KEY_DOMAIN)
+            .chain_update(self.key)
+            .finalize();
+        S::from_le_bytes_mod_order(&digest)
+    }
+
+    fn nullifier_key<F: PrimeField>(&self) -> NullifierKey<F> {
+        let digest = PreHash::new()
+            .chain_update(NULLIFIER_KEY_DOMAIN)
+            .chain_update(self.key)
+            .finalize();
+        NullifierKey::reduce_bytes(&digest)
+    }
+}
+
+impl Drop for ExtendedKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.chain_code.zeroize();
+    }
+}
+
 // `Id` holds user secrets and public address
 pub struct Auth<E: IVC> {
     nullifier_key: NullifierKey<E::Field>,
     signer: Signer<E>,
     address: Address<E::Field>,
+    extended_key: ExtendedKey,
+}
+
+// the signing key is kept by arkeddsa and is not wiped
+impl<E: IVC> Drop for Auth<E> {
+    fn drop(&mut self) {
+        self.nullifier_key.0.zeroize();
+    }
 }
 
 impl<E: IVC> Auth<E> {
@@ -44,14 +139,54 @@ impl<E: IVC> Auth<E> {
         h: &PoseidonConfigs<E::Field>,
         rng: &mut impl CryptoRngCore,
     ) -> Result<Self, Error> {
-        let signer = Signer::generate(&h.eddsa, rng);
-        let nullifier_key = NullifierKey::rand(rng);
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self::from_seed(h, &seed)
+    }
+
+    // master identity of a seed of 16 to 64 bytes, eg. of a mnemonic
+    pub fn from_seed(h: &PoseidonConfigs<E::Field>, seed: &[u8]) -> Result<Self, Error> {
+        (16..=64)
+            .contains(&seed.len())
+            .then_some(())
+            .ok_or(crate::Error::With("bad seed"))?;
+        Ok(Self::from_extended_key(h, ExtendedKey::master(seed)))
+    }
+
+    // child identity at `index`, the same seed and path always give the same
+    // signing key, nullifier key and address
+    pub fn derive_child(&self, h: &PoseidonConfigs<E::Field>, index: u32) -> Self {
+        Self::from_extended_key(h, self.extended_key.child(index))
+    }
+
+    // key senders encrypt the transfer payloads to, see `payload`
+    pub fn encryption_key(&self) -> EncryptionKey<E> {
+        EncryptionKey::new(&self.encryption_secret())
+    }
+
+    pub(crate) fn encryption_secret(&self) -> <E::TE as CurveConfig>::ScalarField {
+        self.extended_key.encryption_secret()
+    }
+
+    // key and chain code the identity is restored from, see `recovery`
+    pub(crate) fn extended_key(&self) -> [u8; 64] {
+        self.extended_key.to_bytes()
+    }
+
+    pub(crate) fn from_extended_key_bytes(h: &PoseidonConfigs<E::Field>, bytes: &[u8; 64]) -> Self {
+        Self::from_extended_key(h, ExtendedKey::from_digest(bytes))
+    }
+
+    fn from_extended_key(h: &PoseidonConfigs<E::Field>, extended_key: ExtendedKey) -> Self {
+        let signer = Signer::from_bytes(&h.eddsa, &extended_key.signer_seed());
+        let nullifier_key = extended_key.nullifier_key();
         let address = h.id_commitment(&nullifier_key, signer.public_key());
-        Ok(Self {
+        Self {
             nullifier_key,
             signer,
             address,
-        })
+            extended_key,
+        }
     }
 
     pub(crate) fn address(&self) -> &Address<E::Field> {
@@ -67,6 +202,167 @@ impl<E: IVC> Auth<E> {
     }
 
     pub(crate) fn sign(&self, msg: &SigHash<E::Field>) -> Signature<E::TE> {
-        self.signer.sign(&msg.inner())
+        self.signer.sign(msg)
+    }
+
+    // key to hand to an auditor, it is derived from the nullifier key so it
+    // is restored with the identity
+    pub fn viewing_key(&self, h: &PoseidonConfigs<E::Field>) -> ViewingKey<E::Field> {
+        h.viewing_key(&self.nullifier_key)
+    }
+}
+
+// elements of the plaintext of a disclosed note, the note and its blind
+pub(crate) const PLAINTEXT_LEN: usize = 9;
+
+// Note encrypted to a viewing key. The plaintext is the note with its
+// blinding factor so that the holder of the key can recompute the note hash
+// and match it against the public states.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncryptedNote<F: PrimeField> {
+    pub(crate) nonce: F,
+    pub(crate) ciphertext: Vec<F>,
+    pub(crate) tag: F,
+}
+
+impl<F: PrimeField + Absorb> ViewingKey<F> {
+    pub fn encrypt(
+        &self,
+        h: &PoseidonConfigs<F>,
+        note: &Note<F>,
+        rng: &mut impl CryptoRngCore,
+    ) -> EncryptedNote<F> {
+        let nonce = F::rand(rng);
+        let mut plaintext = note.to_crh();
+        plaintext.push(note.blind.inner());
+        let tag = h.disclosure_tag(self, &nonce, &plaintext);
+        let keystream = h.keystream(self, &nonce, plaintext.len());
+        let ciphertext = plaintext
+            .iter()
+            .zip(keystream.iter())
+            .map(|(m, k)| *m + k)
+            .collect();
+        EncryptedNote {
+            nonce,
+            ciphertext,
+            tag,
+        }
+    }
+
+    pub fn decrypt(
+        &self,
+        h: &PoseidonConfigs<F>,
+        encrypted: &EncryptedNote<F>,
+    ) -> Result<Note<F>, crate::Error> {
+        let err = crate::Error::With("cannot decrypt note");
+        let keystream = h.keystream(self, &encrypted.nonce, encrypted.ciphertext.len());
+        let plaintext = encrypted
+            .ciphertext
+            .iter()
+            .zip(keystream.iter())
+            .map(|(c, k)| *c - k)
+            .collect::<Vec<_>>();
+        (plaintext.len() == PLAINTEXT_LEN
+            && h.disclosure_tag(self, &encrypted.nonce, &plaintext) == encrypted.tag)
+            .then_some(())
+            .ok_or(err)?;
+
+        let out_index = match small(&plaintext[5], u8::MAX as u64).ok_or(err)? {
+            0 => NoteOutIndex::Issue,
+            i => NoteOutIndex::Out(i as u8 - 1),
+        };
+        Ok(Note {
+            asset_hash: plaintext[0].into(),
+            owner: plaintext[1].into(),
+            value: small(&plaintext[2], u64::MAX).ok_or(err)?,
+            step: small(&plaintext[3], u32::MAX as u64).ok_or(err)? as u32,
+            parent_note: plaintext[4].into(),
+            out_index,
+            memo: plaintext[6].into(),
+            expiry_step: small(&plaintext[7], u32::MAX as u64).ok_or(err)? as u32,
+            blind: plaintext[8].into(),
+        })
+    }
+}
+
+// field element as an integer not larger than `max`
+fn small<F: PrimeField>(x: &F, max: u64) -> Option<u64> {
+    let bigint = x.into_bigint();
+    let limbs = bigint.as_ref();
+    (limbs[1..].iter().all(|limb| *limb == 0) && limbs[0] <= max).then_some(limbs[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+
+    #[derive(Clone)]
+    struct Config;
+
+    impl IVC for Config {
+        type Snark = ark_groth16::Groth16<ark_bn254::Bn254>;
+        type Field = Fr;
+        type TE = ark_ed_on_bn254::EdwardsConfig;
+    }
+
+    // keys an identity is known and spends by
+    fn keys(auth: &Auth<Config>) -> (Address<Fr>, NullifierKey<Fr>, Vec<u8>) {
+        (
+            *auth.address(),
+            *auth.nullifier_key(),
+            auth.encryption_key().to_bytes(),
+        )
+    }
+
+    #[test]
+    fn derivation_is_deterministic() {
+        let h = PoseidonConfigs::generate();
+        let seed = [7u8; 32];
+        let a = Auth::<Config>::from_seed(&h, &seed).unwrap();
+        let b = Auth::<Config>::from_seed(&h, &seed).unwrap();
+        assert_eq!(keys(&a), keys(&b));
+        assert!(a.public_key() == b.public_key());
+        assert_eq!(
+            keys(&a.derive_child(&h, 3).derive_child(&h, 1)),
+            keys(&b.derive_child(&h, 3).derive_child(&h, 1))
+        );
+
+        let other = Auth::<Config>::from_seed(&h, &[8u8; 32]).unwrap();
+        assert_ne!(keys(&a), keys(&other));
+    }
+
+    #[test]
+    fn children_are_distinct() {
+        let h = PoseidonConfigs::generate();
+        let master = Auth::<Config>::from_seed(&h, &[7u8; 32]).unwrap();
+        let mut identities = vec![keys(&master)];
+        for index in [0, 1, 2, 1 << 31, u32::MAX] {
+            identities.push(keys(&master.derive_child(&h, index)));
+        }
+        // the path matters, not just the indexes on it
+        identities.push(keys(&master.derive_child(&h, 1).derive_child(&h, 2)));
+        identities.push(keys(&master.derive_child(&h, 2).derive_child(&h, 1)));
+        for (i, a) in identities.iter().enumerate() {
+            for b in identities[i + 1..].iter() {
+                assert_ne!(a.0, b.0);
+                assert_ne!(a.1, b.1);
+                assert_ne!(a.2, b.2);
+            }
+        }
+    }
+
+    #[test]
+    fn seed_is_16_to_64_bytes() {
+        let h = PoseidonConfigs::generate();
+        for len in [0, 1, 15, 65, 128] {
+            let err = Auth::<Config>::from_seed(&h, &vec![7u8; len])
+                .err()
+                .unwrap();
+            assert_eq!(err.to_string(), crate::Error::With("bad seed").to_string());
+        }
+        for len in [16, 32, 64] {
+            assert!(Auth::<Config>::from_seed(&h, &vec![7u8; len]).is_ok());
+        }
     }
 }
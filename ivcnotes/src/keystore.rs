@@ -0,0 +1,245 @@
+use crate::{circuit::IVC, id::Auth, poseidon::PoseidonConfigs};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand_core::CryptoRngCore;
+use zeroize::Zeroize;
+
+// Password protected file of an identity. The key and the chain code of the
+// identity are encrypted with a key stretched from the password, the header
+// is authenticated along with the ciphertext.
+//
+// magic | version | memory | passes | lanes | salt | nonce | ciphertext | tag
+//
+// Version 2 stretches with Argon2id and encrypts with ChaCha20-Poly1305, the
+// cost of Argon2id is written as u32s with the memory in KiB.
+
+const MAGIC: &[u8; 4] = b"IVCK";
+const VERSION: u8 = 2;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const SECRET_LEN: usize = 64;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 4 + 1 + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+// cost of Argon2id
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cost {
+    memory: u32,
+    passes: u32,
+    lanes: u32,
+}
+
+impl Cost {
+    // the minimum that OWASP recommends for Argon2id
+    const DEFAULT: Cost = Cost {
+        memory: 19 * 1024,
+        passes: 2,
+        lanes: 1,
+    };
+
+    // bounds the work an untrusted file can ask for
+    const MAX: Cost = Cost {
+        memory: 1 << 20,
+        passes: 16,
+        lanes: 8,
+    };
+
+    fn key(&self, password: &[u8], salt: &[u8]) -> Result<[u8; 32], crate::Error> {
+        let err = crate::Error::With("bad keystore");
+        (self.memory <= Self::MAX.memory
+            && self.passes <= Self::MAX.passes
+            && self.lanes <= Self::MAX.lanes)
+            .then_some(())
+            .ok_or(err)?;
+        let params =
+            Params::new(self.memory, self.passes, self.lanes, Some(32)).map_err(|_| err)?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, salt, &mut key)
+            .map_err(|_| err)?;
+        Ok(key)
+    }
+}
+
+fn seal(
+    secret: &[u8; SECRET_LEN],
+    password: &str,
+    cost: &Cost,
+    rng: &mut impl CryptoRngCore,
+) -> Vec<u8> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&cost.memory.to_le_bytes());
+    bytes.extend_from_slice(&cost.passes.to_le_bytes());
+    bytes.extend_from_slice(&cost.lanes.to_le_bytes());
+    bytes.extend_from_slice(&salt);
+    bytes.extend_from_slice(&nonce);
+
+    // the cost is ours so it is within the bounds
+    let mut key = cost.key(password.as_bytes(), &salt).unwrap();
+    let payload = Payload {
+        msg: secret,
+        aad: &bytes,
+    };
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .unwrap();
+    bytes.extend_from_slice(&ciphertext);
+
+    key.zeroize();
+    bytes
+}
+
+fn open(password: &str, bytes: &[u8]) -> Result<[u8; SECRET_LEN], crate::Error> {
+    let err = crate::Error::With("bad keystore");
+    (bytes.len() == HEADER_LEN + SECRET_LEN + TAG_LEN && bytes[..4] == *MAGIC)
+        .then_some(())
+        .ok_or(err)?;
+    (bytes[4] == VERSION).then_some(()).ok_or(err)?;
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let cost = Cost {
+        memory: u32_at(5),
+        passes: u32_at(9),
+        lanes: u32_at(13),
+    };
+    let salt = &bytes[17..17 + SALT_LEN];
+    let nonce = &bytes[17 + SALT_LEN..HEADER_LEN];
+    let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+
+    let mut key = cost.key(password.as_bytes(), salt)?;
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header,
+    };
+    let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| crate::Error::With("wrong password"));
+    key.zeroize();
+
+    let mut plaintext = plaintext?;
+    let secret = plaintext.as_slice().try_into().map_err(|_| err);
+    plaintext.zeroize();
+    secret
+}
+
+impl<E: IVC> Auth<E> {
+    pub fn to_keystore(&self, password: &str, rng: &mut impl CryptoRngCore) -> Vec<u8> {
+        let mut secret = self.extended_key();
+        let bytes = seal(&secret, password, &Cost::DEFAULT, rng);
+        secret.zeroize();
+        bytes
+    }
+
+    pub fn from_keystore(
+        h: &PoseidonConfigs<E::Field>,
+        password: &str,
+        bytes: &[u8],
+    ) -> Result<Self, crate::Error> {
+        let mut secret = open(password, bytes)?;
+        let auth = Self::from_extended_key_bytes(h, &secret);
+        secret.zeroize();
+        Ok(auth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // cheap enough for tests
+    const COST: Cost = Cost {
+        memory: 64,
+        passes: 1,
+        lanes: 1,
+    };
+
+    fn secret() -> [u8; SECRET_LEN] {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn keystore_opens_with_its_password() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let bytes = seal(&secret(), "password", &COST, &mut rng);
+        assert_eq!(bytes.len(), HEADER_LEN + SECRET_LEN + TAG_LEN);
+        assert_eq!(open("password", &bytes), Ok(secret()));
+        assert_eq!(
+            open("passwore", &bytes),
+            Err(crate::Error::With("wrong password"))
+        );
+    }
+
+    #[test]
+    fn keystore_is_authenticated() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let bytes = seal(&secret(), "password", &COST, &mut rng);
+        // header fields that keep the cost valid, the ciphertext and the tag
+        for at in (17..bytes.len()).chain([5, 9]) {
+            let mut tampered = bytes.clone();
+            tampered[at] ^= 1;
+            assert!(open("password", &tampered).is_err(), "byte {}", at);
+        }
+    }
+
+    #[test]
+    fn keystore_bounds_the_cost() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let bytes = seal(&secret(), "password", &COST, &mut rng);
+        for (at, cost) in [
+            (5, Cost::MAX.memory + 1),
+            (9, Cost::MAX.passes + 1),
+            (13, Cost::MAX.lanes + 1),
+            (9, 0),
+        ] {
+            let mut tampered = bytes.clone();
+            tampered[at..at + 4].copy_from_slice(&cost.to_le_bytes());
+            assert_eq!(
+                open("password", &tampered),
+                Err(crate::Error::With("bad keystore"))
+            );
+        }
+    }
+
+    // vectors of OpenSSL 3.5 through the python cryptography package, the
+    // inputs of RFC 9106 5.3 without the secret and the associated data
+    #[test]
+    fn argon2id_matches_openssl() {
+        let cost = Cost {
+            memory: 32,
+            passes: 3,
+            lanes: 4,
+        };
+        let key = cost.key(&[1u8; 32], &[2u8; 16]).unwrap();
+        assert_eq!(
+            key.to_vec(),
+            unhex("03aab965c12001c9d7d0d2de33192c0494b684bb148196d73c1df1acaf6d0c2e")
+        );
+    }
+
+    // Argon2id(password, salt, 64, 1, 1) then ChaCha20Poly1305(key, nonce,
+    // secret, header) in python with salt 100.., nonce 200.. and secret 0..
+    #[test]
+    fn keystore_matches_openssl() {
+        let hex = "4956434b024000000001000000010000006465666768696a6b6c6d6e6f70717273\
+                   c8c9cacbcccdcecfd0d1d2d3467340f0e65c94ff770dccbbe999f6dd8e75e391\
+                   250cf9f821b5251703f6958aa6c6c40b7cf0a3d1024daf8fca355ebaa741780d\
+                   4b70be7deea5aa57124e01ac8e18c43b9f172761a133da0c19b6bb8f";
+        assert_eq!(open("password", &unhex(hex)), Ok(secret()));
+    }
+}
@@ -1,14 +1,61 @@
+// without the prover transaction building is only partially reachable
+#![cfg_attr(not(feature = "prover"), allow(dead_code))]
+
 use ark_ff::PrimeField;
 use std::borrow::Borrow;
 
+pub mod acceptance;
+pub mod amount;
 pub mod asset;
+pub mod burn;
+pub mod catalog;
+pub mod challenge;
 pub mod circuit;
+pub mod codec;
+pub mod cold;
+pub mod confirmation;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+#[cfg(feature = "heavy-tests")]
+pub mod differential;
+#[cfg(feature = "prover")]
+pub mod estimate;
+pub mod evidence;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod gossip;
+pub mod handshake;
 // pub mod cs;
 pub mod id;
+pub mod keystore;
+pub mod limits;
 pub mod note;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod params;
+pub mod payload;
 pub mod poseidon;
+pub mod recovery;
+#[cfg(feature = "prover")]
+pub mod reference;
+pub mod registry;
+pub mod relay;
+#[cfg(feature = "rest")]
+pub mod rest;
+#[cfg(feature = "prover")]
+pub mod scenario;
+pub mod screening;
+pub mod security;
+pub mod select;
+#[cfg(feature = "solidity")]
+pub mod solidity;
+pub mod store;
+pub mod subscription;
+pub mod transparency;
 pub mod tx;
+pub mod usage;
 pub mod wallet;
+pub mod wire;
 
 crate::field_wrap!(SigHash);
 crate::field_wrap!(Address);
@@ -19,12 +66,36 @@ crate::field_wrap!(AssetHash);
 crate::field_wrap!(Blind);
 crate::field_wrap!(NoteHash);
 crate::field_wrap!(BlindNoteHash);
+// reference to the payout of a redeemed note on the external settlement layer
+crate::field_wrap!(SettlementRef);
+// commitment to the policy circuit of an asset
+crate::field_wrap!(PolicyHash);
+// key that decrypts disclosed notes but cannot spend them
+crate::field_wrap!(ViewingKey);
+// commitment to the memo of a note, zero if it has none
+crate::field_wrap!(MemoHash);
+// digest of the signing key of an issuer, an asset can commit to it instead
+// of the address of the issuer
+crate::field_wrap!(IssuerKey);
+// commitment to the receiver of a transfer, zero if it is not bound to one
+crate::field_wrap!(ReceiverTag);
+// commitment to the note a step discloses to the viewing key of its sender
+crate::field_wrap!(DisclosureHash);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     With(&'static str),
 }
 
+impl Error {
+    // stable code of the error, see `catalog`
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::With(message) => catalog::code(message),
+        }
+    }
+}
+
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match *self {
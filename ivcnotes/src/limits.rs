@@ -0,0 +1,60 @@
+use crate::{circuit::IVC, note::IVCStep, note::NoteHistory};
+
+// `Limits` bound what is taken from an untrusted source before any work is
+// spent on it. Verifier services and wallets are expected to share the same
+// values so that what one accepts the other can process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    // proofs of a history including the merged histories
+    pub max_proofs: usize,
+    // nesting of merged histories
+    pub max_merge_depth: usize,
+    // circuit versions in a handshake
+    pub max_versions: usize,
+    // relays in a handshake and the length of each
+    pub max_relays: usize,
+    pub max_relay_len: usize,
+    // size of a compressed artifact once decompressed
+    pub max_decompressed_len: usize,
+    // bytes of the wire records a history carries that are not known
+    pub max_unknown_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_proofs: 4096,
+            max_merge_depth: 32,
+            max_versions: 64,
+            max_relays: 16,
+            max_relay_len: 256,
+            max_decompressed_len: 1 << 24,
+            max_unknown_bytes: 1 << 16,
+        }
+    }
+}
+
+impl Limits {
+    pub fn check_history<E: IVC>(&self, history: &NoteHistory<E>) -> Result<(), crate::Error> {
+        self.check_steps(&history.steps)
+    }
+
+    pub(crate) fn check_steps<E: IVC>(&self, steps: &[IVCStep<E>]) -> Result<(), crate::Error> {
+        let mut proofs = 0;
+        self.within(steps, 0, &mut proofs)
+            .then_some(())
+            .ok_or(crate::Error::With("history exceeds limits"))
+    }
+
+    // stops at the first step over the limits, the steps of a history with
+    // no merges are at depth zero
+    fn within<E: IVC>(&self, steps: &[IVCStep<E>], depth: usize, proofs: &mut usize) -> bool {
+        if !steps.is_empty() && depth > self.max_merge_depth {
+            return false;
+        }
+        steps.iter().all(|step| {
+            *proofs += 1;
+            *proofs <= self.max_proofs && self.within(&step.merged, depth + 1, proofs)
+        })
+    }
+}
@@ -1,38 +1,99 @@
 use crate::{
-    asset::Asset, circuit::IVC, poseidon::PoseidonConfigs, tx::IssueTx, Address, AssetHash, Blind,
-    BlindNoteHash, Nullifier, StateHash,
+    asset::Asset,
+    circuit::{cache::Lru, inputs::PublicInput, IVC},
+    id::EncryptedNote,
+    limits::Limits,
+    poseidon::{PoseidonConfigs, ToCRH},
+    tx::IssueTx,
+    Address, AssetHash, Blind, BlindNoteHash, FWrap, MemoHash, NoteHash, Nullifier, ReceiverTag,
+    StateHash, ViewingKey,
 };
 use ark_crypto_primitives::{snark::SNARK, sponge::Absorb};
 use ark_ff::PrimeField;
+use sha2::{Digest, Sha512};
+use std::collections::HashSet;
+
+// bound of the plaintext of a memo, eg. an invoice reference
+pub const MAX_MEMO: usize = 512;
+
+// expiry step of the notes that don't expire
+pub const NO_EXPIRY: u32 = u32::MAX;
+
+// commitment to the plaintext of a memo, an empty memo is no memo
+pub fn memo_hash<F: PrimeField>(memo: &[u8]) -> MemoHash<F> {
+    match memo.is_empty() {
+        true => MemoHash::default(),
+        false => MemoHash::reduce_bytes(&Sha512::digest(memo)),
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum NoteOutIndex {
     // Original note hash the issue tag
     Issue,
-    // Output with index 0 conventionally this is the refund note
-    Out0,
-    // Output with index 1 conventionally this is the sent note
-    Out1,
+    // Output of a split, 0 is the first sent note, 1 is the change that goes
+    // back to the sender and the rest are the other sent notes
+    Out(u8),
 }
 
 impl NoteOutIndex {
+    // first sent note, the one a split bound to its receiver commits to
+    pub const RECEIVER: NoteOutIndex = NoteOutIndex::Out(0);
+
+    // output the sender keeps, the circuit checks that it is owned by the
+    // sender of a split
+    pub const CHANGE: NoteOutIndex = NoteOutIndex::Out(1);
+
+    pub fn is_change(&self) -> bool {
+        *self == Self::CHANGE
+    }
+
+    // position of the `i`th sent note among the outputs, sent notes take the
+    // positions around the change in order
+    pub(crate) fn sent_position(i: usize) -> usize {
+        let change = Self::CHANGE.position();
+        match i < change {
+            true => i,
+            false => i + 1,
+        }
+    }
+
     pub(crate) fn inner<F: ark_ff::Field>(&self) -> F {
         let u: u8 = self.into();
         u.into()
     }
+
+    // position of the note among the leaves of the state, an issued note is
+    // the second leaf
+    pub(crate) fn position(&self) -> usize {
+        match self {
+            NoteOutIndex::Issue => 1,
+            NoteOutIndex::Out(i) => *i as usize,
+        }
+    }
 }
 
 impl From<&NoteOutIndex> for u8 {
     fn from(val: &NoteOutIndex) -> Self {
         match val {
             NoteOutIndex::Issue => 0,
-            NoteOutIndex::Out0 => 1,
-            NoteOutIndex::Out1 => 2,
+            NoteOutIndex::Out(i) => i + 1,
         }
     }
 }
 
-#[derive(Clone, Debug, Copy)]
+// leaves of the state of a note given the other leaves in order
+pub(crate) fn leaves<F: PrimeField>(
+    blind_note_hash: &BlindNoteHash<F>,
+    out_index: &NoteOutIndex,
+    siblings: &[BlindNoteHash<F>],
+) -> Vec<BlindNoteHash<F>> {
+    let mut leaves = siblings.to_vec();
+    leaves.insert(out_index.position().min(siblings.len()), *blind_note_hash);
+    leaves
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub struct Note<F: PrimeField> {
     // asset hash defines context of the note tree
     pub(crate) asset_hash: AssetHash<F>,
@@ -48,6 +109,11 @@ pub struct Note<F: PrimeField> {
     pub(crate) out_index: NoteOutIndex,
     // blinding factor
     pub(crate) blind: Blind<F>,
+    // commitment to the memo, the plaintext travels with the history
+    pub(crate) memo: MemoHash<F>,
+    // last step the note or the notes split from it can be spent at,
+    // inherited by the outputs
+    pub(crate) expiry_step: u32,
 }
 
 impl<F: PrimeField + Absorb> Note<F> {
@@ -68,8 +134,28 @@ impl<F: PrimeField + Absorb> Note<F> {
             out_index: *out_index,
             parent_note: *parent_note,
             blind,
+            memo: MemoHash::default(),
+            expiry_step: NO_EXPIRY,
         }
     }
+
+    pub fn with_memo(mut self, memo: &MemoHash<F>) -> Self {
+        self.memo = *memo;
+        self
+    }
+
+    pub fn memo(&self) -> &MemoHash<F> {
+        &self.memo
+    }
+
+    pub fn with_expiry_step(mut self, expiry_step: u32) -> Self {
+        self.expiry_step = expiry_step;
+        self
+    }
+
+    pub fn expiry_step(&self) -> u32 {
+        self.expiry_step
+    }
 }
 
 #[derive(Clone)]
@@ -82,6 +168,16 @@ pub struct IVCStep<E: IVC> {
     pub(crate) nullifier: Nullifier<E::Field>,
     // previous owner, signer of the input note or issuer
     pub(crate) sender: Address<E::Field>,
+    // history of the second input of a merge, empty otherwise
+    pub(crate) merged: Vec<IVCStep<E>>,
+    // nullifier of the second input of a merge
+    pub(crate) merge_nullifier: Nullifier<E::Field>,
+    // tag of the receiver of the first sent note, zero if the step is not
+    // bound to its receiver
+    pub(crate) receiver_tag: ReceiverTag<E::Field>,
+    // input note, or the issued note, encrypted to the viewing key of the
+    // sender by the circuit
+    pub(crate) disclosure: EncryptedNote<E::Field>,
 }
 
 impl<E: IVC> std::fmt::Debug for IVCStep<E> {
@@ -90,6 +186,10 @@ impl<E: IVC> std::fmt::Debug for IVCStep<E> {
             .field("state", &self.state)
             .field("nullifier", &self.nullifier)
             .field("sender", &self.sender)
+            .field("merged", &self.merged)
+            .field("merge_nullifier", &self.merge_nullifier)
+            .field("receiver_tag", &self.receiver_tag)
+            .field("disclosure", &self.disclosure)
             .finish()
     }
 }
@@ -100,14 +200,89 @@ impl<E: IVC> IVCStep<E> {
         state: &StateHash<E::Field>,
         nullifier: &Nullifier<E::Field>,
         sender: &Address<E::Field>,
+        disclosure: &EncryptedNote<E::Field>,
     ) -> Self {
         IVCStep {
             proof: proof.clone(),
             state: *state,
             nullifier: *nullifier,
             sender: *sender,
+            merged: vec![],
+            merge_nullifier: Nullifier::default(),
+            receiver_tag: ReceiverTag::default(),
+            disclosure: disclosure.clone(),
         }
     }
+
+    // step of a split bound to the receiver of its first sent note
+    pub(crate) fn with_receiver_tag(mut self, receiver_tag: &ReceiverTag<E::Field>) -> Self {
+        self.receiver_tag = *receiver_tag;
+        self
+    }
+
+    // step of a merge that also spent the current note of `merged`
+    pub(crate) fn with_merged(
+        mut self,
+        merged: &NoteHistory<E>,
+        merge_nullifier: &Nullifier<E::Field>,
+    ) -> Self {
+        self.merged = merged.steps.clone();
+        self.merge_nullifier = *merge_nullifier;
+        self
+    }
+
+    // public input of the step at position `step` of a history
+    pub(crate) fn public_input(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        asset_hash: &AssetHash<E::Field>,
+        state_in: &StateHash<E::Field>,
+        step: u32,
+    ) -> PublicInput<E::Field> {
+        let merge_state_in = self
+            .merged
+            .last()
+            .map(|step| step.state)
+            .unwrap_or_default();
+        PublicInput::new(
+            asset_hash,
+            &self.sender,
+            state_in,
+            &self.state,
+            step,
+            &self.nullifier,
+            0,
+        )
+        .with_merge(&merge_state_in, &self.merge_nullifier)
+        .with_receiver_tag(&self.receiver_tag)
+        .with_disclosure(&h.disclosure(&self.disclosure))
+    }
+}
+
+// proof of a step with its public input
+pub(crate) type Statement<'a, E> = (
+    &'a <<E as IVC>::Snark as SNARK<<E as IVC>::Field>>::Proof,
+    PublicInput<<E as IVC>::Field>,
+);
+
+// proofs of the steps with their public inputs, histories merged into a step
+// come right before it
+pub(crate) fn statements<'a, E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    asset_hash: &AssetHash<E::Field>,
+    steps: &'a [IVCStep<E>],
+) -> Vec<Statement<'a, E>> {
+    let mut out = vec![];
+    let mut state_in = asset_hash.as_ref().into();
+    for (i, step) in steps.iter().enumerate() {
+        out.extend(statements(h, asset_hash, &step.merged));
+        out.push((
+            &step.proof,
+            step.public_input(h, asset_hash, &state_in, i as u32),
+        ));
+        state_in = step.state;
+    }
+    out
 }
 
 #[derive(Clone, Debug)]
@@ -119,8 +294,13 @@ pub struct NoteHistory<E: IVC> {
 
     // unspent note
     pub(crate) current_note: Note<E::Field>,
-    // sibling of unspent note
-    pub(crate) sibling: BlindNoteHash<E::Field>,
+    // other leaves of the state of unspent note
+    pub(crate) siblings: Vec<BlindNoteHash<E::Field>>,
+    // plaintext of the memo of the unspent note, empty if it has none
+    pub(crate) memo: Vec<u8>,
+    // records of the wire encoding this version doesn't know, kept as they
+    // are to be written back
+    pub(crate) unknown: Vec<(u16, Vec<u8>)>,
 }
 
 impl<E: IVC> NoteHistory<E> {
@@ -129,18 +309,39 @@ impl<E: IVC> NoteHistory<E> {
         asset: &Asset<E::Field>,
         issue_tx: &IssueTx<E::Field>,
         proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+        disclosure: &EncryptedNote<E::Field>,
     ) -> Self {
         let note = issue_tx.note;
-        let state = h.state_out_from_issue_tx(issue_tx);
-        let step = IVCStep::new(proof, &state, &Default::default(), &issue_tx.issuer);
+        let state = h.state_out_from_issue_tx(issue_tx, E::OUTPUTS);
+        let step = IVCStep::new(
+            proof,
+            &state,
+            &Default::default(),
+            &issue_tx.issuer,
+            disclosure,
+        );
         NoteHistory {
             asset: *asset,
             steps: vec![step],
             current_note: note,
-            sibling: BlindNoteHash::default(),
+            siblings: vec![BlindNoteHash::default(); E::OUTPUTS - 1],
+            memo: vec![],
+            unknown: vec![],
         }
     }
 
+    // notes spent or issued by the holder of the viewing key in this history
+    // and in the merged ones, in the order of the proofs
+    pub fn disclosed(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        key: &ViewingKey<E::Field>,
+    ) -> Vec<Note<E::Field>> {
+        let mut notes = vec![];
+        disclosed(h, key, &self.steps, &mut notes);
+        notes
+    }
+
     pub fn owner(&self) -> &Address<E::Field> {
         &self.current_note.owner
     }
@@ -149,19 +350,233 @@ impl<E: IVC> NoteHistory<E> {
         &self.current_note.out_index
     }
 
-    pub fn sibling(&self) -> &BlindNoteHash<E::Field> {
-        &self.sibling
+    pub fn siblings(&self) -> &[BlindNoteHash<E::Field>] {
+        &self.siblings
+    }
+
+    // the note is the change the sender kept after its last split rather than
+    // a note it received
+    pub fn is_change(&self) -> bool {
+        self.current_note.out_index.is_change()
+    }
+
+    // the last step is bound to the receiver of the note, a receiver that
+    // knows the blind of the note can show that it was paid
+    pub fn is_receiver_bound(&self) -> bool {
+        self.steps
+            .last()
+            .map_or(false, |step| step.receiver_tag != ReceiverTag::default())
+    }
+
+    // the tag of a bound step commits to the first sent note
+    pub(crate) fn check_receiver_tag(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+    ) -> Result<(), crate::Error> {
+        let note = &self.current_note;
+        (!self.is_receiver_bound()
+            || note.out_index != NoteOutIndex::RECEIVER
+            || self.steps.last().map(|step| step.receiver_tag)
+                == Some(h.receiver_tag(&note.owner, &note.blind)))
+        .then_some(())
+        .ok_or(crate::Error::With("note is not bound to its receiver"))
+    }
+
+    // transfers the note can still go through, none if it doesn't expire
+    pub fn remaining_transfers(&self) -> Option<u32> {
+        let expiry_step = self.current_note.expiry_step;
+        (expiry_step != NO_EXPIRY)
+            .then(|| (expiry_step as usize + 1).saturating_sub(self.steps.len()) as u32)
+    }
+
+    // memo the sender attached to the note, checked against its commitment
+    // when the history is received
+    pub fn memo(&self) -> Option<&[u8]> {
+        (!self.memo.is_empty()).then_some(&self.memo)
     }
 
     pub fn state(&self, h: &PoseidonConfigs<E::Field>) -> StateHash<E::Field> {
         let (_, blind_note_hash) = h.note(&self.current_note);
-        match self.current_note.out_index {
-            NoteOutIndex::Out0 => h.state(&blind_note_hash, &self.sibling),
-            NoteOutIndex::Out1 => h.state(&self.sibling, &blind_note_hash),
-            NoteOutIndex::Issue => {
-                assert_eq!(self.sibling, BlindNoteHash::default());
-                h.state(&BlindNoteHash::default(), &blind_note_hash)
+        self.state_of(h, &blind_note_hash)
+    }
+
+    pub(crate) fn state_cached(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        cache: &mut NoteCache<E::Field>,
+    ) -> StateHash<E::Field> {
+        let (_, blind_note_hash) = cache.note(h, &self.current_note);
+        self.state_of(h, &blind_note_hash)
+    }
+
+    fn state_of(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        blind_note_hash: &BlindNoteHash<E::Field>,
+    ) -> StateHash<E::Field> {
+        let out_index = &self.current_note.out_index;
+        h.state(&leaves(blind_note_hash, out_index, &self.siblings))
+    }
+
+    // NOT A VERIFICATION. Checks the shape of the history without the proofs
+    // for unit tests of integrators where setting up the snark is too slow. A
+    // history that passes can still be forged, wallets verify with `receive`.
+    //
+    // - the history is within the limits and the note is of the asset
+    // - every history starts with an issuance by the issuer of the asset
+    // - only issuances have no nullifier and no nullifier is spent twice
+    // - a merge carries both the merged history and its nullifier
+    // - the note is in the state the last step outputs
+    // - a change note is owned by the sender of the last step
+    // - a step bound to its receiver is bound to the owner of the first sent
+    //   note
+    //
+    // Public inputs are rebuilt from the chain of states and signatures are
+    // witnesses of the proofs, so neither can be checked without the snark.
+    pub fn fast_verify_structure(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        limits: &Limits,
+    ) -> Result<(), crate::Error> {
+        limits.check_history(self)?;
+        let err = crate::Error::With("bad history structure");
+        (self.current_note.asset_hash == self.asset.hash(h)
+            && self.siblings.len() == E::OUTPUTS - 1)
+            .then_some(())
+            .ok_or(err)?;
+        (self.current_note.memo == memo_hash(&self.memo))
+            .then_some(())
+            .ok_or(crate::Error::With("memo does not match its commitment"))?;
+        check_structure(&self.asset, &self.steps, &mut HashSet::new())?;
+        (!self.current_note.out_index.is_change()
+            || self.steps.last().map(|step| step.sender) == Some(self.current_note.owner))
+        .then_some(())
+        .ok_or(err)?;
+        self.check_receiver_tag(h)?;
+        (self.steps.last().map(|step| step.state) == Some(self.state(h)))
+            .then_some(())
+            .ok_or(crate::Error::With("bad current state"))
+    }
+}
+
+fn disclosed<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    key: &ViewingKey<E::Field>,
+    steps: &[IVCStep<E>],
+    notes: &mut Vec<Note<E::Field>>,
+) {
+    for step in steps.iter() {
+        disclosed(h, key, &step.merged, notes);
+        // a merged history repeats the steps it shares with the history
+        if let Ok(note) = key.decrypt(h, &step.disclosure) {
+            if !notes.contains(&note) {
+                notes.push(note);
             }
         }
     }
 }
+
+fn check_structure<E: IVC>(
+    asset: &Asset<E::Field>,
+    steps: &[IVCStep<E>],
+    spent: &mut HashSet<Nullifier<E::Field>>,
+) -> Result<(), crate::Error> {
+    let err = crate::Error::With("bad history structure");
+    let none = Nullifier::default();
+    let issue = steps.first().ok_or(err)?;
+    // the circuit checks the key of an issuance of a key bound asset
+    ((asset.is_key_bound() || issue.sender == asset.issuer)
+        && issue.nullifier == none
+        && issue.merge_nullifier == none
+        && issue.merged.is_empty())
+    .then_some(())
+    .ok_or(err)?;
+    for step in steps[1..].iter() {
+        (step.nullifier != none && step.merged.is_empty() == (step.merge_nullifier == none))
+            .then_some(())
+            .ok_or(err)?;
+        let merged = !step.merged.is_empty();
+        (spent.insert(step.nullifier) && (!merged || spent.insert(step.merge_nullifier)))
+            .then_some(())
+            .ok_or(crate::Error::With("repeated nullifier"))?;
+        if merged {
+            check_structure(asset, &step.merged, spent)?;
+        }
+    }
+    Ok(())
+}
+
+// contents of a note with the blinding factor
+pub type NoteKey<F> = [F; 9];
+
+// `NoteCache` is a bounded LRU of note hashes keyed by the note contents
+#[derive(Clone, Debug)]
+pub struct NoteCache<F: PrimeField> {
+    lru: Lru<NoteKey<F>, (NoteHash<F>, BlindNoteHash<F>)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<F: PrimeField + Absorb> NoteCache<F> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lru: Lru::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn key(note: &Note<F>) -> NoteKey<F> {
+        let mut key = [F::zero(); 9];
+        key[..8].copy_from_slice(&note.to_crh());
+        key[8] = note.blind.inner();
+        key
+    }
+
+    // same as `PoseidonConfigs::note`
+    pub fn note(
+        &mut self,
+        h: &PoseidonConfigs<F>,
+        note: &Note<F>,
+    ) -> (NoteHash<F>, BlindNoteHash<F>) {
+        let key = Self::key(note);
+        if let Some(hashes) = self.lru.get(&key) {
+            self.hits += 1;
+            return hashes;
+        }
+        self.misses += 1;
+        let hashes = h.note(note);
+        self.lru.insert(key, hashes);
+        hashes
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lru.len() == 0
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    // share of the lookups that are served from the cache, zero before any
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+
+    // entries from least to most recently used. The hashes are not restored
+    // as they are, a cache is warmed again by looking up the notes with `note`
+    pub fn entries(&self) -> impl Iterator<Item = (&NoteKey<F>, &(NoteHash<F>, BlindNoteHash<F>))> {
+        self.lru.entries()
+    }
+}
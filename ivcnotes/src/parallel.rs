@@ -0,0 +1,21 @@
+// Parallel proving. With the `parallel` feature the multi-scalar
+// multiplications, FFTs and field arithmetic of the prover run on the global
+// rayon pool, as do the poseidon hashes of the output notes. The snark of the
+// config has to be built with its own parallel feature too, it is enabled
+// here for groth16.
+//
+// The pool uses a thread per core unless it is configured before the first
+// proof, eg. to leave cores to the UI of a mobile wallet.
+
+// sizes the global pool, can be called once and only before the pool is used
+pub fn init_thread_pool(threads: usize) -> Result<(), crate::Error> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .map_err(|_| crate::Error::With("cannot build thread pool"))
+}
+
+// threads the prover runs on
+pub fn threads() -> usize {
+    rayon::current_num_threads()
+}
@@ -0,0 +1,254 @@
+use crate::{
+    poseidon::PoseidonConfigs,
+    security::{FULL_ROUNDS, PARTIAL_ROUNDS},
+};
+use ark_crypto_primitives::sponge::{
+    poseidon::{find_poseidon_ark_and_mds, PoseidonConfig},
+    Absorb,
+};
+use ark_ff::PrimeField;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+// Generation of the poseidon parameters. Every hash of the protocol has its
+// own config so that a value of one can never be taken for a value of
+// another, eg. a note hash for a state. The configs are derived from the field
+// alone, so every deployment on the same curve gets the same parameters and
+// anyone can regenerate and compare them.
+//
+// - the mds matrix is the first one the grain lfsr of the poseidon paper
+//   accepts for the width, as the reference implementation picks it
+// - round constants are sha256 of the domain tag, the field size and a counter,
+//   reduced to the field. The lfsr would give the same constants to every
+//   domain of the same width, the tag separates them.
+// - x^5 with the rounds `security` requires for the width
+//
+// Sighashes and the tagged hashes of `poseidon`, eg. the asset hash, share the
+// `tx` config and are separated by their trailing domain element.
+
+const RATE: usize = 2;
+const CAPACITY: usize = 1;
+const ALPHA: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Domain {
+    Id,
+    Note,
+    Blind,
+    State,
+    Nullifier,
+    Tx,
+    Eddsa,
+}
+
+impl Domain {
+    pub const ALL: [Domain; 7] = [
+        Self::Id,
+        Self::Note,
+        Self::Blind,
+        Self::State,
+        Self::Nullifier,
+        Self::Tx,
+        Self::Eddsa,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Note => "note",
+            Self::Blind => "blind",
+            Self::State => "state",
+            Self::Nullifier => "nullifier",
+            Self::Tx => "tx",
+            Self::Eddsa => "eddsa",
+        }
+    }
+
+    // seed of the round constants of the domain
+    pub fn tag(&self) -> String {
+        format!("ivcnotes/poseidon/{}", self.name())
+    }
+}
+
+// round constants from the tag, 64 bytes are reduced per element so that the
+// bias is negligible
+fn round_constants<F: PrimeField>(domain: Domain, rounds: usize, width: usize) -> Vec<Vec<F>> {
+    let tag = domain.tag();
+    let mut counter = 0u64;
+    let mut element = || {
+        let mut bytes = vec![];
+        for half in 0..2u8 {
+            let digest = Sha256::new()
+                .chain_update(tag.as_bytes())
+                .chain_update(F::MODULUS_BIT_SIZE.to_le_bytes())
+                .chain_update(counter.to_le_bytes())
+                .chain_update([half])
+                .finalize();
+            bytes.extend_from_slice(&digest);
+        }
+        counter += 1;
+        F::from_le_bytes_mod_order(&bytes)
+    };
+    (0..rounds)
+        .map(|_| (0..width).map(|_| element()).collect())
+        .collect()
+}
+
+// sha256 of every parameter of a config
+fn digest<F: PrimeField>(config: &PoseidonConfig<F>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for n in [
+        config.full_rounds as u64,
+        config.partial_rounds as u64,
+        config.alpha,
+        config.rate as u64,
+        config.capacity as u64,
+    ] {
+        hasher.update(n.to_le_bytes());
+    }
+    for e in config.ark.iter().chain(config.mds.iter()).flatten() {
+        let mut bytes = vec![];
+        e.serialize_compressed(&mut bytes).unwrap();
+        hasher.update(bytes);
+    }
+    hasher.finalize().into()
+}
+
+impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
+    // the config of a single domain as `generate` derives it
+    pub fn generate_domain(domain: Domain) -> PoseidonConfig<F> {
+        let width = RATE + CAPACITY;
+        let partial_rounds = PARTIAL_ROUNDS[width - 2];
+        let (_, mds) = find_poseidon_ark_and_mds::<F>(
+            F::MODULUS_BIT_SIZE as u64,
+            RATE,
+            FULL_ROUNDS as u64,
+            partial_rounds as u64,
+            0,
+        );
+        let ark = round_constants(domain, FULL_ROUNDS + partial_rounds, width);
+        PoseidonConfig::new(FULL_ROUNDS, partial_rounds, ALPHA, mds, ark, RATE, CAPACITY)
+    }
+
+    // parameters of every domain for the field
+    pub fn generate() -> Self {
+        Self {
+            id: Self::generate_domain(Domain::Id),
+            note: Self::generate_domain(Domain::Note),
+            blind: Self::generate_domain(Domain::Blind),
+            state: Self::generate_domain(Domain::State),
+            nullifier: Self::generate_domain(Domain::Nullifier),
+            tx: Self::generate_domain(Domain::Tx),
+            eddsa: Self::generate_domain(Domain::Eddsa),
+        }
+    }
+
+    pub fn config(&self, domain: Domain) -> &PoseidonConfig<F> {
+        match domain {
+            Domain::Id => &self.id,
+            Domain::Note => &self.note,
+            Domain::Blind => &self.blind,
+            Domain::State => &self.state,
+            Domain::Nullifier => &self.nullifier,
+            Domain::Tx => &self.tx,
+            Domain::Eddsa => &self.eddsa,
+        }
+    }
+
+    // the domains are separated if no two of them share round constants
+    pub fn check_separation(&self) -> Result<(), crate::Error> {
+        Domain::ALL
+            .iter()
+            .enumerate()
+            .all(|(i, a)| {
+                Domain::ALL[i + 1..]
+                    .iter()
+                    .all(|b| self.config(*a).ark != self.config(*b).ark)
+            })
+            .then_some(())
+            .ok_or(crate::Error::With("poseidon domains are not separated"))
+    }
+
+    // domains whose config differs from the generated one, eg. the ones a
+    // deployment replaced
+    pub fn non_generated(&self) -> Vec<Domain> {
+        Domain::ALL
+            .into_iter()
+            .filter(|domain| {
+                let generated = Self::generate_domain(*domain);
+                digest(self.config(*domain)) != digest(&generated)
+            })
+            .collect()
+    }
+
+    // security and separation of the active set
+    pub fn audit(&self) -> Result<(), crate::Error> {
+        self.check_security()?;
+        self.check_separation()
+    }
+
+    // digest of the configs of every domain in order, to compare sets without
+    // dumping them
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for domain in Domain::ALL {
+            hasher.update(digest(self.config(domain)));
+        }
+        hasher.finalize().into()
+    }
+
+    // every parameter with elements as decimal strings
+    pub fn to_json(&self) -> String {
+        fn matrix<F: PrimeField>(m: &[Vec<F>]) -> String {
+            let rows = m
+                .iter()
+                .map(|row| {
+                    let row = row
+                        .iter()
+                        .map(|e| format!("\"{}\"", e.into_bigint()))
+                        .collect::<Vec<_>>();
+                    format!("[{}]", row.join(","))
+                })
+                .collect::<Vec<_>>();
+            format!("[{}]", rows.join(","))
+        }
+
+        let fingerprint = self
+            .fingerprint()
+            .iter()
+            .fold(String::new(), |mut out, byte| {
+                write!(out, "{:02x}", byte).unwrap();
+                out
+            });
+        let mut out = String::new();
+        write!(
+            out,
+            "{{\"field_bits\":{},\"fingerprint\":\"{}\",\"domains\":[",
+            F::MODULUS_BIT_SIZE,
+            fingerprint
+        )
+        .unwrap();
+        for (i, domain) in Domain::ALL.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let config = self.config(*domain);
+            write!(
+                out,
+                "{{\"domain\":\"{}\",\"tag\":\"{}\",\"rate\":{},\"capacity\":{},\"alpha\":{},\"full_rounds\":{},\"partial_rounds\":{},\"ark\":{},\"mds\":{}}}",
+                domain.name(),
+                domain.tag(),
+                config.rate,
+                config.capacity,
+                config.alpha,
+                config.full_rounds,
+                config.partial_rounds,
+                matrix(&config.ark),
+                matrix(&config.mds),
+            )
+            .unwrap();
+        }
+        out.push_str("]}");
+        out
+    }
+}
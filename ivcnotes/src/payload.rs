@@ -0,0 +1,391 @@
+use crate::{
+    circuit::IVC,
+    codec::{self, Codecs},
+    handshake::Agreement,
+    id::Auth,
+    limits::Limits,
+    note::NoteHistory,
+    wire,
+};
+use ark_ec::{twisted_edwards::Affine, AffineRepr, CurveConfig, CurveGroup};
+use ark_ff::UniformRand;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha256, Sha512};
+
+// Opaque transfer payloads. A note history, that is the note, its proofs and
+// the history behind it, is encoded in the `wire` format, compressed with the
+// codec of the handshake, see `codec`, and sealed to the encryption key of the
+// receiver, ECIES style. An ephemeral key agrees a
+// secret with the receiver key, the secret keys ChaCha20-Poly1305 that
+// encrypts the history and authenticates the header along with it. The key is
+// used once so the nonce is zero.
+//
+// magic | version | ack digest | ephemeral key | ciphertext | tag
+//
+// The secret also gives an ack key, its sha256 is the ack digest in the clear
+// so that a relay can check that whoever removes the payload from an inbox
+// could open it. The compressed frame is inside the ciphertext so a relay
+// doesn't learn the codec either, it is decompressed under the limits of the
+// receiver.
+
+const MAGIC: &[u8; 4] = b"IVCE";
+const VERSION: u8 = 4;
+const TAG_LEN: usize = 16;
+const DIGEST_LEN: usize = 32;
+const HEADER_LEN: usize = 5 + DIGEST_LEN;
+const KDF_DOMAIN: &[u8] = b"ivcnotes payload";
+const ACK_DOMAIN: &[u8] = b"ivcnotes payload ack";
+
+type Point<E> = Affine<<E as IVC>::TE>;
+
+pub struct EncryptionKey<E: IVC>(Affine<E::TE>);
+
+impl<E: IVC> Clone for EncryptionKey<E> {
+    fn clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl<E: IVC> std::fmt::Debug for EncryptionKey<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EncryptionKey").field(&self.0).finish()
+    }
+}
+
+impl<E: IVC> PartialEq for EncryptionKey<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<E: IVC> EncryptionKey<E> {
+    pub(crate) fn new(secret: &<E::TE as CurveConfig>::ScalarField) -> Self {
+        Self((Affine::<E::TE>::generator() * secret).into_affine())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.0.serialize_compressed(&mut bytes).unwrap();
+        bytes
+    }
+
+    // the point must be on the curve and in the prime order subgroup
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, crate::Error> {
+        Affine::<E::TE>::deserialize_compressed(bytes)
+            .ok()
+            .filter(|point| !point.is_zero())
+            .map(Self)
+            .ok_or(crate::Error::With("bad payload"))
+    }
+}
+
+// key of a payload under the domain, the encryption key or the ack key
+fn keys<E: IVC>(
+    domain: &[u8],
+    shared: &Affine<E::TE>,
+    ephemeral: &Affine<E::TE>,
+    to: &Affine<E::TE>,
+) -> [u8; 32] {
+    let mut hasher = Sha512::new().chain_update(domain);
+    for point in [shared, ephemeral, to] {
+        let mut bytes = vec![];
+        point.serialize_compressed(&mut bytes).unwrap();
+        hasher.update(bytes);
+    }
+    hasher.finalize()[..32].try_into().unwrap()
+}
+
+fn ack_key<E: IVC>(
+    shared: &Affine<E::TE>,
+    ephemeral: &Affine<E::TE>,
+    to: &Affine<E::TE>,
+) -> [u8; 32] {
+    keys::<E>(ACK_DOMAIN, shared, ephemeral, to)
+}
+
+// digest a relay checks the ack key of a payload against, none if the bytes
+// are not a payload
+pub(crate) fn ack_digest(bytes: &[u8]) -> Option<[u8; DIGEST_LEN]> {
+    (bytes.len() > HEADER_LEN + TAG_LEN && bytes[..4] == *MAGIC && bytes[4] == VERSION)
+        .then(|| bytes[5..HEADER_LEN].try_into().unwrap())
+}
+
+pub(crate) fn is_ack_key(digest: &[u8; DIGEST_LEN], key: &[u8; 32]) -> bool {
+    Sha256::digest(key).as_slice() == digest
+}
+
+// magic, version, ack digest, ephemeral key, codec frame and tag around the
+// wire encoding when it is not compressed
+pub(crate) fn overhead<E: IVC>() -> usize {
+    HEADER_LEN + Affine::<E::TE>::generator().compressed_size() + codec::HEADER_LEN + TAG_LEN
+}
+
+// ephemeral key and the secret it agrees with `to`
+fn agree_to<E: IVC>(to: &EncryptionKey<E>, rng: &mut impl CryptoRngCore) -> (Point<E>, Point<E>) {
+    let r = <E::TE as CurveConfig>::ScalarField::rand(rng);
+    let ephemeral = (Affine::<E::TE>::generator() * r).into_affine();
+    let shared = (to.0 * r).into_affine();
+    (ephemeral, shared)
+}
+
+// Encrypts other data than histories to `to` the same way under a domain of
+// its own, `header | ephemeral key | ciphertext | tag`. The header is
+// authenticated along with the plaintext.
+pub(crate) fn encrypt<E: IVC>(
+    domain: &[u8],
+    header: &[u8],
+    plaintext: &[u8],
+    to: &EncryptionKey<E>,
+    rng: &mut impl CryptoRngCore,
+) -> Vec<u8> {
+    let (ephemeral, shared) = agree_to(to, rng);
+    let key = keys::<E>(domain, &shared, &ephemeral, &to.0);
+    let mut bytes = header.to_vec();
+    ephemeral.serialize_compressed(&mut bytes).unwrap();
+    let payload = Payload {
+        msg: plaintext,
+        aad: &bytes,
+    };
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(&Nonce::default(), payload)
+        .unwrap();
+    bytes.extend_from_slice(&ciphertext);
+    bytes
+}
+
+// seals a history to the receiver, only its identity can open it. The history
+// is compressed with the codec of the agreement with the receiver.
+pub fn seal<E: IVC>(
+    history: &NoteHistory<E>,
+    to: &EncryptionKey<E>,
+    codecs: &Codecs,
+    agreement: &Agreement,
+    rng: &mut impl CryptoRngCore,
+) -> Result<Vec<u8>, crate::Error> {
+    let plaintext = codecs.compress(agreement, &wire::encode(history))?;
+    let (ephemeral, shared) = agree_to(to, rng);
+    let key = keys::<E>(KDF_DOMAIN, &shared, &ephemeral, &to.0);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&Sha256::digest(ack_key::<E>(&shared, &ephemeral, &to.0)));
+    ephemeral.serialize_compressed(&mut bytes).unwrap();
+    let payload = Payload {
+        msg: &plaintext,
+        aad: &bytes,
+    };
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(&Nonce::default(), payload)
+        .unwrap();
+    bytes.extend_from_slice(&ciphertext);
+    Ok(bytes)
+}
+
+impl<E: IVC> Auth<E> {
+    // ephemeral key and the secret it agrees with this identity
+    fn agree(&self, bytes: &[u8]) -> Result<(Point<E>, Point<E>), crate::Error> {
+        let err = crate::Error::With("bad payload");
+        ack_digest(bytes).ok_or(err)?;
+        let ephemeral =
+            Affine::<E::TE>::deserialize_compressed(&bytes[HEADER_LEN..]).map_err(|_| err)?;
+        let shared = (ephemeral * self.encryption_secret()).into_affine();
+        Ok((ephemeral, shared))
+    }
+
+    // key that proves to a relay that this identity can open the payload
+    pub fn ack_key(&self, bytes: &[u8]) -> Result<[u8; 32], crate::Error> {
+        let (ephemeral, shared) = self.agree(bytes)?;
+        let key = ack_key::<E>(&shared, &ephemeral, &self.encryption_key().0);
+        ack_digest(bytes)
+            .filter(|digest| is_ack_key(digest, &key))
+            .map(|_| key)
+            .ok_or(crate::Error::With("cannot open payload"))
+    }
+
+    // opens a payload sealed to this identity, the history is not verified
+    pub fn open(
+        &self,
+        bytes: &[u8],
+        codecs: &Codecs,
+        limits: &Limits,
+    ) -> Result<NoteHistory<E>, crate::Error> {
+        let err = crate::Error::With("bad payload");
+        let (ephemeral, shared) = self.agree(bytes)?;
+        let at = HEADER_LEN + ephemeral.compressed_size();
+        (bytes.len() >= at + TAG_LEN).then_some(()).ok_or(err)?;
+        let (header, ciphertext) = bytes.split_at(at);
+
+        let to = self.encryption_key();
+        let key = keys::<E>(KDF_DOMAIN, &shared, &ephemeral, &to.0);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: header,
+        };
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(&Nonce::default(), payload)
+            .map_err(|_| crate::Error::With("cannot open payload"))?;
+        wire::decode(&codecs.decompress(&plaintext, limits)?, limits)
+    }
+
+    // decrypts what `encrypt` encrypted to this identity under the domain
+    pub(crate) fn decrypt(&self, domain: &[u8], header: &[u8], bytes: &[u8]) -> Option<Vec<u8>> {
+        bytes.starts_with(header).then_some(())?;
+        let ephemeral = Affine::<E::TE>::deserialize_compressed(&bytes[header.len()..]).ok()?;
+        let at = header.len() + ephemeral.compressed_size();
+        (bytes.len() >= at + TAG_LEN).then_some(())?;
+        let (aad, ciphertext) = bytes.split_at(at);
+
+        let shared = (ephemeral * self.encryption_secret()).into_affine();
+        let key = keys::<E>(domain, &shared, &ephemeral, &self.encryption_key().0);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(&Nonce::default(), payload)
+            .ok()
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::{Asset, Terms},
+        circuit::mock::{self, Mock},
+        handshake::{negotiate, Hello},
+        poseidon::PoseidonConfigs,
+        wallet::CommReceiver,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    struct Fixture {
+        history: NoteHistory<Mock>,
+        receiver: Auth<Mock>,
+        payload: Vec<u8>,
+    }
+
+    fn fixture() -> Fixture {
+        let h = PoseidonConfigs::generate();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut alice = mock::wallet(&h, &mut rng);
+        let asset = Asset::new(issuer.address(), &Terms::iou(1, 1));
+        issuer.issue(&mut rng, &mut alice, &asset, 100).unwrap();
+        let history = alice.spendables()[0].clone();
+
+        let receiver = Auth::generate(&h, &mut rng).unwrap();
+        let agreement = negotiate(&Hello::default(), &Hello::default()).unwrap();
+        let payload = seal(
+            &history,
+            &receiver.encryption_key(),
+            &Codecs::default(),
+            &agreement,
+            &mut rng,
+        )
+        .unwrap();
+        Fixture {
+            history,
+            receiver,
+            payload,
+        }
+    }
+
+    fn open(auth: &Auth<Mock>, payload: &[u8]) -> Result<NoteHistory<Mock>, crate::Error> {
+        auth.open(payload, &Codecs::default(), &Limits::default())
+    }
+
+    #[test]
+    fn seal_open_round_trip() {
+        let Fixture {
+            history,
+            receiver,
+            payload,
+        } = fixture();
+        let opened = open(&receiver, &payload).unwrap();
+        assert_eq!(wire::encode(&opened), wire::encode(&history));
+
+        // the ack key is what the digest in the clear commits to
+        let digest = ack_digest(&payload).unwrap();
+        assert!(is_ack_key(&digest, &receiver.ack_key(&payload).unwrap()));
+        assert!(!is_ack_key(&digest, &[0; 32]));
+    }
+
+    #[test]
+    fn tampered_payload() {
+        let Fixture {
+            receiver, payload, ..
+        } = fixture();
+        let cannot_open = Some(crate::Error::With("cannot open payload"));
+        let bad_payload = Some(crate::Error::With("bad payload"));
+
+        // the ciphertext, the tag and the ack digest are authenticated
+        for at in [HEADER_LEN + 40, payload.len() - 1, 5] {
+            let mut tampered = payload.clone();
+            tampered[at] ^= 1;
+            assert_eq!(open(&receiver, &tampered).err(), cannot_open);
+        }
+        // so is the ephemeral key, if it still decodes to a point
+        let mut tampered = payload.clone();
+        tampered[HEADER_LEN] ^= 1;
+        assert!(open(&receiver, &tampered).is_err());
+
+        let mut tampered = payload.clone();
+        tampered[0] ^= 1;
+        assert_eq!(open(&receiver, &tampered).err(), bad_payload);
+        let mut tampered = payload.clone();
+        tampered[4] = VERSION + 1;
+        assert_eq!(open(&receiver, &tampered).err(), bad_payload);
+        for len in [0, HEADER_LEN, HEADER_LEN + 32, payload.len() - TAG_LEN] {
+            assert!(open(&receiver, &payload[..len]).is_err());
+        }
+        assert_eq!(open(&receiver, &payload[..HEADER_LEN]).err(), bad_payload);
+    }
+
+    #[test]
+    fn wrong_recipient_key() {
+        let Fixture { payload, .. } = fixture();
+        let h = PoseidonConfigs::generate();
+        let other = Auth::<Mock>::generate(&h, &mut StdRng::seed_from_u64(3)).unwrap();
+        assert_eq!(
+            open(&other, &payload).unwrap_err(),
+            crate::Error::With("cannot open payload")
+        );
+        assert_eq!(
+            other.ack_key(&payload).unwrap_err(),
+            crate::Error::With("cannot open payload")
+        );
+    }
+
+    #[test]
+    fn encrypt_decrypt_under_domain() {
+        let h = PoseidonConfigs::generate();
+        let mut rng = StdRng::seed_from_u64(1);
+        let receiver = Auth::<Mock>::generate(&h, &mut rng).unwrap();
+        let other = Auth::<Mock>::generate(&h, &mut rng).unwrap();
+        let bytes = encrypt(
+            b"domain",
+            b"head",
+            b"secret",
+            &receiver.encryption_key(),
+            &mut rng,
+        );
+        assert!(bytes.starts_with(b"head"));
+
+        assert_eq!(
+            receiver.decrypt(b"domain", b"head", &bytes),
+            Some(b"secret".to_vec())
+        );
+        assert_eq!(receiver.decrypt(b"other", b"head", &bytes), None);
+        assert_eq!(receiver.decrypt(b"domain", b"hear", &bytes), None);
+        assert_eq!(other.decrypt(b"domain", b"head", &bytes), None);
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(receiver.decrypt(b"domain", b"head", &tampered), None);
+    }
+}
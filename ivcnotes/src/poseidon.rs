@@ -1,24 +1,49 @@
 use crate::{
-    circuit::inputs::NoteVar,
-    note::Note,
-    tx::{IssueTx, SplitTx},
-    Address, Blind, BlindNoteHash, FWrap, NoteHash, Nullifier, NullifierKey, SigHash, StateHash,
+    id::EncryptedNote,
+    note::{leaves, Note, NoteOutIndex},
+    subscription::Authorization,
+    tx::{BurnTx, IssueTx, MergeTx, SplitTx},
+    Address, AssetHash, Blind, BlindNoteHash, DisclosureHash, FWrap, IssuerKey, NoteHash,
+    Nullifier, NullifierKey, PolicyHash, ReceiverTag, SettlementRef, SigHash, StateHash,
+    ViewingKey,
 };
 use ark_crypto_primitives::{
-    crh::{
-        poseidon::constraints::CRHGadget, poseidon::constraints::CRHParametersVar, poseidon::CRH,
-        CRHScheme, CRHSchemeGadget,
-    },
+    crh::{poseidon::CRH, CRHScheme},
     sponge::{poseidon::PoseidonConfig, Absorb},
 };
 use ark_ec::twisted_edwards::TECurveConfig;
 use ark_ff::{BigInteger, PrimeField};
-use ark_r1cs_std::{
-    alloc::AllocVar, fields::fp::FpVar, groups::curves::twisted_edwards::AffineVar,
-};
-use ark_relations::r1cs::{Namespace, Result as CSResult};
 use arkeddsa::PublicKey;
 
+#[cfg(feature = "prover")]
+use {
+    crate::circuit::inputs::NoteVar,
+    ark_crypto_primitives::crh::{
+        poseidon::constraints::CRHGadget, poseidon::constraints::CRHParametersVar, CRHSchemeGadget,
+    },
+    ark_r1cs_std::{
+        alloc::AllocVar, fields::fp::FpVar, groups::curves::twisted_edwards::AffineVar,
+    },
+    ark_relations::r1cs::{Namespace, Result as CSResult},
+};
+
+const SIGHASH_BURN_DOMAIN: u64 = 1;
+const AUTHORIZATION_DOMAIN: u64 = 2;
+const SIGHASH_REDEEM_DOMAIN: u64 = 3;
+const SIGHASH_MERGE_DOMAIN: u64 = 4;
+const VIEWING_KEY_DOMAIN: u64 = 5;
+const KEYSTREAM_DOMAIN: u64 = 6;
+const DISCLOSURE_TAG_DOMAIN: u64 = 7;
+const LOG_LEAF_DOMAIN: u64 = 8;
+const LOG_NODE_DOMAIN: u64 = 9;
+const TREE_HEAD_DOMAIN: u64 = 10;
+const ASSET_DOMAIN: u64 = 11;
+const RECEIVER_LEAF_DOMAIN: u64 = 12;
+const RECEIVER_NODE_DOMAIN: u64 = 13;
+const ISSUER_KEY_DOMAIN: u64 = 14;
+const RECEIVER_TAG_DOMAIN: u64 = 15;
+const DISCLOSURE_DOMAIN: u64 = 16;
+
 pub trait ToCRH<F: PrimeField> {
     type Output;
     fn to_crh(&self) -> Vec<Self::Output>;
@@ -35,7 +60,34 @@ impl<F: PrimeField + Absorb> ToCRH<F> for Note<F> {
         let step = self.step.into();
         let parent = self.parent_note.inner();
         let out_index = self.out_index.inner();
-        vec![asset_hash, owner, value, step, parent, out_index]
+        let memo = self.memo.inner();
+        let expiry_step = self.expiry_step.into();
+        vec![
+            asset_hash,
+            owner,
+            value,
+            step,
+            parent,
+            out_index,
+            memo,
+            expiry_step,
+        ]
+    }
+}
+
+impl<F: PrimeField> ToCRH<F> for Authorization<F> {
+    type Output = F;
+
+    // serialize into field elements
+    fn to_crh(&self) -> Vec<F> {
+        vec![
+            self.payer.inner(),
+            self.payee.inner(),
+            self.asset_hash.inner(),
+            self.cap.into(),
+            self.period.into(),
+            self.start.into(),
+        ]
     }
 }
 
@@ -87,6 +139,7 @@ impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
         CRH::<F>::evaluate(&self.id, input).unwrap().into()
     }
 
+    #[cfg(feature = "prover")]
     pub fn var_id_commitment<TE: TECurveConfig<BaseField = F>>(
         &self,
         cs: impl Into<Namespace<F>>,
@@ -107,11 +160,24 @@ impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
         (note_hash, blind)
     }
 
+    // blind hashes of the output notes of a transaction, on the thread pool
+    // with the `parallel` feature
+    pub fn blind_notes(&self, notes: &[Note<F>]) -> Vec<BlindNoteHash<F>> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            notes.par_iter().map(|note| self.note(note).1).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        notes.iter().map(|note| self.note(note).1).collect()
+    }
+
     pub fn blind_note(&self, note: &NoteHash<F>, blind: &Blind<F>) -> BlindNoteHash<F> {
         let input = vec![note.inner(), blind.inner()];
         CRH::<F>::evaluate(&self.blind, input).unwrap().into()
     }
 
+    #[cfg(feature = "prover")]
     pub fn var_note(&self, cs: impl Into<Namespace<F>>, note: &NoteVar<F>) -> CSResult<FpVar<F>> {
         let cs = cs.into().cs();
         let input = note.to_crh();
@@ -119,6 +185,7 @@ impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
         CRHGadget::evaluate(&params, &input)
     }
 
+    #[cfg(feature = "prover")]
     pub fn var_blind_note(
         &self,
         cs: impl Into<Namespace<F>>,
@@ -131,74 +198,435 @@ impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
         CRHGadget::evaluate(&params, &input)
     }
 
-    pub fn state_out_from_issue_tx(&self, tx: &IssueTx<F>) -> StateHash<F> {
+    // the issued note is the only non empty leaf of the state
+    pub fn state_out_from_issue_tx(&self, tx: &IssueTx<F>, outputs: usize) -> StateHash<F> {
         let (_, blind_note_hash) = self.note(tx.note());
-        self.state(&Default::default(), &blind_note_hash)
+        let siblings = vec![BlindNoteHash::default(); outputs - 1];
+        self.state(&leaves(&blind_note_hash, &NoteOutIndex::Issue, &siblings))
     }
 
     pub fn state_out_from_split_tx(&self, tx: &SplitTx<F>) -> StateHash<F> {
-        let (_, blind_note_hash_0) = self.note(tx.note_out_0());
-        let (_, blind_note_hash_1) = self.note(tx.note_out_1());
-        self.state(&blind_note_hash_0, &blind_note_hash_1)
+        let leaves: Vec<_> = tx
+            .notes_out()
+            .iter()
+            .map(|note| self.note(note).1)
+            .collect();
+        self.state(&leaves)
     }
 
-    pub fn state(&self, out0: &BlindNoteHash<F>, out1: &BlindNoteHash<F>) -> StateHash<F> {
-        let input = vec![out0.inner(), out1.inner()];
+    pub fn state(&self, leaves: &[BlindNoteHash<F>]) -> StateHash<F> {
+        let input = leaves.iter().map(|leaf| leaf.inner()).collect::<Vec<_>>();
         CRH::<F>::evaluate(&self.state, input).unwrap().into()
     }
 
+    #[cfg(feature = "prover")]
     pub fn var_state(
         &self,
         cs: impl Into<Namespace<F>>,
-        out0: &FpVar<F>,
-        out1: &FpVar<F>,
+        leaves: &[FpVar<F>],
     ) -> CSResult<FpVar<F>> {
         let cs = cs.into().cs();
-        let input = vec![out0.clone(), out1.clone()];
         let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.state)?;
-        CRHGadget::evaluate(&params, &input)
+        CRHGadget::evaluate(&params, leaves)
     }
 
     pub fn sighash_split_tx(&self, tx: &SplitTx<F>) -> SigHash<F> {
         let (note_in, _) = self.note(&tx.note_in);
-        let (note_out_0, _) = self.note(&tx.note_out_0);
-        let (note_out_1, _) = self.note(&tx.note_out_1);
-        self.sighash(&note_in, &note_out_0, &note_out_1)
+        let notes_out: Vec<_> = tx
+            .notes_out()
+            .iter()
+            .map(|note| self.note(note).0)
+            .collect();
+        self.sighash(&note_in, &notes_out)
+    }
+
+    pub fn sighash_merge_tx(&self, tx: &MergeTx<F>) -> SigHash<F> {
+        let (note_in, _) = self.note(&tx.note_in);
+        let (note_merged, _) = self.note(&tx.note_merged);
+        let notes_out: Vec<_> = tx
+            .notes_out()
+            .iter()
+            .map(|note| self.note(note).0)
+            .collect();
+        self.sighash_merge(&note_in, &note_merged, &notes_out)
+    }
+
+    pub fn sighash_burn_tx(&self, tx: &BurnTx<F>) -> SigHash<F> {
+        let (note_in, _) = self.note(&tx.note_in);
+        match tx.settlement.inner().is_zero() {
+            true => self.sighash_burn(&note_in, tx.value()),
+            false => self.sighash_redeem(&note_in, tx.value(), &tx.settlement),
+        }
     }
 
     pub fn sighash_issue_tx(&self, tx: &Note<F>) -> SigHash<F> {
         let (note, _) = self.note(tx);
-        self.sighash(&Default::default(), &Default::default(), &note)
+        self.sighash(&Default::default(), &[Default::default(), note])
+    }
+
+    pub fn sighash(&self, input: &NoteHash<F>, outputs: &[NoteHash<F>]) -> SigHash<F> {
+        let input = std::iter::once(input)
+            .chain(outputs)
+            .map(|note| note.inner())
+            .collect::<Vec<_>>();
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
     }
 
-    pub fn sighash(
+    #[cfg(feature = "prover")]
+    pub fn var_sighash(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        input: &FpVar<F>,
+        outputs: &[FpVar<F>],
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = std::iter::once(input)
+            .chain(outputs)
+            .cloned()
+            .collect::<Vec<_>>();
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // sighash of a burn tx, the domain tag separates it from split tx sighashes
+    pub fn sighash_burn(&self, input: &NoteHash<F>, value: u64) -> SigHash<F> {
+        let input = vec![input.inner(), F::from(value), F::from(SIGHASH_BURN_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_sighash_burn(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        input: &FpVar<F>,
+        value: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(SIGHASH_BURN_DOMAIN))?;
+        let input = vec![input.clone(), value.clone(), domain];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // sighash of a burn that redeems the note, also commits to the payout reference
+    pub fn sighash_redeem(
         &self,
         input: &NoteHash<F>,
-        out0: &NoteHash<F>,
-        out1: &NoteHash<F>,
+        value: u64,
+        settlement: &SettlementRef<F>,
     ) -> SigHash<F> {
-        let input = vec![input.inner(), out0.inner(), out1.inner()];
+        let input = vec![
+            input.inner(),
+            F::from(value),
+            settlement.inner(),
+            F::from(SIGHASH_REDEEM_DOMAIN),
+        ];
         CRH::<F>::evaluate(&self.tx, input).unwrap().into()
     }
 
-    pub fn var_sighash(
+    #[cfg(feature = "prover")]
+    pub fn var_sighash_redeem(
         &self,
         cs: impl Into<Namespace<F>>,
         input: &FpVar<F>,
-        out0: &FpVar<F>,
-        out1: &FpVar<F>,
+        value: &FpVar<F>,
+        settlement: &FpVar<F>,
     ) -> CSResult<FpVar<F>> {
         let cs = cs.into().cs();
-        let input = vec![input.clone(), out0.clone(), out1.clone()];
+        let domain = FpVar::new_constant(cs.clone(), F::from(SIGHASH_REDEEM_DOMAIN))?;
+        let input = vec![input.clone(), value.clone(), settlement.clone(), domain];
         let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
         CRHGadget::evaluate(&params, &input)
     }
 
+    // sighash of a merge tx, commits to both inputs and to the outputs
+    pub fn sighash_merge(
+        &self,
+        input: &NoteHash<F>,
+        merged: &NoteHash<F>,
+        outputs: &[NoteHash<F>],
+    ) -> SigHash<F> {
+        let input = [input, merged]
+            .into_iter()
+            .chain(outputs)
+            .map(|note| note.inner())
+            .chain(std::iter::once(F::from(SIGHASH_MERGE_DOMAIN)))
+            .collect::<Vec<_>>();
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_sighash_merge(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        input: &FpVar<F>,
+        merged: &FpVar<F>,
+        outputs: &[FpVar<F>],
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(SIGHASH_MERGE_DOMAIN))?;
+        let input = [input, merged]
+            .into_iter()
+            .chain(outputs)
+            .chain(std::iter::once(&domain))
+            .cloned()
+            .collect::<Vec<_>>();
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // message the payer signs for a recurring payment authorization
+    pub fn authorization(&self, authorization: &Authorization<F>) -> SigHash<F> {
+        let mut input = authorization.to_crh();
+        input.push(F::from(AUTHORIZATION_DOMAIN));
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
     pub fn nullifier(&self, note_in: &NoteHash<F>, key: &NullifierKey<F>) -> Nullifier<F> {
         let input = vec![note_in.inner(), key.inner()];
-        CRH::<F>::evaluate(&self.state, input).unwrap().into()
+        CRH::<F>::evaluate(&self.nullifier, input).unwrap().into()
+    }
+
+    // leaf of the issuer log, the first element tags the kind of the action
+    pub fn log_leaf(&self, action: &[F]) -> F {
+        let mut input = action.to_vec();
+        input.push(F::from(LOG_LEAF_DOMAIN));
+        CRH::<F>::evaluate(&self.tx, input).unwrap()
+    }
+
+    pub fn log_node(&self, left: &F, right: &F) -> F {
+        let input = vec![*left, *right, F::from(LOG_NODE_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap()
+    }
+
+    // asset hash, commits to the issuer so that only its identity can prove
+    // an issuance of the asset. The issuer is its address or the digest of
+    // its signing key. Dual control is committed as 0 or 1.
+    pub fn asset(
+        &self,
+        issuer: &F,
+        terms: &F,
+        policy: &PolicyHash<F>,
+        dual_control: bool,
+    ) -> AssetHash<F> {
+        let input = vec![
+            *issuer,
+            *terms,
+            policy.inner(),
+            F::from(dual_control),
+            F::from(ASSET_DOMAIN),
+        ];
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_asset(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        issuer: &FpVar<F>,
+        terms: &FpVar<F>,
+        policy: &FpVar<F>,
+        dual_control: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(ASSET_DOMAIN))?;
+        let input = vec![
+            issuer.clone(),
+            terms.clone(),
+            policy.clone(),
+            dual_control.clone(),
+            domain,
+        ];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    pub fn issuer_key<TE: TECurveConfig<BaseField = F>>(
+        &self,
+        public_key: &PublicKey<TE>,
+    ) -> IssuerKey<F> {
+        let (x, y) = public_key.xy();
+        let input = vec![*x, *y, F::from(ISSUER_KEY_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_issuer_key<TE: TECurveConfig<BaseField = F>>(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        public_key: &AffineVar<TE, FpVar<F>>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(ISSUER_KEY_DOMAIN))?;
+        let input = vec![public_key.x.clone(), public_key.y.clone(), domain];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // tag of the receiver of a transfer, the blind of its note hides the
+    // receiver from everyone else
+    pub fn receiver_tag(&self, owner: &Address<F>, blind: &Blind<F>) -> ReceiverTag<F> {
+        let input = vec![owner.inner(), blind.inner(), F::from(RECEIVER_TAG_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_receiver_tag(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        owner: &FpVar<F>,
+        blind: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(RECEIVER_TAG_DOMAIN))?;
+        let input = vec![owner.clone(), blind.clone(), domain];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // leaf of a registered receiver, empty leaves are zero and have no preimage
+    pub fn receiver_leaf(&self, address: &Address<F>) -> F {
+        let input = vec![address.inner(), F::from(RECEIVER_LEAF_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap()
+    }
+
+    pub fn receiver_node(&self, left: &F, right: &F) -> F {
+        let input = vec![*left, *right, F::from(RECEIVER_NODE_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_receiver_leaf(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        address: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(RECEIVER_LEAF_DOMAIN))?;
+        let input = vec![address.clone(), domain];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_receiver_node(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        left: &FpVar<F>,
+        right: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(RECEIVER_NODE_DOMAIN))?;
+        let input = vec![left.clone(), right.clone(), domain];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // message the issuer signs for a tree head of its log
+    pub fn tree_head(&self, size: u64, root: &F) -> SigHash<F> {
+        let input = vec![F::from(size), *root, F::from(TREE_HEAD_DOMAIN)];
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    // the nullifier key cannot be recovered from the viewing key
+    pub fn viewing_key(&self, key: &NullifierKey<F>) -> ViewingKey<F> {
+        let input = vec![key.inner(), F::from(VIEWING_KEY_DOMAIN)];
+        CRH::<F>::evaluate(&self.nullifier, input).unwrap().into()
+    }
+
+    pub fn keystream(&self, key: &ViewingKey<F>, nonce: &F, len: usize) -> Vec<F> {
+        (0..len)
+            .map(|i| {
+                let input = vec![
+                    key.inner(),
+                    *nonce,
+                    F::from(i as u64),
+                    F::from(KEYSTREAM_DOMAIN),
+                ];
+                CRH::<F>::evaluate(&self.tx, input).unwrap()
+            })
+            .collect()
+    }
+
+    // authenticates the plaintext of a disclosed note
+    pub fn disclosure_tag(&self, key: &ViewingKey<F>, nonce: &F, plaintext: &[F]) -> F {
+        let mut input = vec![key.inner(), *nonce];
+        input.extend_from_slice(plaintext);
+        input.push(F::from(DISCLOSURE_TAG_DOMAIN));
+        CRH::<F>::evaluate(&self.tx, input).unwrap()
+    }
+
+    // commitment to the note a step discloses, public input of the step
+    pub fn disclosure(&self, encrypted: &EncryptedNote<F>) -> DisclosureHash<F> {
+        let mut input = vec![encrypted.nonce];
+        input.extend_from_slice(&encrypted.ciphertext);
+        input.push(encrypted.tag);
+        input.push(F::from(DISCLOSURE_DOMAIN));
+        CRH::<F>::evaluate(&self.tx, input).unwrap().into()
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_viewing_key(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        key: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(VIEWING_KEY_DOMAIN))?;
+        let input = vec![key.clone(), domain];
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.nullifier)?;
+        CRHGadget::evaluate(&params, &input)
+    }
+
+    // encrypts the plaintext to the viewing key, gives the ciphertext and the tag
+    #[cfg(feature = "prover")]
+    pub fn var_encrypt(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        key: &FpVar<F>,
+        nonce: &FpVar<F>,
+        plaintext: &[FpVar<F>],
+    ) -> CSResult<(Vec<FpVar<F>>, FpVar<F>)> {
+        let cs = cs.into().cs();
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        let domain = FpVar::new_constant(cs.clone(), F::from(KEYSTREAM_DOMAIN))?;
+        let ciphertext = plaintext
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let i = FpVar::new_constant(cs.clone(), F::from(i as u64))?;
+                let input = vec![key.clone(), nonce.clone(), i, domain.clone()];
+                Ok(m + CRHGadget::evaluate(&params, &input)?)
+            })
+            .collect::<CSResult<Vec<_>>>()?;
+
+        let domain = FpVar::new_constant(cs.clone(), F::from(DISCLOSURE_TAG_DOMAIN))?;
+        let mut input = vec![key.clone(), nonce.clone()];
+        input.extend_from_slice(plaintext);
+        input.push(domain);
+        let tag = CRHGadget::evaluate(&params, &input)?;
+        Ok((ciphertext, tag))
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn var_disclosure(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        nonce: &FpVar<F>,
+        ciphertext: &[FpVar<F>],
+        tag: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let domain = FpVar::new_constant(cs.clone(), F::from(DISCLOSURE_DOMAIN))?;
+        let mut input = vec![nonce.clone()];
+        input.extend_from_slice(ciphertext);
+        input.push(tag.clone());
+        input.push(domain);
+        let params = CRHParametersVar::<F>::new_constant(cs.clone(), &self.tx)?;
+        CRHGadget::evaluate(&params, &input)
     }
 
+    #[cfg(feature = "prover")]
     pub fn var_nullifier(
         &self,
         cs: impl Into<Namespace<F>>,
@@ -0,0 +1,176 @@
+use crate::{circuit::IVC, id::Auth, poseidon::PoseidonConfigs};
+use sha2::{Digest, Sha512};
+
+// Paper backup of an identity. The key and the chain code of the identity are
+// written as words of two syllables, one word per byte, after a version word
+// and followed by two checksum words. Restoring gives back the same signing
+// key, nullifier key and address and the same children.
+//
+// The wordlist is our own and not BIP39, a BIP39 phrase doesn't restore here
+// and our words don't restore in BIP39 wallets.
+
+const VERSION: u8 = 0;
+const CHECKSUM_DOMAIN: &[u8] = b"ivcnotes mnemonic";
+const SYLLABLES: [&str; 16] = [
+    "ba", "de", "fi", "go", "hu", "ka", "le", "mi", "no", "pu", "ra", "se", "ti", "vo", "wu", "zi",
+];
+const WORDS: usize = 1 + 64 + 2;
+
+fn checksum(bytes: &[u8]) -> [u8; 2] {
+    let digest = Sha512::new()
+        .chain_update(CHECKSUM_DOMAIN)
+        .chain_update(bytes)
+        .finalize();
+    [digest[0], digest[1]]
+}
+
+fn word(byte: u8) -> String {
+    [
+        SYLLABLES[(byte >> 4) as usize],
+        SYLLABLES[(byte & 0x0f) as usize],
+    ]
+    .concat()
+}
+
+fn byte(word: &str) -> Option<u8> {
+    let syllable = |s: &str| SYLLABLES.iter().position(|syllable| *syllable == s);
+    (word.len() == 4 && word.is_ascii()).then_some(())?;
+    let hi = syllable(&word[..2])?;
+    let lo = syllable(&word[2..])?;
+    Some((hi << 4 | lo) as u8)
+}
+
+pub fn to_mnemonic<E: IVC>(auth: &Auth<E>) -> String {
+    let mut bytes = vec![VERSION];
+    bytes.extend_from_slice(&auth.extended_key());
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bytes.into_iter().map(word).collect::<Vec<_>>().join(" ")
+}
+
+// words are case insensitive and separated by any whitespace
+pub fn from_mnemonic<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    mnemonic: &str,
+) -> Result<Auth<E>, crate::Error> {
+    let err = crate::Error::With("bad mnemonic");
+    let bytes = mnemonic
+        .split_whitespace()
+        .map(|word| byte(&word.to_ascii_lowercase()).ok_or(err))
+        .collect::<Result<Vec<_>, _>>()?;
+    (bytes.len() == WORDS && bytes[0] == VERSION)
+        .then_some(())
+        .ok_or(err)?;
+
+    let (payload, given) = bytes.split_at(WORDS - 2);
+    (checksum(payload) == given)
+        .then_some(())
+        .ok_or(crate::Error::With("mnemonic checksum mismatch"))?;
+    Ok(Auth::from_extended_key_bytes(
+        h,
+        payload[1..].try_into().unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Address;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[derive(Clone)]
+    struct Config;
+
+    impl IVC for Config {
+        type Snark = ark_groth16::Groth16<ark_bn254::Bn254>;
+        type Field = ark_bn254::Fr;
+        type TE = ark_ed_on_bn254::EdwardsConfig;
+    }
+
+    fn setup() -> (PoseidonConfigs<ark_bn254::Fr>, Auth<Config>, String) {
+        let h = PoseidonConfigs::generate();
+        let auth = Auth::generate(&h, &mut StdRng::seed_from_u64(1)).unwrap();
+        let mnemonic = to_mnemonic(&auth);
+        (h, auth, mnemonic)
+    }
+
+    // address of the restored identity
+    fn restore(
+        h: &PoseidonConfigs<ark_bn254::Fr>,
+        mnemonic: &str,
+    ) -> Result<Address<ark_bn254::Fr>, crate::Error> {
+        from_mnemonic::<Config>(h, mnemonic).map(|auth| *auth.address())
+    }
+
+    #[test]
+    fn mnemonic_round_trip() {
+        let (h, auth, mnemonic) = setup();
+        assert_eq!(mnemonic.split(' ').count(), WORDS);
+
+        let restored = from_mnemonic::<Config>(&h, &mnemonic).unwrap();
+        assert_eq!(restored.address(), auth.address());
+        assert_eq!(restored.nullifier_key(), auth.nullifier_key());
+        assert!(restored.public_key() == auth.public_key());
+        assert_eq!(
+            restored.derive_child(&h, 7).address(),
+            auth.derive_child(&h, 7).address()
+        );
+        assert_eq!(to_mnemonic(&restored), mnemonic);
+    }
+
+    #[test]
+    fn words_are_case_insensitive() {
+        let (h, auth, mnemonic) = setup();
+        let mixed = mnemonic
+            .split(' ')
+            .enumerate()
+            .map(|(i, word)| match i % 2 {
+                0 => word.to_ascii_uppercase(),
+                _ => word.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\t ");
+        assert_eq!(restore(&h, &mixed).unwrap(), *auth.address());
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let (h, _, mnemonic) = setup();
+        let words = mnemonic.split(' ').collect::<Vec<_>>();
+        // a word of the key or of the checksum replaced by another
+        for i in [1, 30, WORDS - 1] {
+            let mut words = words.clone();
+            let other = word(byte(words[i]).unwrap() ^ 1);
+            words[i] = &other;
+            assert_eq!(
+                restore(&h, &words.join(" ")).unwrap_err(),
+                crate::Error::With("mnemonic checksum mismatch")
+            );
+        }
+    }
+
+    #[test]
+    fn bad_mnemonics() {
+        let (h, _, mnemonic) = setup();
+        let words = mnemonic.split(' ').collect::<Vec<_>>();
+        let bad = crate::Error::With("bad mnemonic");
+
+        // wrong word count
+        assert_eq!(restore(&h, "").unwrap_err(), bad);
+        assert_eq!(restore(&h, &words[..WORDS - 1].join(" ")).unwrap_err(), bad);
+        assert_eq!(
+            restore(&h, &[mnemonic.as_str(), "baba"].join(" ")).unwrap_err(),
+            bad
+        );
+        // unknown words, eg. of bip39
+        for unknown in ["abandon", "babe", "bab", "bába"] {
+            let mut words = words.clone();
+            words[5] = unknown;
+            assert_eq!(restore(&h, &words.join(" ")).unwrap_err(), bad);
+        }
+        // unknown version
+        let mut words = words.clone();
+        words[0] = "bade";
+        assert_eq!(restore(&h, &words.join(" ")).unwrap_err(), bad);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    asset::{Asset, Terms},
+    circuit::{Prover, Verifier, IVC},
+    id::Auth,
+    note::NoteHistory,
+    poseidon::PoseidonConfigs,
+    wallet::{CommReceiver, Wallet},
+    AssetHash, StateHash,
+};
+use ark_crypto_primitives::snark::SNARK;
+use rand::{CryptoRng, RngCore};
+
+// Reference flow of the protocol. An issuer issues the test asset to alice and
+// alice sends part of it to bob. Intermediate values are exposed so that the
+// flow can be asserted, benchmarked and reproduced by other implementations.
+
+pub const TEST_MATURITY: u64 = 1_700_000_000;
+pub const TEST_UNIT: u64 = 1;
+pub const ISSUE_VALUE: u64 = 100;
+pub const SPLIT_VALUE: u64 = 30;
+
+pub fn test_terms() -> Terms {
+    Terms::iou(TEST_MATURITY, TEST_UNIT)
+}
+
+pub fn test_asset<E: IVC>(issuer: &Wallet<E>) -> Asset<E::Field> {
+    Asset::new(issuer.address(), &test_terms())
+}
+
+pub struct Actors<E: IVC> {
+    pub issuer: Wallet<E>,
+    pub alice: Wallet<E>,
+    pub bob: Wallet<E>,
+}
+
+impl<E: IVC> Actors<E> {
+    pub fn new<R: RngCore + CryptoRng>(
+        h: &PoseidonConfigs<E::Field>,
+        pk: &<<E as IVC>::Snark as SNARK<E::Field>>::ProvingKey,
+        vk: &<<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey,
+        rng: &mut R,
+    ) -> Result<Self, crate::Error> {
+        let mut wallet = || -> Result<Wallet<E>, crate::Error> {
+            let auth = Auth::generate(h, rng).map_err(|_| crate::Error::With("bad auth"))?;
+            let prover = Prover::new(pk.clone());
+            let verifier = Verifier::new(vk.clone());
+            Ok(Wallet::new(auth, h, prover, verifier))
+        };
+        Ok(Self {
+            issuer: wallet()?,
+            alice: wallet()?,
+            bob: wallet()?,
+        })
+    }
+}
+
+// values to assert after each stage of the flow
+#[derive(Clone, Debug)]
+pub struct Checkpoint<E: IVC> {
+    pub asset_hash: AssetHash<E::Field>,
+    // state after the last step of the history
+    pub state: StateHash<E::Field>,
+    pub history: NoteHistory<E>,
+}
+
+impl<E: IVC> Checkpoint<E> {
+    fn new(h: &PoseidonConfigs<E::Field>, history: &NoteHistory<E>) -> Self {
+        Self {
+            asset_hash: history.asset.hash(h),
+            state: history.state(h),
+            history: history.clone(),
+        }
+    }
+}
+
+// issuer issues `ISSUE_VALUE` to alice
+pub fn issue<E: IVC, R: RngCore + CryptoRng>(
+    h: &PoseidonConfigs<E::Field>,
+    actors: &mut Actors<E>,
+    rng: &mut R,
+) -> Result<Checkpoint<E>, crate::Error> {
+    let asset = test_asset(&actors.issuer);
+    actors
+        .issuer
+        .issue(rng, &mut actors.alice, &asset, ISSUE_VALUE)?;
+    let history = actors
+        .alice
+        .spendables()
+        .last()
+        .ok_or(crate::Error::With("note is not received"))?;
+    Ok(Checkpoint::new(h, history))
+}
+
+// alice sends `SPLIT_VALUE` to bob, returns alice's change and bob's note
+pub fn split<E: IVC, R: RngCore + CryptoRng>(
+    h: &PoseidonConfigs<E::Field>,
+    actors: &mut Actors<E>,
+    rng: &mut R,
+) -> Result<(Checkpoint<E>, Checkpoint<E>), crate::Error> {
+    let index = actors.alice.spendables().len() - 1;
+    actors
+        .alice
+        .split(rng, &mut actors.bob, index, SPLIT_VALUE)?;
+    let change = &actors.alice.spendables()[index];
+    let sent = actors
+        .bob
+        .spendables()
+        .last()
+        .ok_or(crate::Error::With("note is not received"))?;
+    Ok((Checkpoint::new(h, change), Checkpoint::new(h, sent)))
+}
+
+pub struct Flow<E: IVC> {
+    pub actors: Actors<E>,
+    pub issued: Checkpoint<E>,
+    pub change: Checkpoint<E>,
+    pub sent: Checkpoint<E>,
+}
+
+// runs the full issue, split and receive flow, receiving verifies the histories
+pub fn run<E: IVC, R: RngCore + CryptoRng>(
+    h: &PoseidonConfigs<E::Field>,
+    pk: &<<E as IVC>::Snark as SNARK<E::Field>>::ProvingKey,
+    vk: &<<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey,
+    rng: &mut R,
+) -> Result<Flow<E>, crate::Error> {
+    let mut actors = Actors::new(h, pk, vk, rng)?;
+    let issued = issue(h, &mut actors, rng)?;
+    let (change, sent) = split(h, &mut actors, rng)?;
+    Ok(Flow {
+        actors,
+        issued,
+        change,
+        sent,
+    })
+}
@@ -0,0 +1,231 @@
+use crate::{
+    circuit::{inputs::PublicInput, Verifier, IVC},
+    handshake::Reader,
+    note::{statements, NoteHistory},
+    poseidon::PoseidonConfigs,
+    wallet::{CommReceiver, Wallet},
+    Nullifier, StateHash,
+};
+use ark_crypto_primitives::snark::SNARK;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+// Public registry of revealed nullifiers. Senders publish every step of a
+// history that spends a note, receivers look up the nullifiers of a history
+// before accepting it. A nullifier that is published with another state is a
+// double spend, the note was spent twice and only one of the outputs can be
+// honest.
+//
+// A spend is published with its proof and public input and the registry
+// verifies it before recording the nullifiers it reveals. Only the owner of
+// a note can prove its spend, so nobody can take a nullifier first to make the
+// real payment look like a double spend.
+//
+// The registry is trusted to not rewrite what it published.
+
+#[cfg(feature = "relay")]
+pub mod http;
+
+// step that spends a note, with the proof of the spend
+pub struct Spend<E: IVC> {
+    pub(crate) proof: <<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+    pub(crate) input: PublicInput<E::Field>,
+}
+
+impl<E: IVC> Clone for Spend<E> {
+    fn clone(&self) -> Self {
+        Self {
+            proof: self.proof.clone(),
+            input: self.input.clone(),
+        }
+    }
+}
+
+impl<E: IVC> std::fmt::Debug for Spend<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Spend").field("input", &self.input).finish()
+    }
+}
+
+impl<E: IVC> Spend<E> {
+    // nullifiers the spend reveals, two for a merge
+    pub fn nullifiers(&self) -> Vec<Nullifier<E::Field>> {
+        [self.input.nullifier, self.input.merge_nullifier]
+            .into_iter()
+            .filter(|nullifier| *nullifier != Nullifier::default())
+            .collect()
+    }
+
+    // state the spend outputs
+    pub fn state(&self) -> StateHash<E::Field> {
+        self.input.state_out
+    }
+
+    // proof | public inputs in the order of the abi
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.proof.serialize_compressed(&mut bytes).unwrap();
+        for element in self.input.to_verifier() {
+            element.serialize_compressed(&mut bytes).unwrap();
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, crate::Error> {
+        let err = crate::Error::With("bad spend");
+        let mut reader = Reader(bytes);
+        let proof = CanonicalDeserialize::deserialize_compressed(&mut reader.0).map_err(|_| err)?;
+        let elements = (0..crate::circuit::abi::LAYOUT.len())
+            .map(|_| CanonicalDeserialize::deserialize_compressed(&mut reader.0))
+            .collect::<Result<Vec<E::Field>, _>>()
+            .map_err(|_| err)?;
+        let input = PublicInput::from_verifier(&elements).ok_or(err)?;
+        reader.0.is_empty().then_some(()).ok_or(err)?;
+        Ok(Self { proof, input })
+    }
+}
+
+pub trait NullifierRegistry<E: IVC> {
+    // publishing the same spend again is not an error
+    fn publish(&self, spend: &Spend<E>) -> impl Future<Output = Result<(), crate::Error>> + Send;
+
+    // state the spend of the nullifier outputs if it is published
+    fn lookup(
+        &self,
+        nullifier: &Nullifier<E::Field>,
+    ) -> impl Future<Output = Result<Option<StateHash<E::Field>>, crate::Error>> + Send;
+}
+
+// state each published nullifier was spent to
+type Published<F> = HashMap<Nullifier<F>, StateHash<F>>;
+
+// `MemoryRegistry` keeps the spends in memory, for tests and for registry servers
+pub struct MemoryRegistry<E: IVC> {
+    verifier: Verifier<E>,
+    spends: Mutex<Published<E::Field>>,
+}
+
+impl<E: IVC> MemoryRegistry<E> {
+    pub fn new(vk: <<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey) -> Self {
+        Self {
+            verifier: Verifier::new(vk),
+            spends: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // records the nullifiers of a spend once its proof verifies
+    pub(crate) fn insert(&self, spend: &Spend<E>) -> Result<(), crate::Error> {
+        let nullifiers = spend.nullifiers();
+        (!nullifiers.is_empty())
+            .then_some(())
+            .ok_or(crate::Error::With("spend reveals no nullifier"))?;
+        self.verifier
+            .verify_proof(&spend.proof, &spend.input)?
+            .then_some(())
+            .ok_or(crate::Error::With("verification failed"))?;
+        let mut spends = self
+            .spends
+            .lock()
+            .map_err(|_| crate::Error::With("registry lock poisoned"))?;
+        let state = spend.state();
+        nullifiers
+            .iter()
+            .all(|nullifier| {
+                spends
+                    .get(nullifier)
+                    .map_or(true, |published| *published == state)
+            })
+            .then_some(())
+            .ok_or(crate::Error::With("double spend"))?;
+        for nullifier in nullifiers {
+            spends.insert(nullifier, state);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get(
+        &self,
+        nullifier: &Nullifier<E::Field>,
+    ) -> Result<Option<StateHash<E::Field>>, crate::Error> {
+        self.spends
+            .lock()
+            .map(|spends| spends.get(nullifier).copied())
+            .map_err(|_| crate::Error::With("registry lock poisoned"))
+    }
+}
+
+impl<E: IVC> NullifierRegistry<E> for MemoryRegistry<E> {
+    fn publish(&self, spend: &Spend<E>) -> impl Future<Output = Result<(), crate::Error>> + Send {
+        std::future::ready(self.insert(spend))
+    }
+
+    fn lookup(
+        &self,
+        nullifier: &Nullifier<E::Field>,
+    ) -> impl Future<Output = Result<Option<StateHash<E::Field>>, crate::Error>> + Send {
+        std::future::ready(self.get(nullifier))
+    }
+}
+
+// steps of a history that spend a note, the issuances reveal no nullifier
+pub fn spends<E: IVC>(h: &PoseidonConfigs<E::Field>, history: &NoteHistory<E>) -> Vec<Spend<E>> {
+    statements(h, &history.current_note.asset_hash, &history.steps)
+        .into_iter()
+        .map(|(proof, input)| Spend {
+            proof: proof.clone(),
+            input,
+        })
+        .filter(|spend| !spend.nullifiers().is_empty())
+        .collect()
+}
+
+// publishes the spends of a history, senders publish the history they deliver
+pub async fn publish_history<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    registry: &impl NullifierRegistry<E>,
+    history: &NoteHistory<E>,
+) -> Result<(), crate::Error> {
+    for spend in spends(h, history).iter() {
+        registry.publish(spend).await?;
+    }
+    Ok(())
+}
+
+// checks every spent note of a history against the registry, a spend that is
+// not published yet is accepted
+pub async fn check_history<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    registry: &impl NullifierRegistry<E>,
+    history: &NoteHistory<E>,
+) -> Result<(), crate::Error> {
+    for spend in spends(h, history).iter() {
+        for nullifier in spend.nullifiers() {
+            match registry.lookup(&nullifier).await? {
+                Some(published) if published != spend.state() => {
+                    return Err(crate::Error::With("double spend"))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<E: IVC> Wallet<E> {
+    // receives a history after checking it against the registry, the note
+    // itself must not be spent either
+    pub async fn receive_registered(
+        &mut self,
+        registry: &impl NullifierRegistry<E>,
+        history: &NoteHistory<E>,
+    ) -> Result<(), crate::Error> {
+        check_history(&self.h, registry, history).await?;
+        registry
+            .lookup(&self.nullifier(history))
+            .await?
+            .is_none()
+            .then_some(())
+            .ok_or(crate::Error::With("note is already spent"))?;
+        self.receive(history)
+    }
+}
@@ -0,0 +1,149 @@
+use super::{MemoryRegistry, NullifierRegistry, Spend};
+use crate::{
+    circuit::IVC,
+    relay::http::{error, hex, is_decimal, request_status, serve_one, unhex, value, Message},
+    FWrap, Nullifier, StateHash,
+};
+use ark_ff::PrimeField;
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener},
+};
+
+// Reference http json registry and its client, field elements are decimal and
+// spends are the hex of `Spend::to_bytes`. A spend is posted under one of the
+// nullifiers it reveals.
+//
+// POST /nullifiers/{nullifier}  {"spend":"..."}  ->  {}, 409 if spent elsewhere
+// GET  /nullifiers/{nullifier}                   ->  {"state":"..."} or {}
+
+// only the canonical decimal of an element is accepted
+fn parse<F: PrimeField>(value: &str) -> Option<F> {
+    is_decimal(value)
+        .then(|| F::from_str(value).ok())
+        .flatten()
+        .filter(|element| element.to_string() == value)
+}
+
+pub struct RegistryServer {
+    listener: TcpListener,
+}
+
+impl RegistryServer {
+    pub fn bind(addr: SocketAddr) -> Result<Self, crate::Error> {
+        let listener =
+            TcpListener::bind(addr).map_err(|_| crate::Error::With("cannot bind relay"))?;
+        Ok(Self { listener })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, crate::Error> {
+        self.listener
+            .local_addr()
+            .map_err(|_| crate::Error::With("cannot bind relay"))
+    }
+
+    // serves requests, a misbehaving client doesn't stop the server
+    pub fn serve<E: IVC>(&self, registry: &MemoryRegistry<E>) -> ! {
+        loop {
+            let _ = self.serve_one(registry);
+        }
+    }
+
+    pub fn serve_one<E: IVC>(&self, registry: &MemoryRegistry<E>) -> Result<(), crate::Error> {
+        serve_one(&self.listener, |request| respond(registry, request))
+    }
+}
+
+fn respond<E: IVC>(registry: &MemoryRegistry<E>, request: &Message) -> (u16, String) {
+    let mut parts = request.first.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return error(400, "bad request");
+    };
+    let nullifier = match path.strip_prefix("/nullifiers/").map(parse::<E::Field>) {
+        Some(Some(nullifier)) => Nullifier::from(nullifier),
+        _ => return error(404, "not found"),
+    };
+    let result = match method {
+        "POST" => match value(&request.body, "spend").and_then(unhex) {
+            Some(bytes) => Spend::<E>::from_bytes(&bytes)
+                .and_then(|spend| {
+                    spend
+                        .nullifiers()
+                        .contains(&nullifier)
+                        .then_some(spend)
+                        .ok_or(crate::Error::With("bad spend"))
+                })
+                .and_then(|spend| registry.insert(&spend))
+                .map(|_| "{}".to_string()),
+            None => return error(400, "bad request"),
+        },
+        "GET" => registry.get(&nullifier).map(|state| match state {
+            Some(state) => format!("{{\"state\":\"{}\"}}", state.inner()),
+            None => "{}".to_string(),
+        }),
+        _ => return error(404, "not found"),
+    };
+    match result {
+        Ok(body) => (200, body),
+        Err(crate::Error::With("double spend")) => error(409, "double spend"),
+        Err(crate::Error::With(message)) => error(400, message),
+    }
+}
+
+// `HttpRegistry` talks to a registry server
+#[derive(Clone, Debug)]
+pub struct HttpRegistry {
+    addr: SocketAddr,
+}
+
+impl HttpRegistry {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+}
+
+impl HttpRegistry {
+    fn post<E: IVC>(&self, spend: &Spend<E>) -> Result<(), crate::Error> {
+        let nullifier = spend
+            .nullifiers()
+            .first()
+            .copied()
+            .ok_or(crate::Error::With("spend reveals no nullifier"))?;
+        let path = format!("/nullifiers/{}", nullifier.inner());
+        let body = format!("{{\"spend\":\"{}\"}}", hex(&spend.to_bytes()));
+        match request_status(&self.addr, "POST", &path, &body)? {
+            (200, _) => Ok(()),
+            (409, _) => Err(crate::Error::With("double spend")),
+            _ => Err(crate::Error::With("relay rejected the request")),
+        }
+    }
+
+    fn get<F: PrimeField>(
+        &self,
+        nullifier: &Nullifier<F>,
+    ) -> Result<Option<StateHash<F>>, crate::Error> {
+        let path = format!("/nullifiers/{}", nullifier.inner());
+        match request_status(&self.addr, "GET", &path, "")? {
+            (200, body) => match value(&body, "state") {
+                Some(state) => parse::<F>(state)
+                    .map(|state| Some(state.into()))
+                    .ok_or(crate::Error::With("bad relay response")),
+                None => Ok(None),
+            },
+            _ => Err(crate::Error::With("relay rejected the request")),
+        }
+    }
+}
+
+impl<E: IVC> NullifierRegistry<E> for HttpRegistry {
+    fn publish(&self, spend: &Spend<E>) -> impl Future<Output = Result<(), crate::Error>> + Send {
+        std::future::ready(self.post(spend))
+    }
+
+    fn lookup(
+        &self,
+        nullifier: &Nullifier<E::Field>,
+    ) -> impl Future<Output = Result<Option<StateHash<E::Field>>, crate::Error>> + Send {
+        std::future::ready(self.get(nullifier))
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    circuit::IVC,
+    payload,
+    wallet::{CommReceiver, Wallet},
+    Address, FWrap,
+};
+use ark_ff::PrimeField;
+use std::{collections::BTreeMap, future::Future, sync::Mutex};
+
+// Transport of sealed payloads between wallets. A sender drops the payload in
+// the inbox of the receiver on a relay, the receiver lists its inbox, fetches
+// the payloads one at a time and acks what it has processed. Payloads are
+// sealed to the receiver so the relay learns only the addresses and the sizes.
+// The relay is trusted for availability only, it can withhold or drop payloads
+// but not forge them.
+//
+// An ack carries the ack key of the payload, which only the sender and the
+// receiver can derive. The relay checks it against the digest in the clear in
+// the payload, so nobody else can remove a payload from an inbox.
+
+#[cfg(feature = "relay")]
+pub mod http;
+
+// bounds what a relay keeps per receiver
+pub const MAX_PAYLOAD: usize = 4 << 20;
+pub const MAX_INBOX: usize = 1024;
+
+pub trait TransferService<F: PrimeField> {
+    // returns the id of the payload in the inbox of the receiver
+    fn send_note(
+        &self,
+        to: &Address<F>,
+        payload: &[u8],
+    ) -> impl Future<Output = Result<u64, crate::Error>> + Send;
+
+    // ids of the payloads that are not acked yet in the order they arrived
+    fn poll_inbox(
+        &self,
+        address: &Address<F>,
+    ) -> impl Future<Output = Result<Vec<u64>, crate::Error>> + Send;
+
+    // payload of the id, none if it is acked
+    fn fetch(
+        &self,
+        address: &Address<F>,
+        id: u64,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, crate::Error>> + Send;
+
+    // removes the payload from the inbox, `key` is its ack key
+    fn ack(
+        &self,
+        address: &Address<F>,
+        id: u64,
+        key: &[u8; 32],
+    ) -> impl Future<Output = Result<(), crate::Error>> + Send;
+}
+
+#[derive(Debug, Default)]
+struct Inbox {
+    next: u64,
+    payloads: BTreeMap<u64, Vec<u8>>,
+}
+
+// `MemoryRelay` keeps the inboxes in memory, for tests and for relay servers
+#[derive(Debug, Default)]
+pub struct MemoryRelay {
+    inboxes: Mutex<BTreeMap<String, Inbox>>,
+}
+
+impl MemoryRelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<String, Inbox>>, crate::Error> {
+        self.inboxes
+            .lock()
+            .map_err(|_| crate::Error::With("relay lock poisoned"))
+    }
+
+    // inboxes are keyed by the decimal address as on the http relay
+    pub(crate) fn push(&self, to: &str, payload: &[u8]) -> Result<u64, crate::Error> {
+        (payload.len() <= MAX_PAYLOAD)
+            .then_some(())
+            .ok_or(crate::Error::With("payload too large"))?;
+        payload::ack_digest(payload).ok_or(crate::Error::With("bad payload"))?;
+        let mut inboxes = self.lock()?;
+        let inbox = inboxes.entry(to.to_string()).or_default();
+        (inbox.payloads.len() < MAX_INBOX)
+            .then_some(())
+            .ok_or(crate::Error::With("inbox is full"))?;
+        let id = inbox.next;
+        inbox.next += 1;
+        inbox.payloads.insert(id, payload.to_vec());
+        Ok(id)
+    }
+
+    pub(crate) fn pending(&self, address: &str) -> Result<Vec<u64>, crate::Error> {
+        Ok(self
+            .lock()?
+            .get(address)
+            .map(|inbox| inbox.payloads.keys().copied().collect())
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn get(&self, address: &str, id: u64) -> Result<Option<Vec<u8>>, crate::Error> {
+        Ok(self
+            .lock()?
+            .get(address)
+            .and_then(|inbox| inbox.payloads.get(&id).cloned()))
+    }
+
+    // acking an unknown id is not an error, acks can be retried. Empty inboxes
+    // are kept so that ids are never reused.
+    pub(crate) fn remove(
+        &self,
+        address: &str,
+        id: u64,
+        key: &[u8; 32],
+    ) -> Result<(), crate::Error> {
+        let mut inboxes = self.lock()?;
+        let Some(inbox) = inboxes.get_mut(address) else {
+            return Ok(());
+        };
+        if let Some(payload) = inbox.payloads.get(&id) {
+            payload::ack_digest(payload)
+                .filter(|digest| payload::is_ack_key(digest, key))
+                .ok_or(crate::Error::With("bad ack key"))?;
+            inbox.payloads.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> TransferService<F> for MemoryRelay {
+    async fn send_note(&self, to: &Address<F>, payload: &[u8]) -> Result<u64, crate::Error> {
+        self.push(&to.inner().to_string(), payload)
+    }
+
+    async fn poll_inbox(&self, address: &Address<F>) -> Result<Vec<u64>, crate::Error> {
+        self.pending(&address.inner().to_string())
+    }
+
+    async fn fetch(&self, address: &Address<F>, id: u64) -> Result<Option<Vec<u8>>, crate::Error> {
+        self.get(&address.inner().to_string(), id)
+    }
+
+    async fn ack(&self, address: &Address<F>, id: u64, key: &[u8; 32]) -> Result<(), crate::Error> {
+        self.remove(&address.inner().to_string(), id, key)
+    }
+}
+
+// The payload or the history in it is invalid and will never be received. The
+// other errors, eg. a screening or acceptance rejection or a failure to
+// verify, can go away so those payloads are kept to be retried.
+fn is_final(err: &crate::Error) -> bool {
+    let crate::Error::With(message) = err;
+    [
+        "bad payload",
+        "cannot open payload",
+        "bad wire encoding",
+        "unsupported wire version",
+        "unsupported wire record",
+        "not me",
+        "bad history structure",
+        "repeated nullifier",
+        "bad current state",
+        "memo does not match its commitment",
+        "note is not bound to its receiver",
+        "verification failed",
+    ]
+    .contains(message)
+}
+
+impl<E: IVC> Wallet<E> {
+    // Receives the payloads waiting in the inbox of the wallet and acks them,
+    // a payload that is invalid is acked too since it will never do. Payloads
+    // that fail for a reason that can go away are left in the inbox and so are
+    // payloads the wallet can't derive the ack key of. Returns the number of
+    // notes received.
+    pub async fn receive_inbox(
+        &mut self,
+        service: &impl TransferService<E::Field>,
+    ) -> Result<usize, crate::Error> {
+        let address = *self.address();
+        let mut received = 0;
+        for id in service.poll_inbox(&address).await? {
+            let Some(payload) = service.fetch(&address, id).await? else {
+                continue;
+            };
+            let Ok(key) = self.ack_key(&payload) else {
+                continue;
+            };
+            match self.receive_sealed(&payload) {
+                Ok(()) => received += 1,
+                Err(err) if !is_final(&err) => continue,
+                Err(_) => {}
+            }
+            service.ack(&address, id, &key).await?;
+        }
+        Ok(received)
+    }
+}
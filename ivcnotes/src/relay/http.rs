@@ -0,0 +1,423 @@
+use super::{MemoryRelay, TransferService, MAX_PAYLOAD};
+use crate::{Address, FWrap};
+use ark_ff::PrimeField;
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+// Reference http json relay and its client. Addresses are decimal and
+// payloads are hex.
+//
+// POST   /inbox/{address}       {"payload":"..."}  ->  {"id":0}
+// GET    /inbox/{address}                          ->  {"ids":[0,1]}
+// GET    /inbox/{address}/{id}                     ->  {"payload":"..."}, 404 if acked
+// DELETE /inbox/{address}/{id}  {"key":"..."}      ->  {}, 403 if the key is not the ack key
+//
+// The client does blocking io inside its futures, an async runtime should
+// drive it on a thread that may block.
+
+const MAX_HEAD: usize = 8 * 1024;
+const MAX_BODY: usize = 2 * MAX_PAYLOAD + 64;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        write!(out, "{:02x}", byte).unwrap();
+        out
+    })
+}
+
+pub(crate) fn unhex(hex: &str) -> Option<Vec<u8>> {
+    (hex.len() % 2 == 0).then_some(())?;
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// value of `key` in a flat json object written by this module
+pub(crate) fn value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let rest = &json[json.find(&format!("\"{}\":", key))? + key.len() + 3..];
+    match rest.strip_prefix('"') {
+        Some(rest) => rest.split('"').next(),
+        None => rest.split(|c: char| !c.is_ascii_digit()).next(),
+    }
+}
+
+// field elements are decimal in paths
+pub(crate) fn is_decimal(value: &str) -> bool {
+    !value.is_empty() && value.len() <= 100 && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+pub(crate) struct Message {
+    // request line or status line
+    pub(crate) first: String,
+    pub(crate) body: String,
+}
+
+impl Message {
+    fn read(reader: &mut impl BufRead) -> Option<Self> {
+        // the head is read through a bounded reader, a client can't make us
+        // buffer a line without end
+        let mut head = reader.by_ref().take(MAX_HEAD as u64);
+        let mut line = String::new();
+        let mut next = |line: &mut String| {
+            line.clear();
+            head.read_line(line).ok()?;
+            // a line cut by the bound or by the end of the stream
+            line.ends_with('\n').then_some(())?;
+            Some(line.trim_end().to_string())
+        };
+
+        let first = next(&mut line)?;
+        let mut len = 0;
+        loop {
+            let header = next(&mut line)?;
+            if header.is_empty() {
+                break;
+            }
+            let (name, value) = header.split_once(':')?;
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                len = value.trim().parse().ok()?;
+            }
+        }
+        (len <= MAX_BODY).then_some(())?;
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).ok()?;
+        Some(Self {
+            first,
+            body: String::from_utf8(body).ok()?,
+        })
+    }
+}
+
+pub struct RelayServer {
+    listener: TcpListener,
+}
+
+impl RelayServer {
+    pub fn bind(addr: SocketAddr) -> Result<Self, crate::Error> {
+        let listener =
+            TcpListener::bind(addr).map_err(|_| crate::Error::With("cannot bind relay"))?;
+        Ok(Self { listener })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, crate::Error> {
+        self.listener
+            .local_addr()
+            .map_err(|_| crate::Error::With("cannot bind relay"))
+    }
+
+    // serves requests, a misbehaving client doesn't stop the server
+    pub fn serve(&self, relay: &MemoryRelay) -> ! {
+        loop {
+            let _ = self.serve_one(relay);
+        }
+    }
+
+    pub fn serve_one(&self, relay: &MemoryRelay) -> Result<(), crate::Error> {
+        serve_one(&self.listener, |request| respond(relay, request))
+    }
+}
+
+// answers a single request of a client
+pub(crate) fn serve_one(
+    listener: &TcpListener,
+    respond: impl FnOnce(&Message) -> (u16, String),
+) -> Result<(), crate::Error> {
+    let io = |_| crate::Error::With("relay failed");
+    let (stream, _) = listener.accept().map_err(io)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(io)?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(io)?);
+    let (status, body) = match Message::read(&mut reader) {
+        Some(request) => respond(&request),
+        None => error(400, "bad request"),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        if status == 200 { "OK" } else { "Error" },
+        body.len(),
+        body
+    )
+    .map_err(io)
+}
+
+pub(crate) fn error(status: u16, message: &str) -> (u16, String) {
+    (status, format!("{{\"error\":\"{}\"}}", message))
+}
+
+fn respond(relay: &MemoryRelay, request: &Message) -> (u16, String) {
+    let mut parts = request.first.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return error(400, "bad request");
+    };
+    let segments = path.split('/').skip(1).collect::<Vec<_>>();
+    let result = match (method, segments.as_slice()) {
+        ("POST", ["inbox", to]) if is_decimal(to) => value(&request.body, "payload")
+            .and_then(unhex)
+            .ok_or(crate::Error::With("bad payload"))
+            .and_then(|payload| relay.push(to, &payload))
+            .map(|id| format!("{{\"id\":{}}}", id)),
+        ("GET", ["inbox", address]) if is_decimal(address) => relay.pending(address).map(|ids| {
+            let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+            format!("{{\"ids\":[{}]}}", ids.join(","))
+        }),
+        ("GET", ["inbox", address, id]) if is_decimal(address) => match id.parse() {
+            Ok(id) => match relay.get(address, id) {
+                Ok(Some(payload)) => Ok(format!("{{\"payload\":\"{}\"}}", hex(&payload))),
+                Ok(None) => return error(404, "not found"),
+                Err(err) => Err(err),
+            },
+            Err(_) => return error(400, "bad request"),
+        },
+        ("DELETE", ["inbox", address, id]) if is_decimal(address) => {
+            let key = value(&request.body, "key")
+                .and_then(unhex)
+                .and_then(|key| <[u8; 32]>::try_from(key).ok());
+            match (id.parse(), key) {
+                (Ok(id), Some(key)) => relay.remove(address, id, &key).map(|_| "{}".to_string()),
+                _ => return error(400, "bad request"),
+            }
+        }
+        _ => return error(404, "not found"),
+    };
+    match result {
+        Ok(body) => (200, body),
+        Err(crate::Error::With("bad ack key")) => error(403, "bad ack key"),
+        Err(crate::Error::With(message)) => error(400, message),
+    }
+}
+
+// `HttpTransfer` talks to a relay server
+#[derive(Clone, Debug)]
+pub struct HttpTransfer {
+    addr: SocketAddr,
+}
+
+impl HttpTransfer {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+
+    fn request(&self, method: &str, path: &str, body: &str) -> Result<String, crate::Error> {
+        request(&self.addr, method, path, body)
+    }
+}
+
+// sends a request and returns the body of a successful response
+pub(crate) fn request(
+    addr: &SocketAddr,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> Result<String, crate::Error> {
+    request_status(addr, method, path, body).and_then(|(status, body)| match status {
+        200 => Ok(body),
+        _ => Err(crate::Error::With("relay rejected the request")),
+    })
+}
+
+pub(crate) fn request_status(
+    addr: &SocketAddr,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> Result<(u16, String), crate::Error> {
+    let io = |_| crate::Error::With("relay failed");
+    let mut stream = TcpStream::connect_timeout(addr, TIMEOUT).map_err(io)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(io)?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    )
+    .map_err(io)?;
+    let response = Message::read(&mut BufReader::new(stream))
+        .ok_or(crate::Error::With("bad relay response"))?;
+    response
+        .first
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .map(|status| (status, response.body))
+        .ok_or(crate::Error::With("bad relay response"))
+}
+
+impl<F: PrimeField> TransferService<F> for HttpTransfer {
+    async fn send_note(&self, to: &Address<F>, payload: &[u8]) -> Result<u64, crate::Error> {
+        let path = format!("/inbox/{}", to.inner());
+        let body = self.request(
+            "POST",
+            &path,
+            &format!("{{\"payload\":\"{}\"}}", hex(payload)),
+        )?;
+        value(&body, "id")
+            .and_then(|id| id.parse().ok())
+            .ok_or(crate::Error::With("bad relay response"))
+    }
+
+    async fn poll_inbox(&self, address: &Address<F>) -> Result<Vec<u64>, crate::Error> {
+        let body = self.request("GET", &format!("/inbox/{}", address.inner()), "")?;
+        let ids = body
+            .strip_prefix("{\"ids\":[")
+            .and_then(|ids| ids.strip_suffix("]}"))
+            .ok_or(crate::Error::With("bad relay response"))?;
+        ids.split(',')
+            .filter(|id| !id.is_empty())
+            .map(|id| id.parse().ok())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(crate::Error::With("bad relay response"))
+    }
+
+    async fn fetch(&self, address: &Address<F>, id: u64) -> Result<Option<Vec<u8>>, crate::Error> {
+        let path = format!("/inbox/{}/{}", address.inner(), id);
+        match request_status(&self.addr, "GET", &path, "")? {
+            (200, body) => value(&body, "payload")
+                .and_then(unhex)
+                .map(Some)
+                .ok_or(crate::Error::With("bad relay response")),
+            (404, _) => Ok(None),
+            _ => Err(crate::Error::With("relay rejected the request")),
+        }
+    }
+
+    async fn ack(&self, address: &Address<F>, id: u64, key: &[u8; 32]) -> Result<(), crate::Error> {
+        let path = format!("/inbox/{}/{}", address.inner(), id);
+        let body = format!("{{\"key\":\"{}\"}}", hex(key));
+        self.request("DELETE", &path, &body).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        future::Future,
+        io::Cursor,
+        pin::pin,
+        task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    };
+
+    // the futures of the transfer services do their io inline and are ready
+    // at the first poll
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        fn raw() -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| {}, |_| {}, |_| {});
+        let waker = unsafe { Waker::from_raw(raw()) };
+        match pin!(future).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is not ready"),
+        }
+    }
+
+    #[test]
+    fn head_is_bounded() {
+        let request = "POST /inbox/1 HTTP/1.1\r\nContent-Length: 4\r\n\r\nbodyrest";
+        let message = Message::read(&mut Cursor::new(request)).unwrap();
+        assert_eq!(message.first, "POST /inbox/1 HTTP/1.1");
+        assert_eq!(message.body, "body");
+
+        // a header line without end is not buffered past the bound
+        let endless = std::io::repeat(b'a');
+        assert!(Message::read(&mut BufReader::new(endless)).is_none());
+        let long = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD));
+        assert!(Message::read(&mut Cursor::new(long)).is_none());
+        // nor is a head cut by the end of the stream taken as complete
+        assert!(Message::read(&mut Cursor::new("GET / HTTP/1.1\r\nX: a")).is_none());
+        // the bound is on the head only
+        let body = "b".repeat(MAX_HEAD + 1);
+        let request = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        assert_eq!(Message::read(&mut Cursor::new(request)).unwrap().body, body);
+    }
+
+    #[cfg(feature = "prover")]
+    #[test]
+    fn payload_round_trip_through_the_relay() {
+        use crate::{
+            asset::{Asset, Terms},
+            circuit::mock::{self, Mock},
+            codec::Codecs,
+            handshake::{negotiate, Hello},
+            note::NoteHistory,
+            payload::{seal, EncryptionKey},
+            poseidon::PoseidonConfigs,
+            wallet::CommReceiver,
+        };
+        use ark_bn254::Fr;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        // seals what it receives to the key of the receiver
+        struct Sealer {
+            address: Address<Fr>,
+            key: EncryptionKey<Mock>,
+            payloads: Vec<Vec<u8>>,
+        }
+
+        impl CommReceiver<Mock> for Sealer {
+            fn receive(&mut self, history: &NoteHistory<Mock>) -> Result<(), crate::Error> {
+                let agreement = negotiate(&Hello::default(), &Hello::default())?;
+                let mut rng = StdRng::seed_from_u64(2);
+                let payload = seal(history, &self.key, &Codecs::default(), &agreement, &mut rng)?;
+                self.payloads.push(payload);
+                Ok(())
+            }
+
+            fn address(&self) -> &Address<Fr> {
+                &self.address
+            }
+        }
+
+        let h = PoseidonConfigs::generate();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut alice = mock::wallet(&h, &mut rng);
+        let mut sealer = Sealer {
+            address: *alice.address(),
+            key: alice.encryption_key(),
+            payloads: vec![],
+        };
+        let asset = Asset::new(issuer.address(), &Terms::iou(1, 1));
+        issuer.issue(&mut rng, &mut sealer, &asset, 100).unwrap();
+
+        let relay = MemoryRelay::new();
+        let server = RelayServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let client = HttpTransfer::new(server.local_addr().unwrap());
+        let address = *alice.address();
+        std::thread::scope(|scope| {
+            // send, poll, fetch and ack, then poll the empty inbox
+            scope.spawn(|| {
+                for _ in 0..5 {
+                    server.serve_one(&relay).unwrap();
+                }
+            });
+            let id = ready(client.send_note(&address, &sealer.payloads[0])).unwrap();
+            assert_eq!(id, 0);
+            assert_eq!(ready(alice.receive_inbox(&client)).unwrap(), 1);
+            assert_eq!(
+                ready(client.poll_inbox(&address)).unwrap(),
+                Vec::<u64>::new()
+            );
+        });
+        assert_eq!(alice.spendables()[0].current_note.value, 100);
+
+        // the payload is gone and so is a fetch of it
+        assert_eq!(relay.pending(&address.inner().to_string()).unwrap(), vec![]);
+        assert_eq!(relay.get(&address.inner().to_string(), 0).unwrap(), None);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{circuit::IVC, wallet::Wallet, FWrap};
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::RwLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Read-only json api of a wallet for dashboards and accounting tools. It is
+// bound to loopback only and every request must carry the bearer token.
+//
+// GET /balances  value of the spendable notes per asset
+// GET /history   spendable notes and the sent notes if receipts are kept
+// GET /pending   installments of the subscriptions that are due now
+
+const MAX_HEAD: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct RestServer {
+    listener: TcpListener,
+    token: String,
+}
+
+impl RestServer {
+    pub fn bind(addr: SocketAddr, token: &str) -> Result<Self, crate::Error> {
+        addr.ip()
+            .is_loopback()
+            .then_some(())
+            .ok_or(crate::Error::With("rest api is local only"))?;
+        (!token.is_empty())
+            .then_some(())
+            .ok_or(crate::Error::With("empty api token"))?;
+        let listener =
+            TcpListener::bind(addr).map_err(|_| crate::Error::With("cannot bind rest api"))?;
+        Ok(Self {
+            listener,
+            token: token.to_string(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, crate::Error> {
+        self.listener
+            .local_addr()
+            .map_err(|_| crate::Error::With("cannot bind rest api"))
+    }
+
+    // serves requests until the listener fails, the wallet is locked only
+    // while a response is built
+    pub fn serve<E: IVC>(&self, wallet: &RwLock<Wallet<E>>) -> Result<(), crate::Error> {
+        loop {
+            let (stream, _) = self
+                .listener
+                .accept()
+                .map_err(|_| crate::Error::With("rest api failed"))?;
+            // a misbehaving client doesn't stop the server
+            let _ = self.handle(stream, |request| {
+                let wallet = wallet
+                    .read()
+                    .map_err(|_| crate::Error::With("wallet lock poisoned"))?;
+                Ok(respond(&wallet, &self.token, request, now()))
+            });
+        }
+    }
+
+    // serves a single request
+    pub fn serve_one<E: IVC>(&self, wallet: &Wallet<E>) -> Result<(), crate::Error> {
+        let (stream, _) = self
+            .listener
+            .accept()
+            .map_err(|_| crate::Error::With("rest api failed"))?;
+        self.handle(stream, |request| {
+            Ok(respond(wallet, &self.token, request, now()))
+        })
+    }
+
+    fn handle(
+        &self,
+        stream: TcpStream,
+        respond: impl FnOnce(&Request) -> Result<(u16, String), crate::Error>,
+    ) -> Result<(), crate::Error> {
+        let io = |_| crate::Error::With("rest api failed");
+        stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(io)?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(io)?);
+        let (status, body) = match Request::read(&mut reader) {
+            Some(request) => respond(&request)?,
+            None => error(400, "bad request"),
+        };
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason(status),
+            body.len(),
+            body
+        )
+        .map_err(io)
+    }
+}
+
+pub(crate) struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+}
+
+impl Request {
+    fn read(reader: &mut impl BufRead) -> Option<Self> {
+        // the head is read through a bounded reader, a client can't make us
+        // buffer a line without end
+        let mut head = reader.by_ref().take(MAX_HEAD as u64);
+        let mut line = String::new();
+        let mut next = |line: &mut String| {
+            line.clear();
+            head.read_line(line).ok()?;
+            // a line cut by the bound or by the end of the stream
+            line.ends_with('\n').then_some(())?;
+            Some(line.trim_end().to_string())
+        };
+
+        let first = next(&mut line)?;
+        let mut parts = first.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let path = target.split('?').next()?.to_string();
+
+        let mut token = None;
+        loop {
+            let header = next(&mut line)?;
+            if header.is_empty() {
+                break;
+            }
+            let (name, value) = header.split_once(':')?;
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                token = value
+                    .trim()
+                    .strip_prefix("Bearer ")
+                    .map(|token| token.trim().to_string());
+            }
+        }
+
+        Some(Self {
+            method,
+            path,
+            token,
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// compares without exiting at the first mismatch
+fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, String) {
+    (status, format!("{{\"error\":\"{}\"}}", message))
+}
+
+pub(crate) fn respond<E: IVC>(
+    wallet: &Wallet<E>,
+    token: &str,
+    request: &Request,
+    now: u64,
+) -> (u16, String) {
+    match &request.token {
+        Some(given) if token_eq(given, token) => {}
+        _ => return error(401, "unauthorized"),
+    }
+    if request.method != "GET" {
+        return error(405, "method not allowed");
+    }
+    match request.path.as_str() {
+        "/balances" => (200, balances(wallet)),
+        "/history" => (200, history(wallet)),
+        "/pending" => (200, pending(wallet, now)),
+        _ => error(404, "not found"),
+    }
+}
+
+fn balances<E: IVC>(wallet: &Wallet<E>) -> String {
+    let mut out = String::from("{\"balances\":[");
+    for (i, (asset_hash, value)) in wallet.balances().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(
+            out,
+            "{{\"asset\":\"{}\",\"value\":{}}}",
+            asset_hash.inner(),
+            value
+        )
+        .unwrap();
+    }
+    out.push_str("]}");
+    out
+}
+
+fn history<E: IVC>(wallet: &Wallet<E>) -> String {
+    let mut out = String::from("{\"notes\":[");
+    for (i, note_history) in wallet.spendables().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let state = note_history
+            .steps
+            .last()
+            .map(|step| step.state.inner().to_string())
+            .unwrap_or_default();
+        let remaining = note_history
+            .remaining_transfers()
+            .map_or("null".to_string(), |remaining| remaining.to_string());
+        write!(
+            out,
+            "{{\"asset\":\"{}\",\"value\":{},\"steps\":{},\"state\":\"{}\",\"remaining\":{}}}",
+            note_history.current_note.asset_hash.inner(),
+            note_history.current_note.value,
+            note_history.steps.len(),
+            state,
+            remaining
+        )
+        .unwrap();
+    }
+    out.push_str("],\"sent\":[");
+    for (i, receipt) in wallet.receipts().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(
+            out,
+            "{{\"asset\":\"{}\",\"receiver\":\"{}\",\"value\":{}}}",
+            receipt.evidence().transfer().asset_hash.inner(),
+            receipt.receiver().inner(),
+            receipt.value()
+        )
+        .unwrap();
+    }
+    out.push_str("]}");
+    out
+}
+
+fn pending<E: IVC>(wallet: &Wallet<E>, now: u64) -> String {
+    let mut out = String::from("{\"installments\":[");
+    for (i, signed) in wallet.due(now).iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let authorization = signed.authorization();
+        write!(
+            out,
+            "{{\"asset\":\"{}\",\"payee\":\"{}\",\"cap\":{},\"period\":{}}}",
+            authorization.asset_hash.inner(),
+            authorization.payee.inner(),
+            authorization.cap,
+            authorization.period_at(now).unwrap_or_default()
+        )
+        .unwrap();
+    }
+    out.push_str("]}");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn rest_api_is_local_only() {
+        assert_eq!(
+            RestServer::bind("0.0.0.0:0".parse().unwrap(), "token")
+                .map(|_| ())
+                .unwrap_err(),
+            crate::Error::With("rest api is local only")
+        );
+        assert_eq!(
+            RestServer::bind("127.0.0.1:0".parse().unwrap(), "")
+                .map(|_| ())
+                .unwrap_err(),
+            crate::Error::With("empty api token")
+        );
+        let server = RestServer::bind("127.0.0.1:0".parse().unwrap(), "token").unwrap();
+        assert!(server.local_addr().unwrap().ip().is_loopback());
+    }
+
+    #[test]
+    fn head_is_bounded() {
+        let request = "GET /balances?x=1 HTTP/1.1\r\nAuthorization: Bearer t\r\n\r\n";
+        let request = Request::read(&mut Cursor::new(request)).unwrap();
+        assert_eq!(request.path, "/balances");
+        assert_eq!(request.token.as_deref(), Some("t"));
+
+        let endless = std::io::repeat(b'a');
+        assert!(Request::read(&mut BufReader::new(endless)).is_none());
+        let long = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD));
+        assert!(Request::read(&mut Cursor::new(long)).is_none());
+        assert!(Request::read(&mut Cursor::new("GET / HTTP/1.1\r\n")).is_none());
+    }
+
+    #[cfg(feature = "prover")]
+    #[test]
+    fn requests_need_the_token() {
+        use crate::{circuit::mock, poseidon::PoseidonConfigs};
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let h = PoseidonConfigs::generate();
+        let wallet = mock::wallet(&h, &mut StdRng::seed_from_u64(1));
+        let server = RestServer::bind("127.0.0.1:0".parse().unwrap(), "secret").unwrap();
+        let addr = server.local_addr().unwrap();
+        let get = move |path: &str, token: Option<&str>| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let authorization = token
+                .map(|token| format!("Authorization: Bearer {}\r\n", token))
+                .unwrap_or_default();
+            write!(stream, "GET {} HTTP/1.1\r\n{}\r\n", path, authorization).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+            let body = response.split("\r\n\r\n").nth(1).unwrap().to_string();
+            (status, body)
+        };
+
+        let requests = [
+            ("/balances", None),
+            ("/balances", Some("secreT")),
+            ("/balances", Some("secret2")),
+            ("/balances", Some("secret")),
+            ("/nothing", Some("secret")),
+        ];
+        // the wallet stays on this thread, the client runs on another
+        let client = std::thread::spawn(move || {
+            requests
+                .iter()
+                .map(|(path, token)| get(path, *token))
+                .collect::<Vec<(u16, String)>>()
+        });
+        for _ in requests {
+            server.serve_one(&wallet).unwrap();
+        }
+        let responses = client.join().unwrap();
+        let unauthorized = (401, "{\"error\":\"unauthorized\"}".to_string());
+        assert_eq!(responses[0], unauthorized);
+        assert_eq!(responses[1], unauthorized);
+        assert_eq!(responses[2], unauthorized);
+        assert_eq!(responses[3], (200, "{\"balances\":[]}".to_string()));
+        assert_eq!(responses[4].0, 404);
+    }
+}
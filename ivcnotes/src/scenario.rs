@@ -0,0 +1,325 @@
+use crate::{
+    circuit::{profile::Profile, Circuit, Verifier, IVC},
+    note::NoteHistory,
+    poseidon::PoseidonConfigs,
+    reference::{self, Actors},
+    wire,
+};
+use ark_crypto_primitives::snark::SNARK;
+#[cfg(feature = "batch")]
+use ark_crypto_primitives::sponge::Absorb;
+#[cfg(feature = "batch")]
+use ark_ec::pairing::Pairing;
+#[cfg(feature = "batch")]
+use ark_groth16::Groth16;
+use ark_serialize::CanonicalSerialize;
+use rand::{rngs::StdRng, SeedableRng};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// Benchmark scenarios. The same wallet flows run on every instantiation of
+// `IVC` and prover backend that is compiled in, from a fixed seed so that keys,
+// notes and proofs are the same on every run. Reports of different configs
+// compare on the same machine since times are wall clock. Memory is the peak
+// resident size of the process as linux reports it, the peak is reset before
+// each run where the kernel allows it.
+//
+// - setup: circuit specific setup of the step circuit
+// - issue: the issuer issues a note to alice
+// - split: alice splits the note to bob, proving and receiving
+// - chain: the note goes back and forth, time of a hop as the history grows
+// - verify: the last history is verified from scratch
+// - batch: with the `batch` feature, the notes both wallets hold after the
+//   chain are verified in one batch, steps they share are checked once
+// - single: the same notes verified one by one
+
+// hops of the chain scenario
+pub const CHAIN: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Sequential,
+    // prover on a rayon pool of the given threads
+    #[cfg(feature = "parallel")]
+    Rayon(usize),
+}
+
+impl Backend {
+    // backends compiled in
+    pub fn available() -> Vec<Self> {
+        #[allow(unused_mut)]
+        let mut backends = vec![Self::Sequential];
+        #[cfg(feature = "parallel")]
+        backends.push(Self::Rayon(
+            std::thread::available_parallelism().map_or(1, |n| n.get()),
+        ));
+        backends
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sequential => "sequential",
+            #[cfg(feature = "parallel")]
+            Self::Rayon(_) => "rayon",
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        match self {
+            Self::Sequential => 1,
+            #[cfg(feature = "parallel")]
+            Self::Rayon(threads) => *threads,
+        }
+    }
+
+    fn install<T: Send>(&self, f: impl FnOnce() -> T + Send) -> Result<T, crate::Error> {
+        #[cfg(feature = "parallel")]
+        {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(self.threads())
+                .build()
+                .map(|pool| pool.install(f))
+                .map_err(|_| crate::Error::With("cannot build thread pool"))
+        }
+        #[cfg(not(feature = "parallel"))]
+        Ok(f())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timing {
+    pub scenario: &'static str,
+    pub time: Duration,
+}
+
+impl Timing {
+    fn new(scenario: &'static str, time: Duration) -> Self {
+        Self { scenario, time }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    pub instantiation: &'static str,
+    pub backend: Backend,
+    pub constraints: usize,
+    pub proving_key_size: usize,
+    pub verifying_key_size: usize,
+    pub proof_size: usize,
+    // steps and wire size of the last history of the chain
+    pub history_len: usize,
+    pub history_size: usize,
+    // in bytes, `None` where the platform doesn't report it
+    pub peak_memory: Option<u64>,
+    pub timings: Vec<Timing>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub seed: u64,
+    pub runs: Vec<Run>,
+}
+
+impl Report {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, runs: vec![] }
+    }
+
+    // runs the scenarios of `E` on every available backend
+    pub fn add<E: IVC>(&mut self, instantiation: &'static str) -> Result<(), crate::Error>
+    where
+        <<E as IVC>::Snark as SNARK<E::Field>>::ProcessedVerifyingKey: Send,
+    {
+        for backend in Backend::available() {
+            let seed = self.seed;
+            let run = backend.install(|| run::<E>(instantiation, backend, seed))??;
+            self.runs.push(run);
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write!(out, "{{\"seed\":{},\"runs\":[", self.seed).unwrap();
+        for (i, run) in self.runs.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let peak_memory = run
+                .peak_memory
+                .map_or("null".to_string(), |bytes| bytes.to_string());
+            write!(
+                out,
+                "{{\"instantiation\":\"{}\",\"backend\":\"{}\",\"threads\":{},\"constraints\":{},\"proving_key_size\":{},\"verifying_key_size\":{},\"proof_size\":{},\"history_len\":{},\"history_size\":{},\"peak_memory\":{},\"timings\":{{",
+                run.instantiation,
+                run.backend.name(),
+                run.backend.threads(),
+                run.constraints,
+                run.proving_key_size,
+                run.verifying_key_size,
+                run.proof_size,
+                run.history_len,
+                run.history_size,
+                peak_memory,
+            )
+            .unwrap();
+            for (j, timing) in run.timings.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                write!(
+                    out,
+                    "\"{}\":{:.3}",
+                    timing.scenario,
+                    timing.time.as_secs_f64() * 1e3
+                )
+                .unwrap();
+            }
+            out.push_str("}}");
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+// runs the scenarios of `E` on the current thread pool
+pub fn run<E: IVC>(
+    instantiation: &'static str,
+    backend: Backend,
+    seed: u64,
+) -> Result<Run, crate::Error>
+where
+    <<E as IVC>::Snark as SNARK<E::Field>>::ProcessedVerifyingKey: Send,
+{
+    // resets the peak resident size, ignored where it is not supported
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+    let mut rng = StdRng::seed_from_u64(seed);
+    let h = PoseidonConfigs::<E::Field>::generate();
+    let mut timings = vec![];
+    let err = |_| crate::Error::With("setup failed");
+
+    let start = Instant::now();
+    let (pk, vk) =
+        E::Snark::circuit_specific_setup(Circuit::<E>::empty(&h), &mut rng).map_err(err)?;
+    timings.push(Timing::new("setup", start.elapsed()));
+    let (_, history) = flow(&h, &pk, &vk, &mut rng, &mut timings)?;
+    let verifier = Verifier::<E>::new(vk.clone());
+    let start = Instant::now();
+    verifier.verify_history_parallel(&h, &history, backend.threads())?;
+    timings.push(Timing::new("verify", start.elapsed()));
+
+    let proof_size = history
+        .steps
+        .last()
+        .map(|step| step.proof.compressed_size())
+        .unwrap_or_default();
+    Ok(Run {
+        instantiation,
+        backend,
+        constraints: Profile::generate(Circuit::<E>::empty(&h))
+            .map(|profile| profile.num_constraints)
+            .unwrap_or_default(),
+        proving_key_size: pk.compressed_size(),
+        verifying_key_size: vk.compressed_size(),
+        proof_size,
+        history_len: history.steps.len(),
+        history_size: wire::encode(&history).len(),
+        peak_memory: peak_memory(),
+        timings,
+    })
+}
+
+// issue, split and chain scenarios, returns the wallets and the last history
+// of the chain
+fn flow<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    pk: &<<E as IVC>::Snark as SNARK<E::Field>>::ProvingKey,
+    vk: &<<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey,
+    rng: &mut StdRng,
+    timings: &mut Vec<Timing>,
+) -> Result<(Actors<E>, NoteHistory<E>), crate::Error> {
+    let mut actors = Actors::<E>::new(h, pk, vk, rng)?;
+    let asset = reference::test_asset(&actors.issuer);
+    let Actors { issuer, alice, bob } = &mut actors;
+
+    let start = Instant::now();
+    issuer.issue(rng, alice, &asset, reference::ISSUE_VALUE)?;
+    timings.push(Timing::new("issue", start.elapsed()));
+
+    let start = Instant::now();
+    alice.split(rng, bob, 0, reference::SPLIT_VALUE)?;
+    timings.push(Timing::new("split", start.elapsed()));
+
+    // the holder sends a unit of the note it received last to the other
+    let start = Instant::now();
+    let (mut from, mut to) = (bob, alice);
+    for _ in 0..CHAIN {
+        let index = from.spendables().len() - 1;
+        from.split(rng, to, index, 1)?;
+        (from, to) = (to, from);
+    }
+    timings.push(Timing::new("chain", start.elapsed() / CHAIN as u32));
+
+    let history = from
+        .spendables()
+        .last()
+        .ok_or(crate::Error::With("note is not received"))?
+        .clone();
+    Ok((actors, history))
+}
+
+// Verifies the notes the wallets hold after the chain in one batch and one by
+// one, on the current thread. The timings are added to the runs of the
+// instantiation.
+#[cfg(feature = "batch")]
+impl Report {
+    pub fn add_batch<P, E>(&mut self, instantiation: &'static str) -> Result<(), crate::Error>
+    where
+        P: Pairing,
+        P::ScalarField: Absorb,
+        E: IVC<Snark = Groth16<P>, Field = P::ScalarField>,
+    {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let h = PoseidonConfigs::<E::Field>::generate();
+        let (pk, vk) = E::Snark::circuit_specific_setup(Circuit::<E>::empty(&h), &mut rng)
+            .map_err(|_| crate::Error::With("setup failed"))?;
+        let (actors, _) = flow(&h, &pk, &vk, &mut rng, &mut vec![])?;
+        let histories = actors
+            .alice
+            .spendables()
+            .iter()
+            .chain(actors.bob.spendables().iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let verifier = Verifier::<E>::new(vk);
+        verifier.prepared()?;
+
+        let start = Instant::now();
+        verifier.verify_batch(&h, &histories, &mut rng)?;
+        let batch = Timing::new("batch", start.elapsed());
+        let start = Instant::now();
+        for history in histories.iter() {
+            verifier.verify_history_parallel(&h, history, 1)?;
+        }
+        let single = Timing::new("single", start.elapsed());
+
+        self.runs
+            .iter_mut()
+            .filter(|run| run.instantiation == instantiation)
+            .for_each(|run| run.timings.extend([batch.clone(), single.clone()]));
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Address, AssetHash};
+use ark_ff::PrimeField;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Clear,
+    Hit,
+}
+
+// what to do when a provider reports a hit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HitAction {
+    // fail the operation
+    Reject,
+    // let the operation through and record the hit
+    Flag,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Issue,
+    Receive,
+}
+
+// a screening hit that is let through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flag<F: PrimeField> {
+    pub operation: Operation,
+    pub asset_hash: AssetHash<F>,
+    pub address: Address<F>,
+}
+
+// `ScreeningProvider` is implemented by integrators to plug their compliance
+// vendors in. It is only given the data that is public to the wallet anyway.
+pub trait ScreeningProvider<F: PrimeField> {
+    fn screen(
+        &self,
+        operation: Operation,
+        asset_hash: &AssetHash<F>,
+        address: &Address<F>,
+    ) -> Verdict;
+}
+
+pub(crate) struct Screening<F: PrimeField> {
+    provider: Box<dyn ScreeningProvider<F>>,
+    action: HitAction,
+    flags: Vec<Flag<F>>,
+}
+
+impl<F: PrimeField> Screening<F> {
+    pub(crate) fn new(provider: Box<dyn ScreeningProvider<F>>, action: HitAction) -> Self {
+        Self {
+            provider,
+            action,
+            flags: vec![],
+        }
+    }
+
+    pub(crate) fn flags(&self) -> &[Flag<F>] {
+        &self.flags
+    }
+
+    pub(crate) fn screen<'a>(
+        &mut self,
+        operation: Operation,
+        asset_hash: &AssetHash<F>,
+        addresses: impl IntoIterator<Item = &'a Address<F>>,
+    ) -> Result<(), crate::Error> {
+        for address in addresses {
+            if self.provider.screen(operation, asset_hash, address) == Verdict::Hit {
+                match self.action {
+                    HitAction::Reject => return Err(crate::Error::With("screening hit")),
+                    HitAction::Flag => self.flags.push(Flag {
+                        operation,
+                        asset_hash: *asset_hash,
+                        address: *address,
+                    }),
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{circuit::IVC, poseidon::PoseidonConfigs};
+use ark_crypto_primitives::sponge::{poseidon::PoseidonConfig, Absorb};
+use ark_ec::CurveConfig;
+use ark_ff::PrimeField;
+use std::marker::PhantomData;
+
+// Security level an `IVC` config claims. The pieces the crate can see are
+// checked against it at compile time when a prover or a verifier of the config
+// is built, and the poseidon configs with `PoseidonConfigs::check_security`.
+//
+// - note hashes, states and nullifiers are single elements of the application
+//   field, collisions cost about half of its size
+// - signatures and payload keys rest on discrete log on the inner curve
+// - 64 bit values summed over the outputs must not wrap around in the field
+//
+// The pairing curve of the snark is not visible from the `SNARK` trait.
+// BN254 gives about 100 bits, a 128 bit deployment has to pick its snark
+// accordingly, eg. over BLS12-381.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecurityLevel {
+    Bits100,
+    Bits128,
+}
+
+impl SecurityLevel {
+    pub const fn bits(&self) -> u32 {
+        match self {
+            Self::Bits100 => 100,
+            Self::Bits128 => 128,
+        }
+    }
+
+    // smallest application field and inner curve group, in bits, for the
+    // level, generic attacks cost about the square root
+    pub const fn min_group_bits(&self) -> u32 {
+        match self {
+            Self::Bits100 => 200,
+            Self::Bits128 => 250,
+        }
+    }
+}
+
+// bits of the value range checks in the circuit
+pub const VALUE_BITS: u32 = 64;
+
+// partial rounds of poseidon with x^5 over 254 bit fields by the width, at
+// least 8 full rounds. These are the 128 bit numbers, there is no reduced table
+// for 100 bits so both levels require them.
+pub(crate) const PARTIAL_ROUNDS: [usize; 16] = [
+    56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
+];
+pub(crate) const FULL_ROUNDS: usize = 8;
+
+pub(crate) struct Checked<E>(PhantomData<E>);
+
+impl<E: IVC> Checked<E> {
+    // evaluated when a prover or a verifier of `E` is built
+    pub(crate) const OK: bool = {
+        let min = E::SECURITY.min_group_bits();
+        assert!(
+            E::Field::MODULUS_BIT_SIZE >= min,
+            "application field is too small for the security level"
+        );
+        assert!(
+            <<E::TE as CurveConfig>::ScalarField as PrimeField>::MODULUS_BIT_SIZE >= min,
+            "inner curve is too small for the security level"
+        );
+        assert!(
+            VALUE_BITS + usize::BITS - E::OUTPUTS.leading_zeros() < E::Field::MODULUS_BIT_SIZE,
+            "value range checks can wrap around in the field"
+        );
+        true
+    };
+}
+
+fn check_poseidon<F: PrimeField>(config: &PoseidonConfig<F>) -> bool {
+    let width = config.rate + config.capacity;
+    config.alpha == 5
+        && config.full_rounds >= FULL_ROUNDS
+        && (2..PARTIAL_ROUNDS.len() + 2).contains(&width)
+        && config.partial_rounds >= PARTIAL_ROUNDS[width - 2]
+}
+
+impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
+    // every config must use x^5 and at least the rounds of the table for its width
+    pub fn check_security(&self) -> Result<(), crate::Error> {
+        [
+            &self.id,
+            &self.note,
+            &self.blind,
+            &self.state,
+            &self.nullifier,
+            &self.tx,
+            &self.eddsa,
+        ]
+        .into_iter()
+        .all(check_poseidon)
+        .then_some(())
+        .ok_or(crate::Error::With(
+            "poseidon config below the security level",
+        ))
+    }
+}
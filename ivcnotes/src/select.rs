@@ -0,0 +1,216 @@
+use crate::{circuit::IVC, note::NoteHistory};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlannedSplit {
+    // index of the spendable note history in the wallet
+    pub index: usize,
+    // value to send out of this note
+    pub value: u64,
+}
+
+pub trait CoinSelector<E: IVC> {
+    // plan the splits that together pay `value`
+    fn plan(
+        &self,
+        spendables: &[NoteHistory<E>],
+        value: u64,
+    ) -> Result<Vec<PlannedSplit>, crate::Error>;
+}
+
+// `MinDepth` picks notes so that the longest history produced by the payment
+// is as short as possible. Every split extends the history of the spent note
+// by one step, so we look for the smallest depth bound that can still cover
+// the payment and then use as few notes as possible under that bound.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MinDepth;
+
+impl<E: IVC> CoinSelector<E> for MinDepth {
+    fn plan(
+        &self,
+        spendables: &[NoteHistory<E>],
+        value: u64,
+    ) -> Result<Vec<PlannedSplit>, crate::Error> {
+        if value == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut candidates: Vec<(usize, usize, u64)> = spendables
+            .iter()
+            .enumerate()
+            .filter(|(_, history)| history.current_note.value > 0)
+            .map(|(index, history)| (index, history.steps.len(), history.current_note.value))
+            .collect();
+
+        // find the smallest depth bound that covers the payment
+        candidates.sort_by_key(|(_, depth, _)| *depth);
+        let mut covered = 0u64;
+        let bound = candidates
+            .iter()
+            .find(|(_, _, note_value)| {
+                covered = covered.saturating_add(*note_value);
+                covered >= value
+            })
+            .map(|(_, depth, _)| *depth)
+            .ok_or(crate::Error::With("insufficient funds"))?;
+
+        // under the bound prefer larger notes to keep the number of splits low
+        candidates.retain(|(_, depth, _)| *depth <= bound);
+        candidates.sort_by(|a, b| b.2.cmp(&a.2).then(a.1.cmp(&b.1)));
+
+        let mut remaining = value;
+        let mut plan = vec![];
+        for (index, _, note_value) in candidates {
+            if remaining == 0 {
+                break;
+            }
+            let value = note_value.min(remaining);
+            plan.push(PlannedSplit { index, value });
+            remaining -= value;
+        }
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::{Asset, Terms},
+        note::{IVCStep, Note, NoteOutIndex},
+    };
+    use ark_ff::Zero;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[derive(Clone)]
+    struct Config;
+
+    impl IVC for Config {
+        type Snark = ark_groth16::Groth16<ark_bn254::Bn254>;
+        type Field = ark_bn254::Fr;
+        type TE = ark_ed_on_bn254::EdwardsConfig;
+    }
+
+    // rounds of the simulation, each on a fresh random wallet
+    const ROUNDS: usize = 2000;
+
+    // history of `depth` steps ending in a note of `value`, the selector
+    // looks at nothing else so proofs and hashes are left empty
+    fn history(depth: usize, value: u64) -> NoteHistory<Config> {
+        let asset = Asset {
+            issuer: Default::default(),
+            terms: Terms::iou(0, 1),
+            policy: Default::default(),
+            issuer_key: Default::default(),
+            dual_control: false,
+        };
+        let step = IVCStep::new(
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+        );
+        let note = Note::new(
+            &Default::default(),
+            &Default::default(),
+            value,
+            depth as u32,
+            &NoteOutIndex::RECEIVER,
+            &Default::default(),
+            ark_bn254::Fr::zero().into(),
+        );
+        NoteHistory {
+            asset,
+            steps: vec![step; depth],
+            current_note: note,
+            siblings: vec![Default::default(); Config::OUTPUTS - 1],
+            memo: vec![],
+            unknown: vec![],
+        }
+    }
+
+    // up to 16 notes of depth 1 to 10, some of them empty
+    fn wallet(rng: &mut StdRng) -> Vec<NoteHistory<Config>> {
+        (0..rng.gen_range(0..=16))
+            .map(|_| {
+                let value = match rng.gen_bool(0.1) {
+                    true => 0,
+                    false => rng.gen_range(1..=1000),
+                };
+                history(rng.gen_range(1..=10), value)
+            })
+            .collect()
+    }
+
+    fn total(spendables: &[NoteHistory<Config>]) -> u64 {
+        spendables.iter().map(|h| h.current_note.value).sum()
+    }
+
+    // smallest depth such that the notes up to it cover the value, by brute force
+    fn min_bound(spendables: &[NoteHistory<Config>], value: u64) -> Option<usize> {
+        (1..=10).find(|bound| {
+            let covered: u64 = spendables
+                .iter()
+                .filter(|h| h.steps.len() <= *bound)
+                .map(|h| h.current_note.value)
+                .sum();
+            covered >= value
+        })
+    }
+
+    #[test]
+    fn min_depth_covers_the_payment() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..ROUNDS {
+            let spendables = wallet(&mut rng);
+            let value = rng.gen_range(0..=total(&spendables));
+            let plan = MinDepth.plan(&spendables, value).unwrap();
+
+            assert_eq!(plan.iter().map(|split| split.value).sum::<u64>(), value);
+            let mut indices = plan.iter().map(|split| split.index).collect::<Vec<_>>();
+            indices.sort_unstable();
+            indices.dedup();
+            assert_eq!(indices.len(), plan.len());
+            for split in plan.iter() {
+                let note_value = spendables[split.index].current_note.value;
+                assert!(split.value > 0 && split.value <= note_value);
+            }
+        }
+    }
+
+    #[test]
+    fn min_depth_picks_the_shallowest_notes() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..ROUNDS {
+            let spendables = wallet(&mut rng);
+            let value = rng.gen_range(1..=total(&spendables).max(1));
+            let Some(bound) = min_bound(&spendables, value) else {
+                continue;
+            };
+            let plan = MinDepth.plan(&spendables, value).unwrap();
+
+            let depth = plan
+                .iter()
+                .map(|split| spendables[split.index].steps.len())
+                .max();
+            assert_eq!(depth, Some(bound));
+            // every note but the last one is spent in full
+            for split in plan[..plan.len() - 1].iter() {
+                assert_eq!(split.value, spendables[split.index].current_note.value);
+            }
+        }
+    }
+
+    #[test]
+    fn min_depth_fails_on_insufficient_funds() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..ROUNDS {
+            let spendables = wallet(&mut rng);
+            let value = total(&spendables) + rng.gen_range(1..=1000);
+            assert!(matches!(
+                MinDepth.plan(&spendables, value),
+                Err(crate::Error::With("insufficient funds"))
+            ));
+        }
+    }
+}
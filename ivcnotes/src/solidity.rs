@@ -0,0 +1,293 @@
+use crate::{
+    burn::BurnRecord,
+    circuit::{abi, inputs::PublicInput, IVC},
+    poseidon::PoseidonConfigs,
+};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_groth16::{Groth16, Proof, VerifyingKey};
+use std::fmt::Write;
+
+// Settlement of redeemed notes on EVM chains. The exported contract verifies
+// a step proof of Groth16 over BN254 with the pairing precompiles, a
+// settlement contract verifies every proof of a burn record with it and
+// checks that the states chain as `BurnRecord::verify` does.
+//
+// verifyProof(uint256[2] a, uint256[2][2] b, uint256[2] c, uint256[] input)
+//
+// Points are affine with the point at infinity as zeros, G2 coordinates are
+// given with the imaginary part first as the precompiles expect. Public
+// inputs are in the order of the circuit abi.
+
+// first four bytes of keccak256 of the signature of `verifyProof`
+pub const VERIFY_SELECTOR: [u8; 4] = [0xc3, 0x2e, 0x37, 0x0e];
+
+fn uint<F: PrimeField>(value: &F) -> String {
+    value.into_bigint().to_string()
+}
+
+fn g1(point: &G1Affine) -> [Fq; 2] {
+    point.xy().map(|(x, y)| [*x, *y]).unwrap_or([Fq::zero(); 2])
+}
+
+fn g2(point: &G2Affine) -> [Fq; 4] {
+    let (x, y) = point.xy().map(|(x, y)| (*x, *y)).unwrap_or_default();
+    let [x, y]: [Fq2; 2] = [x, y];
+    [x.c1, x.c0, y.c1, y.c0]
+}
+
+// source of a verifier contract of the key
+pub fn export_solidity_verifier(vk: &VerifyingKey<Bn254>) -> String {
+    let inputs = vk.gamma_abc_g1.len() - 1;
+    let mut out = String::new();
+    writeln!(out, "// SPDX-License-Identifier: MIT OR Apache-2.0").unwrap();
+    writeln!(out, "pragma solidity ^0.8.0;\n").unwrap();
+    writeln!(
+        out,
+        "// Groth16 verifier of ivcnotes steps over BN254, circuit abi version {}.",
+        abi::VERSION
+    )
+    .unwrap();
+    writeln!(out, "contract IvcnotesVerifier {{").unwrap();
+    writeln!(out, "    uint256 constant R = {};", Fr::MODULUS).unwrap();
+    writeln!(out, "    uint256 constant Q = {};", Fq::MODULUS).unwrap();
+    writeln!(out, "    uint256 constant INPUTS = {};\n", inputs).unwrap();
+
+    let [x, y] = g1(&vk.alpha_g1);
+    writeln!(out, "    uint256 constant ALPHA_X = {};", uint(&x)).unwrap();
+    writeln!(out, "    uint256 constant ALPHA_Y = {};", uint(&y)).unwrap();
+    for (name, point) in [
+        ("BETA", &vk.beta_g2),
+        ("GAMMA", &vk.gamma_g2),
+        ("DELTA", &vk.delta_g2),
+    ] {
+        let coordinates = g2(point);
+        for (suffix, value) in ["X1", "X0", "Y1", "Y0"].iter().zip(coordinates.iter()) {
+            writeln!(
+                out,
+                "    uint256 constant {}_{} = {};",
+                name,
+                suffix,
+                uint(value)
+            )
+            .unwrap();
+        }
+    }
+
+    writeln!(
+        out,
+        "\n    function ic() internal pure returns (uint256[{}] memory) {{",
+        2 * (inputs + 1)
+    )
+    .unwrap();
+    let ic = vk
+        .gamma_abc_g1
+        .iter()
+        .flat_map(g1)
+        .map(|value| format!("uint256({})", uint(&value)))
+        .collect::<Vec<_>>();
+    writeln!(
+        out,
+        "        return [\n            {}\n        ];",
+        ic.join(",\n            ")
+    )
+    .unwrap();
+    writeln!(out, "    }}").unwrap();
+
+    // solidity takes only literals as lengths of memory arrays
+    out.push_str(&CONTRACT_BODY.replace("POINTS", &(2 * (inputs + 1)).to_string()));
+    out
+}
+
+const CONTRACT_BODY: &str = r#"
+    function add(uint256[2] memory p, uint256[2] memory q) internal view returns (uint256[2] memory r) {
+        (bool ok, bytes memory out) = address(6).staticcall(abi.encode(p[0], p[1], q[0], q[1]));
+        require(ok, "ec add failed");
+        (r[0], r[1]) = abi.decode(out, (uint256, uint256));
+    }
+
+    function mul(uint256[2] memory p, uint256 s) internal view returns (uint256[2] memory r) {
+        (bool ok, bytes memory out) = address(7).staticcall(abi.encode(p[0], p[1], s));
+        require(ok, "ec mul failed");
+        (r[0], r[1]) = abi.decode(out, (uint256, uint256));
+    }
+
+    function verifyProof(
+        uint256[2] calldata a,
+        uint256[2][2] calldata b,
+        uint256[2] calldata c,
+        uint256[] calldata input
+    ) public view returns (bool) {
+        require(input.length == INPUTS, "bad number of inputs");
+        require(a[1] < Q, "bad proof");
+        uint256[POINTS] memory points = ic();
+        uint256[2] memory x = [points[0], points[1]];
+        for (uint256 i = 0; i < INPUTS; i++) {
+            require(input[i] < R, "input is not in the field");
+            x = add(x, mul([points[2 * i + 2], points[2 * i + 3]], input[i]));
+        }
+
+        // e(-a, b) e(alpha, beta) e(x, gamma) e(c, delta) == 1
+        uint256[24] memory p = [
+            a[0], (Q - a[1]) % Q, b[0][0], b[0][1], b[1][0], b[1][1],
+            ALPHA_X, ALPHA_Y, BETA_X1, BETA_X0, BETA_Y1, BETA_Y0,
+            x[0], x[1], GAMMA_X1, GAMMA_X0, GAMMA_Y1, GAMMA_Y0,
+            c[0], c[1], DELTA_X1, DELTA_X0, DELTA_Y1, DELTA_Y0
+        ];
+        (bool ok, bytes memory out) = address(8).staticcall(abi.encodePacked(p));
+        return ok && out.length == 32 && abi.decode(out, (uint256)) == 1;
+    }
+}
+"#;
+
+fn word<F: PrimeField>(out: &mut Vec<u8>, value: &F) {
+    let bytes = value.into_bigint().to_bytes_be();
+    out.extend(std::iter::repeat(0).take(32 - bytes.len()));
+    out.extend_from_slice(&bytes);
+}
+
+// public inputs as abi encoded uint256 words
+pub fn pack_public_inputs(inputs: &[Fr]) -> Vec<u8> {
+    let mut out = vec![];
+    inputs.iter().for_each(|input| word(&mut out, input));
+    out
+}
+
+// calldata of `verifyProof` for a proof and its public input
+pub fn calldata(proof: &Proof<Bn254>, public_input: &PublicInput<Fr>) -> Vec<u8> {
+    let inputs = public_input.to_verifier();
+    let mut out = VERIFY_SELECTOR.to_vec();
+    g1(&proof.a).iter().for_each(|value| word(&mut out, value));
+    g2(&proof.b).iter().for_each(|value| word(&mut out, value));
+    g1(&proof.c).iter().for_each(|value| word(&mut out, value));
+    // offset of the dynamic input array after the 9 head words
+    word(&mut out, &Fr::from(9 * 32u64));
+    word(&mut out, &Fr::from(inputs.len() as u64));
+    out.extend(pack_public_inputs(&inputs));
+    out
+}
+
+impl<E: IVC<Snark = Groth16<Bn254>, Field = Fr>> BurnRecord<E> {
+    // calldata of every proof of the record in order, the burn comes last
+    pub fn calldata(&self, h: &PoseidonConfigs<Fr>) -> Vec<Vec<u8>> {
+        self.statements(h)
+            .into_iter()
+            .map(|(proof, public_input)| calldata(proof, &public_input))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::abi::LAYOUT;
+    use ark_crypto_primitives::snark::SNARK;
+    use ark_ec::{CurveGroup, Group};
+    use ark_groth16::prepare_verifying_key;
+
+    // regenerated with IVCNOTES_BLESS=1 on an intended change of the contract
+    const GOLDEN: &str = "testdata/verifier.sol";
+
+    // key of fixed multiples of the generators
+    fn key() -> VerifyingKey<Bn254> {
+        let g1 = |k: u64| (ark_bn254::G1Projective::generator() * Fr::from(k)).into_affine();
+        let g2 = |k: u64| (ark_bn254::G2Projective::generator() * Fr::from(k)).into_affine();
+        VerifyingKey {
+            alpha_g1: g1(2),
+            beta_g2: g2(3),
+            gamma_g2: g2(5),
+            delta_g2: g2(7),
+            gamma_abc_g1: (0..=LAYOUT.len() as u64).map(|i| g1(11 + i)).collect(),
+        }
+    }
+
+    // every field takes a distinct value
+    fn public_input() -> PublicInput<Fr> {
+        PublicInput {
+            asset_hash: Fr::from(101).into(),
+            sender: Fr::from(102).into(),
+            state_in: Fr::from(103).into(),
+            state_out: Fr::from(104).into(),
+            step: 105,
+            nullifier: Fr::from(106).into(),
+            burn_value: 107,
+            settlement: Fr::from(108).into(),
+            merge_state_in: Fr::from(109).into(),
+            merge_nullifier: Fr::from(110).into(),
+            receiver_tag: Fr::from(111).into(),
+            disclosure: Fr::from(112).into(),
+        }
+    }
+
+    fn words<F: PrimeField>(bytes: &[u8]) -> Vec<F> {
+        assert_eq!(bytes.len() % 32, 0);
+        bytes.chunks(32).map(F::from_be_bytes_mod_order).collect()
+    }
+
+    #[test]
+    fn public_inputs_are_packed_in_abi_order() {
+        let public_input = public_input();
+        let inputs = public_input.to_verifier();
+        let packed = words::<Fr>(&pack_public_inputs(&inputs));
+        assert_eq!(packed, inputs);
+        for (i, field) in LAYOUT.iter().enumerate() {
+            assert_eq!(packed[i], public_input.get(*field), "{}", field.name());
+        }
+
+        // the input array follows the proof in the calldata
+        let proof = Proof::<Bn254> {
+            a: key().gamma_abc_g1[1],
+            b: key().beta_g2,
+            c: key().gamma_abc_g1[2],
+        };
+        let calldata = calldata(&proof, &public_input);
+        assert_eq!(calldata[..4], VERIFY_SELECTOR);
+        let (head, tail) = calldata[4..].split_at(8 * 32);
+        let head = words::<Fq>(head);
+        assert_eq!(head[..2], g1(&proof.a));
+        assert_eq!(head[2..6], g2(&proof.b));
+        assert_eq!(head[6..], g1(&proof.c));
+        let tail = words::<Fr>(tail);
+        assert_eq!(tail[0], Fr::from(9 * 32u64));
+        assert_eq!(tail[1], Fr::from(LAYOUT.len() as u64));
+        assert_eq!(tail[2..], packed);
+    }
+
+    #[test]
+    fn contract_accumulates_inputs_as_the_native_verifier() {
+        // x = ic[0] + sum of input[i] ic[i + 1] as in `verifyProof`
+        let vk = key();
+        let inputs = public_input().to_verifier();
+        let x = inputs
+            .iter()
+            .zip(vk.gamma_abc_g1[1..].iter())
+            .fold(vk.gamma_abc_g1[0].into_group(), |x, (input, ic)| {
+                x + *ic * input
+            });
+        let pvk = prepare_verifying_key(&vk);
+        let native = Groth16::<Bn254>::prepare_inputs(&pvk, &inputs).unwrap();
+        assert_eq!(x, native);
+        // the contract rejects any other number of inputs
+        assert!(export_solidity_verifier(&vk)
+            .contains(&format!("uint256 constant INPUTS = {};", LAYOUT.len())));
+        assert!(
+            Groth16::<Bn254>::verify_with_processed_vk(&pvk, &inputs[1..], &Proof::default())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn exported_verifier_matches_golden() {
+        let source = export_solidity_verifier(&key());
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN);
+        if std::env::var_os("IVCNOTES_BLESS").is_some() {
+            std::fs::write(&path, &source).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            source == golden,
+            "exported verifier differs from {GOLDEN}, run with IVCNOTES_BLESS=1 if intended"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    circuit::IVC,
+    handshake::Reader,
+    limits::Limits,
+    note::NoteHistory,
+    wallet::{CommReceiver, Wallet},
+    wire,
+};
+#[cfg(feature = "prover")]
+use crate::{
+    wallet::{Outcome, Request},
+    FWrap,
+};
+#[cfg(feature = "prover")]
+use std::collections::hash_map::Entry;
+
+// Note store of a wallet, the unspent histories in the `wire` encoding so that
+// the records of newer versions are kept.
+//
+// magic | version | count | (len | history)*
+//
+// Spent notes are not stored, their histories continue in the outputs and
+// the nullifiers are checked against the feed of the relay.
+//
+// Outcomes of the operations done under an idempotency key are stored in a
+// record of their own, so that a retry after a restart doesn't spend again.
+//
+// magic | version | count | (key | request | count | (delivered | len | history)*)*
+//
+// The key is its u32 length and utf-8 bytes. An issue request is 0 | asset
+// hash | receiver | value and a send is 1 | count | (receiver | value)*.
+
+const MAGIC: &[u8; 4] = b"IVCS";
+const VERSION: u8 = 1;
+#[cfg(feature = "prover")]
+const OUTCOMES_MAGIC: &[u8; 4] = b"IVCI";
+#[cfg(feature = "prover")]
+const OUTCOMES_VERSION: u8 = 1;
+
+pub fn encode<E: IVC>(histories: &[NoteHistory<E>]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&(histories.len() as u32).to_le_bytes());
+    for history in histories.iter() {
+        let history = wire::encode(history);
+        bytes.extend_from_slice(&(history.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&history);
+    }
+    bytes
+}
+
+pub fn decode<E: IVC>(bytes: &[u8], limits: &Limits) -> Result<Vec<NoteHistory<E>>, crate::Error> {
+    let err = crate::Error::With("bad note store");
+    (bytes.len() >= 9 && bytes[..4] == *MAGIC && bytes[4] == VERSION)
+        .then_some(())
+        .ok_or(err)?;
+    let mut reader = Reader(&bytes[5..]);
+    let count = reader.u32().ok_or(err)?;
+    let histories = (0..count)
+        .map(|_| {
+            let len = reader.u32().ok_or(err)? as usize;
+            wire::decode(reader.take(len).ok_or(err)?, limits)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    reader.0.is_empty().then_some(histories).ok_or(err)
+}
+
+#[cfg(feature = "prover")]
+fn encode_outcomes<E: IVC>(outcomes: &[(&String, &Outcome<E>)]) -> Vec<u8> {
+    let mut bytes = OUTCOMES_MAGIC.to_vec();
+    bytes.push(OUTCOMES_VERSION);
+    bytes.extend_from_slice(&(outcomes.len() as u32).to_le_bytes());
+    for (key, outcome) in outcomes.iter() {
+        bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(key.as_bytes());
+        match &outcome.request {
+            Request::Issue(asset_hash, receiver, value) => {
+                bytes.push(0);
+                wire::put(&mut bytes, &asset_hash.inner());
+                wire::put(&mut bytes, &receiver.inner());
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Request::Send(outputs) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(outputs.len() as u32).to_le_bytes());
+                for (receiver, value) in outputs.iter() {
+                    wire::put(&mut bytes, &receiver.inner());
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        bytes.extend_from_slice(&(outcome.sent.len() as u32).to_le_bytes());
+        for (history, delivered) in outcome.sent.iter().zip(outcome.delivered.iter()) {
+            let history = wire::encode(history);
+            bytes.push(*delivered as u8);
+            bytes.extend_from_slice(&(history.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&history);
+        }
+    }
+    bytes
+}
+
+#[cfg(feature = "prover")]
+fn decode_outcomes<E: IVC>(
+    bytes: &[u8],
+    limits: &Limits,
+) -> Result<Vec<(String, Outcome<E>)>, crate::Error> {
+    let err = crate::Error::With("bad outcome store");
+    (bytes.len() >= 9 && bytes[..4] == *OUTCOMES_MAGIC && bytes[4] == OUTCOMES_VERSION)
+        .then_some(())
+        .ok_or(err)?;
+    let mut reader = Reader(&bytes[5..]);
+    let count = reader.u32().ok_or(err)?;
+    let mut outcomes = vec![];
+    for _ in 0..count {
+        let len = reader.u32().ok_or(err)? as usize;
+        let key = reader.take(len).ok_or(err)?;
+        let key = String::from_utf8(key.to_vec()).map_err(|_| err)?;
+        let request = match reader.u8().ok_or(err)? {
+            0 => {
+                let asset_hash = wire::get::<E::Field>(&mut reader).ok_or(err)?;
+                let receiver = wire::get::<E::Field>(&mut reader).ok_or(err)?;
+                let value = reader.u64().ok_or(err)?;
+                Request::Issue(asset_hash.into(), receiver.into(), value)
+            }
+            1 => {
+                let n = reader.u32().ok_or(err)?;
+                let outputs = (0..n)
+                    .map(|_| {
+                        let receiver = wire::get::<E::Field>(&mut reader)?;
+                        Some((receiver.into(), reader.u64()?))
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(err)?;
+                Request::Send(outputs)
+            }
+            _ => return Err(err),
+        };
+        let n = reader.u32().ok_or(err)?;
+        let mut sent = vec![];
+        let mut delivered = vec![];
+        for _ in 0..n {
+            delivered.push(match reader.u8().ok_or(err)? {
+                0 => false,
+                1 => true,
+                _ => return Err(err),
+            });
+            let len = reader.u32().ok_or(err)? as usize;
+            sent.push(wire::decode(reader.take(len).ok_or(err)?, limits)?);
+        }
+        let outcome = Outcome {
+            request,
+            sent,
+            delivered,
+        };
+        outcomes.push((key, outcome));
+    }
+    reader.0.is_empty().then_some(outcomes).ok_or(err)
+}
+
+// a note is identified by the state it is an output of and its position
+fn same_note<E: IVC>(a: &NoteHistory<E>, b: &NoteHistory<E>) -> bool {
+    a.steps.last().map(|step| step.state) == b.steps.last().map(|step| step.state)
+        && a.current_note.out_index == b.current_note.out_index
+}
+
+impl<E: IVC> Wallet<E> {
+    // unspent notes to persist, balances follow from them. Pending notes are
+    // included and are pending again once imported.
+    pub fn export_notes(&self) -> Vec<u8> {
+        let pending = self.pending().iter().map(|note| note.history().clone());
+        encode(
+            &self
+                .spendables()
+                .iter()
+                .cloned()
+                .chain(pending)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    // restores exported notes, every history is verified as if it was
+    // received and notes the wallet holds are skipped. Returns the number of
+    // notes added.
+    pub fn import_notes(&mut self, bytes: &[u8]) -> Result<usize, crate::Error> {
+        let limits = *self.limits();
+        let mut added = 0;
+        for history in decode::<E>(bytes, &limits)? {
+            let known = self
+                .spendables()
+                .iter()
+                .chain(self.pending().iter().map(|note| note.history()))
+                .any(|held| same_note(held, &history));
+            if !known {
+                self.receive(&history)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    // outcomes of the operations done under an idempotency key, to persist
+    // along with the notes
+    #[cfg(feature = "prover")]
+    pub fn export_outcomes(&self) -> Vec<u8> {
+        let mut outcomes = self.outcomes.iter().collect::<Vec<_>>();
+        outcomes.sort_by(|a, b| a.0.cmp(b.0));
+        encode_outcomes(&outcomes)
+    }
+
+    // restores exported outcomes, the keys the wallet holds are skipped. The
+    // histories are not verified here, their receivers verify them when they
+    // are delivered. Returns the number of outcomes added.
+    #[cfg(feature = "prover")]
+    pub fn import_outcomes(&mut self, bytes: &[u8]) -> Result<usize, crate::Error> {
+        let limits = *self.limits();
+        let mut added = 0;
+        for (key, outcome) in decode_outcomes::<E>(bytes, &limits)? {
+            if let Entry::Vacant(entry) = self.outcomes.entry(key) {
+                entry.insert(outcome);
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{circuit::IVC, poseidon::PoseidonConfigs, Address, AssetHash, FWrap, SigHash};
+use ark_ff::PrimeField;
+use arkeddsa::{signature::Signature, PublicKey};
+
+// `Authorization` lets the payee collect up to `cap` of the asset once per
+// `period` seconds starting from `start`, a unix timestamp
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Authorization<F: PrimeField> {
+    pub payer: Address<F>,
+    pub payee: Address<F>,
+    pub asset_hash: AssetHash<F>,
+    pub cap: u64,
+    pub period: u64,
+    pub start: u64,
+}
+
+impl<F: PrimeField> Authorization<F> {
+    // index of the period `now` falls in
+    pub fn period_at(&self, now: u64) -> Option<u64> {
+        match now.checked_sub(self.start) {
+            Some(elapsed) if self.period > 0 => Some(elapsed / self.period),
+            _ => None,
+        }
+    }
+}
+
+// authorization signed by the payer, travels with every installment
+#[derive(Clone)]
+pub struct SignedAuthorization<E: IVC> {
+    pub(crate) authorization: Authorization<E::Field>,
+    pub(crate) public_key: PublicKey<E::TE>,
+    pub(crate) signature: Signature<E::TE>,
+}
+
+impl<E: IVC> std::fmt::Debug for SignedAuthorization<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignedAuthorization")
+            .field("authorization", &self.authorization)
+            .finish()
+    }
+}
+
+impl<E: IVC> SignedAuthorization<E> {
+    pub fn authorization(&self) -> &Authorization<E::Field> {
+        &self.authorization
+    }
+
+    // public key of the payer, expected to be pinned by the payee when subscribing
+    pub fn public_key(&self) -> &PublicKey<E::TE> {
+        &self.public_key
+    }
+
+    pub fn id(&self, h: &PoseidonConfigs<E::Field>) -> SigHash<E::Field> {
+        h.authorization(&self.authorization)
+    }
+
+    pub fn verify(&self, h: &PoseidonConfigs<E::Field>) -> Result<(), crate::Error> {
+        let msg = self.id(h);
+        self.public_key
+            .verify(&h.eddsa, &[msg.inner()], &self.signature)
+            .map_err(|_| crate::Error::With("bad authorization signature"))
+    }
+}
+
+// `Installment` accompanies a payment made under an authorization
+#[derive(Clone, Debug)]
+pub struct Installment<E: IVC> {
+    pub(crate) authorization: SignedAuthorization<E>,
+    pub(crate) period: u64,
+    pub(crate) value: u64,
+}
+
+impl<E: IVC> Installment<E> {
+    pub fn authorization(&self) -> &SignedAuthorization<E> {
+        &self.authorization
+    }
+
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    // checks of the payee, tracking the periods already collected is left to the payee
+    pub fn verify(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        payee: &Address<E::Field>,
+        now: u64,
+    ) -> Result<(), crate::Error> {
+        self.authorization.verify(h)?;
+        let authorization = &self.authorization.authorization;
+        (authorization.payee == *payee)
+            .then_some(())
+            .ok_or(crate::Error::With("not the payee"))?;
+        (self.value <= authorization.cap)
+            .then_some(())
+            .ok_or(crate::Error::With("installment exceeds the cap"))?;
+        (authorization.period_at(now) == Some(self.period))
+            .then_some(())
+            .ok_or(crate::Error::With("installment out of period"))
+    }
+}
+
+// payer side state of an authorization
+#[derive(Clone, Debug)]
+pub(crate) struct Subscription<E: IVC> {
+    pub(crate) authorization: SignedAuthorization<E>,
+    // last period an installment is paid for
+    pub(crate) paid: Option<u64>,
+}
+
+impl<E: IVC> Subscription<E> {
+    pub(crate) fn new(authorization: SignedAuthorization<E>) -> Self {
+        Self {
+            authorization,
+            paid: None,
+        }
+    }
+
+    // period to pay at `now` if it is not paid yet
+    pub(crate) fn due(&self, now: u64) -> Option<u64> {
+        let period = self.authorization.authorization.period_at(now)?;
+        match self.paid {
+            Some(paid) if paid >= period => None,
+            _ => Some(period),
+        }
+    }
+}
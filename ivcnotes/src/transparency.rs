@@ -0,0 +1,431 @@
+use crate::{
+    circuit::IVC, note::NoteHistory, poseidon::PoseidonConfigs, AssetHash, FWrap, StateHash,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::PrimeField;
+use arkeddsa::{signature::Signature, PublicKey};
+
+// Append-only public log of issuer actions. The issuer appends every action
+// and publishes signed tree heads. Monitors keep the last head they verified,
+// check that each new head extends it and check that the issuances they see
+// in note histories are logged, so an unlogged issuance or a rewritten log is
+// detected after the fact.
+//
+// The tree is the one of RFC 9162 with poseidon in place of sha256.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssuerAction<F: PrimeField> {
+    // issuance of a note, identified by the state the issue step commits to
+    Issue {
+        asset_hash: AssetHash<F>,
+        state: StateHash<F>,
+    },
+}
+
+impl<F: PrimeField + Absorb> IssuerAction<F> {
+    // issuance a history starts with
+    pub fn issue<E: IVC<Field = F>>(history: &NoteHistory<E>) -> Self {
+        Self::Issue {
+            asset_hash: history.current_note.asset_hash,
+            state: history.steps[0].state,
+        }
+    }
+
+    fn leaf(&self, h: &PoseidonConfigs<F>) -> F {
+        match self {
+            Self::Issue { asset_hash, state } => {
+                h.log_leaf(&[F::from(0u64), asset_hash.inner(), state.inner()])
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreeHead<F: PrimeField> {
+    pub size: u64,
+    pub root: F,
+}
+
+#[derive(Clone)]
+pub struct SignedTreeHead<E: IVC> {
+    pub(crate) head: TreeHead<E::Field>,
+    pub(crate) signature: Signature<E::TE>,
+}
+
+impl<E: IVC> std::fmt::Debug for SignedTreeHead<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignedTreeHead")
+            .field("head", &self.head)
+            .finish()
+    }
+}
+
+impl<E: IVC> SignedTreeHead<E> {
+    pub fn head(&self) -> &TreeHead<E::Field> {
+        &self.head
+    }
+
+    pub fn verify(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        public_key: &PublicKey<E::TE>,
+    ) -> Result<(), crate::Error> {
+        let msg = h.tree_head(self.head.size, &self.head.root);
+        public_key
+            .verify(&h.eddsa, &[msg.inner()], &self.signature)
+            .map_err(|_| crate::Error::With("bad tree head signature"))
+    }
+}
+
+// audit path of the leaf at `index` in the tree of `size` leaves
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof<F: PrimeField> {
+    pub index: u64,
+    pub size: u64,
+    pub path: Vec<F>,
+}
+
+// proof that the tree of `to` leaves extends the tree of `from` leaves
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsistencyProof<F: PrimeField> {
+    pub from: u64,
+    pub to: u64,
+    pub path: Vec<F>,
+}
+
+// largest power of two smaller than `n`, `n` is at least two
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - (n - 1).leading_zeros() - 1)
+}
+
+fn root<F: PrimeField + Absorb>(h: &PoseidonConfigs<F>, leaves: &[F]) -> F {
+    match leaves.len() {
+        0 => F::zero(),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            h.log_node(&root(h, &leaves[..k]), &root(h, &leaves[k..]))
+        }
+    }
+}
+
+fn path<F: PrimeField + Absorb>(h: &PoseidonConfigs<F>, m: usize, leaves: &[F]) -> Vec<F> {
+    if leaves.len() <= 1 {
+        return vec![];
+    }
+    let k = split(leaves.len());
+    let (mut path, sibling) = if m < k {
+        (path(h, m, &leaves[..k]), root(h, &leaves[k..]))
+    } else {
+        (path(h, m - k, &leaves[k..]), root(h, &leaves[..k]))
+    };
+    path.push(sibling);
+    path
+}
+
+fn subproof<F: PrimeField + Absorb>(
+    h: &PoseidonConfigs<F>,
+    m: usize,
+    leaves: &[F],
+    complete: bool,
+) -> Vec<F> {
+    let n = leaves.len();
+    if m == n {
+        return if complete {
+            vec![]
+        } else {
+            vec![root(h, leaves)]
+        };
+    }
+    let k = split(n);
+    let (mut proof, sibling) = if m <= k {
+        (
+            subproof(h, m, &leaves[..k], complete),
+            root(h, &leaves[k..]),
+        )
+    } else {
+        (
+            subproof(h, m - k, &leaves[k..], false),
+            root(h, &leaves[..k]),
+        )
+    };
+    proof.push(sibling);
+    proof
+}
+
+// log kept by the issuer
+#[derive(Clone, Debug, Default)]
+pub struct IssuerLog<F: PrimeField> {
+    leaves: Vec<F>,
+}
+
+impl<F: PrimeField + Absorb> IssuerLog<F> {
+    pub fn new() -> Self {
+        Self { leaves: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    // returns the index of the action in the log
+    pub fn append(&mut self, h: &PoseidonConfigs<F>, action: &IssuerAction<F>) -> u64 {
+        self.leaves.push(action.leaf(h));
+        self.leaves.len() as u64 - 1
+    }
+
+    pub fn head(&self, h: &PoseidonConfigs<F>) -> TreeHead<F> {
+        TreeHead {
+            size: self.leaves.len() as u64,
+            root: root(h, &self.leaves),
+        }
+    }
+
+    // proof of the action at `index` against the current head
+    pub fn inclusion_proof(
+        &self,
+        h: &PoseidonConfigs<F>,
+        index: u64,
+    ) -> Result<InclusionProof<F>, crate::Error> {
+        ((index as usize) < self.leaves.len())
+            .then_some(())
+            .ok_or(crate::Error::With("bad log index"))?;
+        Ok(InclusionProof {
+            index,
+            size: self.leaves.len() as u64,
+            path: path(h, index as usize, &self.leaves),
+        })
+    }
+
+    // proof that the current head extends the head of size `from`
+    pub fn consistency_proof(
+        &self,
+        h: &PoseidonConfigs<F>,
+        from: u64,
+    ) -> Result<ConsistencyProof<F>, crate::Error> {
+        (from > 0 && from as usize <= self.leaves.len())
+            .then_some(())
+            .ok_or(crate::Error::With("bad log index"))?;
+        Ok(ConsistencyProof {
+            from,
+            to: self.leaves.len() as u64,
+            path: subproof(h, from as usize, &self.leaves, true),
+        })
+    }
+}
+
+pub fn verify_inclusion<F: PrimeField + Absorb>(
+    h: &PoseidonConfigs<F>,
+    action: &IssuerAction<F>,
+    proof: &InclusionProof<F>,
+    head: &TreeHead<F>,
+) -> bool {
+    if proof.index >= proof.size || proof.size != head.size {
+        return false;
+    }
+    let (mut f, mut s) = (proof.index, proof.size - 1);
+    let mut r = action.leaf(h);
+    for p in proof.path.iter() {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            r = h.log_node(p, &r);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            r = h.log_node(&r, p);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && r == head.root
+}
+
+pub fn verify_consistency<F: PrimeField + Absorb>(
+    h: &PoseidonConfigs<F>,
+    proof: &ConsistencyProof<F>,
+    first: &TreeHead<F>,
+    second: &TreeHead<F>,
+) -> bool {
+    if proof.from != first.size || proof.to != second.size || first.size > second.size {
+        return false;
+    }
+    if first.size == second.size {
+        return proof.path.is_empty() && first.root == second.root;
+    }
+    if first.size == 0 {
+        return proof.path.is_empty();
+    }
+
+    let mut path = proof.path.clone();
+    if first.size.is_power_of_two() {
+        path.insert(0, first.root);
+    }
+    let (mut f, mut s) = (first.size - 1, second.size - 1);
+    while f & 1 == 1 {
+        f >>= 1;
+        s >>= 1;
+    }
+    let Some((c, rest)) = path.split_first() else {
+        return false;
+    };
+    let (mut fr, mut sr) = (*c, *c);
+    for c in rest {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            fr = h.log_node(c, &fr);
+            sr = h.log_node(c, &sr);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            sr = h.log_node(&sr, c);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    fr == first.root && sr == second.root && s == 0
+}
+
+// `LogMonitor` follows the log of an issuer whose public key is pinned
+#[derive(Clone)]
+pub struct LogMonitor<E: IVC> {
+    public_key: PublicKey<E::TE>,
+    // last head that is verified
+    head: Option<TreeHead<E::Field>>,
+}
+
+impl<E: IVC> LogMonitor<E> {
+    pub fn new(public_key: &PublicKey<E::TE>) -> Self {
+        Self {
+            public_key: public_key.clone(),
+            head: None,
+        }
+    }
+
+    pub fn head(&self) -> Option<&TreeHead<E::Field>> {
+        self.head.as_ref()
+    }
+
+    // accepts a new head if it is signed by the issuer and extends the last
+    // one, the proof is ignored for the first head
+    pub fn update(
+        &mut self,
+        h: &PoseidonConfigs<E::Field>,
+        signed: &SignedTreeHead<E>,
+        proof: &ConsistencyProof<E::Field>,
+    ) -> Result<(), crate::Error> {
+        signed.verify(h, &self.public_key)?;
+        if let Some(head) = self.head.as_ref() {
+            verify_consistency(h, proof, head, &signed.head)
+                .then_some(())
+                .ok_or(crate::Error::With("log is not consistent"))?;
+        }
+        self.head = Some(signed.head);
+        Ok(())
+    }
+
+    pub fn check_included(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        action: &IssuerAction<E::Field>,
+        proof: &InclusionProof<E::Field>,
+    ) -> Result<(), crate::Error> {
+        let head = self
+            .head
+            .as_ref()
+            .ok_or(crate::Error::With("no verified tree head"))?;
+        verify_inclusion(h, action, proof, head)
+            .then_some(())
+            .ok_or(crate::Error::With("action is not in the log"))
+    }
+
+    // the issuance the history starts with must be logged
+    pub fn check_history(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        history: &NoteHistory<E>,
+        proof: &InclusionProof<E::Field>,
+    ) -> Result<(), crate::Error> {
+        self.check_included(h, &IssuerAction::issue(history), proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+
+    fn action(i: u64) -> IssuerAction<Fr> {
+        IssuerAction::Issue {
+            asset_hash: Fr::from(i).into(),
+            state: Fr::from(i + 100).into(),
+        }
+    }
+
+    // log of the actions `first..first + size`
+    fn log_of(h: &PoseidonConfigs<Fr>, first: u64, size: u64) -> IssuerLog<Fr> {
+        let mut log = IssuerLog::new();
+        for i in first..first + size {
+            log.append(h, &action(i));
+        }
+        log
+    }
+
+    #[test]
+    fn inclusion_proofs_of_small_trees() {
+        let h = PoseidonConfigs::<Fr>::generate();
+        for size in 1..=13 {
+            let log = log_of(&h, 0, size);
+            let head = log.head(&h);
+            for index in 0..size {
+                let proof = log.inclusion_proof(&h, index).unwrap();
+                assert!(verify_inclusion(&h, &action(index), &proof, &head));
+                assert!(!verify_inclusion(&h, &action(size), &proof, &head));
+                for at in 0..proof.path.len() {
+                    let mut altered = proof.clone();
+                    altered.path[at] += Fr::from(1u64);
+                    assert!(!verify_inclusion(&h, &action(index), &altered, &head));
+                }
+                let mut moved = proof.clone();
+                moved.index = (index + 1) % size;
+                assert!(size == 1 || !verify_inclusion(&h, &action(index), &moved, &head));
+            }
+            assert!(log.inclusion_proof(&h, size).is_err());
+        }
+    }
+
+    #[test]
+    fn consistency_proofs_of_small_trees() {
+        let h = PoseidonConfigs::<Fr>::generate();
+        for size in 1..=13 {
+            let log = log_of(&h, 0, size);
+            let second = log.head(&h);
+            for from in 1..=size {
+                let first = log_of(&h, 0, from).head(&h);
+                let proof = log.consistency_proof(&h, from).unwrap();
+                assert!(verify_consistency(&h, &proof, &first, &second));
+                for at in 0..proof.path.len() {
+                    let mut altered = proof.clone();
+                    altered.path[at] += Fr::from(1u64);
+                    assert!(!verify_consistency(&h, &altered, &first, &second));
+                }
+                // a rewritten prefix of the same size
+                let rewritten = log_of(&h, 1, from).head(&h);
+                assert!(!verify_consistency(&h, &proof, &rewritten, &second));
+            }
+            assert!(log.consistency_proof(&h, 0).is_err());
+            assert!(log.consistency_proof(&h, size + 1).is_err());
+        }
+    }
+}
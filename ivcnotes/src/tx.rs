@@ -5,7 +5,7 @@ use arkeddsa::signature::Signature;
 
 use crate::{
     note::{Note, NoteOutIndex},
-    Address, BlindNoteHash, Nullifier,
+    Address, BlindNoteHash, Nullifier, SettlementRef,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -68,11 +68,11 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct SplitTx<F: PrimeField> {
     pub(crate) note_in: Note<F>,
-    pub(crate) note_out_0: Note<F>,
-    pub(crate) note_out_1: Note<F>,
+    // outputs in the order of their index
+    pub(crate) notes_out: Vec<Note<F>>,
 }
 
 #[derive(Debug, Clone)]
@@ -89,11 +89,10 @@ where
 }
 
 impl<F: PrimeField + Absorb> SplitTx<F> {
-    pub(crate) fn new(note_in: &Note<F>, note_out_0: &Note<F>, note_out_1: &Note<F>) -> Self {
+    pub(crate) fn new(note_in: &Note<F>, notes_out: &[Note<F>]) -> Self {
         Self {
             note_in: *note_in,
-            note_out_0: *note_out_0,
-            note_out_1: *note_out_1,
+            notes_out: notes_out.to_vec(),
         }
     }
 
@@ -105,12 +104,8 @@ impl<F: PrimeField + Absorb> SplitTx<F> {
         SealedSplitTx::new(self, sig, nullifier)
     }
 
-    pub(crate) fn note_out_0(&self) -> &Note<F> {
-        &self.note_out_0
-    }
-
-    pub(crate) fn note_out_1(&self) -> &Note<F> {
-        &self.note_out_1
+    pub(crate) fn notes_out(&self) -> &[Note<F>] {
+        &self.notes_out
     }
 }
 
@@ -124,7 +119,7 @@ where
         nullifier: &Nullifier<TE::BaseField>,
     ) -> Self {
         SealedSplitTx {
-            tx: *tx,
+            tx: tx.clone(),
             signature: signature.clone(),
             nullifier: *nullifier,
         }
@@ -138,11 +133,142 @@ where
         &self.signature
     }
 
-    pub(crate) fn note_out_0(&self) -> &Note<TE::BaseField> {
-        self.tx.note_out_0()
+    pub(crate) fn notes_out(&self) -> &[Note<TE::BaseField>] {
+        self.tx.notes_out()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeTx<F: PrimeField> {
+    pub(crate) note_in: Note<F>,
+    // second input, owned by the owner of `note_in`
+    pub(crate) note_merged: Note<F>,
+    // outputs in the order of their index, the merged note is the first one
+    pub(crate) notes_out: Vec<Note<F>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SealedMergeTx<TE: TECurveConfig + Clone>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    // wrap the transaction
+    pub(crate) tx: MergeTx<TE::BaseField>,
+    // store the signature
+    pub(crate) signature: Signature<TE>,
+    // and the nullifiers of both inputs
+    pub(crate) nullifier: Nullifier<TE::BaseField>,
+    pub(crate) merge_nullifier: Nullifier<TE::BaseField>,
+}
+
+impl<F: PrimeField + Absorb> MergeTx<F> {
+    pub(crate) fn new(note_in: &Note<F>, note_merged: &Note<F>, notes_out: &[Note<F>]) -> Self {
+        Self {
+            note_in: *note_in,
+            note_merged: *note_merged,
+            notes_out: notes_out.to_vec(),
+        }
+    }
+
+    pub(crate) fn seal<TE: TECurveConfig<BaseField = F> + Clone>(
+        &self,
+        sig: &Signature<TE>,
+        nullifier: &Nullifier<TE::BaseField>,
+        merge_nullifier: &Nullifier<TE::BaseField>,
+    ) -> SealedMergeTx<TE> {
+        SealedMergeTx {
+            tx: self.clone(),
+            signature: sig.clone(),
+            nullifier: *nullifier,
+            merge_nullifier: *merge_nullifier,
+        }
+    }
+
+    pub(crate) fn notes_out(&self) -> &[Note<F>] {
+        &self.notes_out
+    }
+}
+
+impl<TE: TECurveConfig + Clone> SealedMergeTx<TE>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    pub(crate) fn nullifier(&self) -> &Nullifier<TE::BaseField> {
+        &self.nullifier
+    }
+
+    pub(crate) fn merge_nullifier(&self) -> &Nullifier<TE::BaseField> {
+        &self.merge_nullifier
+    }
+
+    pub(crate) fn signature(&self) -> &Signature<TE> {
+        &self.signature
+    }
+
+    pub(crate) fn notes_out(&self) -> &[Note<TE::BaseField>] {
+        self.tx.notes_out()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BurnTx<F: PrimeField> {
+    // the note to destroy, its whole value is burnt
+    pub(crate) note_in: Note<F>,
+    // payout reference if the note is redeemed, zero for a plain burn
+    pub(crate) settlement: SettlementRef<F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SealedBurnTx<TE: TECurveConfig + Clone>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    // wrap the transaction
+    pub(crate) tx: BurnTx<TE::BaseField>,
+    // store the signature
+    pub(crate) signature: Signature<TE>,
+    // and the nullifier
+    pub(crate) nullifier: Nullifier<TE::BaseField>,
+}
+
+impl<F: PrimeField + Absorb> BurnTx<F> {
+    pub(crate) fn new(note_in: &Note<F>, settlement: &SettlementRef<F>) -> Self {
+        Self {
+            note_in: *note_in,
+            settlement: *settlement,
+        }
     }
 
-    pub(crate) fn note_out_1(&self) -> &Note<TE::BaseField> {
-        self.tx.note_out_1()
+    pub(crate) fn seal<TE: TECurveConfig<BaseField = F> + Clone>(
+        &self,
+        sig: &Signature<TE>,
+        nullifier: &Nullifier<TE::BaseField>,
+    ) -> SealedBurnTx<TE> {
+        SealedBurnTx {
+            tx: *self,
+            signature: sig.clone(),
+            nullifier: *nullifier,
+        }
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        self.note_in.value
+    }
+}
+
+impl<TE: TECurveConfig + Clone> SealedBurnTx<TE>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    pub(crate) fn tx(&self) -> &BurnTx<TE::BaseField> {
+        &self.tx
+    }
+
+    pub(crate) fn nullifier(&self) -> &Nullifier<TE::BaseField> {
+        &self.nullifier
+    }
+
+    pub(crate) fn signature(&self) -> &Signature<TE> {
+        &self.signature
     }
 }
@@ -0,0 +1,111 @@
+use crate::{AssetHash, Nullifier};
+use ark_ff::PrimeField;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyOp {
+    // signature with the spend key
+    Sign,
+    // proof generated with the wallet secrets
+    Prove,
+    // note history received
+    Receive,
+}
+
+// raise an alert when an operation happens more than `limit` times in `window`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Threshold {
+    pub op: KeyOp,
+    pub limit: usize,
+    pub window: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alert {
+    pub op: KeyOp,
+    pub count: usize,
+    pub window: Duration,
+}
+
+// a note the wallet holds as unspent is spent elsewhere, so the keys are
+// likely compromised
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpentAlert<F: PrimeField> {
+    pub nullifier: Nullifier<F>,
+    pub asset_hash: AssetHash<F>,
+    pub value: u64,
+}
+
+// `KeyUsage` counts operations done with the wallet keys and raises alerts on
+// unusual activity, which can be a sign of a compromised wallet
+#[derive(Clone, Debug, Default)]
+pub struct KeyUsage {
+    totals: HashMap<KeyOp, u64>,
+    // recent operation times, kept as long as the widest window of the op
+    recent: HashMap<KeyOp, VecDeque<Instant>>,
+    thresholds: Vec<Threshold>,
+    alerts: Vec<Alert>,
+}
+
+impl KeyUsage {
+    pub fn new(thresholds: Vec<Threshold>) -> Self {
+        Self {
+            thresholds,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: Vec<Threshold>) {
+        self.thresholds = thresholds;
+    }
+
+    pub fn total(&self, op: KeyOp) -> u64 {
+        self.totals.get(&op).copied().unwrap_or_default()
+    }
+
+    pub fn alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+
+    pub fn take_alerts(&mut self) -> Vec<Alert> {
+        std::mem::take(&mut self.alerts)
+    }
+
+    pub(crate) fn record(&mut self, op: KeyOp) {
+        self.record_at(op, Instant::now())
+    }
+
+    pub(crate) fn record_at(&mut self, op: KeyOp, now: Instant) {
+        *self.totals.entry(op).or_default() += 1;
+
+        let thresholds: Vec<&Threshold> = self.thresholds.iter().filter(|t| t.op == op).collect();
+        let Some(retention) = thresholds.iter().map(|t| t.window).max() else {
+            return;
+        };
+
+        let recent = self.recent.entry(op).or_default();
+        recent.push_back(now);
+        while let Some(first) = recent.front() {
+            match now.duration_since(*first) > retention {
+                true => recent.pop_front(),
+                false => break,
+            };
+        }
+
+        for threshold in thresholds {
+            let count = recent
+                .iter()
+                .filter(|at| now.duration_since(**at) <= threshold.window)
+                .count();
+            // alert once when the limit is crossed
+            if count == threshold.limit + 1 {
+                self.alerts.push(Alert {
+                    op,
+                    count,
+                    window: threshold.window,
+                });
+            }
+        }
+    }
+}
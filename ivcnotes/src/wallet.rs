@@ -1,17 +1,132 @@
 use crate::{
+    acceptance::AcceptancePolicy,
     asset::Asset,
-    circuit::{
-        inputs::{AuxInputs, PublicInput},
-        Prover, Verifier, IVC,
-    },
-    id::Auth,
-    note::{IVCStep, Note, NoteHistory, NoteOutIndex},
+    burn::{Cosignature, RedemptionRequest},
+    challenge::{Challenge, MAX_CHALLENGES},
+    circuit::{abi, cache::VerificationCache, Verifier, IVC},
+    codec::Codecs,
+    cold::{self, ColdNote},
+    confirmation::{Epoch, PendingNote},
+    evidence::Receipt,
+    gossip::NullifierDigest,
+    handshake::{Feature, Hello},
+    id::{Auth, EncryptedNote},
+    limits::Limits,
+    note::{memo_hash, statements, NoteCache, NoteHistory, NoteOutIndex},
+    payload::EncryptionKey,
     poseidon::PoseidonConfigs,
-    tx::{IssueTx, SealedIssueTx, SealedSplitTx, SplitTx},
-    Address, Blind, BlindNoteHash, FWrap,
+    screening::{Flag, HitAction, Operation, Screening, ScreeningProvider},
+    select::{CoinSelector, PlannedSplit},
+    subscription::{Authorization, SignedAuthorization, Subscription},
+    transparency::{SignedTreeHead, TreeHead},
+    tx::{
+        BurnTx, IssueTx, MergeTx, SealedBurnTx, SealedIssueTx, SealedMergeTx, SealedSplitTx,
+        SplitTx,
+    },
+    usage::{KeyOp, KeyUsage, SpentAlert},
+    Address, AssetHash, IssuerKey, Nullifier, PolicyHash, SettlementRef, SigHash, ViewingKey,
+};
+use arkeddsa::PublicKey;
+use rand_core::CryptoRngCore;
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "prover")]
+use {
+    crate::{
+        burn::BurnRecord,
+        circuit::{
+            inputs::{AuxInputs, MergeInput, Output, PublicInput},
+            Prover,
+        },
+        cold::ColdExport,
+        estimate::{self, Calibration},
+        note::{IVCStep, Note, MAX_MEMO, NO_EXPIRY},
+        subscription::Installment,
+        Blind, BlindNoteHash, FWrap, StateHash,
+    },
+    ark_crypto_primitives::snark::SNARK,
+    rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng},
+    std::sync::OnceLock,
 };
 
-use rand::{CryptoRng, RngCore};
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SweepProgress {
+    // notes that are proven or failed so far
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sweep<E: IVC> {
+    // histories to deliver to the destination
+    pub sent: Vec<NoteHistory<E>>,
+    // errors of the notes that are left in the wallet
+    pub failed: Vec<crate::Error>,
+}
+
+// splits of a payment, a failed split stops it and the rest of the plan is
+// not sent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Payment {
+    // splits that are sent, in the order of the plan
+    pub sent: Vec<PlannedSplit>,
+    // error of the split that stopped the payment
+    pub failed: Option<crate::Error>,
+}
+
+// outcome of an operation done under an idempotency key
+#[cfg(feature = "prover")]
+#[derive(Clone, Debug)]
+pub struct Outcome<E: IVC> {
+    pub(crate) request: Request<E::Field>,
+    // histories in the order of the receivers
+    pub(crate) sent: Vec<NoteHistory<E>>,
+    pub(crate) delivered: Vec<bool>,
+}
+
+#[cfg(feature = "prover")]
+impl<E: IVC> Outcome<E> {
+    pub fn sent(&self) -> &[NoteHistory<E>] {
+        &self.sent
+    }
+
+    pub fn is_delivered(&self) -> bool {
+        self.delivered.iter().all(|delivered| *delivered)
+    }
+}
+
+// what an operation is asked for, a retry must ask for the same
+#[cfg(feature = "prover")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Request<F: ark_ff::PrimeField> {
+    Issue(AssetHash<F>, Address<F>, u64),
+    Send(Vec<(Address<F>, u64)>),
+}
+
+// a step that is signed but not proven yet
+#[cfg(feature = "prover")]
+struct PendingStep<E: IVC> {
+    public_inputs: PublicInput<E::Field>,
+    aux_inputs: AuxInputs<E>,
+    // blind hashes of the outputs in the order of their index
+    blind_note_hashes: Vec<BlindNoteHash<E::Field>>,
+    disclosure: EncryptedNote<E::Field>,
+}
+
+// a split that is signed but not proven yet
+#[cfg(feature = "prover")]
+struct PendingTransfer<E: IVC> {
+    spendable_index: usize,
+    public_inputs: PublicInput<E::Field>,
+    aux_inputs: AuxInputs<E>,
+    // number of outputs that are sent, they follow the change
+    sent: usize,
+    // plaintext memos of the sent outputs
+    memos: Vec<Vec<u8>>,
+    notes_out: Vec<Note<E::Field>>,
+    blind_note_hashes: Vec<BlindNoteHash<E::Field>>,
+    disclosure: EncryptedNote<E::Field>,
+}
 
 pub trait CommReceiver<E: IVC> {
     fn receive(&mut self, history: &NoteHistory<E>) -> Result<(), crate::Error>;
@@ -24,43 +139,99 @@ pub struct Wallet<E: IVC> {
     // auth object that holds private keys
     auth: Auth<E>,
     // configs for poseidion hasher
-    h: PoseidonConfigs<E::Field>,
+    pub(crate) h: PoseidonConfigs<E::Field>,
     // prover
+    #[cfg(feature = "prover")]
     prover: Prover<E>,
-    // verifier
-    verifier: Verifier<E>,
+    // verifiers keyed by the commitment of the asset policy of their keys
+    verifiers: HashMap<PolicyHash<E::Field>, Verifier<E>>,
+    // results of verified steps, shared prefixes of histories are verified once
+    cache: VerificationCache,
+    // hashes of the notes that are seen recently
+    note_cache: NoteCache<E::Field>,
+    // optional screening of counterparties
+    screening: Option<Screening<E::Field>>,
+    // counters of operations done with the keys
+    usage: KeyUsage,
+    // rules to evaluate before accepting a note
+    acceptance: AcceptancePolicy<E::Field>,
+    // bounds of the incoming histories
+    limits: Limits,
+    // codecs of the payloads, see `codec`
+    codecs: Codecs,
+    // received notes waiting for their transfer to be confirmed
+    pending: Vec<PendingNote<E>>,
+    // latest published epoch observed
+    epoch: Option<u64>,
+    // proofs of payment of the sent notes, kept only if enabled
+    receipts: Option<Vec<Receipt<E>>>,
+    // recurring payments authorized by this wallet
+    subscriptions: Vec<Subscription<E>>,
+    // unspent notes that are seen spent
+    spent_alerts: Vec<SpentAlert<E::Field>>,
+    // notes exported to cold storage and not imported back
+    cold: Vec<ColdNote<E::Field>>,
+    // payment challenges handed out and not answered yet, oldest first
+    challenges: Vec<Challenge>,
+    // new identity and the notes moved to it while a key rotation is in progress
+    successor: Option<(Auth<E>, Vec<NoteHistory<E>>)>,
+    // operations done under an idempotency key
+    #[cfg(feature = "prover")]
+    pub(crate) outcomes: HashMap<String, Outcome<E>>,
+    // proving speed of the device and the size of a step, for the estimates
+    #[cfg(feature = "prover")]
+    calibration: Calibration,
+    #[cfg(feature = "prover")]
+    step_constraints: OnceLock<usize>,
 }
 
+const VERIFICATION_CACHE_CAPACITY: usize = 1024;
+const NOTE_CACHE_CAPACITY: usize = 1024;
+
 impl<E: IVC> CommReceiver<E> for Wallet<E> {
     fn receive(&mut self, note_history: &NoteHistory<E>) -> Result<(), crate::Error> {
         (note_history.current_note.owner == *self.address())
             .then_some(())
             .ok_or(crate::Error::With("not me"))?;
 
-        let asset_hash = &note_history.asset.hash();
-        let mut state_in = &asset_hash.as_ref().into();
-
-        for (i, step) in note_history.steps.iter().enumerate() {
-            let state_out = &step.state;
-            let public_input = PublicInput::new(
-                asset_hash,
-                &step.sender,
-                state_in,
-                state_out,
-                i as u32,
-                &step.nullifier,
-            );
-            if i == note_history.steps.len() - 1 {
-                (note_history.state(&self.h) == *state_out)
-                    .then_some(())
-                    .ok_or(crate::Error::With("bad current state"))?;
-            }
-            self.verifier
-                .verify_proof(&step.proof, &public_input)
-                .map_err(|_| crate::Error::With("verification failed"))?;
-            state_in = state_out;
+        self.limits.check_history(note_history)?;
+        self.acceptance.evaluate(note_history)?;
+
+        let asset_hash = &note_history.asset.hash(&self.h);
+        // merged histories are verified along with the history
+        let statements = statements(&self.h, asset_hash, &note_history.steps);
+        if let Some(screening) = self.screening.as_mut() {
+            let senders = statements
+                .iter()
+                .map(|(_, public_input)| &public_input.sender);
+            screening.screen(Operation::Receive, asset_hash, senders)?;
+        }
+
+        let state_out = note_history.steps.last().map(|step| step.state);
+        (Some(note_history.state_cached(&self.h, &mut self.note_cache)) == state_out)
+            .then_some(())
+            .ok_or(crate::Error::With("bad current state"))?;
+        (note_history.current_note.memo == memo_hash(&note_history.memo))
+            .then_some(())
+            .ok_or(crate::Error::With("memo does not match its commitment"))?;
+        note_history.check_receiver_tag(&self.h)?;
+
+        // steps of an asset with a policy are proven with the circuit of the
+        // policy, only its key verifies them
+        let verifier = self
+            .verifiers
+            .get(&note_history.asset.policy)
+            .ok_or(crate::Error::With("no verifier for the asset policy"))?;
+        for (proof, public_input) in statements.iter() {
+            // an error is not an invalid proof, the proof may verify later
+            verifier
+                .verify_proof_cached(&mut self.cache, proof, public_input)
+                .map_err(|_| crate::Error::With("cannot verify proof"))?
+                .then_some(())
+                .ok_or(crate::Error::With("verification failed"))?;
         }
-        self.spendables.push(note_history.clone());
+        self.accept(note_history.clone());
+        self.usage.record(KeyOp::Receive);
 
         Ok(())
     }
@@ -77,8 +248,7 @@ impl<E: IVC> Auth<E> {
         h: &PoseidonConfigs<E::Field>,
         tx: &IssueTx<E::Field>,
     ) -> Result<SealedIssueTx<E::TE>, crate::Error> {
-        let (note_hash, _) = h.note(tx.note());
-        let sighash = h.sighash(&Default::default(), &Default::default(), &note_hash);
+        let sighash = h.sighash_issue_tx(tx.note());
         let signature = self.sign(&sighash);
         Ok(tx.seal(signature))
     }
@@ -95,24 +265,544 @@ impl<E: IVC> Auth<E> {
         let nullifier = h.nullifier(&note_in, self.nullifier_key());
         Ok(tx.seal(&signature, &nullifier))
     }
+
+    // sign merge transaction and generate the nullifiers of both inputs
+    pub(crate) fn merge(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        tx: &MergeTx<E::Field>,
+    ) -> Result<SealedMergeTx<E::TE>, crate::Error> {
+        let sighash = h.sighash_merge_tx(tx);
+        let signature = self.sign(&sighash);
+        let (note_in, _) = h.note(&tx.note_in);
+        let (note_merged, _) = h.note(&tx.note_merged);
+        let nullifier = h.nullifier(&note_in, self.nullifier_key());
+        let merge_nullifier = h.nullifier(&note_merged, self.nullifier_key());
+        Ok(tx.seal(&signature, &nullifier, &merge_nullifier))
+    }
+
+    // sign burn transaction and generate the nullifier
+    pub(crate) fn burn(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        tx: &BurnTx<E::Field>,
+    ) -> Result<SealedBurnTx<E::TE>, crate::Error> {
+        let sighash = h.sighash_burn_tx(tx);
+        let signature = self.sign(&sighash);
+        let (note_in, _) = h.note(&tx.note_in);
+        let nullifier = h.nullifier(&note_in, self.nullifier_key());
+        Ok(tx.seal(&signature, &nullifier))
+    }
 }
 
 impl<E: IVC> Wallet<E> {
     pub fn new(
         auth: Auth<E>,
         poseidon: &PoseidonConfigs<E::Field>,
-        prover: Prover<E>,
+        #[cfg(feature = "prover")] prover: Prover<E>,
         verifier: Verifier<E>,
     ) -> Self {
         Self {
             spendables: vec![],
             auth,
             h: poseidon.clone(),
+            #[cfg(feature = "prover")]
             prover,
-            verifier,
+            verifiers: HashMap::from([(*verifier.policy(), verifier)]),
+            cache: VerificationCache::new(VERIFICATION_CACHE_CAPACITY),
+            note_cache: NoteCache::new(NOTE_CACHE_CAPACITY),
+            screening: None,
+            usage: KeyUsage::default(),
+            acceptance: AcceptancePolicy::default(),
+            limits: Limits::default(),
+            codecs: Codecs::default(),
+            pending: vec![],
+            epoch: None,
+            receipts: None,
+            subscriptions: vec![],
+            spent_alerts: vec![],
+            cold: vec![],
+            challenges: vec![],
+            successor: None,
+            #[cfg(feature = "prover")]
+            outcomes: HashMap::new(),
+            #[cfg(feature = "prover")]
+            calibration: Calibration::default(),
+            #[cfg(feature = "prover")]
+            step_constraints: OnceLock::new(),
+        }
+    }
+
+    // cross checks a nullifier feed against the unspent notes, returns the number of new alerts
+    pub fn check_nullifiers<'a>(
+        &mut self,
+        nullifiers: impl IntoIterator<Item = &'a Nullifier<E::Field>>,
+    ) -> usize {
+        let observed: HashSet<&Nullifier<E::Field>> = nullifiers.into_iter().collect();
+        let mut raised = 0;
+        for history in self.spendables.iter() {
+            let (note_hash, _) = self.note_cache.note(&self.h, &history.current_note);
+            let nullifier = self.h.nullifier(&note_hash, self.auth.nullifier_key());
+            let known = self.spent_alerts.iter().any(|a| a.nullifier == nullifier);
+            if observed.contains(&nullifier) && !known {
+                self.spent_alerts.push(SpentAlert {
+                    nullifier,
+                    asset_hash: history.asset.hash(&self.h),
+                    value: history.current_note.value,
+                });
+                raised += 1;
+            }
+        }
+        raised
+    }
+
+    // nullifier the note of a history owned by this wallet reveals when spent
+    pub fn nullifier(&self, history: &NoteHistory<E>) -> Nullifier<E::Field> {
+        let (note_hash, _) = self.h.note(&history.current_note);
+        self.h.nullifier(&note_hash, self.auth.nullifier_key())
+    }
+
+    // indices of the unspent notes whose nullifier a peer may have seen, a
+    // hit can be a false positive of the filter
+    pub fn possibly_spent(&self, digest: &NullifierDigest<E::Field>) -> Vec<usize> {
+        self.spendables
+            .iter()
+            .enumerate()
+            .filter(|(_, history)| digest.contains(&self.nullifier(history)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    pub fn spent_alerts(&self) -> &[SpentAlert<E::Field>] {
+        &self.spent_alerts
+    }
+
+    pub fn take_spent_alerts(&mut self) -> Vec<SpentAlert<E::Field>> {
+        std::mem::take(&mut self.spent_alerts)
+    }
+
+    // Starts a key rotation with a fresh identity and returns its keystore,
+    // which must be stored before `rotate_keys` moves any value to it. Notes
+    // waiting for confirmation or in cold storage are not moved so a rotation
+    // is refused while there are any.
+    #[cfg(feature = "prover")]
+    pub fn begin_rotation<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        password: &str,
+    ) -> Result<Vec<u8>, crate::Error> {
+        (self.pending.is_empty() && self.cold.is_empty())
+            .then_some(())
+            .ok_or(crate::Error::With(
+                "cannot rotate with pending or cold notes",
+            ))?;
+        self.successor
+            .is_none()
+            .then_some(())
+            .ok_or(crate::Error::With("key rotation in progress"))?;
+        let auth = Auth::generate(&self.h, rng).map_err(|_| crate::Error::With("bad auth"))?;
+        let keystore = auth.to_keystore(password, rng);
+        self.successor = Some((auth, vec![]));
+        Ok(keystore)
+    }
+
+    // address of the identity of the rotation in progress
+    pub fn successor(&self) -> Option<&Address<E::Field>> {
+        self.successor.as_ref().map(|(auth, _)| auth.address())
+    }
+
+    // Moves every note to the identity of `begin_rotation`, which replaces the
+    // current one once nothing is left. Notes that fail are left as they are
+    // and reported, calling it again retries them and `finish_rotation` gives
+    // up on them. The moved histories are returned so that they can be stored
+    // along with the keystore of the successor.
+    #[cfg(feature = "prover")]
+    pub fn rotate_keys<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Sweep<E>, crate::Error> {
+        (self.pending.is_empty() && self.cold.is_empty())
+            .then_some(())
+            .ok_or(crate::Error::With(
+                "cannot rotate with pending or cold notes",
+            ))?;
+        let to = *self
+            .successor()
+            .ok_or(crate::Error::With("no key rotation in progress"))?;
+
+        let mut sent = vec![];
+        let mut failed = vec![];
+        let mut index = 0;
+        while index < self.spendables.len() {
+            let value = self.spendables[index].current_note.value;
+            if value == 0 {
+                index += 1;
+                continue;
+            }
+            match self.transfer(rng, &[(to, value)], &[], index) {
+                Ok(moved) => {
+                    // drop the empty change
+                    self.spendables.remove(index);
+                    sent.extend(moved);
+                }
+                Err(err) => {
+                    failed.push(err);
+                    index += 1;
+                }
+            }
+        }
+        if let Some((_, notes)) = self.successor.as_mut() {
+            notes.extend(sent.iter().cloned());
+        }
+
+        if failed.is_empty() {
+            self.finish_rotation()?;
+        }
+        Ok(Sweep { sent, failed })
+    }
+
+    // Replaces the identity with the successor. Notes still held by the
+    // current identity are returned, they are spendable only with its keys.
+    #[cfg(feature = "prover")]
+    pub fn finish_rotation(&mut self) -> Result<Vec<NoteHistory<E>>, crate::Error> {
+        let (auth, notes) = self
+            .successor
+            .take()
+            .ok_or(crate::Error::With("no key rotation in progress"))?;
+        self.auth = auth;
+        Ok(std::mem::replace(&mut self.spendables, notes)
+            .into_iter()
+            .filter(|history| history.current_note.value > 0)
+            .collect())
+    }
+
+    // start or stop keeping receipts of the outgoing transfers
+    pub fn keep_receipts(&mut self, enabled: bool) {
+        match (enabled, self.receipts.is_some()) {
+            (true, false) => self.receipts = Some(vec![]),
+            (false, true) => self.receipts = None,
+            _ => {}
+        }
+    }
+
+    pub fn receipts(&self) -> &[Receipt<E>] {
+        self.receipts.as_deref().unwrap_or_default()
+    }
+
+    pub fn acceptance_policy(&self) -> &AcceptancePolicy<E::Field> {
+        &self.acceptance
+    }
+
+    // a lower confirmation requirement applies to the pending notes too
+    pub fn set_acceptance_policy(&mut self, policy: AcceptancePolicy<E::Field>) {
+        self.acceptance = policy;
+        self.promote();
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    // adds the verifier of the assets of a policy, it replaces the one of the
+    // same policy
+    pub fn add_verifier(&mut self, verifier: Verifier<E>) {
+        self.verifiers.insert(*verifier.policy(), verifier);
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn codecs(&self) -> &Codecs {
+        &self.codecs
+    }
+
+    pub fn set_codecs(&mut self, codecs: Codecs) {
+        self.codecs = codecs;
+    }
+
+    // hello of the wallet for a handshake, it announces the codecs the
+    // payloads sealed to the wallet can be compressed with
+    pub fn hello(&self) -> Hello {
+        let features = Feature::SUPPORTED
+            .into_iter()
+            .chain(self.codecs.features())
+            .collect::<Vec<_>>();
+        Hello::new(&[abi::VERSION], &features)
+    }
+
+    pub fn spendables(&self) -> &[NoteHistory<E>] {
+        &self.spendables
+    }
+
+    // value of the spendable notes per asset in the order assets are first seen
+    pub fn balances(&self) -> Vec<(AssetHash<E::Field>, u128)> {
+        let mut balances: Vec<(AssetHash<E::Field>, u128)> = vec![];
+        for note_history in self.spendables.iter() {
+            let asset_hash = note_history.asset.hash(&self.h);
+            let value = note_history.current_note.value as u128;
+            match balances.iter_mut().find(|(hash, _)| *hash == asset_hash) {
+                Some((_, total)) => *total += value,
+                None => balances.push((asset_hash, value)),
+            }
+        }
+        balances
+    }
+
+    pub fn balance(&self, asset_hash: &AssetHash<E::Field>) -> u128 {
+        self.spendables
+            .iter()
+            .filter(|note_history| note_history.asset.hash(&self.h) == *asset_hash)
+            .map(|note_history| note_history.current_note.value as u128)
+            .sum()
+    }
+
+    // notes in cold storage, see `cold`
+    pub fn cold_notes(&self) -> &[ColdNote<E::Field>] {
+        &self.cold
+    }
+
+    pub fn cold_balance(&self, asset_hash: &AssetHash<E::Field>) -> u128 {
+        self.cold
+            .iter()
+            .filter(|note| note.asset_hash == *asset_hash)
+            .map(|note| note.value as u128)
+            .sum()
+    }
+
+    // Transfers the spendables at `indices` in full to the offline identity
+    // `to` and returns the file that holds the histories sealed to its `key`.
+    // The file is for the wallet of the offline identity, this one keeps
+    // track of the notes as cold.
+    #[cfg(feature = "prover")]
+    pub fn export_cold<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        indices: &[usize],
+        to: &Address<E::Field>,
+        key: &EncryptionKey<E>,
+    ) -> Result<ColdExport, crate::Error> {
+        (to != self.address())
+            .then_some(())
+            .ok_or(crate::Error::With("cold identity is the wallet identity"))?;
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .last()
+            .map_or(true, |last| *last < self.spendables.len())
+            .then_some(())
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        let mut exported = vec![];
+        let mut failed = vec![];
+        for index in indices.into_iter().rev() {
+            let value = self.spendables[index].current_note.value;
+            let asset_hash = self.spendables[index].asset.hash(&self.h);
+            match self.transfer(rng, &[(*to, value)], &[], index) {
+                Ok(moved) => {
+                    // drop the empty change
+                    self.spendables.remove(index);
+                    for note_history in moved {
+                        self.cold.push(ColdNote::new(&asset_hash, &note_history));
+                        exported.push(note_history);
+                    }
+                }
+                Err(err) => failed.push(err),
+            }
+        }
+        exported.reverse();
+        Ok(ColdExport {
+            file: cold::seal(&exported, key, rng),
+            failed,
+        })
+    }
+
+    // Imports a cold storage file sealed to this wallet, histories are
+    // verified as if they were received and notes the wallet holds are
+    // skipped. Returns the number of notes added.
+    pub fn import_cold(&mut self, bytes: &[u8]) -> Result<usize, crate::Error> {
+        let mut added = 0;
+        for sealed in cold::sealed(bytes)? {
+            let note_history = self.auth.open(sealed, &self.codecs, &self.limits)?;
+            let note = ColdNote::new(&note_history.asset.hash(&self.h), &note_history);
+            let known = self
+                .spendables
+                .iter()
+                .chain(self.pending.iter().map(|pending| pending.history()))
+                .any(|held| note.is(held));
+            if !known {
+                self.receive(&note_history)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    // stops tracking a cold note, eg. once the offline wallet spent it
+    pub fn forget_cold(&mut self, index: usize) -> Option<ColdNote<E::Field>> {
+        (index < self.cold.len()).then(|| self.cold.remove(index))
+    }
+
+    // verified notes are held pending if the policy asks for confirmations
+    fn accept(&mut self, note_history: NoteHistory<E>) {
+        let pending = PendingNote::new(note_history);
+        match self.acceptance.min_confirmations {
+            Some(confirmations)
+                if confirmations > 0 && pending.nullifier != Nullifier::default() =>
+            {
+                self.pending.push(pending);
+                self.promote();
+            }
+            _ => self.spendables.push(pending.history),
+        }
+    }
+
+    // moves the confirmed notes to the spendables, returns their number
+    fn promote(&mut self) -> usize {
+        let required = self.acceptance.min_confirmations.unwrap_or(0);
+        let latest = self.epoch;
+        let (confirmed, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|note| required == 0 || note.confirmations(latest) >= required);
+        self.pending = pending;
+        let promoted = confirmed.len();
+        self.spendables
+            .extend(confirmed.into_iter().map(|note| note.history));
+        promoted
+    }
+
+    // takes in a published epoch, returns the number of notes that become
+    // spendable
+    pub fn observe_epoch(&mut self, epoch: &Epoch<E::Field>) -> usize {
+        self.pending.iter_mut().for_each(|note| note.observe(epoch));
+        self.epoch = self.epoch.max(Some(epoch.number));
+        self.promote()
+    }
+
+    pub fn latest_epoch(&self) -> Option<u64> {
+        self.epoch
+    }
+
+    pub fn pending(&self) -> &[PendingNote<E>] {
+        &self.pending
+    }
+
+    // value of the notes that are not confirmed yet, `balance` counts only
+    // the spendable ones
+    pub fn pending_balance(&self, asset_hash: &AssetHash<E::Field>) -> u128 {
+        self.pending
+            .iter()
+            .filter(|note| note.history.asset.hash(&self.h) == *asset_hash)
+            .map(|note| note.history.current_note.value as u128)
+            .sum()
+    }
+
+    // key senders seal the payloads to, published along with the address
+    pub fn encryption_key(&self) -> EncryptionKey<E> {
+        self.auth.encryption_key()
+    }
+
+    // opens a payload sealed to this wallet and receives the history in it
+    pub fn receive_sealed(&mut self, payload: &[u8]) -> Result<(), crate::Error> {
+        let note_history = self.auth.open(payload, &self.codecs, &self.limits)?;
+        self.receive(&note_history)
+    }
+
+    // key to ack a payload sealed to this wallet on a relay, see `relay`
+    pub fn ack_key(&self, payload: &[u8]) -> Result<[u8; 32], crate::Error> {
+        self.auth.ack_key(payload)
+    }
+
+    // fresh challenge to hand to a payer, see `challenge`
+    pub fn challenge(&mut self, rng: &mut impl CryptoRngCore) -> Challenge {
+        if self.challenges.len() == MAX_CHALLENGES {
+            self.challenges.remove(0);
+        }
+        let challenge = Challenge::random(rng);
+        self.challenges.push(challenge);
+        challenge
+    }
+
+    // receives a payment that answers one of the outstanding challenges and
+    // consumes the challenge
+    pub fn receive_payment(&mut self, note_history: &NoteHistory<E>) -> Result<(), crate::Error> {
+        let challenge = note_history
+            .memo()
+            .and_then(Challenge::from_memo)
+            .filter(|challenge| self.challenges.contains(challenge))
+            .ok_or(crate::Error::With("payment does not answer a challenge"))?;
+        (note_history.is_receiver_bound()
+            && note_history.current_note.out_index == NoteOutIndex::RECEIVER)
+            .then_some(())
+            .ok_or(crate::Error::With("note is not bound to its receiver"))?;
+        self.receive(note_history)?;
+        self.challenges
+            .retain(|outstanding| *outstanding != challenge);
+        Ok(())
+    }
+
+    pub fn viewing_key(&self) -> ViewingKey<E::Field> {
+        self.auth.viewing_key(&self.h)
+    }
+
+    // current notes of the spendables encrypted to the viewing key, to be
+    // handed to an auditor together with the key. The notes spent before are
+    // disclosed by the proofs, see `NoteHistory::disclosed`.
+    pub fn disclose(&self, rng: &mut impl CryptoRngCore) -> Vec<EncryptedNote<E::Field>> {
+        let viewing_key = self.viewing_key();
+        self.spendables
+            .iter()
+            .map(|note_history| viewing_key.encrypt(&self.h, &note_history.current_note, rng))
+            .collect()
+    }
+
+    // key monitors of the issuer log pin, see `transparency`
+    pub fn public_key(&self) -> &PublicKey<E::TE> {
+        self.auth.public_key()
+    }
+
+    // key an asset can be bound to with `Asset::with_issuer_key`
+    pub fn issuer_key(&self) -> IssuerKey<E::Field> {
+        self.h.issuer_key(self.auth.public_key())
+    }
+
+    pub fn sign_tree_head(&mut self, head: &TreeHead<E::Field>) -> SignedTreeHead<E> {
+        let signature = self.auth.sign(&self.h.tree_head(head.size, &head.root));
+        self.usage.record(KeyOp::Sign);
+        SignedTreeHead {
+            head: *head,
+            signature,
         }
     }
 
+    pub fn key_usage(&mut self) -> &mut KeyUsage {
+        &mut self.usage
+    }
+
+    pub fn set_screening(
+        &mut self,
+        provider: Box<dyn ScreeningProvider<E::Field>>,
+        action: HitAction,
+    ) {
+        self.screening = Some(Screening::new(provider, action));
+    }
+
+    pub fn screening_flags(&self) -> &[Flag<E::Field>] {
+        self.screening
+            .as_ref()
+            .map(|screening| screening.flags())
+            .unwrap_or_default()
+    }
+
+    pub fn verification_cache(&mut self) -> &mut VerificationCache {
+        &mut self.cache
+    }
+
+    pub fn note_cache(&mut self) -> &mut NoteCache<E::Field> {
+        &mut self.note_cache
+    }
+
+    #[cfg(feature = "prover")]
     pub fn issue<R: RngCore + CryptoRng>(
         &mut self,
         rng: &mut R,
@@ -120,29 +810,122 @@ impl<E: IVC> Wallet<E> {
         asset: &Asset<E::Field>,
         value: u64,
     ) -> Result<(), crate::Error> {
-        let asset_hash = &asset.hash();
+        let note_history =
+            self.issue_history(rng, comm_receiver.address(), asset, value, NO_EXPIRY)?;
+
+        // send the new history to the receivers
+        comm_receiver.receive(&note_history)?;
+
+        Ok(())
+    }
+
+    // issues a note that can go through at most `transfers` transfers, the
+    // notes split from it expire along with it but can still be burnt
+    #[cfg(feature = "prover")]
+    pub fn issue_expiring<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        asset: &Asset<E::Field>,
+        value: u64,
+        transfers: u32,
+    ) -> Result<(), crate::Error> {
+        let expiry_step = transfers.min(NO_EXPIRY - 1);
+        let note_history =
+            self.issue_history(rng, comm_receiver.address(), asset, value, expiry_step)?;
+        comm_receiver.receive(&note_history)?;
+        Ok(())
+    }
+
+    // issues a note to `receiver` and returns its history to deliver
+    #[cfg(feature = "prover")]
+    fn issue_history<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        receiver: &Address<E::Field>,
+        asset: &Asset<E::Field>,
+        value: u64,
+        expiry_step: u32,
+    ) -> Result<NoteHistory<E>, crate::Error> {
+        (match asset.is_key_bound() {
+            true => asset.issuer_key == self.issuer_key(),
+            false => asset.issuer == *self.address(),
+        })
+        .then_some(())
+        .ok_or(crate::Error::With("not the issuer of the asset"))?;
+        (!asset.is_dual_control() || asset.is_key_bound())
+            .then_some(())
+            .ok_or(crate::Error::With("dual control needs a key-bound asset"))?;
+        self.check_prover(asset)?;
+        let asset_hash = &asset.hash(&self.h);
+        if let Some(screening) = self.screening.as_mut() {
+            screening.screen(Operation::Issue, asset_hash, [receiver])?;
+        }
+
         // draw random blinding factor
         let blind = Blind::<E::Field>::rand(rng);
         // create new note
         let note = Note::new(
-            &asset.hash(),
-            comm_receiver.address(),
+            asset_hash,
+            receiver,
             value,
             0,
             &NoteOutIndex::Issue,
             &crate::BlindNoteHash::default(),
             blind,
+        )
+        .with_expiry_step(expiry_step);
+        let pending = self.issue_witness(rng, asset, &note)?;
+
+        // crate proof
+        let proof = self.prover.create_proof(
+            &self.h,
+            pending.public_inputs.clone(),
+            pending.aux_inputs,
+            rng,
+        )?;
+        self.usage.record(KeyOp::Prove);
+
+        // create note history
+        let step = IVCStep::new(
+            &proof,
+            &pending.public_inputs.state_out,
+            &Default::default(),
+            self.address(),
+            &pending.disclosure,
         );
+        Ok(NoteHistory {
+            asset: *asset,
+            steps: vec![step],
+            current_note: note,
+            siblings: vec![BlindNoteHash::default(); E::OUTPUTS - 1],
+            memo: vec![],
+            unknown: vec![],
+        })
+    }
+
+    // signs the issuance of the note and builds its witness, nothing is checked
+    #[cfg(feature = "prover")]
+    fn issue_witness<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        asset: &Asset<E::Field>,
+        note: &Note<E::Field>,
+    ) -> Result<PendingStep<E>, crate::Error> {
+        let asset_hash = &asset.hash(&self.h);
 
         // create the transaction
-        let tx = IssueTx::new(self.address(), &note);
+        let tx = IssueTx::new(self.address(), note);
         // and sign
         let sealed = self.auth.issue(&self.h, &tx)?;
+        self.usage.record(KeyOp::Sign);
 
         // construct public inputs
         let state_in = &asset_hash.as_ref().into();
-        let state_out = &self.h.state_out_from_issue_tx(sealed.tx());
+        let state_out = &self.h.state_out_from_issue_tx(sealed.tx(), E::OUTPUTS);
         let sender = self.address();
+        // the issued note is disclosed to the viewing key of the issuer
+        let disclosure = self.viewing_key().encrypt(&self.h, note, rng);
 
         let public_inputs = PublicInput::new(
             asset_hash,
@@ -151,48 +934,39 @@ impl<E: IVC> Wallet<E> {
             state_out,
             0,
             &Default::default(),
-        );
+            0,
+        )
+        .with_disclosure(&self.h.disclosure(&disclosure));
 
         // contruct aux inputs
-        let receiver = comm_receiver.address();
         let public_key = self.auth.public_key();
         let signature = sealed.signature();
         let nullifier_key = self.auth.nullifier_key();
         let aux_inputs: AuxInputs<E> = AuxInputs::new(
-            receiver,
             public_key,
             signature,
             nullifier_key,
             &Default::default(),
             &NoteOutIndex::Issue,
             0,
-            value,
+            &[],
             &Default::default(),
             &Default::default(),
-            &Default::default(),
-            &blind,
-        );
+            NO_EXPIRY,
+            &[Output::from(note)],
+        )
+        .with_asset(asset)
+        .with_disclosure_nonce(&disclosure.nonce);
 
-        // crate proof
-        let proof = self
-            .prover
-            .create_proof(&self.h, public_inputs, aux_inputs, rng)?;
-
-        // create note history
-        let step = IVCStep::new(&proof, state_out, &Default::default(), self.address());
-        let note_history = NoteHistory {
-            asset: *asset,
-            steps: vec![step],
-            current_note: note,
-            sibling: BlindNoteHash::default(),
-        };
-
-        // send the new history to the receivers
-        comm_receiver.receive(&note_history)?;
-
-        Ok(())
+        Ok(PendingStep {
+            public_inputs,
+            aux_inputs,
+            blind_note_hashes: vec![],
+            disclosure,
+        })
     }
 
+    #[cfg(feature = "prover")]
     pub fn split<R: RngCore + CryptoRng>(
         &mut self,
         rng: &mut R,
@@ -200,115 +974,1857 @@ impl<E: IVC> Wallet<E> {
         spendable_index: usize,
         value: u64,
     ) -> Result<(), crate::Error> {
-        let sender = *self.address();
-        let note_history = self
-            .spendables
-            .get_mut(spendable_index)
-            .ok_or(crate::Error::With("bad spendable index"))?;
-
-        let note_in = note_history.current_note;
-        let step = note_history.steps.len() as u32;
-        let asset_hash = &note_history.asset.hash();
-
-        // find output values
+        self.send_many(rng, &mut [(comm_receiver, value)], spendable_index)
+    }
 
-        let value_out_0 = note_in
-            .value
-            .checked_sub(value)
-            .ok_or(crate::Error::With("insufficient funds"))?;
-        let value_out_1 = value;
+    // splits with a memo for the receiver, eg. an invoice reference. The note
+    // commits to the memo and the plaintext travels with the history.
+    #[cfg(feature = "prover")]
+    pub fn split_with_memo<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        spendable_index: usize,
+        value: u64,
+        memo: &[u8],
+    ) -> Result<(), crate::Error> {
+        self.send(
+            rng,
+            &mut [(comm_receiver, value)],
+            &[memo.to_vec()],
+            spendable_index,
+            false,
+        )
+    }
 
-        // create change note, output 0
-        let note_out_0 = Note::new(
-            asset_hash,
-            &sender,
-            value_out_0,
-            step,
-            &NoteOutIndex::Out0,
-            &note_in.parent_note,
-            Blind::rand(rng),
-        );
+    // splits with the step bound to the receiver. The proof exposes a tag of
+    // the receiver and the blind of its note, so the receiver can show that the
+    // transfer was made to it and the note can't be passed off as paid to
+    // anyone else.
+    #[cfg(feature = "prover")]
+    pub fn split_bound<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        spendable_index: usize,
+        value: u64,
+    ) -> Result<(), crate::Error> {
+        self.send(
+            rng,
+            &mut [(comm_receiver, value)],
+            &[],
+            spendable_index,
+            true,
+        )
+    }
 
-        // crate transfer note, output 1
-        let note_out_1 = Note::new(
-            asset_hash,
-            comm_receiver.address(),
-            value_out_1,
-            step,
-            &NoteOutIndex::Out1,
-            &note_in.parent_note,
-            Blind::rand(rng),
-        );
+    // pays the merchant that handed out `challenge` with a split bound to it
+    // that commits to the challenge, the merchant takes the history with
+    // `receive_payment`
+    #[cfg(feature = "prover")]
+    pub fn pay_challenge<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        spendable_index: usize,
+        value: u64,
+        challenge: &Challenge,
+    ) -> Result<(), crate::Error> {
+        self.send(
+            rng,
+            &mut [(comm_receiver, value)],
+            &[challenge.to_memo()],
+            spendable_index,
+            true,
+        )
+    }
+
+    // pays several receivers out of a single note with one proof, the change
+    // takes an output so there can be up to `E::OUTPUTS - 1` receivers
+    #[cfg(feature = "prover")]
+    pub fn send_many<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        payments: &mut [(&mut dyn CommReceiver<E>, u64)],
+        spendable_index: usize,
+    ) -> Result<(), crate::Error> {
+        self.send(rng, payments, &[], spendable_index, false)
+    }
+
+    // `i`th memo goes to the `i`th receiver, the rest get none
+    #[cfg(feature = "prover")]
+    fn send<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        payments: &mut [(&mut dyn CommReceiver<E>, u64)],
+        memos: &[Vec<u8>],
+        spendable_index: usize,
+        bound: bool,
+    ) -> Result<(), crate::Error> {
+        let outputs: Vec<_> = payments
+            .iter()
+            .map(|(comm_receiver, value)| (*comm_receiver.address(), *value))
+            .collect();
+        let mut pending = self.prepare_transfer(rng, &outputs, memos, spendable_index)?;
+        if bound {
+            // the first sent note
+            let note = &pending.notes_out[NoteOutIndex::RECEIVER.position()];
+            let receiver_tag = self.h.receiver_tag(&note.owner, &note.blind);
+            pending.public_inputs = pending.public_inputs.with_receiver_tag(&receiver_tag);
+        }
+        let note_histories = self.prove_transfer(rng, pending)?;
+
+        for ((comm_receiver, _), note_history) in payments.iter_mut().zip(note_histories.iter()) {
+            comm_receiver.receive(note_history)?;
+
+            if let Some(receipts) = self.receipts.as_mut() {
+                receipts.push(Receipt::new(&self.h, note_history)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Idempotent versions of `issue`, `split` and `send_many`. The outcome is
+    // kept under the caller's key, a retry with the same key doesn't spend or
+    // issue again but delivers the histories that are not delivered yet. The
+    // outcomes survive a restart through `export_outcomes`.
+    #[cfg(feature = "prover")]
+    pub fn issue_idempotent<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        key: &str,
+        comm_receiver: &mut impl CommReceiver<E>,
+        asset: &Asset<E::Field>,
+        value: u64,
+    ) -> Result<(), crate::Error> {
+        let receiver = *comm_receiver.address();
+        let request = Request::Issue(asset.hash(&self.h), receiver, value);
+        if self.check_outcome(key, &request)? {
+            let note_history = self.issue_history(rng, &receiver, asset, value, NO_EXPIRY)?;
+            self.keep_outcome(key, request, vec![note_history]);
+        }
+        self.deliver(key, |_, note_history| comm_receiver.receive(note_history))
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn split_idempotent<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        key: &str,
+        comm_receiver: &mut impl CommReceiver<E>,
+        spendable_index: usize,
+        value: u64,
+    ) -> Result<(), crate::Error> {
+        self.send_many_idempotent(rng, key, &mut [(comm_receiver, value)], spendable_index)
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn send_many_idempotent<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        key: &str,
+        payments: &mut [(&mut dyn CommReceiver<E>, u64)],
+        spendable_index: usize,
+    ) -> Result<(), crate::Error> {
+        let outputs: Vec<_> = payments
+            .iter()
+            .map(|(comm_receiver, value)| (*comm_receiver.address(), *value))
+            .collect();
+        let request = Request::Send(outputs.clone());
+        if self.check_outcome(key, &request)? {
+            let note_histories = self.transfer(rng, &outputs, &[], spendable_index)?;
+            self.keep_outcome(key, request, note_histories);
+        }
+        self.deliver(key, |i, note_history| payments[i].0.receive(note_history))
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn outcome(&self, key: &str) -> Option<&Outcome<E>> {
+        self.outcomes.get(key)
+    }
+
+    // drops the outcome once the caller won't retry anymore
+    #[cfg(feature = "prover")]
+    pub fn forget_outcome(&mut self, key: &str) -> Option<Outcome<E>> {
+        self.outcomes.remove(key)
+    }
+
+    // true if the operation is not done yet under the key
+    #[cfg(feature = "prover")]
+    fn check_outcome(&self, key: &str, request: &Request<E::Field>) -> Result<bool, crate::Error> {
+        match self.outcomes.get(key) {
+            Some(outcome) => (outcome.request == *request)
+                .then_some(false)
+                .ok_or(crate::Error::With("idempotency key reused")),
+            None => Ok(true),
+        }
+    }
+
+    #[cfg(feature = "prover")]
+    fn keep_outcome(&mut self, key: &str, request: Request<E::Field>, sent: Vec<NoteHistory<E>>) {
+        let delivered = vec![false; sent.len()];
+        let outcome = Outcome {
+            request,
+            sent,
+            delivered,
+        };
+        self.outcomes.insert(key.to_string(), outcome);
+    }
+
+    // delivers the histories of the outcome that are not delivered yet, `i`th
+    // history goes to the `i`th receiver
+    #[cfg(feature = "prover")]
+    fn deliver(
+        &mut self,
+        key: &str,
+        mut receive: impl FnMut(usize, &NoteHistory<E>) -> Result<(), crate::Error>,
+    ) -> Result<(), crate::Error> {
+        let outcome = self
+            .outcomes
+            .get_mut(key)
+            .ok_or(crate::Error::With("idempotency key reused"))?;
+        let is_issue = matches!(outcome.request, Request::Issue(..));
+        let pending = outcome
+            .sent
+            .iter()
+            .zip(outcome.delivered.iter_mut())
+            .enumerate()
+            .filter(|(_, (_, delivered))| !**delivered);
+        for (i, (note_history, delivered)) in pending {
+            receive(i, note_history)?;
+            *delivered = true;
+
+            if let Some(receipts) = self.receipts.as_mut().filter(|_| !is_issue) {
+                receipts.push(Receipt::new(&self.h, note_history)?);
+            }
+        }
+        Ok(())
+    }
+
+    // splits the note and returns the histories to send, the change stays at the same index
+    #[cfg(feature = "prover")]
+    fn transfer<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        payments: &[(Address<E::Field>, u64)],
+        memos: &[Vec<u8>],
+        spendable_index: usize,
+    ) -> Result<Vec<NoteHistory<E>>, crate::Error> {
+        let pending = self.prepare_transfer(rng, payments, memos, spendable_index)?;
+        self.prove_transfer(rng, pending)
+    }
+
+    // the circuit of the prover binds the asset hash to its policy
+    #[cfg(feature = "prover")]
+    fn check_prover(&self, asset: &Asset<E::Field>) -> Result<(), crate::Error> {
+        (self.prover.policy() == asset.policy)
+            .then_some(())
+            .ok_or(crate::Error::With("prover does not match the asset policy"))
+    }
+
+    #[cfg(feature = "prover")]
+    fn prove_transfer<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        pending: PendingTransfer<E>,
+    ) -> Result<Vec<NoteHistory<E>>, crate::Error> {
+        // crate proof
+        let proof = self.prover.create_proof(
+            &self.h,
+            pending.public_inputs.clone(),
+            pending.aux_inputs.clone(),
+            rng,
+        )?;
+
+        Ok(self.finish_transfer(pending, &proof))
+    }
+
+    // signs the split, the note history is updated only once it is proven
+    #[cfg(feature = "prover")]
+    fn prepare_transfer<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        payments: &[(Address<E::Field>, u64)],
+        memos: &[Vec<u8>],
+        spendable_index: usize,
+    ) -> Result<PendingTransfer<E>, crate::Error> {
+        let sender = *self.address();
+        let note_history = self
+            .spendables
+            .get(spendable_index)
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        (payments.len() < E::OUTPUTS)
+            .then_some(())
+            .ok_or(crate::Error::With("too many receivers"))?;
+        memos
+            .iter()
+            .all(|memo| memo.len() <= MAX_MEMO)
+            .then_some(())
+            .ok_or(crate::Error::With("memo too long"))?;
+
+        let note_in = note_history.current_note;
+        let step = note_history.steps.len() as u32;
+        (step <= note_in.expiry_step)
+            .then_some(())
+            .ok_or(crate::Error::With("note is expired"))?;
+        self.check_prover(&note_history.asset)?;
+        let asset_hash = &note_history.asset.hash(&self.h);
+        let (_, parent) = self.h.note(&note_in);
+
+        // find output values
+
+        let value_out = payments
+            .iter()
+            .try_fold(0u64, |sum, (_, value)| sum.checked_add(*value))
+            .ok_or(crate::Error::With("amount overflow"))?;
+        let change = note_in
+            .value
+            .checked_sub(value_out)
+            .ok_or(crate::Error::With("insufficient funds"))?;
+
+        // change note is output 1 as the circuit requires, transfer notes
+        // take the other outputs in order and the rest is empty
+        let mut outputs: Vec<_> = payments
+            .iter()
+            .zip(memos.iter().map(Some).chain(std::iter::repeat(None)))
+            .map(|(&(owner, value), memo)| (owner, value, memo))
+            .chain(std::iter::repeat((sender, 0, None)))
+            .take(E::OUTPUTS - 1)
+            .collect();
+        outputs.insert(NoteOutIndex::CHANGE.position(), (sender, change, None));
+        let notes_out: Vec<_> = outputs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (owner, value, memo))| {
+                Note::new(
+                    asset_hash,
+                    &owner,
+                    value,
+                    step,
+                    &NoteOutIndex::Out(i as u8),
+                    &parent,
+                    Blind::rand(rng),
+                )
+                .with_memo(&memo_hash(memo.map_or(&[], Vec::as_slice)))
+                .with_expiry_step(note_in.expiry_step)
+            })
+            .collect();
+
+        let pending = self.split_witness(rng, spendable_index, &notes_out)?;
+
+        Ok(PendingTransfer {
+            spendable_index,
+            public_inputs: pending.public_inputs,
+            aux_inputs: pending.aux_inputs,
+            sent: payments.len(),
+            memos: memos.to_vec(),
+            notes_out,
+            blind_note_hashes: pending.blind_note_hashes,
+            disclosure: pending.disclosure,
+        })
+    }
+
+    // signs the split of the note into the outputs and builds its witness,
+    // nothing is checked
+    #[cfg(feature = "prover")]
+    fn split_witness<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        spendable_index: usize,
+        notes_out: &[Note<E::Field>],
+    ) -> Result<PendingStep<E>, crate::Error> {
+        let sender = *self.address();
+        let note_history = self
+            .spendables
+            .get(spendable_index)
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        let note_in = note_history.current_note;
+        let step = note_history.steps.len() as u32;
+        let asset_hash = &note_history.asset.hash(&self.h);
 
         // create the transaction
-        let tx = SplitTx::new(&note_in, &note_out_0, &note_out_1);
+        let tx = SplitTx::new(&note_in, notes_out);
         // and sign and generate the nullifier
         let sealed = self.auth.split(&self.h, &tx)?;
+        self.usage.record(KeyOp::Sign);
 
         // construct public inputs
-        let state_in = &note_history.state(&self.h);
-        let (_, blind_note_hash_0) = self.h.note(sealed.note_out_0());
-        let (_, blind_note_hash_1) = self.h.note(sealed.note_out_1());
-        let state_out = &self.h.state(&blind_note_hash_0, &blind_note_hash_1);
+        let state_in = &note_history.state_cached(&self.h, &mut self.note_cache);
+        let blind_note_hashes = self.h.blind_notes(sealed.notes_out());
+        let state_out = &self.h.state(&blind_note_hashes);
+        // the input note is disclosed to the viewing key of the sender
+        let disclosure = self.viewing_key().encrypt(&self.h, &note_in, rng);
 
         let public_inputs = PublicInput::new(
             asset_hash,
             &sender,
             state_in,
             state_out,
+            step,
+            sealed.nullifier(),
             0,
-            &Default::default(),
-        );
+        )
+        .with_disclosure(&self.h.disclosure(&disclosure));
 
-        let receiver = comm_receiver.address();
         let public_key = self.auth.public_key();
         let signature = sealed.signature();
         let nullifier_key = self.auth.nullifier_key();
-        let parent = &note_in.parent_note;
-        let input_index = &note_in.out_index; // TODO: issue index is not good for first split tx?
-        let value_in = note_in.value;
-        let value_out = value_out_1;
-        let sibling = &note_history.sibling;
+        let outputs: Vec<_> = notes_out.iter().map(Output::from).collect();
         let aux_inputs: AuxInputs<E> = AuxInputs::new(
-            receiver,
             public_key,
             signature,
             nullifier_key,
-            parent,
-            input_index,
-            value_in,
-            value_out,
-            sibling,
+            &note_in.parent_note,
+            &note_in.out_index,
+            note_in.value,
+            &note_history.siblings,
             &note_in.blind,
-            &note_out_0.blind,
-            &note_out_1.blind,
-        );
+            &note_in.memo,
+            note_in.expiry_step,
+            &outputs,
+        )
+        .with_asset(&note_history.asset)
+        .with_disclosure_nonce(&disclosure.nonce);
 
-        // crate proof
-        let proof = self
-            .prover
-            .create_proof(&self.h, public_inputs, aux_inputs, rng)?;
+        Ok(PendingStep {
+            public_inputs,
+            aux_inputs,
+            blind_note_hashes,
+            disclosure,
+        })
+    }
+
+    #[cfg(feature = "prover")]
+    fn finish_transfer(
+        &mut self,
+        pending: PendingTransfer<E>,
+        proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+    ) -> Vec<NoteHistory<E>> {
+        self.usage.record(KeyOp::Prove);
+        let sender = *self.address();
+        let note_history = &mut self.spendables[pending.spendable_index];
 
         // update note history
 
         // add the new step
-        let step = IVCStep::new(&proof, state_out, sealed.nullifier(), &sender);
+        let step = IVCStep::new(
+            proof,
+            &pending.public_inputs.state_out,
+            &pending.public_inputs.nullifier,
+            &sender,
+            &pending.disclosure,
+        )
+        .with_receiver_tag(&pending.public_inputs.receiver_tag);
 
         note_history.steps.push(step);
 
         // update the leading notes
+        let siblings = |i: usize| {
+            let mut siblings = pending.blind_note_hashes.clone();
+            siblings.remove(i);
+            siblings
+        };
+
+        // history to keep
+        let change = NoteOutIndex::CHANGE.position();
+        note_history.current_note = pending.notes_out[change];
+        note_history.siblings = siblings(change);
+        note_history.memo = vec![];
+
+        // histories to send
+        (0..pending.sent)
+            .map(|i| {
+                let position = NoteOutIndex::sent_position(i);
+                let mut sent = note_history.clone();
+                sent.current_note = pending.notes_out[position];
+                sent.siblings = siblings(position);
+                sent.memo = pending.memos.get(i).cloned().unwrap_or_default();
+                sent
+            })
+            .collect()
+    }
+
+    // Spends every note in full to `to`, eg. a new identity after a compromise.
+    // Proofs are generated on `threads` threads. Notes that fail are left as
+    // they are so that the sweep can be retried, the sent histories are
+    // returned for delivery.
+    #[cfg(feature = "prover")]
+    pub fn sweep_all<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        to: &Address<E::Field>,
+        threads: usize,
+        mut progress: impl FnMut(SweepProgress),
+    ) -> Result<Sweep<E>, crate::Error>
+    where
+        <<E as IVC>::Snark as SNARK<E::Field>>::Proof: Send,
+    {
+        let notes: Vec<(usize, u64)> = self
+            .spendables
+            .iter()
+            .enumerate()
+            .filter(|(_, history)| history.current_note.value > 0)
+            .map(|(index, history)| (index, history.current_note.value))
+            .collect();
+        let total = notes.len();
+        let mut failed = vec![];
+
+        let mut pending = vec![];
+        for (index, value) in notes {
+            match self.prepare_transfer(rng, &[(*to, value)], &[], index) {
+                Ok(transfer) => pending.push(transfer),
+                Err(err) => failed.push(err),
+            }
+        }
+
+        // each proof has its own rng seeded from the caller's
+        let mut rngs = pending
+            .iter()
+            .map(|_| StdRng::from_rng(&mut *rng))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| crate::Error::With("bad rng"))?;
+
+        let mut proofs = Vec::with_capacity(pending.len());
+        let mut done = failed.len();
+        for (batch, rngs) in pending
+            .chunks(threads.max(1))
+            .zip(rngs.chunks_mut(threads.max(1)))
+        {
+            let (prover, h) = (&self.prover, &self.h);
+            let results = std::thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter()
+                    .zip(rngs.iter_mut())
+                    .map(|(transfer, rng)| {
+                        scope.spawn(move || {
+                            prover.create_proof(
+                                h,
+                                transfer.public_inputs.clone(),
+                                transfer.aux_inputs.clone(),
+                                rng,
+                            )
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or(Err(crate::Error::With("proof generation failed")))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            for result in results {
+                done += 1;
+                progress(SweepProgress { done, total });
+                proofs.push(result);
+            }
+        }
+
+        let mut sent = vec![];
+        let mut emptied = vec![];
+        for (transfer, proof) in pending.into_iter().zip(proofs) {
+            match proof {
+                Ok(proof) => {
+                    emptied.push(transfer.spendable_index);
+                    sent.extend(self.finish_transfer(transfer, &proof));
+                }
+                Err(err) => failed.push(err),
+            }
+        }
+
+        // drop the empty change notes
+        emptied.sort_unstable_by(|a, b| b.cmp(a));
+        emptied.into_iter().for_each(|index| {
+            self.spendables.remove(index);
+        });
+
+        Ok(Sweep { sent, failed })
+    }
+
+    // consolidates two notes of the same asset into one, the longer history
+    // carries on and the other one is merged into its next step
+    #[cfg(feature = "prover")]
+    pub fn merge<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        first: usize,
+        second: usize,
+    ) -> Result<(), crate::Error> {
+        let sender = *self.address();
+        let len = self.spendables.len();
+        (first < len && second < len && first != second)
+            .then_some(())
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        let (kept, merged) =
+            match self.spendables[first].steps.len() >= self.spendables[second].steps.len() {
+                true => (first, second),
+                false => (second, first),
+            };
+        let note_history = &self.spendables[kept];
+        let merged_history = &self.spendables[merged];
+        self.check_prover(&note_history.asset)?;
+        let asset_hash = &note_history.asset.hash(&self.h);
+        (merged_history.asset.hash(&self.h) == *asset_hash)
+            .then_some(())
+            .ok_or(crate::Error::With("cannot merge different assets"))?;
+
+        let note_in = note_history.current_note;
+        let note_merged = merged_history.current_note;
+        (note_in.expiry_step == note_merged.expiry_step)
+            .then_some(())
+            .ok_or(crate::Error::With("cannot merge notes of different expiry"))?;
+        let value = note_in
+            .value
+            .checked_add(note_merged.value)
+            .ok_or(crate::Error::With("amount overflow"))?;
+        let step = note_history.steps.len() as u32;
+        // the merged note is spent at the next step of its own history
+        let merged_step = merged_history.steps.len() as u32;
+        (step <= note_in.expiry_step && merged_step <= note_merged.expiry_step)
+            .then_some(())
+            .ok_or(crate::Error::With("note is expired"))?;
+        let (_, parent) = self.h.note(&note_in);
+
+        // merged note is the change and the rest is empty
+        let change = NoteOutIndex::CHANGE.position();
+        let notes_out: Vec<_> = (0..E::OUTPUTS)
+            .map(|i| {
+                Note::new(
+                    asset_hash,
+                    &sender,
+                    match i == change {
+                        true => value,
+                        false => 0,
+                    },
+                    step,
+                    &NoteOutIndex::Out(i as u8),
+                    &parent,
+                    Blind::rand(rng),
+                )
+                .with_expiry_step(note_in.expiry_step)
+            })
+            .collect();
+
+        let pending = self.merge_witness(rng, kept, merged, &notes_out)?;
+
+        // crate proof
+        let proof = self.prover.create_proof(
+            &self.h,
+            pending.public_inputs.clone(),
+            pending.aux_inputs,
+            rng,
+        )?;
+        self.usage.record(KeyOp::Prove);
+
+        // both notes are spent, the merged history goes into the new step
+        let public_inputs = &pending.public_inputs;
+        let step = IVCStep::new(
+            &proof,
+            &public_inputs.state_out,
+            &public_inputs.nullifier,
+            &sender,
+            &pending.disclosure,
+        )
+        .with_merged(&self.spendables[merged], &public_inputs.merge_nullifier);
+        let note_history = &mut self.spendables[kept];
+        note_history.steps.push(step);
+        let mut siblings = pending.blind_note_hashes;
+        siblings.remove(change);
+        note_history.current_note = notes_out[change];
+        note_history.siblings = siblings;
+        note_history.memo = vec![];
+        self.spendables.remove(merged);
+
+        Ok(())
+    }
+
+    // signs the merge of the note of `merged` into the note of `kept` and
+    // builds its witness, nothing is checked
+    #[cfg(feature = "prover")]
+    fn merge_witness<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        kept: usize,
+        merged: usize,
+        notes_out: &[Note<E::Field>],
+    ) -> Result<PendingStep<E>, crate::Error> {
+        let sender = *self.address();
+        let note_history = &self.spendables[kept];
+        let merged_history = &self.spendables[merged];
+        let note_in = note_history.current_note;
+        let note_merged = merged_history.current_note;
+        let step = note_history.steps.len() as u32;
+        let asset_hash = &note_history.asset.hash(&self.h);
+
+        // create the transaction
+        let tx = MergeTx::new(&note_in, &note_merged, notes_out);
+        // and sign and generate the nullifiers
+        let sealed = self.auth.merge(&self.h, &tx)?;
+        self.usage.record(KeyOp::Sign);
+
+        // construct public inputs
+        let state_in = &note_history.state_cached(&self.h, &mut self.note_cache);
+        let merge_state_in = &merged_history.state_cached(&self.h, &mut self.note_cache);
+        let blind_note_hashes = self.h.blind_notes(sealed.notes_out());
+        let state_out = &self.h.state(&blind_note_hashes);
+        // the first input note is disclosed to the viewing key of the sender
+        let disclosure = self.viewing_key().encrypt(&self.h, &note_in, rng);
+
+        let public_inputs = PublicInput::new(
+            asset_hash,
+            &sender,
+            state_in,
+            state_out,
+            step,
+            sealed.nullifier(),
+            0,
+        )
+        .with_merge(merge_state_in, sealed.merge_nullifier())
+        .with_disclosure(&self.h.disclosure(&disclosure));
+
+        let public_key = self.auth.public_key();
+        let signature = sealed.signature();
+        let nullifier_key = self.auth.nullifier_key();
+        let outputs: Vec<_> = notes_out.iter().map(Output::from).collect();
+        let aux_inputs: AuxInputs<E> = AuxInputs::new(
+            public_key,
+            signature,
+            nullifier_key,
+            &note_in.parent_note,
+            &note_in.out_index,
+            note_in.value,
+            &note_history.siblings,
+            &note_in.blind,
+            &note_in.memo,
+            note_in.expiry_step,
+            &outputs,
+        )
+        .with_asset(&note_history.asset)
+        .with_merge(&MergeInput::new(&note_merged, &merged_history.siblings))
+        .with_disclosure_nonce(&disclosure.nonce);
+
+        Ok(PendingStep {
+            public_inputs,
+            aux_inputs,
+            blind_note_hashes,
+            disclosure,
+        })
+    }
+
+    // merges the two smallest notes of the asset until at most `keep` of them
+    // are left, returns the number of merges
+    #[cfg(feature = "prover")]
+    pub fn consolidate<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        asset_hash: &AssetHash<E::Field>,
+        keep: usize,
+    ) -> Result<usize, crate::Error> {
+        let mut merges = 0;
+        loop {
+            let mut notes: Vec<(usize, u64)> = self
+                .spendables
+                .iter()
+                .enumerate()
+                .filter(|(_, note_history)| note_history.asset.hash(&self.h) == *asset_hash)
+                .map(|(index, note_history)| (index, note_history.current_note.value))
+                .collect();
+            if notes.len() <= keep.max(1) {
+                return Ok(merges);
+            }
+            notes.sort_by_key(|(_, value)| *value);
+            self.merge(rng, notes[0].0, notes[1].0)?;
+            merges += 1;
+        }
+    }
+
+    // destroys the whole note, the record is handed to the issuer or to an auditor
+    #[cfg(feature = "prover")]
+    pub fn burn<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        spendable_index: usize,
+    ) -> Result<BurnRecord<E>, crate::Error> {
+        self.destroy(rng, spendable_index, &SettlementRef::default(), None)
+    }
+
+    // destroys the whole note so that the issuer pays it out to `settlement`
+    // on the external settlement layer
+    #[cfg(feature = "prover")]
+    pub fn redeem<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        spendable_index: usize,
+        settlement: &SettlementRef<E::Field>,
+    ) -> Result<BurnRecord<E>, crate::Error> {
+        (*settlement != SettlementRef::default())
+            .then_some(())
+            .ok_or(crate::Error::With("empty settlement reference"))?;
+        self.destroy(rng, spendable_index, settlement, None)
+    }
+
+    // what the issuer co-signs to redeem a note of a dual-control asset
+    pub fn redemption_request(
+        &self,
+        spendable_index: usize,
+        settlement: &SettlementRef<E::Field>,
+    ) -> Result<RedemptionRequest<E::Field>, crate::Error> {
+        (*settlement != SettlementRef::default())
+            .then_some(())
+            .ok_or(crate::Error::With("empty settlement reference"))?;
+        let note_history = self
+            .spendables
+            .get(spendable_index)
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        let (note, _) = self.h.note(&note_history.current_note);
+        Ok(RedemptionRequest {
+            asset_hash: note_history.asset.hash(&self.h),
+            note,
+            value: note_history.current_note.value,
+            settlement: *settlement,
+        })
+    }
+
+    // co-signs the redemption of a note of a dual-control asset bound to the
+    // issuer key of this wallet
+    pub fn cosign_redemption(
+        &mut self,
+        asset: &Asset<E::Field>,
+        request: &RedemptionRequest<E::Field>,
+    ) -> Result<Cosignature<E>, crate::Error> {
+        asset
+            .is_dual_control()
+            .then_some(())
+            .ok_or(crate::Error::With("asset is not under dual control"))?;
+        (asset.issuer_key == self.issuer_key())
+            .then_some(())
+            .ok_or(crate::Error::With("not the issuer of the asset"))?;
+        (request.asset_hash == asset.hash(&self.h))
+            .then_some(())
+            .ok_or(crate::Error::With("redemption request of another asset"))?;
+        let signature = self.auth.sign(&request.sighash(&self.h));
+        self.usage.record(KeyOp::Sign);
+        Ok(Cosignature {
+            public_key: self.auth.public_key().clone(),
+            signature,
+        })
+    }
+
+    // redeems the whole note of a dual-control asset with the co-signature of
+    // its issuer
+    #[cfg(feature = "prover")]
+    pub fn redeem_cosigned<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        spendable_index: usize,
+        settlement: &SettlementRef<E::Field>,
+        cosignature: &Cosignature<E>,
+    ) -> Result<BurnRecord<E>, crate::Error> {
+        let request = self.redemption_request(spendable_index, settlement)?;
+        let asset = &self.spendables[spendable_index].asset;
+        asset
+            .is_dual_control()
+            .then_some(())
+            .ok_or(crate::Error::With("asset is not under dual control"))?;
+        cosignature.verify(&self.h, asset, &request)?;
+        self.destroy(rng, spendable_index, settlement, Some(cosignature))
+    }
+
+    #[cfg(feature = "prover")]
+    fn destroy<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        spendable_index: usize,
+        settlement: &SettlementRef<E::Field>,
+        cosignature: Option<&Cosignature<E>>,
+    ) -> Result<BurnRecord<E>, crate::Error> {
+        let sender = *self.address();
+        let note_history = self
+            .spendables
+            .get(spendable_index)
+            .ok_or(crate::Error::With("bad spendable index"))?;
+
+        let note_in = note_history.current_note;
+        (note_in.value != 0)
+            .then_some(())
+            .ok_or(crate::Error::With("nothing to burn"))?;
+        self.check_prover(&note_history.asset)?;
+        (!note_history.asset.is_dual_control()
+            || *settlement == SettlementRef::default()
+            || cosignature.is_some())
+        .then_some(())
+        .ok_or(crate::Error::With(
+            "redemption needs the co-signature of the issuer",
+        ))?;
+        let pending = self.burn_witness(rng, spendable_index, settlement, cosignature)?;
+
+        // crate proof
+        let proof = self.prover.create_proof(
+            &self.h,
+            pending.public_inputs.clone(),
+            pending.aux_inputs,
+            rng,
+        )?;
+        self.usage.record(KeyOp::Prove);
+
+        // the note is spent
+        let note_history = self.spendables.remove(spendable_index);
+        let public_inputs = &pending.public_inputs;
+        let step = IVCStep::new(
+            &proof,
+            &public_inputs.state_out,
+            &public_inputs.nullifier,
+            &sender,
+            &pending.disclosure,
+        );
+        Ok(BurnRecord::new(
+            &note_history,
+            step,
+            note_in.value,
+            settlement,
+        ))
+    }
+
+    // signs the burn of the note and builds its witness, nothing is checked
+    #[cfg(feature = "prover")]
+    fn burn_witness<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        spendable_index: usize,
+        settlement: &SettlementRef<E::Field>,
+        cosignature: Option<&Cosignature<E>>,
+    ) -> Result<PendingStep<E>, crate::Error> {
+        let sender = *self.address();
+        let note_history = self
+            .spendables
+            .get(spendable_index)
+            .ok_or(crate::Error::With("bad spendable index"))?;
+        let note_in = note_history.current_note;
+        let step = note_history.steps.len() as u32;
+        let asset_hash = &note_history.asset.hash(&self.h);
+
+        // create the transaction
+        let tx = BurnTx::new(&note_in, settlement);
+        // and sign and generate the nullifier
+        let sealed = self.auth.burn(&self.h, &tx)?;
+        self.usage.record(KeyOp::Sign);
+
+        // construct public inputs, no state is left after a burn
+        let state_in = &note_history.state_cached(&self.h, &mut self.note_cache);
+        let state_out = &StateHash::default();
+        // the burnt note is disclosed to the viewing key of the sender
+        let disclosure = self.viewing_key().encrypt(&self.h, &note_in, rng);
+        let public_inputs = PublicInput::new(
+            asset_hash,
+            &sender,
+            state_in,
+            state_out,
+            step,
+            sealed.nullifier(),
+            sealed.tx().value(),
+        )
+        .with_settlement(settlement)
+        .with_disclosure(&self.h.disclosure(&disclosure));
+
+        let public_key = self.auth.public_key();
+        let signature = sealed.signature();
+        let nullifier_key = self.auth.nullifier_key();
+        let aux_inputs: AuxInputs<E> = AuxInputs::new(
+            public_key,
+            signature,
+            nullifier_key,
+            &note_in.parent_note,
+            &note_in.out_index,
+            note_in.value,
+            &note_history.siblings,
+            &note_in.blind,
+            &note_in.memo,
+            note_in.expiry_step,
+            &[],
+        )
+        .with_asset(&note_history.asset)
+        .with_disclosure_nonce(&disclosure.nonce);
+        let aux_inputs = match cosignature {
+            Some(cosignature) => aux_inputs.with_cosignature(cosignature),
+            None => aux_inputs,
+        };
 
-        // 0. history to keep
-        let note_history_0 = note_history;
-        note_history_0.current_note = note_out_0;
-        note_history_0.sibling = blind_note_hash_1;
+        Ok(PendingStep {
+            public_inputs,
+            aux_inputs,
+            blind_note_hashes: vec![],
+            disclosure,
+        })
+    }
 
-        // 1. history to send
-        let mut note_history_1 = note_history_0.clone();
-        note_history_1.current_note = note_out_1;
-        note_history_1.sibling = blind_note_hash_0;
-        comm_receiver.receive(&note_history_1)?;
+    // authorize `payee` to collect up to `cap` once per `period` seconds from `start`
+    pub fn authorize(
+        &mut self,
+        payee: &Address<E::Field>,
+        asset_hash: &AssetHash<E::Field>,
+        cap: u64,
+        period: u64,
+        start: u64,
+    ) -> Result<SignedAuthorization<E>, crate::Error> {
+        (cap > 0 && period > 0)
+            .then_some(())
+            .ok_or(crate::Error::With("bad authorization"))?;
+        let authorization = Authorization {
+            payer: *self.address(),
+            payee: *payee,
+            asset_hash: *asset_hash,
+            cap,
+            period,
+            start,
+        };
+        let signature = self.auth.sign(&self.h.authorization(&authorization));
+        self.usage.record(KeyOp::Sign);
+        let signed = SignedAuthorization {
+            authorization,
+            public_key: self.auth.public_key().clone(),
+            signature,
+        };
+        self.subscriptions.push(Subscription::new(signed.clone()));
+        Ok(signed)
+    }
 
+    pub fn cancel_authorization(&mut self, id: &SigHash<E::Field>) -> Result<(), crate::Error> {
+        let index = self.subscription_index(id)?;
+        self.subscriptions.remove(index);
         Ok(())
     }
+
+    // authorizations with an unpaid installment at `now`
+    pub fn due(&self, now: u64) -> Vec<&SignedAuthorization<E>> {
+        self.subscriptions
+            .iter()
+            .filter(|subscription| subscription.due(now).is_some())
+            .map(|subscription| &subscription.authorization)
+            .collect()
+    }
+
+    fn subscription_index(&self, id: &SigHash<E::Field>) -> Result<usize, crate::Error> {
+        self.subscriptions
+            .iter()
+            .position(|subscription| subscription.authorization.id(&self.h) == *id)
+            .ok_or(crate::Error::With("unknown authorization"))
+    }
+
+    // pays the installment of the current period out of a single note
+    #[cfg(feature = "prover")]
+    pub fn pay_installment<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        id: &SigHash<E::Field>,
+        value: u64,
+        now: u64,
+    ) -> Result<Installment<E>, crate::Error> {
+        let index = self.subscription_index(id)?;
+        let subscription = &self.subscriptions[index];
+        let authorization = subscription.authorization.authorization;
+        let period = subscription
+            .due(now)
+            .ok_or(crate::Error::With("installment is not due"))?;
+        (authorization.payee == *comm_receiver.address())
+            .then_some(())
+            .ok_or(crate::Error::With("not the payee"))?;
+        (value <= authorization.cap)
+            .then_some(())
+            .ok_or(crate::Error::With("installment exceeds the cap"))?;
+
+        let spendable_index = self
+            .spendables
+            .iter()
+            .position(|history| {
+                history.asset.hash(&self.h) == authorization.asset_hash
+                    && history.current_note.value >= value
+            })
+            .ok_or(crate::Error::With("insufficient funds"))?;
+        self.split(rng, comm_receiver, spendable_index, value)?;
+
+        let subscription = &mut self.subscriptions[index];
+        subscription.paid = Some(period);
+        Ok(Installment {
+            authorization: subscription.authorization.clone(),
+            period,
+            value,
+        })
+    }
+
+    // pays the full cap of every due authorization of the receiver
+    #[cfg(feature = "prover")]
+    pub fn pay_due<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        now: u64,
+    ) -> Result<Vec<Installment<E>>, crate::Error> {
+        let due: Vec<(SigHash<E::Field>, u64)> = self
+            .due(now)
+            .into_iter()
+            .filter(|signed| signed.authorization.payee == *comm_receiver.address())
+            .map(|signed| (signed.id(&self.h), signed.authorization.cap))
+            .collect();
+        due.into_iter()
+            .map(|(id, cap)| self.pay_installment(rng, comm_receiver, &id, cap, now))
+            .collect()
+    }
+
+    pub fn plan(
+        &self,
+        selector: &impl CoinSelector<E>,
+        value: u64,
+    ) -> Result<Vec<PlannedSplit>, crate::Error> {
+        selector.plan(&self.spendables, value)
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    // constraints of a step, counted once
+    #[cfg(feature = "prover")]
+    pub(crate) fn step_constraints(&self) -> usize {
+        *self
+            .step_constraints
+            .get_or_init(|| estimate::step_constraints::<E>(&self.h))
+    }
+
+    #[cfg(feature = "prover")]
+    pub fn pay<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        comm_receiver: &mut impl CommReceiver<E>,
+        selector: &impl CoinSelector<E>,
+        value: u64,
+    ) -> Result<Payment, crate::Error> {
+        // change notes stay at the same index so the plan remains valid while executing
+        let plan = self.plan(selector, value)?;
+        let mut sent = vec![];
+        for split in plan {
+            if let Err(err) = self.split(rng, comm_receiver, split.index, split.value) {
+                return Ok(Payment {
+                    sent,
+                    failed: Some(err),
+                });
+            }
+            sent.push(split);
+        }
+        Ok(Payment { sent, failed: None })
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::Terms,
+        circuit::{mock, mock::Mock, policy::AssetPolicyCircuit, policy::SplitVars, Circuit},
+        select::MinDepth,
+    };
+    use ark_bn254::Fr;
+    use ark_r1cs_std::{
+        alloc::AllocVar, boolean::Boolean, eq::EqGadget, fields::fp::FpVar,
+        select::CondSelectGadget,
+    };
+    use ark_relations::r1cs::{
+        ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, Result as CSResult,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::cmp::Ordering;
+
+    // sends at most the cap to others in a split
+    struct Cap(u64);
+
+    impl AssetPolicyCircuit<Fr> for Cap {
+        fn commitment(&self) -> PolicyHash<Fr> {
+            Fr::from(self.0).into()
+        }
+
+        fn enforce(
+            &self,
+            cs: ConstraintSystemRef<Fr>,
+            split: &SplitVars<Fr>,
+            enabled: &Boolean<Fr>,
+        ) -> CSResult<()> {
+            let cap = FpVar::new_constant(cs.clone(), Fr::from(self.0))?;
+            let zero = FpVar::new_constant(cs, Fr::from(0))?;
+            for output in split.outputs.iter() {
+                let sent = enabled.and(&output.owner.is_neq(&split.sender)?)?;
+                let value = FpVar::conditionally_select(&sent, &output.value, &zero)?;
+                value.enforce_cmp(&cap, Ordering::Less, true)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn setup() -> (PoseidonConfigs<Fr>, StdRng) {
+        (PoseidonConfigs::generate(), StdRng::seed_from_u64(1))
+    }
+
+    fn asset_of(issuer: &Wallet<Mock>) -> Asset<Fr> {
+        Asset::new(issuer.address(), &Terms::iou(1, 1))
+    }
+
+    fn with_policy(h: &PoseidonConfigs<Fr>, rng: &mut StdRng, policy: Cap) -> Wallet<Mock> {
+        let auth = Auth::generate(h, rng).unwrap();
+        let verifier = Verifier::new(()).with_policy(&policy.commitment());
+        let prover = Prover::new(()).with_policy(Box::new(policy));
+        Wallet::new(auth, h, prover, verifier)
+    }
+
+    // alice holds a note of `value` issued by the issuer
+    fn funded(
+        h: &PoseidonConfigs<Fr>,
+        rng: &mut StdRng,
+        value: u64,
+    ) -> (Wallet<Mock>, Wallet<Mock>, Asset<Fr>) {
+        let mut issuer = mock::wallet(h, rng);
+        let mut alice = mock::wallet(h, rng);
+        let asset = asset_of(&issuer);
+        issuer.issue(rng, &mut alice, &asset, value).unwrap();
+        (issuer, alice, asset)
+    }
+
+    fn satisfied(
+        h: &PoseidonConfigs<Fr>,
+        public_inputs: &PublicInput<Fr>,
+        aux_inputs: &AuxInputs<Mock>,
+        policy: Option<&dyn AssetPolicyCircuit<Fr>>,
+    ) -> bool {
+        let cs = ConstraintSystem::new_ref();
+        let circuit = Circuit::new(h, public_inputs.clone(), aux_inputs.clone());
+        let circuit = match policy {
+            Some(policy) => circuit.with_policy(policy),
+            None => circuit,
+        };
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn policy_is_enforced_in_split() {
+        let (h, mut rng) = setup();
+        let mut issuer = with_policy(&h, &mut rng, Cap(50));
+        let mut alice = with_policy(&h, &mut rng, Cap(50));
+        let bob = mock::wallet(&h, &mut rng);
+        let asset = asset_of(&issuer).with_policy(&Cap(50));
+        issuer.issue(&mut rng, &mut alice, &asset, 100).unwrap();
+
+        let policy: &dyn AssetPolicyCircuit<Fr> = &Cap(50);
+        let pending = alice
+            .prepare_transfer(&mut rng, &[(*bob.address(), 50)], &[], 0)
+            .unwrap();
+        let (public_inputs, aux_inputs) = (&pending.public_inputs, &pending.aux_inputs);
+        assert!(satisfied(&h, public_inputs, aux_inputs, Some(policy)));
+        // the asset hash commits to the policy, the circuit of another policy
+        // or of none can't spend it
+        assert!(!satisfied(&h, public_inputs, aux_inputs, None));
+        assert!(!satisfied(&h, public_inputs, aux_inputs, Some(&Cap(60))));
+
+        let pending = alice
+            .prepare_transfer(&mut rng, &[(*bob.address(), 51)], &[], 0)
+            .unwrap();
+        let (public_inputs, aux_inputs) = (&pending.public_inputs, &pending.aux_inputs);
+        assert!(!satisfied(&h, public_inputs, aux_inputs, Some(policy)));
+    }
+
+    #[test]
+    fn policy_needs_its_verifier_and_prover() {
+        let (h, mut rng) = setup();
+        let mut issuer = with_policy(&h, &mut rng, Cap(50));
+        let mut alice = with_policy(&h, &mut rng, Cap(50));
+        let mut bob = mock::wallet(&h, &mut rng);
+        let asset = asset_of(&issuer).with_policy(&Cap(50));
+
+        assert_eq!(
+            issuer.issue(&mut rng, &mut bob, &asset, 100).unwrap_err(),
+            crate::Error::With("no verifier for the asset policy")
+        );
+        assert_eq!(
+            bob.issue(&mut rng, &mut alice, &asset, 100).unwrap_err(),
+            crate::Error::With("not the issuer of the asset")
+        );
+        let asset = Asset::new(bob.address(), &Terms::iou(1, 1)).with_policy(&Cap(50));
+        assert_eq!(
+            bob.issue(&mut rng, &mut alice, &asset, 100).unwrap_err(),
+            crate::Error::With("prover does not match the asset policy")
+        );
+
+        bob.add_verifier(Verifier::new(()).with_policy(&Cap(50).commitment()));
+        let asset = asset_of(&issuer).with_policy(&Cap(50));
+        issuer.issue(&mut rng, &mut bob, &asset, 100).unwrap();
+        assert_eq!(bob.balance(&asset.hash(&h)), 100);
+    }
+
+    #[test]
+    fn change_is_the_second_output() {
+        let (h, mut rng) = setup();
+        let (_, mut alice, _) = funded(&h, &mut rng, 100);
+        let bob = mock::wallet(&h, &mut rng);
+
+        let pending = alice
+            .prepare_transfer(&mut rng, &[(*bob.address(), 30)], &[], 0)
+            .unwrap();
+        let receiver = &pending.notes_out[NoteOutIndex::RECEIVER.position()];
+        let change = &pending.notes_out[NoteOutIndex::CHANGE.position()];
+        assert_eq!((receiver.owner, receiver.value), (*bob.address(), 30));
+        assert_eq!((change.owner, change.value), (*alice.address(), 70));
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        // the sender keeps output 0 and sends output 1
+        let mut notes_out = pending.notes_out.clone();
+        notes_out.swap(0, 1);
+        for (i, note) in notes_out.iter_mut().enumerate() {
+            note.out_index = NoteOutIndex::Out(i as u8);
+        }
+        let pending = alice.split_witness(&mut rng, 0, &notes_out).unwrap();
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+    }
+
+    // outputs of a step that spends the note of `index`, owners and values
+    // are in the order of the positions
+    fn notes_out(
+        wallet: &Wallet<Mock>,
+        rng: &mut StdRng,
+        index: usize,
+        outputs: &[(Address<Fr>, u64)],
+    ) -> Vec<Note<Fr>> {
+        let note_history = &wallet.spendables[index];
+        let note_in = note_history.current_note;
+        let (_, parent) = wallet.h.note(&note_in);
+        outputs
+            .iter()
+            .enumerate()
+            .map(|(i, (owner, value))| {
+                Note::new(
+                    &note_history.asset.hash(&wallet.h),
+                    owner,
+                    *value,
+                    note_history.steps.len() as u32,
+                    &NoteOutIndex::Out(i as u8),
+                    &parent,
+                    Blind::rand(rng),
+                )
+                .with_expiry_step(note_in.expiry_step)
+            })
+            .collect()
+    }
+
+    // outputs of a merge into the note of `kept` that leave `value` in the change
+    fn merged_notes(
+        wallet: &Wallet<Mock>,
+        rng: &mut StdRng,
+        kept: usize,
+        value: u64,
+    ) -> Vec<Note<Fr>> {
+        let sender = *wallet.address();
+        notes_out(wallet, rng, kept, &[(sender, 0), (sender, value)])
+    }
+
+    #[test]
+    fn merge_conserves_the_sum_of_one_asset() {
+        let (h, mut rng) = setup();
+        let (mut issuer, mut alice, asset) = funded(&h, &mut rng, 100);
+        issuer.issue(&mut rng, &mut alice, &asset, 50).unwrap();
+        let other = Asset::new(issuer.address(), &Terms::iou(2, 1));
+        issuer.issue(&mut rng, &mut alice, &other, 50).unwrap();
+
+        let notes_out = merged_notes(&alice, &mut rng, 0, 150);
+        let pending = alice.merge_witness(&mut rng, 0, 1, &notes_out).unwrap();
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        // merged value is not the sum
+        for value in [149, 151] {
+            let notes_out = merged_notes(&alice, &mut rng, 0, value);
+            let pending = alice.merge_witness(&mut rng, 0, 1, &notes_out).unwrap();
+            assert!(!satisfied(
+                &h,
+                &pending.public_inputs,
+                &pending.aux_inputs,
+                None
+            ));
+        }
+
+        // the second note is of another asset
+        let notes_out = merged_notes(&alice, &mut rng, 0, 150);
+        let pending = alice.merge_witness(&mut rng, 0, 2, &notes_out).unwrap();
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        // the same note twice reuses its nullifier
+        let notes_out = merged_notes(&alice, &mut rng, 0, 200);
+        let pending = alice.merge_witness(&mut rng, 0, 0, &notes_out).unwrap();
+        assert_eq!(
+            pending.public_inputs.nullifier,
+            pending.public_inputs.merge_nullifier
+        );
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+    }
+
+    #[test]
+    fn burn_leaves_no_state_and_burns_the_note_value() {
+        let (h, mut rng) = setup();
+        let (_, mut alice, _) = funded(&h, &mut rng, 100);
+        let settlement = SettlementRef::default();
+
+        let pending = alice.burn_witness(&mut rng, 0, &settlement, None).unwrap();
+        assert_eq!(pending.public_inputs.burn_value, 100);
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        // a burn leaves a state behind
+        let mut public_inputs = pending.public_inputs.clone();
+        public_inputs.state_out = Fr::from(1).into();
+        assert!(!satisfied(&h, &public_inputs, &pending.aux_inputs, None));
+
+        // the public value is not the value of the note, signed as such
+        let (note_in, _) = alice.h.note(&alice.spendables[0].current_note);
+        for (value, expected) in [(100, true), (99, false), (101, false)] {
+            let mut public_inputs = pending.public_inputs.clone();
+            public_inputs.burn_value = value;
+            let mut aux_inputs = pending.aux_inputs.clone();
+            aux_inputs.signature = alice.auth.sign(&h.sighash_burn(&note_in, value));
+            assert_eq!(satisfied(&h, &public_inputs, &aux_inputs, None), expected);
+        }
+    }
+
+    #[test]
+    fn expired_note_is_not_spent() {
+        let (h, mut rng) = setup();
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut alice = mock::wallet(&h, &mut rng);
+        let mut bob = mock::wallet(&h, &mut rng);
+        let asset = asset_of(&issuer);
+        // alice spends it at step 1 which is the expiry step
+        issuer
+            .issue_expiring(&mut rng, &mut alice, &asset, 100, 1)
+            .unwrap();
+        alice.split(&mut rng, &mut bob, 0, 30).unwrap();
+        assert_eq!(bob.spendables[0].current_note.expiry_step, 1);
+
+        // bob would spend it at step 2
+        assert_eq!(
+            bob.prepare_transfer(&mut rng, &[(*alice.address(), 10)], &[], 0)
+                .err(),
+            Some(crate::Error::With("note is expired"))
+        );
+        let notes = notes_out(
+            &bob,
+            &mut rng,
+            0,
+            &[(*alice.address(), 10), (*bob.address(), 20)],
+        );
+        let pending = bob.split_witness(&mut rng, 0, &notes).unwrap();
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        // burns are not bound by the expiry
+        let settlement = SettlementRef::default();
+        let pending = bob.burn_witness(&mut rng, 0, &settlement, None).unwrap();
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+    }
+
+    #[test]
+    fn note_without_expiry_is_spent_at_any_step() {
+        let (h, mut rng) = setup();
+        let (_, mut alice, _) = funded(&h, &mut rng, 100);
+        let mut bob = mock::wallet(&h, &mut rng);
+        alice.split(&mut rng, &mut bob, 0, 30).unwrap();
+        bob.split(&mut rng, &mut alice, 0, 10).unwrap();
+        assert_eq!(bob.spendables[0].current_note.expiry_step, NO_EXPIRY);
+
+        // u32::MAX less the step is in the range of the u64 witness
+        let pending = bob
+            .prepare_transfer(&mut rng, &[(*alice.address(), 10)], &[], 0)
+            .unwrap();
+        assert_eq!(pending.public_inputs.step, 3);
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+    }
+
+    #[test]
+    fn spent_note_is_disclosed_to_the_viewing_key() {
+        let (h, mut rng) = setup();
+        let (issuer, mut alice, _) = funded(&h, &mut rng, 100);
+        let mut bob = mock::wallet(&h, &mut rng);
+        let note_in = alice.spendables[0].current_note;
+        alice.split(&mut rng, &mut bob, 0, 30).unwrap();
+
+        // the proven step discloses the spent note to alice only
+        let disclosure = &bob.spendables[0].steps.last().unwrap().disclosure;
+        assert_eq!(alice.viewing_key().decrypt(&h, disclosure), Ok(note_in));
+        assert!(bob.viewing_key().decrypt(&h, disclosure).is_err());
+        assert!(issuer.viewing_key().decrypt(&h, disclosure).is_err());
+
+        // the nonce of the disclosure is the one the circuit encrypts with
+        let pending = alice
+            .prepare_transfer(&mut rng, &[(*bob.address(), 10)], &[], 0)
+            .unwrap();
+        let mut aux_inputs = pending.aux_inputs.clone();
+        aux_inputs.disclosure_nonce += Fr::from(1);
+        assert!(!satisfied(&h, &pending.public_inputs, &aux_inputs, None));
+        let mut disclosure = pending.disclosure.clone();
+        disclosure.nonce += Fr::from(1);
+        assert!(alice.viewing_key().decrypt(&h, &disclosure).is_err());
+    }
+
+    #[test]
+    fn key_bound_asset_is_issued_with_its_key() {
+        let (h, mut rng) = setup();
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut mallory = mock::wallet(&h, &mut rng);
+        let alice = mock::wallet(&h, &mut rng);
+        let asset = asset_of(&issuer).with_issuer_key(&issuer.issuer_key());
+        let note = Note::new(
+            &asset.hash(&h),
+            alice.address(),
+            100,
+            0,
+            &NoteOutIndex::Issue,
+            &BlindNoteHash::default(),
+            Blind::rand(&mut rng),
+        );
+
+        let pending = issuer.issue_witness(&mut rng, &asset, &note).unwrap();
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        // signed with another key, claimed as key bound or not
+        let mut pending = mallory.issue_witness(&mut rng, &asset, &note).unwrap();
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+        pending.aux_inputs.key_bound = false;
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+    }
+
+    #[test]
+    fn dual_control_redemption_is_cosigned_by_the_issuer_key() {
+        let (h, mut rng) = setup();
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut alice = mock::wallet(&h, &mut rng);
+        let mut mallory = mock::wallet(&h, &mut rng);
+        let settlement = SettlementRef::from(Fr::from(7));
+
+        // the co-signature is checked against the key the asset is bound to
+        let asset = asset_of(&issuer).with_dual_control();
+        assert_eq!(
+            issuer.issue(&mut rng, &mut alice, &asset, 100).unwrap_err(),
+            crate::Error::With("dual control needs a key-bound asset")
+        );
+        let asset = asset.with_issuer_key(&issuer.issuer_key());
+        issuer.issue(&mut rng, &mut alice, &asset, 100).unwrap();
+        issuer.issue(&mut rng, &mut alice, &asset, 50).unwrap();
+
+        assert_eq!(
+            alice.redeem(&mut rng, 0, &settlement).unwrap_err(),
+            crate::Error::With("redemption needs the co-signature of the issuer")
+        );
+        let request = alice.redemption_request(0, &settlement).unwrap();
+        assert_eq!(
+            mallory.cosign_redemption(&asset, &request).err(),
+            Some(crate::Error::With("not the issuer of the asset"))
+        );
+        let forged = Cosignature {
+            public_key: mallory.auth.public_key().clone(),
+            signature: mallory.auth.sign(&request.sighash(&h)),
+        };
+        assert_eq!(
+            alice
+                .redeem_cosigned(&mut rng, 0, &settlement, &forged)
+                .unwrap_err(),
+            crate::Error::With("bad co-signature")
+        );
+
+        // nor does the circuit take a redemption without the issuer key
+        let cosignature = issuer.cosign_redemption(&asset, &request).unwrap();
+        for cosignature in [None, Some(&forged)] {
+            let pending = alice
+                .burn_witness(&mut rng, 0, &settlement, cosignature)
+                .unwrap();
+            assert!(!satisfied(
+                &h,
+                &pending.public_inputs,
+                &pending.aux_inputs,
+                None
+            ));
+        }
+        let mut pending = alice
+            .burn_witness(&mut rng, 0, &settlement, Some(&cosignature))
+            .unwrap();
+        assert!(satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+        // the asset hash commits to dual control
+        pending.aux_inputs.dual_control = false;
+        assert!(!satisfied(
+            &h,
+            &pending.public_inputs,
+            &pending.aux_inputs,
+            None
+        ));
+
+        let record = alice
+            .redeem_cosigned(&mut rng, 0, &settlement, &cosignature)
+            .unwrap();
+        record.verify(&h, &Verifier::new(())).unwrap();
+        assert_eq!(record.value(), 100);
+
+        // a plain burn pays nothing out and needs no co-signature
+        let record = alice.burn(&mut rng, 0).unwrap();
+        record.verify(&h, &Verifier::new(())).unwrap();
+    }
+
+    // plans the splits it is given
+    struct Fixed(Vec<PlannedSplit>);
+
+    impl CoinSelector<Mock> for Fixed {
+        fn plan(
+            &self,
+            _spendables: &[NoteHistory<Mock>],
+            _value: u64,
+        ) -> Result<Vec<PlannedSplit>, crate::Error> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn payment_keeps_the_change_at_the_index_of_its_note() {
+        let (h, mut rng) = setup();
+        let (mut issuer, mut alice, asset) = funded(&h, &mut rng, 30);
+        let mut bob = mock::wallet(&h, &mut rng);
+        for value in [20, 10] {
+            issuer.issue(&mut rng, &mut alice, &asset, value).unwrap();
+        }
+        let values = |wallet: &Wallet<Mock>| {
+            wallet
+                .spendables
+                .iter()
+                .map(|history| history.current_note.value)
+                .collect::<Vec<_>>()
+        };
+
+        // the larger notes pay, the change of each stays where its note was
+        let plan = alice.plan(&MinDepth, 45).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                PlannedSplit {
+                    index: 0,
+                    value: 30
+                },
+                PlannedSplit {
+                    index: 1,
+                    value: 15
+                },
+            ]
+        );
+        let payment = alice.pay(&mut rng, &mut bob, &MinDepth, 45).unwrap();
+        assert_eq!(payment.sent, plan);
+        assert_eq!(payment.failed, None);
+        assert_eq!(values(&alice), vec![0, 5, 10]);
+        assert_eq!(values(&bob), vec![30, 15]);
+
+        // a failed split stops the payment with what is sent before it
+        let plan = vec![
+            PlannedSplit { index: 2, value: 4 },
+            PlannedSplit { index: 1, value: 6 },
+            PlannedSplit { index: 2, value: 6 },
+        ];
+        let payment = alice
+            .pay(&mut rng, &mut bob, &Fixed(plan.clone()), 16)
+            .unwrap();
+        assert_eq!(payment.sent, plan[..1]);
+        assert_eq!(
+            payment.failed,
+            Some(crate::Error::With("insufficient funds"))
+        );
+        assert_eq!(values(&alice), vec![0, 5, 6]);
+        assert_eq!(values(&bob), vec![30, 15, 4]);
+    }
+
+    // fails the first delivery, as a transport that drops the connection
+    struct Flaky {
+        wallet: Wallet<Mock>,
+        fail: bool,
+    }
+
+    impl CommReceiver<Mock> for Flaky {
+        fn receive(&mut self, history: &NoteHistory<Mock>) -> Result<(), crate::Error> {
+            match std::mem::take(&mut self.fail) {
+                true => Err(crate::Error::With("relay is unreachable")),
+                false => self.wallet.receive(history),
+            }
+        }
+
+        fn address(&self) -> &Address<Fr> {
+            self.wallet.address()
+        }
+    }
+
+    #[test]
+    fn idempotent_retry_returns_the_stored_outcome() {
+        let (h, mut rng) = setup();
+        let (mut issuer, mut alice, asset) = funded(&h, &mut rng, 100);
+        let mut bob = Flaky {
+            wallet: mock::wallet(&h, &mut rng),
+            fail: true,
+        };
+        let mut carol = mock::wallet(&h, &mut rng);
+        let values = |wallet: &Wallet<Mock>| {
+            wallet
+                .spendables
+                .iter()
+                .map(|history| history.current_note.value)
+                .collect::<Vec<_>>()
+        };
+
+        // the note is spent once, a retry only delivers what is not delivered
+        assert_eq!(
+            alice
+                .split_idempotent(&mut rng, "split", &mut bob, 0, 30)
+                .unwrap_err(),
+            crate::Error::With("relay is unreachable")
+        );
+        assert_eq!(values(&alice), vec![70]);
+        assert!(!alice.outcome("split").unwrap().is_delivered());
+        let sent = alice.outcome("split").unwrap().sent()[0].to_bytes();
+        for _ in 0..2 {
+            alice
+                .split_idempotent(&mut rng, "split", &mut bob, 0, 30)
+                .unwrap();
+            assert_eq!(values(&alice), vec![70]);
+            assert_eq!(values(&bob.wallet), vec![30]);
+            let outcome = alice.outcome("split").unwrap();
+            assert!(outcome.is_delivered());
+            assert_eq!(outcome.sent()[0].to_bytes(), sent);
+        }
+
+        // and so for the receivers of `send_many`
+        let mut payments: [(&mut dyn CommReceiver<Mock>, u64); 1] = [(&mut carol, 20)];
+        for _ in 0..2 {
+            alice
+                .send_many_idempotent(&mut rng, "many", &mut payments, 0)
+                .unwrap();
+        }
+        assert_eq!(values(&alice), vec![50]);
+        assert_eq!(values(&carol), vec![20]);
+        assert!(alice.outcome("many").unwrap().is_delivered());
+
+        // a key is bound to what it was first asked for
+        let reused = crate::Error::With("idempotency key reused");
+        assert_eq!(
+            alice
+                .split_idempotent(&mut rng, "split", &mut bob, 0, 31)
+                .unwrap_err(),
+            reused
+        );
+        assert_eq!(
+            alice
+                .split_idempotent(&mut rng, "split", &mut carol, 0, 30)
+                .unwrap_err(),
+            reused
+        );
+        let mut payments: [(&mut dyn CommReceiver<Mock>, u64); 1] = [(&mut bob, 20)];
+        assert_eq!(
+            alice
+                .send_many_idempotent(&mut rng, "many", &mut payments, 0)
+                .unwrap_err(),
+            reused
+        );
+        // nor does the key of a split take other receivers
+        assert_eq!(
+            alice
+                .send_many_idempotent(&mut rng, "split", &mut payments, 0)
+                .unwrap_err(),
+            reused
+        );
+        issuer
+            .issue_idempotent(&mut rng, "issue", &mut alice, &asset, 5)
+            .unwrap();
+        assert_eq!(
+            issuer
+                .issue_idempotent(&mut rng, "issue", &mut carol, &asset, 5)
+                .unwrap_err(),
+            reused
+        );
+        assert_eq!(values(&alice), vec![50, 5]);
+        assert_eq!(values(&bob.wallet), vec![30]);
+        assert_eq!(values(&carol), vec![20]);
+
+        // a forgotten key is free again
+        alice.forget_outcome("split").unwrap();
+        alice
+            .split_idempotent(&mut rng, "split", &mut carol, 0, 30)
+            .unwrap();
+        assert_eq!(values(&alice), vec![20, 5]);
+        assert_eq!(values(&carol), vec![20, 30]);
+    }
 }
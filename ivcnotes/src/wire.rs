@@ -0,0 +1,624 @@
+use crate::{
+    asset::{Asset, Terms},
+    circuit::IVC,
+    handshake::Reader,
+    id::{EncryptedNote, PLAINTEXT_LEN},
+    limits::Limits,
+    note::{IVCStep, Note, NoteHistory, NoteOutIndex, MAX_MEMO, NO_EXPIRY},
+    FWrap, IssuerKey, MemoHash, ReceiverTag,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+// Canonical encoding of a note history to exchange between wallets.
+//
+// magic | version | asset | steps | current note | siblings | records
+//
+// Field elements, points and proofs are in the compressed arkworks encoding,
+// integers are little endian and lists are prefixed with their u32 length. A
+// step is its proof, output state, nullifier, sender, merge nullifier, the
+// steps of the merged history and the disclosed note as its nonce, ciphertext
+// and tag. Public inputs are not written, they are rebuilt
+// from the chain of states when the history is verified, and signatures are
+// witnesses of the proofs.
+//
+// The version is bumped on any change of the layout. Decoders reject versions
+// they don't know, newer wallets keep encoding the versions older ones read
+// for as long as they support them.
+//
+// Fields added within a version go to the trailing records, each one is
+// `tag: u16 | len: u32 | bytes` in increasing order of tags. A record this
+// version doesn't know is kept in the history and written back as it is, so a
+// wallet that is downgraded doesn't drop what a newer one stored. A tag with
+// the critical bit set changes the meaning of the history, a decoder that
+// doesn't know it must reject the history instead.
+//
+// - memo, critical: memo hash of the current note | plaintext of the memo.
+//   Written only if the note commits to a memo.
+// - expiry, critical: expiry step of the current note as u32. Written only if
+//   the note expires.
+// - issuer key, critical: key the asset is bound to. Written only if the asset
+//   is bound to a key.
+// - receiver tags, critical: u32 count | (u32 position | tag) of the steps
+//   bound to their receivers, positions are in the order of the proofs of the
+//   history with merged steps before the step that merges them. Written only
+//   if a step is bound.
+// - dual control, critical: empty. Written only if redemptions of the asset
+//   need the co-signature of the issuer key, the asset is then key-bound.
+
+const MAGIC: &[u8; 4] = b"IVCW";
+pub const VERSION: u8 = 2;
+pub const CRITICAL: u16 = 0x8000;
+const MEMO: u16 = CRITICAL | 1;
+const EXPIRY: u16 = CRITICAL | 2;
+const ISSUER_KEY: u16 = CRITICAL | 3;
+const RECEIVER_TAGS: u16 = CRITICAL | 4;
+const DUAL_CONTROL: u16 = CRITICAL | 5;
+// tags of the records this version reads
+const KNOWN: &[u16] = &[MEMO, EXPIRY, ISSUER_KEY, RECEIVER_TAGS, DUAL_CONTROL];
+
+// version of an encoded history, none if it is not one
+pub fn version(bytes: &[u8]) -> Option<u8> {
+    (bytes.len() > 4 && bytes[..4] == *MAGIC).then(|| bytes[4])
+}
+
+pub(crate) fn put<T: CanonicalSerialize>(bytes: &mut Vec<u8>, value: &T) {
+    value.serialize_compressed(bytes).unwrap();
+}
+
+pub(crate) fn get<T: CanonicalDeserialize>(reader: &mut Reader) -> Option<T> {
+    T::deserialize_compressed(&mut reader.0).ok()
+}
+
+fn put_steps<E: IVC>(bytes: &mut Vec<u8>, steps: &[IVCStep<E>]) {
+    bytes.extend_from_slice(&(steps.len() as u32).to_le_bytes());
+    for step in steps.iter() {
+        put(bytes, &step.proof);
+        put(bytes, &step.state.inner());
+        put(bytes, &step.nullifier.inner());
+        put(bytes, &step.sender.inner());
+        put(bytes, &step.merge_nullifier.inner());
+        put_steps(bytes, &step.merged);
+        put(bytes, &step.disclosure.nonce);
+        bytes.extend_from_slice(&(step.disclosure.ciphertext.len() as u32).to_le_bytes());
+        step.disclosure
+            .ciphertext
+            .iter()
+            .for_each(|element| put(bytes, element));
+        put(bytes, &step.disclosure.tag);
+    }
+}
+
+// stops at the first step over the limits, as `Limits::check_steps` does
+fn get_steps<E: IVC>(
+    reader: &mut Reader,
+    limits: &Limits,
+    depth: usize,
+    proofs: &mut usize,
+) -> Option<Vec<IVCStep<E>>> {
+    let n = reader.u32()? as usize;
+    (n == 0 || depth <= limits.max_merge_depth).then_some(())?;
+    *proofs += n;
+    (*proofs <= limits.max_proofs).then_some(())?;
+    let mut steps = vec![];
+    for _ in 0..n {
+        let proof = get(reader)?;
+        let state = get::<E::Field>(reader)?.into();
+        let nullifier = get::<E::Field>(reader)?.into();
+        let sender = get::<E::Field>(reader)?.into();
+        let merge_nullifier = get::<E::Field>(reader)?.into();
+        let merged = get_steps(reader, limits, depth + 1, proofs)?;
+        let nonce = get(reader)?;
+        (reader.u32()? as usize == PLAINTEXT_LEN).then_some(())?;
+        let ciphertext = (0..PLAINTEXT_LEN)
+            .map(|_| get(reader))
+            .collect::<Option<Vec<_>>>()?;
+        let tag = get(reader)?;
+        steps.push(IVCStep {
+            proof,
+            state,
+            nullifier,
+            sender,
+            merged,
+            merge_nullifier,
+            receiver_tag: ReceiverTag::default(),
+            disclosure: EncryptedNote {
+                nonce,
+                ciphertext,
+                tag,
+            },
+        });
+    }
+    Some(steps)
+}
+
+// receiver tags of the steps in the order of their proofs
+fn receiver_tags<E: IVC>(steps: &[IVCStep<E>], tags: &mut Vec<ReceiverTag<E::Field>>) {
+    for step in steps.iter() {
+        receiver_tags(&step.merged, tags);
+        tags.push(step.receiver_tag);
+    }
+}
+
+fn set_receiver_tags<E: IVC>(
+    steps: &mut [IVCStep<E>],
+    tags: &mut impl Iterator<Item = ReceiverTag<E::Field>>,
+) {
+    for step in steps.iter_mut() {
+        set_receiver_tags(&mut step.merged, tags);
+        step.receiver_tag = tags.next().unwrap_or_default();
+    }
+}
+
+// encodes in the current version
+pub fn encode<E: IVC>(history: &NoteHistory<E>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+
+    let asset = &history.asset;
+    put(&mut bytes, &asset.issuer.inner());
+    match asset.terms {
+        Terms::IOU { maturity, unit } => {
+            bytes.push(0);
+            bytes.extend_from_slice(&maturity.to_le_bytes());
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+    }
+    put(&mut bytes, &asset.policy.inner());
+
+    put_steps(&mut bytes, &history.steps);
+
+    let note = &history.current_note;
+    put(&mut bytes, &note.asset_hash.inner());
+    put(&mut bytes, &note.owner.inner());
+    bytes.extend_from_slice(&note.value.to_le_bytes());
+    bytes.extend_from_slice(&note.step.to_le_bytes());
+    put(&mut bytes, &note.parent_note.inner());
+    bytes.push((&note.out_index).into());
+    put(&mut bytes, &note.blind.inner());
+
+    bytes.extend_from_slice(&(history.siblings.len() as u32).to_le_bytes());
+    history
+        .siblings
+        .iter()
+        .for_each(|sibling| put(&mut bytes, &sibling.inner()));
+
+    let mut records = history.unknown.clone();
+    if note.memo != MemoHash::default() {
+        let mut record = vec![];
+        put(&mut record, &note.memo.inner());
+        record.extend_from_slice(&history.memo);
+        records.push((MEMO, record));
+    }
+    if note.expiry_step != NO_EXPIRY {
+        records.push((EXPIRY, note.expiry_step.to_le_bytes().to_vec()));
+    }
+    let mut tags = vec![];
+    receiver_tags(&history.steps, &mut tags);
+    let bound = tags
+        .iter()
+        .enumerate()
+        .filter(|(_, tag)| **tag != ReceiverTag::default())
+        .collect::<Vec<_>>();
+    if !bound.is_empty() {
+        let mut record = (bound.len() as u32).to_le_bytes().to_vec();
+        for (position, tag) in bound {
+            record.extend_from_slice(&(position as u32).to_le_bytes());
+            put(&mut record, &tag.inner());
+        }
+        records.push((RECEIVER_TAGS, record));
+    }
+    if history.asset.is_key_bound() {
+        let mut record = vec![];
+        put(&mut record, &history.asset.issuer_key.inner());
+        records.push((ISSUER_KEY, record));
+    }
+    if history.asset.is_dual_control() {
+        records.push((DUAL_CONTROL, vec![]));
+    }
+    records.sort_by_key(|(tag, _)| *tag);
+    for (tag, record) in records.iter() {
+        bytes.extend_from_slice(&tag.to_le_bytes());
+        bytes.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(record);
+    }
+    bytes
+}
+
+// decodes a history from an untrusted source, it is not verified
+pub fn decode<E: IVC>(bytes: &[u8], limits: &Limits) -> Result<NoteHistory<E>, crate::Error> {
+    let err = crate::Error::With("bad wire encoding");
+    match version(bytes).ok_or(err)? {
+        VERSION => {}
+        _ => return Err(crate::Error::With("unsupported wire version")),
+    }
+    let mut reader = Reader(&bytes[5..]);
+    let mut history = read_v2(&mut reader, limits).ok_or(err)?;
+    let mut records = read_records(&mut reader, limits)?;
+    if let Some(i) = records.iter().position(|(tag, _)| *tag == MEMO) {
+        let (_, record) = records.remove(i);
+        let mut reader = Reader(&record);
+        let memo = get::<E::Field>(&mut reader).ok_or(err)?.into();
+        (memo != MemoHash::default() && reader.0.len() <= MAX_MEMO)
+            .then_some(())
+            .ok_or(err)?;
+        history.current_note.memo = memo;
+        history.memo = reader.0.to_vec();
+    }
+    if let Some(i) = records.iter().position(|(tag, _)| *tag == EXPIRY) {
+        let (_, record) = records.remove(i);
+        let expiry_step = record.try_into().map(u32::from_le_bytes).map_err(|_| err)?;
+        (expiry_step != NO_EXPIRY).then_some(()).ok_or(err)?;
+        history.current_note.expiry_step = expiry_step;
+    }
+    if let Some(i) = records.iter().position(|(tag, _)| *tag == ISSUER_KEY) {
+        let (_, record) = records.remove(i);
+        let mut reader = Reader(&record);
+        let issuer_key = get::<E::Field>(&mut reader).ok_or(err)?.into();
+        (issuer_key != IssuerKey::default() && reader.0.is_empty())
+            .then_some(())
+            .ok_or(err)?;
+        history.asset.issuer_key = issuer_key;
+    }
+    if let Some(i) = records.iter().position(|(tag, _)| *tag == DUAL_CONTROL) {
+        let (_, record) = records.remove(i);
+        (record.is_empty() && history.asset.is_key_bound())
+            .then_some(())
+            .ok_or(err)?;
+        history.asset.dual_control = true;
+    }
+    if let Some(i) = records.iter().position(|(tag, _)| *tag == RECEIVER_TAGS) {
+        let (_, record) = records.remove(i);
+        let mut tags = vec![];
+        receiver_tags(&history.steps, &mut tags);
+        let mut reader = Reader(&record);
+        let n = reader.u32().ok_or(err)? as usize;
+        (n > 0 && n <= tags.len()).then_some(()).ok_or(err)?;
+        let mut next = 0;
+        for _ in 0..n {
+            // positions are increasing and tags are non zero
+            let position = reader.u32().ok_or(err)? as usize;
+            let tag = get::<E::Field>(&mut reader).ok_or(err)?.into();
+            (position >= next && position < tags.len() && tag != ReceiverTag::default())
+                .then_some(())
+                .ok_or(err)?;
+            tags[position] = tag;
+            next = position + 1;
+        }
+        reader.0.is_empty().then_some(()).ok_or(err)?;
+        set_receiver_tags(&mut history.steps, &mut tags.into_iter());
+    }
+    history.unknown = records;
+    Ok(history)
+}
+
+// reads the records up to the end
+fn read_records(reader: &mut Reader, limits: &Limits) -> Result<Vec<(u16, Vec<u8>)>, crate::Error> {
+    let err = crate::Error::With("bad wire encoding");
+    let mut records: Vec<(u16, Vec<u8>)> = vec![];
+    let mut size = 0;
+    while !reader.0.is_empty() {
+        let tag = reader.u16().ok_or(err)?;
+        let len = reader.u32().ok_or(err)? as usize;
+        records
+            .last()
+            .map_or(true, |(last, _)| *last < tag)
+            .then_some(())
+            .ok_or(err)?;
+        (tag & CRITICAL == 0 || KNOWN.contains(&tag))
+            .then_some(())
+            .ok_or(crate::Error::With("unsupported wire record"))?;
+        size += len;
+        (size <= limits.max_unknown_bytes)
+            .then_some(())
+            .ok_or(crate::Error::With("history exceeds limits"))?;
+        records.push((tag, reader.take(len).ok_or(err)?.to_vec()));
+    }
+    Ok(records)
+}
+
+fn read_v2<E: IVC>(reader: &mut Reader, limits: &Limits) -> Option<NoteHistory<E>> {
+    let issuer = get::<E::Field>(reader)?.into();
+    let terms = match reader.u8()? {
+        0 => Terms::iou(reader.u64()?, reader.u64()?),
+        _ => return None,
+    };
+    let policy = get::<E::Field>(reader)?.into();
+    let asset = Asset {
+        issuer,
+        terms,
+        policy,
+        issuer_key: IssuerKey::default(),
+        dual_control: false,
+    };
+
+    let steps = get_steps(reader, limits, 0, &mut 0)?;
+    (!steps.is_empty()).then_some(())?;
+
+    let asset_hash = get::<E::Field>(reader)?.into();
+    let owner = get::<E::Field>(reader)?.into();
+    let value = reader.u64()?;
+    let step = reader.u32()?;
+    let parent_note = get::<E::Field>(reader)?.into();
+    let out_index = match reader.u8()? {
+        0 => NoteOutIndex::Issue,
+        i => NoteOutIndex::Out(i - 1),
+    };
+    let blind = get::<E::Field>(reader)?.into();
+    let current_note = Note {
+        asset_hash,
+        owner,
+        value,
+        step,
+        parent_note,
+        out_index,
+        blind,
+        memo: MemoHash::default(),
+        expiry_step: NO_EXPIRY,
+    };
+
+    (reader.u32()? as usize == E::OUTPUTS - 1).then_some(())?;
+    let siblings = (0..E::OUTPUTS - 1)
+        .map(|_| get::<E::Field>(reader).map(Into::into))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(NoteHistory {
+        asset,
+        steps,
+        current_note,
+        siblings,
+        memo: vec![],
+        unknown: vec![],
+    })
+}
+
+impl<E: IVC> NoteHistory<E> {
+    // see `wire`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8], limits: &Limits) -> Result<Self, crate::Error> {
+        decode(bytes, limits)
+    }
+
+    // records of the encoding this version doesn't know, in order of tags
+    pub fn unknown_records(&self) -> &[(u16, Vec<u8>)] {
+        &self.unknown
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::mock::{self, Mock},
+        poseidon::PoseidonConfigs,
+        wallet::CommReceiver,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::OnceLock;
+
+    // a history that carries every known record: a memo, an expiry, a
+    // dual-control asset and a bound step, with merged steps behind it
+    fn history() -> NoteHistory<Mock> {
+        static HISTORY: OnceLock<NoteHistory<Mock>> = OnceLock::new();
+        HISTORY.get_or_init(transfers).clone()
+    }
+
+    fn transfers() -> NoteHistory<Mock> {
+        let h = PoseidonConfigs::generate();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut issuer = mock::wallet(&h, &mut rng);
+        let mut alice = mock::wallet(&h, &mut rng);
+        let mut bob = mock::wallet(&h, &mut rng);
+        let mut carol = mock::wallet(&h, &mut rng);
+        let asset = Asset::new(issuer.address(), &Terms::iou(1, 1))
+            .with_issuer_key(&issuer.issuer_key())
+            .with_dual_control();
+        issuer
+            .issue_expiring(&mut rng, &mut alice, &asset, 100, 10)
+            .unwrap();
+        issuer
+            .issue_expiring(&mut rng, &mut alice, &asset, 50, 10)
+            .unwrap();
+        alice.merge(&mut rng, 0, 1).unwrap();
+        alice.split_bound(&mut rng, &mut bob, 0, 30).unwrap();
+        bob.split_with_memo(&mut rng, &mut carol, 0, 10, b"invoice")
+            .unwrap();
+        carol.spendables()[0].clone()
+    }
+
+    fn decode(bytes: &[u8], limits: &Limits) -> Result<NoteHistory<Mock>, crate::Error> {
+        super::decode(bytes, limits)
+    }
+
+    fn proofs(steps: &[IVCStep<Mock>]) -> usize {
+        steps.iter().map(|step| 1 + proofs(&step.merged)).sum()
+    }
+
+    #[test]
+    fn round_trip() {
+        let history = history();
+        assert_eq!(history.memo, b"invoice");
+        assert_ne!(history.current_note.expiry_step, NO_EXPIRY);
+        assert!(history.asset.is_dual_control());
+        let mut tags = vec![];
+        receiver_tags(&history.steps, &mut tags);
+        assert!(tags.iter().any(|tag| *tag != ReceiverTag::default()));
+
+        let bytes = encode(&history);
+        assert_eq!(version(&bytes), Some(VERSION));
+        let decoded = decode(&bytes, &Limits::default()).unwrap();
+        assert_eq!(encode(&decoded), bytes);
+        assert_eq!(decoded.asset.issuer, history.asset.issuer);
+        assert_eq!(decoded.asset.issuer_key, history.asset.issuer_key);
+        assert!(decoded.asset.is_dual_control());
+        assert_eq!(decoded.current_note, history.current_note);
+        assert_eq!(decoded.memo, history.memo);
+        assert_eq!(decoded.siblings, history.siblings);
+        assert!(decoded.unknown.is_empty());
+        let mut decoded_tags = vec![];
+        receiver_tags(&decoded.steps, &mut decoded_tags);
+        assert_eq!(decoded_tags, tags);
+    }
+
+    #[test]
+    fn dual_control_record() {
+        let mut history = history();
+        let bytes = encode(&history);
+        // the record is the last one and empty
+        assert_eq!(bytes[bytes.len() - 6..][..2], DUAL_CONTROL.to_le_bytes());
+        assert_eq!(bytes[bytes.len() - 4..], [0; 4]);
+
+        // it has no body
+        let mut long = bytes[..bytes.len() - 4].to_vec();
+        long.extend_from_slice(&1u32.to_le_bytes());
+        long.push(0);
+        assert_eq!(
+            decode(&long, &Limits::default()).unwrap_err(),
+            crate::Error::With("bad wire encoding")
+        );
+        // nor is it taken for an asset that is not key-bound
+        history.asset.issuer_key = IssuerKey::default();
+        assert_eq!(
+            decode(&encode(&history), &Limits::default()).unwrap_err(),
+            crate::Error::With("bad wire encoding")
+        );
+        // without the record the asset is not under dual control
+        let decoded = decode(&bytes[..bytes.len() - 6], &Limits::default()).unwrap();
+        assert!(decoded.asset.is_key_bound());
+        assert!(!decoded.asset.is_dual_control());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = encode(&history());
+        // a prefix is rejected unless it ends at a record, the records after
+        // it are then just not there
+        let mut records = 0;
+        for len in 0..bytes.len() {
+            if let Ok(decoded) = decode(&bytes[..len], &Limits::default()) {
+                assert_eq!(encode(&decoded), bytes[..len]);
+                records += 1;
+            }
+        }
+        // none, then up to the memo, expiry, issuer key and receiver tags
+        assert_eq!(records, 5);
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = encode(&history());
+        let bad = Err::<(), _>(crate::Error::With("bad wire encoding"));
+        let decode = |bytes: &[u8]| decode(bytes, &Limits::default()).map(|_| ());
+
+        let mut magic = bytes.clone();
+        magic[0] ^= 1;
+        assert_eq!(decode(&magic), bad);
+        let mut version = bytes.clone();
+        version[4] = VERSION + 1;
+        assert_eq!(
+            decode(&version),
+            Err(crate::Error::With("unsupported wire version"))
+        );
+        // trailing bytes that are not a record
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(decode(&trailing), bad);
+        // a record before one with a lower tag
+        let mut unordered = bytes.clone();
+        unordered.extend_from_slice(&1u16.to_le_bytes());
+        unordered.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(decode(&unordered), bad);
+        // a step count past the input is not allocated for
+        let at = 5 + 2 * 32 + 1 + 2 * 8;
+        let mut count = bytes.clone();
+        count[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&count), bad);
+    }
+
+    #[test]
+    fn unknown_records() {
+        let mut history = history();
+        history.unknown = vec![(1, b"newer".to_vec())];
+        let bytes = encode(&history);
+        let decoded = decode(&bytes, &Limits::default()).unwrap();
+        assert_eq!(decoded.unknown_records(), &[(1, b"newer".to_vec())]);
+        assert_eq!(encode(&decoded), bytes);
+
+        history.unknown = vec![(CRITICAL | 0x100, vec![])];
+        assert_eq!(
+            decode(&encode(&history), &Limits::default()).unwrap_err(),
+            crate::Error::With("unsupported wire record")
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let mut history = history();
+        let bytes = encode(&history);
+        let decode = |limits: Limits| decode(&bytes, &limits).map(|_| ());
+
+        // proofs of the history along with the merged ones
+        let n = proofs(&history.steps);
+        let max_proofs = |max_proofs| Limits {
+            max_proofs,
+            ..Limits::default()
+        };
+        assert_eq!(decode(max_proofs(n)), Ok(()));
+        assert_eq!(
+            decode(max_proofs(n - 1)),
+            Err(crate::Error::With("bad wire encoding"))
+        );
+        // the merged steps are one level deep
+        let max_merge_depth = |max_merge_depth| Limits {
+            max_merge_depth,
+            ..Limits::default()
+        };
+        assert_eq!(decode(max_merge_depth(1)), Ok(()));
+        assert_eq!(
+            decode(max_merge_depth(0)),
+            Err(crate::Error::With("bad wire encoding"))
+        );
+        // and so are they for the limits a decoded history is checked against
+        assert_eq!(max_merge_depth(1).check_history(&history), Ok(()));
+        assert!(max_merge_depth(0).check_history(&history).is_err());
+        assert_eq!(max_proofs(n).check_history(&history), Ok(()));
+        assert!(max_proofs(n - 1).check_history(&history).is_err());
+        // a history with no merges is within a depth of zero
+        let mut plain = history.clone();
+        plain.steps.iter_mut().for_each(|step| step.merged.clear());
+        assert_eq!(
+            super::decode::<Mock>(&encode(&plain), &max_merge_depth(0)).map(|_| ()),
+            Ok(())
+        );
+        assert_eq!(max_merge_depth(0).check_history(&plain), Ok(()));
+        // the bodies of the five records are bounded as a whole
+        let size = bytes.len() - encode(&without_records(&history)).len() - 5 * 6;
+        let max_unknown_bytes = |max_unknown_bytes| Limits {
+            max_unknown_bytes,
+            ..Limits::default()
+        };
+        assert_eq!(decode(max_unknown_bytes(size)), Ok(()));
+        assert_eq!(
+            decode(max_unknown_bytes(size - 1)),
+            Err(crate::Error::With("history exceeds limits"))
+        );
+        history.unknown = vec![(1, vec![0; Limits::default().max_unknown_bytes + 1])];
+        assert_eq!(
+            super::decode::<Mock>(&encode(&history), &Limits::default()).unwrap_err(),
+            crate::Error::With("history exceeds limits")
+        );
+    }
+
+    // the history with nothing to write to the records
+    fn without_records(history: &NoteHistory<Mock>) -> NoteHistory<Mock> {
+        let mut history = history.clone();
+        history.current_note.memo = MemoHash::default();
+        history.current_note.expiry_step = NO_EXPIRY;
+        history.asset.issuer_key = IssuerKey::default();
+        history.asset.dual_control = false;
+        set_receiver_tags(&mut history.steps, &mut std::iter::empty());
+        history
+    }
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+pragma solidity ^0.8.0;
+
+// Groth16 verifier of ivcnotes steps over BN254, circuit abi version 15.
+contract IvcnotesVerifier {
+    uint256 constant R = 21888242871839275222246405745257275088548364400416034343698204186575808495617;
+    uint256 constant Q = 21888242871839275222246405745257275088696311157297823662689037894645226208583;
+    uint256 constant INPUTS = 12;
+
+    uint256 constant ALPHA_X = 1368015179489954701390400359078579693043519447331113978918064868415326638035;
+    uint256 constant ALPHA_Y = 9918110051302171585080402603319702774565515993150576347155970296011118125764;
+    uint256 constant BETA_X1 = 7273165102799931111715871471550377909735733521218303035754523677688038059653;
+    uint256 constant BETA_X0 = 2725019753478801796453339367788033689375851816420509565303521482350756874229;
+    uint256 constant BETA_Y1 = 957874124722006818841961785324909313781880061366718538693995380805373202866;
+    uint256 constant BETA_Y0 = 2512659008974376214222774206987427162027254181373325676825515531566330959255;
+    uint256 constant GAMMA_X1 = 4540444681147253467785307942530223364530218361853237193970751657229138047649;
+    uint256 constant GAMMA_X0 = 20954117799226682825035885491234530437475518021362091509513177301640194298072;
+    uint256 constant GAMMA_Y1 = 11631839690097995216017572651900167465857396346217730511548857041925508482915;
+    uint256 constant GAMMA_Y0 = 21508930868448350162258892668132814424284302804699005394342512102884055673846;
+    uint256 constant DELTA_X1 = 18551411094430470096460536606940536822990217226529861227533666875800903099477;
+    uint256 constant DELTA_X0 = 15512671280233143720612069991584289591749188907863576513414377951116606878472;
+    uint256 constant DELTA_Y1 = 1711576522631428957817575436337311654689480489843856945284031697403898093784;
+    uint256 constant DELTA_Y0 = 13376798835316611669264291046140500151806347092962367781523498857425536295743;
+
+    function ic() internal pure returns (uint256[26] memory) {
+        return [
+            uint256(19033251874843656108471242320417533909414939332036131356573128480367742634479),
+            uint256(20792135454608030201903199625673964159744755218442260092768620403349374102584),
+            uint256(17108685722251241369314020928988529881027530433467445791267465866135602972753),
+            uint256(20666112440056908034039013737427066139426903072479162670940363761207457724060),
+            uint256(2672242651313367459976336264061690128665099451055893690004467838496751824703),
+            uint256(18247534626997477790812670345925575171672701304065784723769023620148097699216),
+            uint256(9836339169314901400584090930519505895878753154116006108033708428907043344230),
+            uint256(2085718088180884207082818799076507077917184375787335400014805976331012093279),
+            uint256(20620327752371756597889511849668302065574790742892641857779427155670977738300),
+            uint256(13476221886639441297190182883126933680754442408693165714792516739857175455715),
+            uint256(10835225521862395592687560951453385602895512958032257955899877380493200080708),
+            uint256(2623520004791921319615054428233368525468155544765295675952919303096698181037),
+            uint256(12852522211178622728088728121177131998585782282560100422041774753646305409836),
+            uint256(15918672909255108529698304535345707578139606904951176064731093256171019744261),
+            uint256(20687098839691105097230132006705975099432129393118730698937199498853576759031),
+            uint256(2784555085364475896955849729890301289648525422842103121195101376751786827234),
+            uint256(9642222084729607517877300695132775567109325334448449884825136965142866412173),
+            uint256(4237181956005900153121967166075358295245559468450620141848474158744070559022),
+            uint256(18947110137775984544896515092961257947872750783784269176923414004072777296602),
+            uint256(12292085037693291586083644966434670280746730626861846747147579999202931064992),
+            uint256(13940766438396802022003403700150119103921439873158775302201999840306601026555),
+            uint256(20366854387609749451649589446643328667334616581983267447585608088473228416457),
+            uint256(15727213640762128376977790067421582934261473041285176203873887513123693207669),
+            uint256(19144605879150273414601776380457513460094228635793066771119021730299648624873),
+            uint256(13640322012419910779160519747081036978280854528525356142388876682012724302321),
+            uint256(18538714940515721848968265449014632110570653454278528879450713650630487487382)
+        ];
+    }
+
+    function add(uint256[2] memory p, uint256[2] memory q) internal view returns (uint256[2] memory r) {
+        (bool ok, bytes memory out) = address(6).staticcall(abi.encode(p[0], p[1], q[0], q[1]));
+        require(ok, "ec add failed");
+        (r[0], r[1]) = abi.decode(out, (uint256, uint256));
+    }
+
+    function mul(uint256[2] memory p, uint256 s) internal view returns (uint256[2] memory r) {
+        (bool ok, bytes memory out) = address(7).staticcall(abi.encode(p[0], p[1], s));
+        require(ok, "ec mul failed");
+        (r[0], r[1]) = abi.decode(out, (uint256, uint256));
+    }
+
+    function verifyProof(
+        uint256[2] calldata a,
+        uint256[2][2] calldata b,
+        uint256[2] calldata c,
+        uint256[] calldata input
+    ) public view returns (bool) {
+        require(input.length == INPUTS, "bad number of inputs");
+        require(a[1] < Q, "bad proof");
+        uint256[26] memory points = ic();
+        uint256[2] memory x = [points[0], points[1]];
+        for (uint256 i = 0; i < INPUTS; i++) {
+            require(input[i] < R, "input is not in the field");
+            x = add(x, mul([points[2 * i + 2], points[2 * i + 3]], input[i]));
+        }
+
+        // e(-a, b) e(alpha, beta) e(x, gamma) e(c, delta) == 1
+        uint256[24] memory p = [
+            a[0], (Q - a[1]) % Q, b[0][0], b[0][1], b[1][0], b[1][1],
+            ALPHA_X, ALPHA_Y, BETA_X1, BETA_X0, BETA_Y1, BETA_Y0,
+            x[0], x[1], GAMMA_X1, GAMMA_X0, GAMMA_Y1, GAMMA_Y0,
+            c[0], c[1], DELTA_X1, DELTA_X0, DELTA_Y1, DELTA_Y0
+        ];
+        (bool ok, bytes memory out) = address(8).staticcall(abi.encodePacked(p));
+        return ok && out.length == 32 && abi.decode(out, (uint256)) == 1;
+    }
+}